@@ -16,6 +16,7 @@ pub fn SQLITE_TRANSIENT() -> sqlite3_destructor_type {
 }
 
 /// Run-Time Limit Categories
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(i32)]
 #[non_exhaustive]
 pub enum Limit {
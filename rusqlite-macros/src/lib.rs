@@ -0,0 +1,305 @@
+//! Derive macros for [rusqlite](https://docs.rs/rusqlite): use them through
+//! its `derive` feature, as `rusqlite::types::{ToSql, FromSql}`, and the
+//! attribute macro of its `extension_init` feature, as
+//! `rusqlite::extension::sqlite_extension_init`.
+#![forbid(unsafe_code)]
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, AttributeArgs, Data, DeriveInput, Error, Fields, Ident, ItemFn, Lit, Meta,
+    NestedMeta,
+};
+
+/// Derives `ToSql` for an enum without fields.
+///
+/// See `FromSql` for the attributes.
+#[proc_macro_derive(ToSql, attributes(rusqlite))]
+pub fn derive_to_sql(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input, to_sql).unwrap_or_else(|err| err.to_compile_error().into())
+}
+
+/// Derives `FromSql` for an enum without fields.
+///
+/// Variants are stored as their discriminant (an `INTEGER`), or, with
+/// `#[rusqlite(repr = "text")]` on the enum, as their name (a `TEXT`), which
+/// `#[rusqlite(rename = "...")]` on a variant overrides. Unknown values fail
+/// to convert, with `FromSqlError::OutOfRange` for integers and
+/// `FromSqlError::Other` for text, unless a variant is marked
+/// `#[rusqlite(other)]`, in which case they convert to it.
+#[proc_macro_derive(FromSql, attributes(rusqlite))]
+pub fn derive_from_sql(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input, from_sql).unwrap_or_else(|err| err.to_compile_error().into())
+}
+
+/// Generates the entry point of a loadable extension, calling the annotated
+/// function, `fn(&Connection) -> Result<()>`, with the connection loading
+/// the extension.
+///
+/// The entry point is `sqlite3_<name>_init`, where `<name>` is the name of
+/// the function, or the one given with `#[sqlite_extension_init(name =
+/// "...")]`. `name` can be repeated, to export the function under several
+/// entry points. Errors and panics of the function fail the loading, with
+/// their message.
+#[proc_macro_attribute]
+pub fn sqlite_extension_init(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as AttributeArgs);
+    let function = parse_macro_input!(item as ItemFn);
+    extension_init(&args, &function).unwrap_or_else(|err| err.to_compile_error().into())
+}
+
+fn extension_init(args: &[NestedMeta], function: &ItemFn) -> syn::Result<TokenStream> {
+    let ident = &function.sig.ident;
+    let mut names = Vec::new();
+    for arg in args {
+        match arg {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("name") => match &nv.lit {
+                Lit::Str(s) => {
+                    let name = s.value();
+                    if name.is_empty()
+                        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                    {
+                        return Err(Error::new_spanned(
+                            s,
+                            "the extension name must be alphanumeric",
+                        ));
+                    }
+                    if names.contains(&name) {
+                        return Err(Error::new_spanned(s, "duplicate extension name"));
+                    }
+                    names.push(name);
+                }
+                lit => return Err(Error::new_spanned(lit, "expected a string")),
+            },
+            arg => return Err(Error::new_spanned(arg, "unknown attribute")),
+        }
+    }
+    if names.is_empty() {
+        names.push(ident.to_string());
+    }
+    let entry_points = names.iter().map(|name| {
+        let entry_point = Ident::new(&format!("sqlite3_{}_init", name), ident.span());
+        let doc = format!("Entry point of the `{}` SQLite extension.", name);
+        quote! {
+            #[doc = #doc]
+            ///
+            /// # Safety
+            ///
+            /// Called by SQLite only, with a valid connection handle.
+            #[no_mangle]
+            pub unsafe extern "C" fn #entry_point(
+                db: *mut ::rusqlite::ffi::sqlite3,
+                pz_err_msg: *mut *mut ::std::os::raw::c_char,
+                p_api: *const ::std::os::raw::c_void,
+            ) -> ::std::os::raw::c_int {
+                ::rusqlite::extension::init_extension(db, pz_err_msg, p_api, #ident)
+            }
+        }
+    });
+    Ok(quote! {
+        #function
+
+        #(#entry_points)*
+    }
+    .into())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Repr {
+    Integer,
+    Text,
+}
+
+struct Variant {
+    ident: Ident,
+    name: String,
+    other: bool,
+}
+
+struct Enum<'a> {
+    ident: &'a Ident,
+    repr: Repr,
+    variants: Vec<Variant>,
+}
+
+fn expand(
+    input: &DeriveInput,
+    generate: fn(&Enum<'_>) -> TokenStream2,
+) -> syn::Result<TokenStream> {
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => return Err(Error::new_spanned(input, "only enums are supported")),
+    };
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "generic enums are not supported",
+        ));
+    }
+    let mut repr = Repr::Integer;
+    for meta in attributes(&input.attrs)? {
+        match meta {
+            Meta::NameValue(nv) if nv.path.is_ident("repr") => {
+                repr = match &nv.lit {
+                    Lit::Str(s) if s.value() == "integer" => Repr::Integer,
+                    Lit::Str(s) if s.value() == "text" => Repr::Text,
+                    lit => return Err(Error::new_spanned(lit, "expected \"integer\" or \"text\"")),
+                }
+            }
+            meta => return Err(Error::new_spanned(meta, "unknown attribute")),
+        }
+    }
+    let mut variants = Vec::new();
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(Error::new_spanned(
+                variant,
+                "only variants without fields are supported",
+            ));
+        }
+        let mut name = variant.ident.to_string();
+        let mut other = false;
+        for meta in attributes(&variant.attrs)? {
+            match meta {
+                Meta::NameValue(nv) if nv.path.is_ident("rename") => match &nv.lit {
+                    Lit::Str(s) => name = s.value(),
+                    lit => return Err(Error::new_spanned(lit, "expected a string")),
+                },
+                Meta::Path(path) if path.is_ident("other") => {
+                    if variants.iter().any(|v: &Variant| v.other) {
+                        return Err(Error::new_spanned(path, "duplicate `other` variant"));
+                    }
+                    other = true;
+                }
+                meta => return Err(Error::new_spanned(meta, "unknown attribute")),
+            }
+        }
+        if repr == Repr::Text && variants.iter().any(|v: &Variant| v.name == name) {
+            return Err(Error::new_spanned(variant, "duplicate variant name"));
+        }
+        variants.push(Variant {
+            ident: variant.ident.clone(),
+            name,
+            other,
+        });
+    }
+    Ok(generate(&Enum {
+        ident: &input.ident,
+        repr,
+        variants,
+    })
+    .into())
+}
+
+// The contents of the `#[rusqlite(...)]` attributes.
+fn attributes(attrs: &[syn::Attribute]) -> syn::Result<Vec<Meta>> {
+    let mut metas = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("rusqlite")) {
+        match attr.parse_meta()? {
+            Meta::List(list) => {
+                for nested in list.nested {
+                    match nested {
+                        NestedMeta::Meta(meta) => metas.push(meta),
+                        NestedMeta::Lit(lit) => {
+                            return Err(Error::new_spanned(lit, "unknown attribute"))
+                        }
+                    }
+                }
+            }
+            meta => return Err(Error::new_spanned(meta, "expected `rusqlite(...)`")),
+        }
+    }
+    Ok(metas)
+}
+
+fn to_sql(e: &Enum<'_>) -> TokenStream2 {
+    let ident = e.ident;
+    let body = match e.repr {
+        Repr::Integer => {
+            // A match, rather than `*self as i64`, which would require `Copy`.
+            let arms = e.variants.iter().map(|v| {
+                let variant = &v.ident;
+                quote! { #ident::#variant => #ident::#variant as i64 }
+            });
+            quote! {
+                ::rusqlite::types::ToSqlOutput::from(match self { #(#arms,)* })
+            }
+        }
+        Repr::Text => {
+            let arms = e.variants.iter().map(|v| {
+                let (variant, name) = (&v.ident, &v.name);
+                quote! { #ident::#variant => #name }
+            });
+            quote! {
+                ::rusqlite::types::ToSqlOutput::from(match self { #(#arms,)* })
+            }
+        }
+    };
+    quote! {
+        impl ::rusqlite::types::ToSql for #ident {
+            #[inline]
+            fn to_sql(&self) -> ::rusqlite::Result<::rusqlite::types::ToSqlOutput<'_>> {
+                ::std::result::Result::Ok(#body)
+            }
+        }
+    }
+}
+
+fn from_sql(e: &Enum<'_>) -> TokenStream2 {
+    let ident = e.ident;
+    let other = e.variants.iter().find(|v| v.other).map(|v| &v.ident);
+    let body = match e.repr {
+        Repr::Integer => {
+            let arms = e.variants.iter().map(|v| {
+                let variant = &v.ident;
+                quote! { i if i == #ident::#variant as i64 => Ok(#ident::#variant) }
+            });
+            let unknown = match other {
+                Some(variant) => quote! { _ => Ok(#ident::#variant) },
+                None => quote! { i => Err(::rusqlite::types::FromSqlError::OutOfRange(i)) },
+            };
+            quote! {
+                match value.as_i64()? {
+                    #(#arms,)*
+                    #unknown,
+                }
+            }
+        }
+        Repr::Text => {
+            let arms = e.variants.iter().map(|v| {
+                let (variant, name) = (&v.ident, &v.name);
+                quote! { #name => Ok(#ident::#variant) }
+            });
+            let unknown = match other {
+                Some(variant) => quote! { _ => Ok(#ident::#variant) },
+                None => {
+                    let message = format!("unknown {} variant: {{:?}}", ident);
+                    quote! {
+                        s => Err(::rusqlite::types::FromSqlError::Other(
+                            ::std::format!(#message, s).into(),
+                        ))
+                    }
+                }
+            };
+            quote! {
+                match value.as_str()? {
+                    #(#arms,)*
+                    #unknown,
+                }
+            }
+        }
+    };
+    quote! {
+        impl ::rusqlite::types::FromSql for #ident {
+            fn column_result(
+                value: ::rusqlite::types::ValueRef<'_>,
+            ) -> ::rusqlite::types::FromSqlResult<Self> {
+                use ::std::result::Result::{Err, Ok};
+                #body
+            }
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! `feature = "allocator"` Route SQLite's memory allocations through a Rust
+//! allocator.
+//!
+//! ```rust,no_run
+//! use std::alloc::System;
+//! # use rusqlite::Result;
+//!
+//! fn main() -> Result<()> {
+//!     // Before any other use of SQLite:
+//!     unsafe { rusqlite::allocator::config_malloc(&System) }?;
+//!     let conn = rusqlite::Connection::open_in_memory()?;
+//!     Ok(())
+//! }
+//! ```
+
+use std::alloc::{GlobalAlloc, Layout};
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::error::error_from_sqlite_code;
+use crate::{ffi, Error, Result};
+
+type Allocator = &'static (dyn GlobalAlloc + Sync);
+
+// Set once by `config_malloc`, and never freed: SQLite may use it until the
+// process exits.
+static ALLOCATOR: AtomicPtr<Allocator> = AtomicPtr::new(ptr::null_mut());
+
+// SQLite needs the size of allocations back, so each one is prefixed with
+// its size. This also keeps the 8-byte alignment SQLite requires.
+const HEADER: usize = 8;
+
+/// `feature = "allocator"` Make SQLite allocate all of its memory from
+/// `allocator` (e.g. `&std::alloc::System`, jemalloc, or an allocator which
+/// keeps track of usage), with `SQLITE_CONFIG_MALLOC`.
+///
+/// This can only be done once per process, before SQLite is initialized, i.e.
+/// before the first connection is opened.
+///
+/// # Failure
+///
+/// Will return `Err` if an allocator has already been configured, or with
+/// `SQLITE_MISUSE` if SQLite is already initialized.
+///
+/// # Safety
+///
+/// This function is not threadsafe. No other SQLite calls may be made while
+/// `config_malloc` is running, and multiple threads may not call it
+/// simultaneously.
+pub unsafe fn config_malloc(allocator: &'static (dyn GlobalAlloc + Sync)) -> Result<()> {
+    let boxed = Box::into_raw(Box::new(allocator));
+    if ALLOCATOR
+        .compare_exchange(ptr::null_mut(), boxed, Ordering::AcqRel, Ordering::Acquire)
+        .is_err()
+    {
+        drop(Box::from_raw(boxed));
+        return Err(Error::SqliteFailure(
+            ffi::Error::new(ffi::SQLITE_MISUSE),
+            Some("SQLite allocator already configured".to_owned()),
+        ));
+    }
+
+    let methods = ffi::sqlite3_mem_methods {
+        xMalloc: Some(x_malloc),
+        xFree: Some(x_free),
+        xRealloc: Some(x_realloc),
+        xSize: Some(x_size),
+        xRoundup: Some(x_roundup),
+        xInit: Some(x_init),
+        xShutdown: Some(x_shutdown),
+        pAppData: ptr::null_mut(),
+    };
+    // SQLite copies `methods`.
+    let rc = ffi::sqlite3_config(ffi::SQLITE_CONFIG_MALLOC, &methods);
+    if rc == ffi::SQLITE_OK {
+        Ok(())
+    } else {
+        // SQLite will never call us, so a later attempt can be made.
+        ALLOCATOR.store(ptr::null_mut(), Ordering::Release);
+        drop(Box::from_raw(boxed));
+        Err(error_from_sqlite_code(rc, None))
+    }
+}
+
+#[inline]
+unsafe fn allocator() -> Allocator {
+    *ALLOCATOR.load(Ordering::Acquire)
+}
+
+#[inline]
+fn layout(size: usize) -> Option<Layout> {
+    Layout::from_size_align(size.checked_add(HEADER)?, HEADER).ok()
+}
+
+// Returns the start of the allocation and its size, given what SQLite sees.
+#[inline]
+unsafe fn header(p: *mut c_void) -> (*mut u8, usize) {
+    let base = (p as *mut u8).sub(HEADER);
+    (base, *(base as *const usize))
+}
+
+#[inline]
+unsafe fn init_header(base: *mut u8, size: usize) -> *mut c_void {
+    if base.is_null() {
+        return ptr::null_mut();
+    }
+    *(base as *mut usize) = size;
+    base.add(HEADER) as *mut c_void
+}
+
+unsafe extern "C" fn x_malloc(n: c_int) -> *mut c_void {
+    match layout(n as usize) {
+        Some(layout) if n > 0 => init_header(allocator().alloc(layout), n as usize),
+        _ => ptr::null_mut(),
+    }
+}
+
+unsafe extern "C" fn x_free(p: *mut c_void) {
+    if p.is_null() {
+        return;
+    }
+    let (base, size) = header(p);
+    allocator().dealloc(base, layout(size).unwrap());
+}
+
+unsafe extern "C" fn x_realloc(p: *mut c_void, n: c_int) -> *mut c_void {
+    if n <= 0 || layout(n as usize).is_none() {
+        return ptr::null_mut();
+    }
+    let (base, size) = header(p);
+    let new_base = allocator().realloc(base, layout(size).unwrap(), n as usize + HEADER);
+    init_header(new_base, n as usize)
+}
+
+unsafe extern "C" fn x_size(p: *mut c_void) -> c_int {
+    if p.is_null() {
+        0
+    } else {
+        header(p).1 as c_int
+    }
+}
+
+extern "C" fn x_roundup(n: c_int) -> c_int {
+    n.saturating_add(7) & !7
+}
+
+extern "C" fn x_init(_: *mut c_void) -> c_int {
+    ffi::SQLITE_OK
+}
+
+extern "C" fn x_shutdown(_: *mut c_void) {}
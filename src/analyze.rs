@@ -0,0 +1,183 @@
+use std::time::{Duration, Instant};
+
+use crate::util::quote_identifier;
+use crate::{params, Connection, OptionalExtension, Result};
+
+/// How much work [`Connection::analyze_incremental`] may do in a call.
+///
+/// ```rust,no_run
+/// # use rusqlite::{AnalyzeBudget, Connection, Result};
+/// # use std::time::Duration;
+/// fn maintenance(conn: &Connection) -> Result<()> {
+///     let budget = AnalyzeBudget::new(Duration::from_millis(200)).analysis_limit(1000);
+///     loop {
+///         let report = conn.analyze_incremental(&budget)?;
+///         println!("analyzed {:?}", report.analyzed);
+///         std::thread::sleep(Duration::from_secs(60));
+///     }
+/// }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct AnalyzeBudget {
+    time: Duration,
+    analysis_limit: u32,
+}
+
+impl AnalyzeBudget {
+    /// Tables are analyzed until `time` has elapsed, with an
+    /// `analysis_limit` of 400 rows per index.
+    pub fn new(time: Duration) -> AnalyzeBudget {
+        AnalyzeBudget {
+            time,
+            analysis_limit: 400,
+        }
+    }
+
+    /// Sets the approximate number of rows of each index which `ANALYZE`
+    /// examines, or `0` for all of them. Requires SQLite 3.32.0: older
+    /// versions examine all the rows.
+    #[must_use]
+    pub fn analysis_limit(mut self, limit: u32) -> Self {
+        self.analysis_limit = limit;
+        self
+    }
+}
+
+/// What a call to [`Connection::analyze_incremental`] did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AnalyzeReport {
+    /// The tables analyzed, in order.
+    pub analyzed: Vec<String>,
+    /// The number of tables of the `main` database not analyzed by this
+    /// connection yet.
+    pub remaining: usize,
+}
+
+impl Connection {
+    /// Refreshes the query planner statistics of some tables of the `main`
+    /// database, running `ANALYZE` on one table at a time until the time of
+    /// `budget` has elapsed, with its `analysis_limit`.
+    ///
+    /// Each call resumes where the previous one of this connection stopped:
+    /// tables never analyzed by it come first, then the ones analyzed the
+    /// longest ago, so repeated calls, e.g. from a maintenance thread, keep
+    /// all the statistics fresh without long write locks. At least one
+    /// table is analyzed per call; a table is never interrupted, but the
+    /// `analysis_limit` bounds the time it takes. The order is kept in the
+    /// `temp` database.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite calls fail.
+    pub fn analyze_incremental(&self, budget: &AnalyzeBudget) -> Result<AnalyzeReport> {
+        let start = Instant::now();
+        self.execute_batch(
+            "CREATE TEMP TABLE IF NOT EXISTS _rusqlite_analyze(
+                 name TEXT PRIMARY KEY,
+                 analyzed INTEGER
+             );
+             DELETE FROM temp._rusqlite_analyze
+             WHERE name NOT IN (SELECT name FROM main.sqlite_master WHERE type = 'table');
+             INSERT OR IGNORE INTO temp._rusqlite_analyze (name)
+             SELECT name FROM main.sqlite_master
+             WHERE type = 'table' AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\';",
+        )?;
+        let previous_limit: Option<i64> = self
+            .query_row("PRAGMA analysis_limit", [], |row| row.get(0))
+            .optional()?;
+        self.set_analysis_limit(i64::from(budget.analysis_limit))?;
+        let result = self.analyze_tables(budget, start);
+        if let Some(limit) = previous_limit {
+            self.set_analysis_limit(limit)?;
+        }
+        let analyzed = result?;
+        let remaining: i64 = self.query_row(
+            "SELECT count(*) FROM temp._rusqlite_analyze WHERE analyzed IS NULL",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(AnalyzeReport {
+            analyzed,
+            remaining: remaining as usize,
+        })
+    }
+
+    fn analyze_tables(&self, budget: &AnalyzeBudget, start: Instant) -> Result<Vec<String>> {
+        let mut analyzed = Vec::new();
+        while analyzed.is_empty() || start.elapsed() < budget.time {
+            let next: Option<String> = self
+                .query_row(
+                    "SELECT name FROM temp._rusqlite_analyze
+                     ORDER BY analyzed IS NOT NULL, analyzed, name LIMIT 1",
+                    [],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            let name = match next {
+                // Every table was analyzed by this call.
+                Some(ref name) if analyzed.contains(name) => break,
+                Some(name) => name,
+                None => break,
+            };
+            self.execute_batch(&format!("ANALYZE main.{}", quote_identifier(&name)))?;
+            self.execute(
+                "UPDATE temp._rusqlite_analyze
+                 SET analyzed = (SELECT coalesce(max(analyzed), 0) + 1 FROM temp._rusqlite_analyze)
+                 WHERE name = ?",
+                params![name],
+            )?;
+            analyzed.push(name);
+        }
+        Ok(analyzed)
+    }
+
+    fn set_analysis_limit(&self, limit: i64) -> Result<()> {
+        // Returns the new limit, or nothing before SQLite 3.32.0.
+        let mut stmt = self.prepare(&format!("PRAGMA analysis_limit = {}", limit))?;
+        let mut rows = stmt.query([])?;
+        while rows.next()?.is_some() {}
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::AnalyzeBudget;
+    use crate::{Connection, Result};
+
+    #[test]
+    fn test_analyze_incremental() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE a (x INTEGER);
+             CREATE INDEX a_x ON a (x);
+             CREATE TABLE b (y INTEGER);
+             CREATE INDEX b_y ON b (y);
+             INSERT INTO a VALUES (1), (2), (3);
+             INSERT INTO b VALUES (1), (1), (1);",
+        )?;
+
+        let budget = AnalyzeBudget::new(Duration::from_secs(0)).analysis_limit(100);
+        let report = db.analyze_incremental(&budget)?;
+        assert_eq!(report.analyzed, ["a"]);
+        assert_eq!(report.remaining, 1);
+        let stats: i64 = db.query_row("SELECT count(*) FROM sqlite_stat1", [], |r| r.get(0))?;
+        assert_eq!(stats, 1);
+
+        let report = db.analyze_incremental(&budget)?;
+        assert_eq!(report.analyzed, ["b"]);
+        assert_eq!(report.remaining, 0);
+        // Round robin.
+        let report = db.analyze_incremental(&budget)?;
+        assert_eq!(report.analyzed, ["a"]);
+
+        let report = db.analyze_incremental(&AnalyzeBudget::new(Duration::from_secs(60)))?;
+        assert_eq!(report.analyzed, ["b", "a"]);
+        let stats: i64 = db.query_row("SELECT count(*) FROM sqlite_stat1", [], |r| r.get(0))?;
+        assert_eq!(stats, 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,312 @@
+//! `feature = "arrow"` Export query results as Arrow record batches.
+
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, BinaryBuilder, Float64Builder, Int64Builder, StringBuilder};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use fallible_streaming_iterator::FallibleStreamingIterator;
+
+use crate::types::ValueRef;
+use crate::{Error, Params, Result, Row, Rows, Statement};
+
+impl Statement<'_> {
+    /// `feature = "arrow"` Execute the prepared statement, returning an
+    /// iterator over its result as Arrow record batches of up to `batch_size`
+    /// rows each.
+    ///
+    /// The type of each column is given by its declared type, following
+    /// SQLite's [affinity rules](https://sqlite.org/datatype3.html#determination_of_column_affinity):
+    /// `INTEGER` affinity maps to `Int64`, `TEXT` to `Utf8`, `BLOB` to `Binary`
+    /// and `REAL` to `Float64`. For expressions and `NUMERIC` affinity, the
+    /// type is that of the value in the first row. Every column is nullable.
+    ///
+    /// ```rust,no_run
+    /// # use rusqlite::{Connection, Result};
+    /// fn export(conn: &Connection) -> Result<usize> {
+    ///     let mut stmt = conn.prepare("SELECT id, name FROM person")?;
+    ///     let mut rows = 0;
+    ///     for batch in stmt.query_arrow([], 1024)? {
+    ///         rows += batch?.num_rows();
+    ///     }
+    ///     Ok(rows)
+    /// }
+    /// ```
+    ///
+    /// ## Failure
+    ///
+    /// Will return `Err` if binding parameters fails or if the first step
+    /// fails. Items are `Err` if stepping fails, or with
+    /// `Error::InvalidColumnType` if a value cannot be represented in the
+    /// Arrow type of its column (e.g. a blob in an `Int64` column).
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `batch_size` is 0.
+    pub fn query_arrow<P: Params>(
+        &mut self,
+        params: P,
+        batch_size: usize,
+    ) -> Result<ArrowBatches<'_>> {
+        assert!(batch_size > 0, "batch_size must be positive");
+        // Names must be read before the statement is reset by an empty result.
+        let declared: Vec<(String, Option<DataType>)> = (0..self.column_count())
+            .map(|idx| {
+                let name = self.column_name_unwrap(idx).to_owned();
+                let decltype = self.stmt.column_decltype(idx);
+                (name, decltype.and_then(|t| affinity(t.to_bytes())))
+            })
+            .collect();
+        let mut rows = self.query(params)?;
+        rows.advance()?;
+        let fields: Vec<Field> = declared
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (name, data_type))| {
+                let data_type = data_type.unwrap_or_else(|| match rows.get() {
+                    Some(row) => storage_class(row.get_ref_unwrap(idx)),
+                    None => DataType::Utf8,
+                });
+                Field::new(&name, data_type, true)
+            })
+            .collect();
+        Ok(ArrowBatches {
+            rows,
+            schema: Arc::new(Schema::new(fields)),
+            batch_size,
+        })
+    }
+}
+
+/// `feature = "arrow"` An iterator over the result of a query as Arrow record
+/// batches, returned by [`Statement::query_arrow`].
+pub struct ArrowBatches<'stmt> {
+    // Positioned on the first row not yet exported.
+    rows: Rows<'stmt>,
+    schema: SchemaRef,
+    batch_size: usize,
+}
+
+impl ArrowBatches<'_> {
+    /// Returns the schema of the record batches.
+    #[inline]
+    pub fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+
+    fn next_batch(&mut self) -> Result<RecordBatch> {
+        let mut builders: Vec<ColumnBuilder> = self
+            .schema
+            .fields()
+            .iter()
+            .map(|field| ColumnBuilder::new(field.data_type(), self.batch_size))
+            .collect();
+        let mut len = 0;
+        while let Some(row) = self.rows.get() {
+            append_row(&mut builders, row)?;
+            len += 1;
+            self.rows.advance()?;
+            if len == self.batch_size {
+                break;
+            }
+        }
+        let columns = builders.into_iter().map(ColumnBuilder::finish).collect();
+        Ok(RecordBatch::try_new(self.schema(), columns).expect("arrays match the schema"))
+    }
+}
+
+impl Iterator for ArrowBatches<'_> {
+    type Item = Result<RecordBatch>;
+
+    fn next(&mut self) -> Option<Result<RecordBatch>> {
+        self.rows.get()?;
+        Some(self.next_batch())
+    }
+}
+
+// Column affinity of a declared type, unless it is NUMERIC, in which case the
+// type depends on the values.
+fn affinity(decltype: &[u8]) -> Option<DataType> {
+    let decltype = decltype.to_ascii_uppercase();
+    let contains = |s: &[u8]| decltype.windows(s.len()).any(|w| w == s);
+    if contains(b"INT") {
+        Some(DataType::Int64)
+    } else if contains(b"CHAR") || contains(b"CLOB") || contains(b"TEXT") {
+        Some(DataType::Utf8)
+    } else if contains(b"BLOB") {
+        Some(DataType::Binary)
+    } else if contains(b"REAL") || contains(b"FLOA") || contains(b"DOUB") {
+        Some(DataType::Float64)
+    } else {
+        None
+    }
+}
+
+fn storage_class(value: ValueRef<'_>) -> DataType {
+    match value {
+        ValueRef::Integer(_) => DataType::Int64,
+        ValueRef::Real(_) => DataType::Float64,
+        ValueRef::Null | ValueRef::Text(_) => DataType::Utf8,
+        ValueRef::Blob(_) => DataType::Binary,
+    }
+}
+
+enum ColumnBuilder {
+    Int64(Int64Builder),
+    Float64(Float64Builder),
+    Utf8(StringBuilder),
+    Binary(BinaryBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(data_type: &DataType, capacity: usize) -> ColumnBuilder {
+        match data_type {
+            DataType::Int64 => ColumnBuilder::Int64(Int64Builder::with_capacity(capacity)),
+            DataType::Float64 => ColumnBuilder::Float64(Float64Builder::with_capacity(capacity)),
+            DataType::Binary => ColumnBuilder::Binary(BinaryBuilder::new()),
+            _ => ColumnBuilder::Utf8(StringBuilder::new()),
+        }
+    }
+
+    // Returns false if `value` can't be converted to the type of the column.
+    fn append(&mut self, value: ValueRef<'_>) -> bool {
+        match (self, value) {
+            (ColumnBuilder::Int64(b), ValueRef::Null) => b.append_null(),
+            (ColumnBuilder::Float64(b), ValueRef::Null) => b.append_null(),
+            (ColumnBuilder::Utf8(b), ValueRef::Null) => b.append_null(),
+            (ColumnBuilder::Binary(b), ValueRef::Null) => b.append_null(),
+            (ColumnBuilder::Int64(b), ValueRef::Integer(i)) => b.append_value(i),
+            (ColumnBuilder::Float64(b), ValueRef::Integer(i)) => b.append_value(i as f64),
+            (ColumnBuilder::Float64(b), ValueRef::Real(f)) => b.append_value(f),
+            (ColumnBuilder::Utf8(b), ValueRef::Integer(i)) => b.append_value(i.to_string()),
+            (ColumnBuilder::Utf8(b), ValueRef::Real(f)) => b.append_value(f.to_string()),
+            (ColumnBuilder::Utf8(b), ValueRef::Text(_)) => match value.as_str() {
+                Ok(s) => b.append_value(s),
+                Err(_) => return false,
+            },
+            (ColumnBuilder::Binary(b), ValueRef::Text(bytes))
+            | (ColumnBuilder::Binary(b), ValueRef::Blob(bytes)) => b.append_value(bytes),
+            _ => return false,
+        }
+        true
+    }
+
+    fn finish(self) -> ArrayRef {
+        match self {
+            ColumnBuilder::Int64(mut b) => Arc::new(b.finish()),
+            ColumnBuilder::Float64(mut b) => Arc::new(b.finish()),
+            ColumnBuilder::Utf8(mut b) => Arc::new(b.finish()),
+            ColumnBuilder::Binary(mut b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+fn append_row(builders: &mut [ColumnBuilder], row: &Row<'_>) -> Result<()> {
+    for (idx, builder) in builders.iter_mut().enumerate() {
+        let value = row.get_ref_unwrap(idx);
+        if !builder.append(value) {
+            return Err(Error::InvalidColumnType(
+                idx,
+                row.column_name(idx)?.into(),
+                value.data_type(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use arrow::array::{Array, BinaryArray, Float64Array, Int64Array, StringArray};
+    use arrow::datatypes::DataType;
+
+    use crate::types::Type;
+    use crate::{Connection, Error, Result};
+
+    #[test]
+    fn test_query_arrow() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE foo(i INTEGER, r DOUBLE, t VARCHAR(10), b BLOB, n NUMERIC);
+             INSERT INTO foo VALUES(1, 1.5, 'a', x'01', 1);
+             INSERT INTO foo VALUES(NULL, 2, NULL, NULL, 2);
+             INSERT INTO foo VALUES(3, NULL, 'c', x'', NULL);",
+        )?;
+        let mut stmt = db.prepare("SELECT *, i * 2 AS e FROM foo ORDER BY rowid")?;
+        let batches = stmt.query_arrow([], 2)?;
+        let schema = batches.schema();
+        let types: Vec<&DataType> = schema.fields().iter().map(|f| f.data_type()).collect();
+        assert_eq!(
+            types,
+            vec![
+                &DataType::Int64,
+                &DataType::Float64,
+                &DataType::Utf8,
+                &DataType::Binary,
+                &DataType::Int64,
+                &DataType::Int64,
+            ]
+        );
+        assert_eq!(schema.field(5).name(), "e");
+
+        let batches = batches.collect::<Result<Vec<_>>>()?;
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].num_rows(), 2);
+        assert_eq!(batches[1].num_rows(), 1);
+
+        let batch = &batches[0];
+        let i = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(i.value(0), 1);
+        assert!(i.is_null(1));
+        let r = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!((r.value(0), r.value(1)), (1.5, 2.0));
+        let t = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(t.value(0), "a");
+        assert!(t.is_null(1));
+        let b = batches[1]
+            .column(3)
+            .as_any()
+            .downcast_ref::<BinaryArray>()
+            .unwrap();
+        assert_eq!(b.value(0), b"");
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_arrow_empty() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        let mut stmt = db.prepare("SELECT 1 AS x WHERE 0")?;
+        let batches = stmt.query_arrow([], 10)?;
+        assert_eq!(batches.schema().field(0).data_type(), &DataType::Utf8);
+        assert_eq!(batches.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_arrow_type_mismatch() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE foo(i INTEGER);
+             INSERT INTO foo VALUES(1);
+             INSERT INTO foo VALUES(x'00');",
+        )?;
+        let mut stmt = db.prepare("SELECT i FROM foo ORDER BY rowid")?;
+        match stmt.query_arrow([], 10)?.next().unwrap().unwrap_err() {
+            Error::InvalidColumnType(0, name, Type::Blob) => assert_eq!(name, "i"),
+            err => panic!("Unexpected error {}", err),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+//! [Automatic extensions](https://sqlite.org/c3ref/auto_extension.html),
+//! initialized on every connection opened afterwards in the process.
+//!
+//! The entry points are those of loadable extensions, as generated by
+//! `#[sqlite_extension_init]` with the `extension_init` feature, so that
+//! the same extension can be either loaded or linked statically.
+use std::mem;
+use std::os::raw::{c_char, c_int, c_void};
+
+use crate::error::error_from_sqlite_code;
+use crate::{ffi, Result};
+
+/// Entry point of an extension: called with the connection being opened,
+/// where to put an error message, allocated with `sqlite3_malloc`, and the
+/// API routines of the host.
+pub type RawAutoExtension = unsafe extern "C" fn(
+    db: *mut ffi::sqlite3,
+    pz_err_msg: *mut *mut c_char,
+    p_api: *const c_void,
+) -> c_int;
+
+// `sqlite3_auto_extension` declares its argument as `void (*)(void)`.
+fn entry_point(ax: RawAutoExtension) -> Option<unsafe extern "C" fn()> {
+    Some(unsafe { mem::transmute::<RawAutoExtension, unsafe extern "C" fn()>(ax) })
+}
+
+/// Register `ax` to be called on each new connection. Registering the same
+/// entry point again is a no-op. Opening a connection fails when `ax`
+/// returns an error.
+///
+/// # Safety
+///
+/// `ax` is called by SQLite, while opening connections on any thread: it
+/// must be a valid entry point.
+pub unsafe fn register(ax: RawAutoExtension) -> Result<()> {
+    let rc = ffi::sqlite3_auto_extension(entry_point(ax));
+    if rc == ffi::SQLITE_OK {
+        Ok(())
+    } else {
+        Err(error_from_sqlite_code(rc, None))
+    }
+}
+
+/// Unregister `ax`, returning whether it was registered. Connections
+/// already opened are not affected.
+#[cfg(feature = "modern_sqlite")] // SQLite >= 3.8.7
+pub fn unregister(ax: RawAutoExtension) -> bool {
+    unsafe { ffi::sqlite3_cancel_auto_extension(entry_point(ax)) == 1 }
+}
+
+/// Unregister all the automatic extensions.
+pub fn reset() {
+    unsafe { ffi::sqlite3_reset_auto_extension() }
+}
@@ -128,6 +128,49 @@ impl Connection {
             More => unreachable!(),
         }
     }
+
+    /// `feature = "backup"` Writes a compacted copy of the `name` database to
+    /// the given destination path, which must not exist or be an empty file.
+    ///
+    /// With `feature = "modern_sqlite"`, this runs `VACUUM INTO`, which
+    /// leaves out the free pages and defragments the copy. Otherwise, the
+    /// database is copied as is with the backup API.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the destination path is not valid UTF-8, or cannot
+    /// be written to, or if the copy fails.
+    pub fn vacuum_into<P: AsRef<Path>>(&self, name: DatabaseName<'_>, dst_path: P) -> Result<()> {
+        #[cfg(feature = "modern_sqlite")] // 3.27.0
+        {
+            use crate::Error;
+            let dst_path = dst_path.as_ref();
+            let dst_path = dst_path
+                .to_str()
+                .ok_or_else(|| Error::InvalidPath(dst_path.to_owned()))?;
+            let schema = match name {
+                DatabaseName::Main => "main",
+                DatabaseName::Temp => "temp",
+                DatabaseName::Attached(s) => s,
+            };
+            self.execute(
+                &format!("VACUUM {} INTO ?", crate::util::quote_identifier(schema)),
+                [dst_path],
+            )
+            .map(|_| ())
+        }
+        #[cfg(not(feature = "modern_sqlite"))]
+        {
+            // Like `VACUUM INTO`, never overwrite a database.
+            if std::fs::metadata(dst_path.as_ref()).map_or(false, |m| m.len() > 0) {
+                return Err(error_from_sqlite_code(
+                    ffi::SQLITE_ERROR,
+                    Some("output file already exists".to_owned()),
+                ));
+            }
+            self.backup(name, dst_path, None)
+        }
+    }
 }
 
 /// `feature = "backup"` Possible successful results of calling [`Backup::step`].
@@ -200,8 +243,8 @@ impl Backup<'_, '_> {
         to: &'b mut Connection,
         to_name: DatabaseName<'_>,
     ) -> Result<Backup<'a, 'b>> {
-        let to_name = to_name.to_cstring()?;
-        let from_name = from_name.to_cstring()?;
+        let to_name = to_name.to_sql_str()?;
+        let from_name = from_name.to_sql_str()?;
 
         let to_db = to.db.borrow_mut().db;
 
@@ -282,6 +325,26 @@ impl Backup<'_, '_> {
         pages_per_step: c_int,
         pause_between_pages: Duration,
         progress: Option<fn(Progress)>,
+    ) -> Result<()> {
+        self.run_to_completion_with(pages_per_step, pause_between_pages, |p| {
+            if let Some(progress) = progress {
+                progress(p)
+            }
+        })
+    }
+
+    /// Like [`run_to_completion`](Backup::run_to_completion), but `progress`
+    /// can be any closure, e.g. one updating a progress bar or collecting
+    /// statistics.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if any of the calls to [`step`](Backup::step) return `Err`.
+    pub fn run_to_completion_with<F: FnMut(Progress)>(
+        &self,
+        pages_per_step: c_int,
+        pause_between_pages: Duration,
+        mut progress: F,
     ) -> Result<()> {
         use self::StepResult::{Busy, Done, Locked, More};
 
@@ -289,9 +352,7 @@ impl Backup<'_, '_> {
 
         loop {
             let r = self.step(pages_per_step)?;
-            if let Some(progress) = progress {
-                progress(self.progress())
-            }
+            progress(self.progress());
             match r {
                 More | Busy | Locked => thread::sleep(pause_between_pages),
                 Done => return Ok(()),
@@ -344,6 +405,63 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_backup_progress() -> Result<()> {
+        let src = Connection::open_in_memory()?;
+        src.execute_batch(
+            "CREATE TABLE foo(x BLOB);
+             INSERT INTO foo VALUES(zeroblob(100000));",
+        )?;
+        let mut dst = Connection::open_in_memory()?;
+
+        let mut steps = Vec::new();
+        {
+            let backup = Backup::new(&src, &mut dst)?;
+            backup.run_to_completion_with(10, Duration::from_millis(0), |p| {
+                steps.push((p.remaining, p.pagecount))
+            })?;
+        }
+        assert!(steps.len() > 1);
+        let pagecount = steps[0].1;
+        assert_eq!(steps[0].0, pagecount - 10);
+        assert_eq!(*steps.last().unwrap(), (0, pagecount));
+
+        let len: i64 = dst.query_row("SELECT length(x) FROM foo", [], |r| r.get(0))?;
+        assert_eq!(len, 100_000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_vacuum_into() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let src = Connection::open_in_memory()?;
+        src.execute_batch(
+            "CREATE TABLE foo(x BLOB);
+             INSERT INTO foo VALUES(42), (zeroblob(100000));
+             DELETE FROM foo WHERE x != 42;",
+        )?;
+        let path = dir.path().join("snapshot.db");
+        src.vacuum_into(DatabaseName::Main, &path)?;
+
+        let dst = Connection::open(&path)?;
+        let the_answer: i64 = dst.query_row("SELECT x FROM foo", [], |r| r.get(0))?;
+        assert_eq!(42, the_answer);
+        #[cfg(feature = "modern_sqlite")]
+        {
+            let free: i64 = dst.query_row("PRAGMA freelist_count", [], |r| r.get(0))?;
+            assert_eq!(free, 0);
+        }
+        drop(dst);
+        // The destination must be empty.
+        let err = src.vacuum_into(DatabaseName::Main, &path).unwrap_err();
+        assert!(
+            err.to_string().contains("output file already exists"),
+            "{}",
+            err
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_backup_temp() -> Result<()> {
         let src = Connection::open_in_memory()?;
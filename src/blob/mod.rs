@@ -192,7 +192,7 @@ use std::ptr;
 
 use super::ffi;
 use super::types::{ToSql, ToSqlOutput};
-use crate::{Connection, DatabaseName, Result};
+use crate::{Connection, DatabaseName, Error, Result};
 
 mod pos_io;
 
@@ -213,7 +213,7 @@ impl Connection {
     ///
     /// Will return `Err` if `db`/`table`/`column` cannot be converted to a
     /// C-compatible string or if the underlying SQLite BLOB open call
-    /// fails.
+    /// fails, with [`Error::WithoutRowid`] for a `WITHOUT ROWID` table.
     #[inline]
     pub fn blob_open<'a>(
         &'a self,
@@ -223,9 +223,10 @@ impl Connection {
         row_id: i64,
         read_only: bool,
     ) -> Result<Blob<'a>> {
+        let (db_name, table_name) = (db, table);
         let mut c = self.db.borrow_mut();
         let mut blob = ptr::null_mut();
-        let db = db.to_cstring()?;
+        let db = db.to_sql_str()?;
         let table = super::str_to_cstring(table)?;
         let column = super::str_to_cstring(column)?;
         let rc = unsafe {
@@ -239,11 +240,21 @@ impl Connection {
                 &mut blob,
             )
         };
-        c.decode_result(rc).map(|_| Blob {
-            conn: self,
-            blob,
-            pos: 0,
-        })
+        let result = c.decode_result(rc);
+        drop(c);
+        match result {
+            Ok(()) => Ok(Blob {
+                conn: self,
+                blob,
+                pos: 0,
+            }),
+            Err(err) => match self.table_options(Some(db_name), table_name) {
+                Ok(Some(options)) if options.without_rowid => {
+                    Err(Error::WithoutRowid(table_name.to_owned()))
+                }
+                _ => Err(err),
+            },
+        }
     }
 }
 
@@ -406,7 +417,7 @@ impl Drop for Blob<'_> {
 /// later written using incremental BLOB I/O routines.
 ///
 /// A negative value for the zeroblob results in a zero-length BLOB.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct ZeroBlob(pub i32);
 
 impl ToSql for ZeroBlob {
@@ -419,8 +430,9 @@ impl ToSql for ZeroBlob {
 
 #[cfg(test)]
 mod test {
-    use crate::{Connection, DatabaseName, Result};
-    use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+    use super::ZeroBlob;
+    use crate::{Connection, DatabaseName, Error, Result};
+    use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 
     fn db_with_test_blob() -> Result<(Connection, i64)> {
         let db = Connection::open_in_memory()?;
@@ -482,6 +494,17 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_blob_without_rowid() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch("CREATE TABLE test (id INTEGER PRIMARY KEY, content BLOB) WITHOUT ROWID")?;
+        match db.blob_open(DatabaseName::Main, "test", "content", 1, true) {
+            Err(Error::WithoutRowid(table)) => assert_eq!(table, "test"),
+            _ => panic!("expected WithoutRowid"),
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_blob_in_bufreader() -> Result<()> {
         let (db, rowid) = db_with_test_blob()?;
@@ -546,4 +569,59 @@ mod test {
             Ok(())
         }
     }
+
+    #[test]
+    fn test_blob_reopen_scan() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch("CREATE TABLE test (content BLOB)")?;
+        {
+            let mut stmt = db.prepare("INSERT INTO test VALUES (?)")?;
+            for i in 0..1000 {
+                stmt.execute([ZeroBlob(i % 17)])?;
+            }
+        }
+
+        // One handle, moved from row to row.
+        let mut blob = db.blob_open(DatabaseName::Main, "test", "content", 1, false)?;
+        let mut total = 0;
+        for rowid in 1..=1000 {
+            blob.reopen(rowid)?;
+            blob.write_all(&vec![1; blob.len()]).unwrap();
+            total += blob.len();
+        }
+        blob.close()?;
+        let sum: i64 = db.query_row(
+            "SELECT sum(length(replace(hex(content), '01', ''))) FROM test",
+            [],
+            |r| r.get(0),
+        )?;
+        assert_eq!(sum, 0);
+        let len: i64 = db.query_row("SELECT sum(length(content)) FROM test", [], |r| r.get(0))?;
+        assert_eq!(len as usize, total);
+        Ok(())
+    }
+
+    #[test]
+    fn test_blob_stream() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch("CREATE TABLE test (content BLOB)")?;
+        // Large enough to span many pages, and not a multiple of the buffers.
+        let len: usize = 8 * 1024 * 1024 + 7;
+        db.execute("INSERT INTO test VALUES (?)", [ZeroBlob(len as i32)])?;
+        let rowid = db.last_insert_rowid();
+
+        let source = (0..len).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let mut blob = db.blob_open(DatabaseName::Main, "test", "content", rowid, false)?;
+        assert_eq!(io::copy(&mut &source[..], &mut blob).unwrap(), len as u64);
+        blob.close()?;
+
+        let blob = db.blob_open(DatabaseName::Main, "test", "content", rowid, true)?;
+        let mut copy = Vec::new();
+        assert_eq!(
+            io::copy(&mut BufReader::new(blob), &mut copy).unwrap(),
+            len as u64
+        );
+        assert!(copy == source);
+        Ok(())
+    }
 }
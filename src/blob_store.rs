@@ -0,0 +1,299 @@
+//! `feature = "blob_store"` Store deduplicated blobs, addressed by their
+//! SHA-256 hash.
+//!
+//! Each blob is stored once, however many times it is [put](BlobStore::put),
+//! with a reference count. [`release`](BlobStore::release) decrements it, and
+//! [`collect_garbage`](BlobStore::collect_garbage) deletes the blobs which are
+//! not referenced anymore.
+//!
+//! ```rust,no_run
+//! use rusqlite::blob_store::{BlobStore, Hash};
+//! use rusqlite::{Connection, Result};
+//!
+//! fn archive(conn: &Connection, reports: &[Vec<u8>]) -> Result<Vec<Hash>> {
+//!     let store = BlobStore::new(conn)?;
+//!     reports.iter().map(|report| store.put(report)).collect()
+//! }
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use sha2::{Digest, Sha256};
+
+use crate::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use crate::util::quote_identifier;
+use crate::{params, Connection, OptionalExtension, Result, Savepoint};
+
+/// The SHA-256 hash of a blob.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Hash([u8; 32]);
+
+impl Hash {
+    /// Hashes `data`.
+    #[inline]
+    pub fn of(data: &[u8]) -> Hash {
+        Hash(Sha256::digest(data).into())
+    }
+
+    /// Creates a hash from its bytes.
+    #[inline]
+    pub fn from_bytes(bytes: [u8; 32]) -> Hash {
+        Hash(bytes)
+    }
+
+    /// Returns the bytes of the hash.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+/// Lowercase hexadecimal.
+impl fmt::Display for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in &self.0 {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+/// Error returned when parsing a [`Hash`] which is not 64 hexadecimal digits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseHashError;
+
+impl fmt::Display for ParseHashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid SHA-256 hash")
+    }
+}
+
+impl std::error::Error for ParseHashError {}
+
+impl FromStr for Hash {
+    type Err = ParseHashError;
+
+    fn from_str(s: &str) -> std::result::Result<Hash, ParseHashError> {
+        fn digit(c: u8) -> std::result::Result<u8, ParseHashError> {
+            match c {
+                b'0'..=b'9' => Ok(c - b'0'),
+                b'a'..=b'f' => Ok(c - b'a' + 10),
+                b'A'..=b'F' => Ok(c - b'A' + 10),
+                _ => Err(ParseHashError),
+            }
+        }
+
+        let s = s.as_bytes();
+        if s.len() != 64 {
+            return Err(ParseHashError);
+        }
+        let mut bytes = [0; 32];
+        for (byte, digits) in bytes.iter_mut().zip(s.chunks(2)) {
+            *byte = digit(digits[0])? << 4 | digit(digits[1])?;
+        }
+        Ok(Hash(bytes))
+    }
+}
+
+/// 32-byte blob.
+impl ToSql for Hash {
+    #[inline]
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(&self.0[..]))
+    }
+}
+
+/// 32-byte blob.
+impl FromSql for Hash {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let blob = value.as_blob()?;
+        let mut bytes = [0; 32];
+        if blob.len() != bytes.len() {
+            return Err(FromSqlError::InvalidType);
+        }
+        bytes.copy_from_slice(blob);
+        Ok(Hash(bytes))
+    }
+}
+
+/// A store of deduplicated, reference-counted blobs, in a table of a
+/// connection.
+#[derive(Debug)]
+pub struct BlobStore<'conn> {
+    conn: &'conn Connection,
+    table: String,
+}
+
+impl BlobStore<'_> {
+    /// Opens the store in the `rusqlite_blob_store` table, which is created if
+    /// it doesn't exist.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the table cannot be created.
+    #[inline]
+    pub fn new(conn: &Connection) -> Result<BlobStore<'_>> {
+        BlobStore::with_table(conn, "rusqlite_blob_store")
+    }
+
+    /// Opens the store in `table`, which is created if it doesn't exist.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the table cannot be created.
+    pub fn with_table<'conn>(conn: &'conn Connection, table: &str) -> Result<BlobStore<'conn>> {
+        let table = quote_identifier(table);
+        conn.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                hash BLOB PRIMARY KEY NOT NULL,
+                data BLOB NOT NULL,
+                refs INTEGER NOT NULL
+            )",
+            table
+        ))?;
+        Ok(BlobStore { conn, table })
+    }
+
+    /// Stores `data` if it is not stored yet, adds a reference to it, and
+    /// returns its hash.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite call fails.
+    pub fn put(&self, data: &[u8]) -> Result<Hash> {
+        let hash = Hash::of(data);
+        let sp = Savepoint::with_depth_and_name(self.conn, 0, "_rusqlite_blob_store")?;
+        self.conn
+            .prepare_cached(&format!(
+                "INSERT OR IGNORE INTO {} (hash, data, refs) VALUES (?, ?, 0)",
+                self.table
+            ))?
+            .execute(params![hash, data])?;
+        self.add_ref(&hash)?;
+        sp.commit()?;
+        Ok(hash)
+    }
+
+    /// Returns the blob with `hash`, or `None` if it is not stored.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite call fails.
+    pub fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>> {
+        self.conn
+            .prepare_cached(&format!("SELECT data FROM {} WHERE hash = ?", self.table))?
+            .query_row([hash], |row| row.get(0))
+            .optional()
+    }
+
+    /// Returns the number of references to the blob with `hash`, or `None`
+    /// if it is not stored.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite call fails.
+    pub fn ref_count(&self, hash: &Hash) -> Result<Option<u64>> {
+        self.conn
+            .prepare_cached(&format!("SELECT refs FROM {} WHERE hash = ?", self.table))?
+            .query_row([hash], |row| row.get::<_, i64>(0))
+            .optional()
+            .map(|refs| refs.map(|refs| refs as u64))
+    }
+
+    /// Adds a reference to the blob with `hash`, and returns whether it is
+    /// stored.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite call fails.
+    pub fn add_ref(&self, hash: &Hash) -> Result<bool> {
+        self.conn
+            .prepare_cached(&format!(
+                "UPDATE {} SET refs = refs + 1 WHERE hash = ?",
+                self.table
+            ))?
+            .execute([hash])
+            .map(|n| n > 0)
+    }
+
+    /// Removes a reference to the blob with `hash`, and returns whether it
+    /// had any. The blob is kept until the next
+    /// [`collect_garbage`](BlobStore::collect_garbage).
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite call fails.
+    pub fn release(&self, hash: &Hash) -> Result<bool> {
+        self.conn
+            .prepare_cached(&format!(
+                "UPDATE {} SET refs = refs - 1 WHERE hash = ? AND refs > 0",
+                self.table
+            ))?
+            .execute([hash])
+            .map(|n| n > 0)
+    }
+
+    /// Deletes all the blobs which are not referenced, and returns their
+    /// number.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite call fails.
+    pub fn collect_garbage(&self) -> Result<usize> {
+        self.conn
+            .execute(&format!("DELETE FROM {} WHERE refs <= 0", self.table), [])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BlobStore, Hash};
+    use crate::{Connection, Result};
+
+    #[test]
+    fn test_hash() {
+        let hash = Hash::of(b"abc");
+        let hex = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert_eq!(hash.to_string(), hex);
+        assert_eq!(hex.parse(), Ok(hash));
+        assert_eq!(hex.to_uppercase().parse(), Ok(hash));
+        assert!(hex[1..].parse::<Hash>().is_err());
+        assert!(hex.replace('a', "g").parse::<Hash>().is_err());
+        // Only hex digits, without signs.
+        assert!(format!("+{}", &hex[1..]).parse::<Hash>().is_err());
+        assert!(format!("{}-1", &hex[2..]).parse::<Hash>().is_err());
+    }
+
+    #[test]
+    fn test_blob_store() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        let store = BlobStore::new(&db)?;
+        let a = store.put(b"first")?;
+        let b = store.put(b"second")?;
+        assert_eq!(store.put(b"first")?, a);
+        assert_ne!(a, b);
+        assert_eq!(store.get(&a)?.as_deref(), Some(&b"first"[..]));
+        assert_eq!(store.ref_count(&a)?, Some(2));
+        assert_eq!(
+            db.query_row("SELECT count(*) FROM rusqlite_blob_store", [], |r| r
+                .get::<_, i64>(0))?,
+            2
+        );
+
+        assert!(store.release(&a)?);
+        assert!(store.release(&b)?);
+        assert!(!store.release(&b)?);
+        assert_eq!(store.collect_garbage()?, 1);
+        assert_eq!(store.get(&b)?, None);
+        assert_eq!(store.ref_count(&b)?, None);
+        assert!(!store.add_ref(&b)?);
+        assert_eq!(store.ref_count(&a)?, Some(1));
+
+        // Stores in other tables are independent.
+        let other = BlobStore::with_table(&db, "other \"store\"")?;
+        assert_eq!(other.get(&a)?, None);
+        assert_eq!(other.put(b"first")?, a);
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+use crate::types::ToSql;
+use crate::util::quote_identifier;
+use crate::{Connection, Error, OptionalExtension, Result};
+
+/// The work done so far by [`Connection::update_in_batches`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BatchProgress {
+    /// The number of batches run, each in its own transaction.
+    pub batches: usize,
+    /// The number of rows changed by all the batches.
+    pub changes: usize,
+    /// The first rowid of the next batch.
+    pub next_rowid: i64,
+    /// The largest rowid of the table when the first batch started: rows
+    /// inserted after that are not processed.
+    pub last_rowid: i64,
+}
+
+impl BatchProgress {
+    /// Returns `true` if all the rows have been processed.
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        self.next_rowid > self.last_rowid
+    }
+}
+
+impl Connection {
+    /// Runs `sql`, an `UPDATE` or `DELETE` of `table`, by batches of rows,
+    /// each in its own transaction, so that the write lock is released
+    /// between batches for other connections.
+    ///
+    /// `sql` must restrict the rows changed to the ones whose rowid is
+    /// between the `:batch_start` and `:batch_end` parameters, bound to
+    /// ranges of rowids holding `batch_size` rows, from the smallest rowid of
+    /// the table to the largest; its other parameters are bound to
+    /// `params`. After each batch, `progress` is called, and the remaining
+    /// batches are skipped if it returns `false`, e.g. when a time budget is
+    /// spent. A `DELETE` resumes when run again; an `UPDATE` can also
+    /// restrict the rows to the ones from the returned `next_rowid`.
+    ///
+    /// Rowid ranges, rather than `LIMIT`, make sure each row is visited
+    /// once, even when `sql` does not make it ineligible for the next
+    /// batches. Each range ends at the rowid of its last row, so that gaps
+    /// between rowids don't make batches smaller. The table must have
+    /// rowids.
+    ///
+    /// ```rust,no_run
+    /// # use rusqlite::{Connection, Result};
+    /// fn purge(conn: &Connection, before: &str) -> Result<usize> {
+    ///     let done = conn.update_in_batches(
+    ///         "events",
+    ///         "DELETE FROM events WHERE created < :before \
+    ///          AND rowid BETWEEN :batch_start AND :batch_end",
+    ///         &[(":before", &before)],
+    ///         10_000,
+    ///         |progress| {
+    ///             println!("{} events deleted", progress.changes);
+    ///             true
+    ///         },
+    ///     )?;
+    ///     Ok(done.changes)
+    /// }
+    /// ```
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `table` is a `WITHOUT ROWID` table, if `sql`
+    /// does not have the `:batch_start` and `:batch_end` parameters, or if
+    /// the underlying SQLite calls fail. The batches already committed are
+    /// kept.
+    pub fn update_in_batches<F>(
+        &self,
+        table: &str,
+        sql: &str,
+        params: &[(&str, &dyn ToSql)],
+        batch_size: u32,
+        mut progress: F,
+    ) -> Result<BatchProgress>
+    where
+        F: FnMut(&BatchProgress) -> bool,
+    {
+        self.check_rowid(None, table)?;
+        let mut stmt = self.prepare(sql)?;
+        let parameter_index = |name: &str| {
+            stmt.parameter_index(name)?
+                .ok_or_else(|| Error::InvalidParameterName(name.to_owned()))
+        };
+        let start_index = parameter_index(":batch_start")?;
+        let end_index = parameter_index(":batch_end")?;
+        let params = params
+            .iter()
+            .map(|&(name, value)| Ok((parameter_index(name)?, value)))
+            .collect::<Result<Vec<_>>>()?;
+        for (index, value) in params {
+            stmt.raw_bind_parameter(index, value)?;
+        }
+
+        let (first, last): (Option<i64>, Option<i64>) = self.query_row(
+            &format!(
+                "SELECT min(rowid), max(rowid) FROM {}",
+                quote_identifier(table)
+            ),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let (first, last) = match (first, last) {
+            (Some(first), Some(last)) => (first, last),
+            // An empty table.
+            _ => return Ok(BatchProgress::default()),
+        };
+        let mut end_stmt = self.prepare(&format!(
+            "SELECT rowid FROM {} WHERE rowid >= ?1 ORDER BY rowid LIMIT 1 OFFSET ?2",
+            quote_identifier(table)
+        ))?;
+        let mut done = BatchProgress {
+            batches: 0,
+            changes: 0,
+            next_rowid: first,
+            last_rowid: last,
+        };
+        let batch_size = i64::from(batch_size.max(1));
+        while !done.is_done() {
+            let start = done.next_rowid;
+            let tx = self.unchecked_transaction()?;
+            let end = end_stmt
+                .query_row([start, batch_size - 1], |row| row.get::<_, i64>(0))
+                .optional()?
+                .map_or(last, |end| end.min(last));
+            stmt.raw_bind_parameter(start_index, start)?;
+            stmt.raw_bind_parameter(end_index, end)?;
+            let changes = stmt.raw_execute()?;
+            tx.commit()?;
+
+            done.batches += 1;
+            done.changes += changes;
+            // `last` is at most i64::MAX, so this only overflows when done.
+            done.next_rowid = end.wrapping_add(1);
+            if end == i64::MAX {
+                break;
+            }
+            if !done.is_done() && !progress(&done) {
+                break;
+            }
+        }
+        Ok(done)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Connection, Error, Result};
+
+    fn events() -> Result<Connection> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE events (id INTEGER PRIMARY KEY, kind TEXT, done INTEGER DEFAULT 0);
+             WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 95)
+             INSERT INTO events (id, kind) SELECT i, CASE i % 2 WHEN 0 THEN 'even' ELSE 'odd' END FROM n;",
+        )?;
+        Ok(db)
+    }
+
+    #[test]
+    fn test_update_in_batches() -> Result<()> {
+        let db = events()?;
+        let mut calls = Vec::new();
+        let done = db.update_in_batches(
+            "events",
+            "UPDATE events SET done = done + 1 WHERE kind = :kind \
+             AND rowid BETWEEN :batch_start AND :batch_end",
+            &[(":kind", &"odd")],
+            10,
+            |progress| {
+                calls.push(progress.changes);
+                true
+            },
+        )?;
+        assert_eq!(done.batches, 10);
+        assert_eq!(done.changes, 48);
+        assert!(done.is_done());
+        // Not called after the last batch.
+        assert_eq!(calls.len(), 9);
+        assert_eq!(calls[0], 5);
+        // Each row is updated once.
+        let max: i64 = db.query_row("SELECT max(done) FROM events", [], |r| r.get(0))?;
+        assert_eq!(max, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_in_batches_resumes() -> Result<()> {
+        let db = events()?;
+        let sql = "DELETE FROM events WHERE rowid BETWEEN :batch_start AND :batch_end";
+        let mut budget = 3;
+        let done = db.update_in_batches("events", sql, &[], 20, |_| {
+            budget -= 1;
+            budget > 0
+        })?;
+        assert_eq!(done.batches, 3);
+        assert_eq!(done.changes, 60);
+        assert_eq!(done.next_rowid, 61);
+        assert!(!done.is_done());
+
+        let done = db.update_in_batches("events", sql, &[], 20, |_| true)?;
+        assert_eq!((done.batches, done.changes), (2, 35));
+        let done = db.update_in_batches("events", sql, &[], 20, |_| true)?;
+        assert_eq!(done.batches, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sparse_rowids() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE t (x);
+             WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 10)
+             INSERT INTO t (rowid, x) SELECT i * 1000000000000, i FROM n;",
+        )?;
+        let done = db.update_in_batches(
+            "t",
+            "UPDATE t SET x = -x WHERE rowid BETWEEN :batch_start AND :batch_end",
+            &[],
+            3,
+            |_| true,
+        )?;
+        assert_eq!((done.batches, done.changes), (4, 10));
+        assert!(done.is_done());
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_in_batches_errors() -> Result<()> {
+        let db = events()?;
+        match db
+            .update_in_batches("events", "DELETE FROM events", &[], 10, |_| true)
+            .unwrap_err()
+        {
+            Error::InvalidParameterName(name) => assert_eq!(name, ":batch_start"),
+            err => panic!("Unexpected error {}", err),
+        }
+        db.execute_batch("CREATE TABLE calls (id INTEGER PRIMARY KEY) WITHOUT ROWID")?;
+        db.update_in_batches(
+            "calls",
+            "DELETE FROM calls WHERE id BETWEEN :batch_start AND :batch_end",
+            &[],
+            10,
+            |_| true,
+        )
+        .unwrap_err();
+        Ok(())
+    }
+}
@@ -0,0 +1,429 @@
+//! Step statements from async code, yielding instead of blocking while the
+//! database is busy.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use fallible_streaming_iterator::FallibleStreamingIterator;
+
+use crate::{Params, Result, Row, Rows, Statement};
+
+/// How the futures of [`Statement::execute_yielding`] and
+/// [`Rows::next_yielding`] retry a step which fails with `SQLITE_BUSY`.
+///
+/// The first retry is after `initial`, and each following one after twice
+/// the previous delay; no delay is longer than `max`. Once `timeout` has elapsed since the
+/// first attempt, the next failure is returned as the result.
+#[derive(Clone, Copy, Debug)]
+pub struct BusyBackoff {
+    initial: Duration,
+    max: Duration,
+    timeout: Duration,
+}
+
+impl Default for BusyBackoff {
+    /// Retries after 1ms, up to every 100ms, for 5s like the default
+    /// [`busy_timeout`](crate::Connection::busy_timeout).
+    fn default() -> BusyBackoff {
+        BusyBackoff {
+            initial: Duration::from_millis(1),
+            max: Duration::from_millis(100),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl BusyBackoff {
+    /// Sets the delay before the first retry.
+    #[must_use]
+    pub fn initial(mut self, initial: Duration) -> Self {
+        self.initial = initial;
+        self
+    }
+
+    /// Sets the longest delay between two retries.
+    #[must_use]
+    pub fn max(mut self, max: Duration) -> Self {
+        self.max = max;
+        self
+    }
+
+    /// Sets how long to retry before failing with `SQLITE_BUSY`.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// The timer of the executor polling the futures of
+/// [`Statement::execute_yielding`] and [`Rows::next_yielding`], which waits
+/// before each retry.
+///
+/// It is implemented by the closures returning a future completing after the
+/// given delay, e.g. `|delay| Box::pin(tokio::time::sleep(delay))`.
+pub trait Timer {
+    /// The future completing after a delay.
+    type Sleep: Future<Output = ()> + Unpin;
+
+    /// Returns a future completing after `delay`, which wakes the task
+    /// polling it then.
+    fn sleep(&mut self, delay: Duration) -> Self::Sleep;
+}
+
+impl<F, S> Timer for F
+where
+    F: FnMut(Duration) -> S,
+    S: Future<Output = ()> + Unpin,
+{
+    type Sleep = S;
+
+    fn sleep(&mut self, delay: Duration) -> S {
+        self(delay)
+    }
+}
+
+// The retry state of a future.
+struct Retry<T: Timer> {
+    backoff: BusyBackoff,
+    timer: T,
+    started: Option<Instant>,
+    delay: Duration,
+    // The delay before the next retry, if the last step was busy.
+    sleep: Option<T::Sleep>,
+}
+
+impl<T: Timer> Retry<T> {
+    fn new(backoff: BusyBackoff, timer: T) -> Retry<T> {
+        Retry {
+            backoff,
+            timer,
+            started: None,
+            delay: backoff.initial.min(backoff.max),
+            sleep: None,
+        }
+    }
+
+    // Whether a busy step can still be retried.
+    fn can_retry(&mut self) -> bool {
+        let started = *self.started.get_or_insert_with(Instant::now);
+        started.elapsed() < self.backoff.timeout
+    }
+
+    // Whether the last step was busy, and the statement left unreset.
+    fn is_waiting(&self) -> bool {
+        self.sleep.is_some()
+    }
+
+    // Polls the delay before the next retry, if any.
+    fn poll_delay(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if let Some(ref mut sleep) = self.sleep {
+            if Pin::new(sleep).poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            self.sleep = None;
+        }
+        Poll::Ready(())
+    }
+
+    // Starts the delay before the next retry.
+    fn schedule(&mut self) {
+        let delay = self.delay;
+        let max = self.backoff.max;
+        self.delay = delay.checked_mul(2).map_or(max, |d| d.min(max));
+        self.sleep = Some(self.timer.sleep(delay));
+    }
+}
+
+impl<'conn> Statement<'conn> {
+    /// Executes the prepared statement like
+    /// [`execute`](Statement::execute), in a future which, when the
+    /// database is busy, returns `Pending` and is woken again after a delay
+    /// given by `backoff` and waited by `timer`, instead of blocking the
+    /// thread.
+    ///
+    /// The busy handler of the connection still runs before `SQLITE_BUSY` is
+    /// returned: set a zero [`busy_timeout`](crate::Connection::busy_timeout)
+    /// so that steps fail at once. Dropping the future while it waits resets
+    /// the statement.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if binding parameters fails. The future returns the
+    /// errors of `execute`, including `SQLITE_BUSY` once the timeout of
+    /// `backoff` has elapsed.
+    pub fn execute_yielding<P: Params, T: Timer>(
+        &mut self,
+        params: P,
+        backoff: BusyBackoff,
+        timer: T,
+    ) -> Result<ExecuteYielding<'_, 'conn, T>> {
+        params.__bind_in(self)?;
+        self.check_update()?;
+        Ok(ExecuteYielding {
+            stmt: self,
+            retry: Retry::new(backoff, timer),
+        })
+    }
+}
+
+/// The future of [`Statement::execute_yielding`], returning the number of
+/// rows changed.
+#[must_use = "futures do nothing unless polled"]
+pub struct ExecuteYielding<'stmt, 'conn, T: Timer> {
+    stmt: &'stmt mut Statement<'conn>,
+    retry: Retry<T>,
+}
+
+impl<T: Timer + Unpin> Future for ExecuteYielding<'_, '_, T> {
+    type Output = Result<usize>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            if this.retry.poll_delay(cx).is_pending() {
+                return Poll::Pending;
+            }
+            let can_retry = this.retry.can_retry();
+            match this.stmt.execute_step(can_retry) {
+                Some(result) => return Poll::Ready(result),
+                None => this.retry.schedule(),
+            }
+        }
+    }
+}
+
+impl<T: Timer> Drop for ExecuteYielding<'_, '_, T> {
+    fn drop(&mut self) {
+        if self.retry.is_waiting() {
+            self.stmt.reset();
+        }
+    }
+}
+
+impl<'stmt> Rows<'stmt> {
+    /// Gets the next row like [`next`](Rows::next), in a future which, when
+    /// the database is busy, returns `Pending` and is woken again after a
+    /// delay given by `backoff` and waited by `timer`, instead of blocking
+    /// the thread.
+    ///
+    /// As with [`Statement::execute_yielding`], the connection should have a
+    /// zero busy timeout. Dropping the future while it waits resets the
+    /// statement, which ends the rows.
+    pub fn next_yielding<T: Timer>(
+        &mut self,
+        backoff: BusyBackoff,
+        timer: T,
+    ) -> NextYielding<'_, 'stmt, T> {
+        NextYielding {
+            rows: Some(self),
+            retry: Retry::new(backoff, timer),
+        }
+    }
+}
+
+/// The future of [`Rows::next_yielding`], returning the next row, or `None`
+/// after the last one.
+#[must_use = "futures do nothing unless polled"]
+pub struct NextYielding<'a, 'stmt, T: Timer> {
+    rows: Option<&'a mut Rows<'stmt>>,
+    retry: Retry<T>,
+}
+
+impl<'a, 'stmt, T: Timer + Unpin> Future for NextYielding<'a, 'stmt, T> {
+    type Output = Result<Option<&'a Row<'stmt>>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        loop {
+            if this.retry.poll_delay(cx).is_pending() {
+                return Poll::Pending;
+            }
+            let rows = this
+                .rows
+                .take()
+                .expect("NextYielding polled after completion");
+            let can_retry = this.retry.can_retry();
+            match rows.advance_unless_busy(can_retry) {
+                Ok(true) => {
+                    let rows: &'a Rows<'stmt> = rows;
+                    return Poll::Ready(Ok(rows.get()));
+                }
+                Ok(false) => {
+                    this.rows = Some(rows);
+                    this.retry.schedule();
+                }
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+    }
+}
+
+impl<T: Timer> Drop for NextYielding<'_, '_, T> {
+    fn drop(&mut self) {
+        if let Some(ref mut rows) = self.rows {
+            if self.retry.is_waiting() {
+                rows.abandon();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::{BusyBackoff, Retry};
+    use crate::util::executor::{block_on, poll_once};
+    use crate::{Connection, Error, ErrorCode, Result};
+
+    // Completes at a deadline, waking the task from a thread, as the timer
+    // of an executor would.
+    struct Sleep {
+        until: Instant,
+        waking: bool,
+    }
+
+    impl Future for Sleep {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            let now = Instant::now();
+            if now >= self.until {
+                return Poll::Ready(());
+            }
+            if !self.waking {
+                self.waking = true;
+                let (delay, waker) = (self.until - now, cx.waker().clone());
+                thread::spawn(move || {
+                    thread::sleep(delay);
+                    waker.wake();
+                });
+            }
+            Poll::Pending
+        }
+    }
+
+    fn sleep(delay: Duration) -> Sleep {
+        Sleep {
+            until: Instant::now() + delay,
+            waking: false,
+        }
+    }
+
+    #[test]
+    fn test_retry_delays() {
+        let mut delays = Vec::new();
+        let backoff = BusyBackoff::default()
+            .initial(Duration::from_secs(10))
+            .max(Duration::from_secs(1));
+        let mut retry = Retry::new(backoff, |delay| {
+            delays.push(delay);
+            std::future::ready(())
+        });
+        retry.schedule();
+        retry.schedule();
+        assert_eq!(delays, vec![Duration::from_secs(1); 2]);
+
+        // Doubling the delay must not overflow.
+        let (initial, max) = (
+            Duration::from_secs(u64::MAX / 2 + 1),
+            Duration::from_secs(u64::MAX),
+        );
+        let mut delays = Vec::new();
+        let mut retry = Retry::new(BusyBackoff::default().initial(initial).max(max), |delay| {
+            delays.push(delay);
+            std::future::ready(())
+        });
+        retry.schedule();
+        retry.schedule();
+        assert_eq!(delays, vec![initial, max]);
+    }
+
+    #[test]
+    fn test_execute_yielding() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db3");
+        let db1 = Connection::open(&path)?;
+        db1.execute_batch("CREATE TABLE foo (x INTEGER)")?;
+        let db2 = Connection::open(&path)?;
+        db2.busy_timeout(Duration::from_secs(0))?;
+
+        db1.execute_batch("BEGIN IMMEDIATE")?;
+        let mut stmt = db2.prepare("INSERT INTO foo VALUES (?)")?;
+        let backoff = BusyBackoff::default().timeout(Duration::from_millis(20));
+        let (result, pending) = block_on(stmt.execute_yielding([1], backoff, sleep)?);
+        match result {
+            Err(Error::SqliteFailure(err, _)) => assert_eq!(err.code, ErrorCode::DatabaseBusy),
+            r => panic!("unexpected {:?}", r),
+        }
+        assert!(pending > 0);
+
+        // The lock is released while the future waits.
+        let committer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            db1.execute_batch("COMMIT")
+        });
+        let future = stmt.execute_yielding([2], BusyBackoff::default(), sleep)?;
+        let (result, pending) = block_on(future);
+        committer.join().unwrap()?;
+        assert_eq!(result?, 1);
+        assert!(pending > 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_yielding() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        let mut stmt = db.prepare("SELECT 1 UNION ALL SELECT 2")?;
+        let mut rows = stmt.query([])?;
+        let mut values = Vec::new();
+        loop {
+            let (row, pending) = block_on(rows.next_yielding(BusyBackoff::default(), sleep));
+            assert_eq!(pending, 0);
+            match row? {
+                Some(row) => values.push(row.get::<_, i64>(0)?),
+                None => break,
+            }
+        }
+        assert_eq!(values, vec![1, 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_drop_while_busy() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db3");
+        let db1 = Connection::open(&path)?;
+        db1.execute_batch("CREATE TABLE foo (x INTEGER); INSERT INTO foo VALUES (1);")?;
+        let db2 = Connection::open(&path)?;
+        db2.busy_timeout(Duration::from_secs(0))?;
+        let mut insert = db2.prepare("INSERT INTO foo VALUES (?)")?;
+        let mut select = db2.prepare("SELECT x FROM foo")?;
+        db1.execute_batch("BEGIN EXCLUSIVE")?;
+
+        let mut future = insert.execute_yielding([2], BusyBackoff::default(), sleep)?;
+        assert!(poll_once(&mut future).is_pending());
+        drop(future);
+        #[cfg(feature = "modern_sqlite")]
+        assert!(!insert.is_busy());
+
+        let mut rows = select.query([])?;
+        let mut future = rows.next_yielding(BusyBackoff::default(), sleep);
+        assert!(poll_once(&mut future).is_pending());
+        drop(future);
+        assert!(rows.next()?.is_none());
+        drop(rows);
+
+        db1.execute_batch("COMMIT")?;
+        let count: i64 = db2.query_row("SELECT count(*) FROM foo", [], |r| r.get(0))?;
+        assert_eq!(count, 1);
+        Ok(())
+    }
+}
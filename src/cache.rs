@@ -1,9 +1,9 @@
 //! Prepared statements cache for faster execution.
 
 use crate::raw_statement::RawStatement;
-use crate::{Connection, Result, Statement};
+use crate::{ffi, Connection, Result, Statement};
 use hashlink::LruCache;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
 
@@ -50,15 +50,93 @@ impl Connection {
     }
 
     /// Remove/finalize all prepared statements currently in the cache.
+    ///
+    /// SQLite re-prepares a cached statement when the schema changes, but
+    /// one which refers to a dropped table or column then fails; flushing
+    /// the cache after a migration avoids these errors.
     #[inline]
     pub fn flush_prepared_statement_cache(&self) {
         self.cache.flush()
     }
+
+    /// Returns the counters of the prepared statement cache, since the
+    /// connection was opened.
+    #[inline]
+    pub fn prepared_statement_cache_stats(&self) -> StatementCacheStats {
+        self.cache.stats()
+    }
+
+    /// Returns the SQL of the prepared statements currently in the cache,
+    /// from least to most recently used. Statements which are in use are not
+    /// in the cache.
+    ///
+    /// Together with
+    /// [`prime_prepared_statement_cache`](Connection::prime_prepared_statement_cache),
+    /// this allows warming up the cache of a new connection with the
+    /// statements used by another one.
+    #[inline]
+    pub fn prepared_statement_cache_sql(&self) -> Vec<String> {
+        self.cache.sql()
+    }
+
+    /// Prepare each statement of `sql` and put it in the cache, so that
+    /// later calls to [`prepare_cached`](Connection::prepare_cached) don't
+    /// have to. Statements are cached in order, so the last one is the most
+    /// recently used; beyond the capacity of the cache, the first ones are
+    /// evicted.
+    ///
+    /// ```rust,no_run
+    /// # use rusqlite::{Connection, Result};
+    /// fn open_like(conn: &Connection, path: &str) -> Result<Connection> {
+    ///     let new_conn = Connection::open(path)?;
+    ///     new_conn.prime_prepared_statement_cache(conn.prepared_statement_cache_sql())?;
+    ///     Ok(new_conn)
+    /// }
+    /// ```
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if preparing any of the statements fails, e.g. if
+    /// it refers to a table which doesn't exist. The statements before it
+    /// are still cached.
+    pub fn prime_prepared_statement_cache<I>(&self, sql: I) -> Result<()>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        for sql in sql {
+            self.prepare_cached(sql.as_ref())?;
+        }
+        Ok(())
+    }
 }
 
 /// Prepared statements LRU cache.
 // #[derive(Debug)] // FIXME: https://github.com/kyren/hashlink/pull/4
-pub struct StatementCache(RefCell<LruCache<Arc<str>, RawStatement>>);
+pub struct StatementCache(
+    RefCell<LruCache<Arc<str>, RawStatement>>,
+    Cell<StatementCacheStats>,
+);
+
+/// Counters of a connection's prepared statement cache, see
+/// [`Connection::prepared_statement_cache_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StatementCacheStats {
+    /// Number of calls to [`prepare_cached`](Connection::prepare_cached)
+    /// which reused a cached statement.
+    pub hits: u64,
+    /// Number of calls to [`prepare_cached`](Connection::prepare_cached)
+    /// which had to prepare the statement.
+    pub misses: u64,
+    /// Number of statements finalized to make room in the cache, or because
+    /// its capacity was lowered. Flushed statements are not counted.
+    pub evictions: u64,
+    /// Number of statements currently in the cache.
+    pub size: usize,
+    /// Maximum number of statements in the cache.
+    pub capacity: usize,
+}
 
 /// Cacheable statement.
 ///
@@ -116,12 +194,33 @@ impl StatementCache {
     /// Create a statement cache.
     #[inline]
     pub fn with_capacity(capacity: usize) -> StatementCache {
-        StatementCache(RefCell::new(LruCache::new(capacity)))
+        StatementCache(
+            RefCell::new(LruCache::new(capacity)),
+            Cell::new(StatementCacheStats::default()),
+        )
     }
 
     #[inline]
     fn set_capacity(&self, capacity: usize) {
-        self.0.borrow_mut().set_capacity(capacity)
+        let mut cache = self.0.borrow_mut();
+        let len = cache.len();
+        cache.set_capacity(capacity);
+        self.count_evictions(len - cache.len());
+    }
+
+    fn count_evictions(&self, n: usize) {
+        let mut stats = self.1.get();
+        stats.evictions += n as u64;
+        self.1.set(stats);
+    }
+
+    fn stats(&self) -> StatementCacheStats {
+        let cache = self.0.borrow();
+        StatementCacheStats {
+            size: cache.len(),
+            capacity: cache.capacity(),
+            ..self.1.get()
+        }
     }
 
     // Search the cache for a prepared-statement object that implements `sql`.
@@ -138,10 +237,18 @@ impl StatementCache {
     ) -> Result<CachedStatement<'conn>> {
         let trimmed = sql.trim();
         let mut cache = self.0.borrow_mut();
+        let mut stats = self.1.get();
         let stmt = match cache.remove(trimmed) {
-            Some(raw_stmt) => Ok(Statement::new(conn, raw_stmt)),
-            None => conn.prepare(trimmed),
+            Some(raw_stmt) => {
+                stats.hits += 1;
+                Ok(Statement::new(conn, raw_stmt))
+            }
+            None => {
+                stats.misses += 1;
+                conn.prepare(trimmed)
+            }
         };
+        self.1.set(stats);
         stmt.map(|mut stmt| {
             stmt.stmt.set_statement_cache_key(trimmed);
             CachedStatement::new(stmt, self)
@@ -156,7 +263,11 @@ impl StatementCache {
         let mut cache = self.0.borrow_mut();
         stmt.clear_bindings();
         if let Some(sql) = stmt.statement_cache_key() {
+            // Counts both the least recently used statement dropped by a full
+            // cache, and a statement with the same SQL being replaced.
+            let len = cache.len();
             cache.insert(sql, stmt);
+            self.count_evictions(len + 1 - cache.len());
         } else {
             debug_assert!(
                 false,
@@ -165,6 +276,22 @@ impl StatementCache {
         }
     }
 
+    // Statements held by the cache, which `Connection::close` finalizes.
+    pub(crate) fn contains_stmt(&self, stmt: *mut ffi::sqlite3_stmt) -> bool {
+        self.0
+            .borrow()
+            .iter()
+            .any(|(_, cached)| unsafe { cached.ptr() } == stmt)
+    }
+
+    fn sql(&self) -> Vec<String> {
+        self.0
+            .borrow()
+            .iter()
+            .map(|(sql, _)| sql.to_string())
+            .collect()
+    }
+
     #[inline]
     fn flush(&self) {
         let mut cache = self.0.borrow_mut();
@@ -174,7 +301,7 @@ impl StatementCache {
 
 #[cfg(test)]
 mod test {
-    use super::StatementCache;
+    use super::{StatementCache, StatementCacheStats};
     use crate::{Connection, Result};
     use fallible_iterator::FallibleIterator;
 
@@ -304,6 +431,56 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_schema_change_by_other_connection() -> Result<()> {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("schema.db3");
+        let db = Connection::open(&path)?;
+        db.execute_batch("CREATE TABLE foo (x INT, y TEXT)")?;
+        let sql = "INSERT INTO foo (x, y) VALUES (?, ?)";
+        db.prepare_cached(sql)?.execute(crate::params![1, "a"])?;
+
+        // SQLite prepares the cached statement again when it is stepped.
+        let other = Connection::open(&path)?;
+        other.execute_batch(
+            "DROP TABLE foo;
+             CREATE TABLE foo (id INTEGER PRIMARY KEY, y TEXT, x INT);",
+        )?;
+        db.prepare_cached(sql)?.execute(crate::params![2, "b"])?;
+        let row: (i64, String) =
+            db.query_row("SELECT x, y FROM foo", [], |r| Ok((r.get(0)?, r.get(1)?)))?;
+        assert_eq!(row, (2, "b".to_owned()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stats() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.set_prepared_statement_cache_capacity(2);
+        for sql in &["SELECT 1", "SELECT 1", "SELECT 2", "SELECT 3", "SELECT 1"] {
+            db.prepare_cached(sql)?;
+        }
+        {
+            let _a = db.prepare_cached("SELECT 4")?;
+            let _b = db.prepare_cached("SELECT 4")?;
+        }
+        assert_eq!(
+            db.prepared_statement_cache_stats(),
+            StatementCacheStats {
+                hits: 1,
+                misses: 6,
+                evictions: 4,
+                size: 2,
+                capacity: 2,
+            }
+        );
+        db.set_prepared_statement_cache_capacity(1);
+        db.flush_prepared_statement_cache();
+        let stats = db.prepared_statement_cache_stats();
+        assert_eq!((stats.evictions, stats.size), (5, 0));
+        Ok(())
+    }
+
     #[test]
     fn test_connection_close() -> Result<()> {
         let conn = Connection::open_in_memory()?;
@@ -337,6 +514,30 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_prime_cache() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch("CREATE TABLE foo (x INT)")?;
+        let sql = ["SELECT x FROM foo", "PRAGMA schema_version", "SELECT 1"];
+        for sql in &sql {
+            db.prepare_cached(sql)?;
+        }
+        db.prepare_cached(sql[0])?;
+        let exported = db.prepared_statement_cache_sql();
+        assert_eq!(exported, [sql[1], sql[2], sql[0]]);
+
+        let other = Connection::open_in_memory()?;
+        other.prime_prepared_statement_cache(&exported[..2])?;
+        assert_eq!(2, other.cache.len());
+        assert_eq!(other.prepared_statement_cache_sql(), &exported[..2]);
+
+        // There's no table `foo` on `other`.
+        other.flush_prepared_statement_cache();
+        assert!(other.prime_prepared_statement_cache(&exported).is_err());
+        assert_eq!(other.prepared_statement_cache_sql(), &exported[..2]);
+        Ok(())
+    }
+
     #[test]
     fn test_empty_stmt() -> Result<()> {
         let conn = Connection::open_in_memory()?;
@@ -0,0 +1,224 @@
+//! Memoize the results of read-only queries.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use fallible_iterator::FallibleIterator;
+
+use crate::types::{ToSql, ToSqlOutput, Value};
+use crate::{Connection, Error, Result};
+
+/// The rows of a memoized query result.
+pub type CachedRows = Rc<[Vec<Value>]>;
+
+// The parameters of a query, and its rows.
+type Memoized = (Vec<Value>, CachedRows);
+
+/// Memoizes the results of registered read-only queries, keyed by SQL and
+/// parameters, until the database changes.
+///
+/// The cache is invalidated when `PRAGMA data_version` changes (a commit by
+/// another connection), when `PRAGMA schema_version` changes, or when a row
+/// is inserted, updated or deleted through this connection.
+///
+/// ```rust,no_run
+/// # use rusqlite::{CachedQueries, Connection, Result};
+/// fn dashboard(conn: &Connection) -> Result<()> {
+///     let mut queries = CachedQueries::new(conn);
+///     queries.register("SELECT cohort, count(*) FROM samples GROUP BY cohort")?;
+///     for _ in 0..10 {
+///         // Only runs the query once, unless `samples` changes.
+///         let rows = queries.query("SELECT cohort, count(*) FROM samples GROUP BY cohort", &[])?;
+///         println!("{:?}", rows);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct CachedQueries<'conn> {
+    conn: &'conn Connection,
+    registered: HashSet<String>,
+    results: RefCell<HashMap<String, Vec<Memoized>>>,
+    version: Cell<Option<(i64, i64, usize)>>,
+}
+
+impl<'conn> CachedQueries<'conn> {
+    /// Creates a memoization layer over `conn`, with no registered query.
+    #[inline]
+    pub fn new(conn: &'conn Connection) -> CachedQueries<'conn> {
+        CachedQueries {
+            conn,
+            registered: HashSet::new(),
+            results: RefCell::new(HashMap::new()),
+            version: Cell::new(None),
+        }
+    }
+
+    /// Registers `sql` as a query whose results are memoized.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `sql` cannot be prepared, or with
+    /// `Error::InvalidQuery` if it returns no rows or (with
+    /// `feature = "modern_sqlite"`) if it is not read-only.
+    pub fn register(&mut self, sql: &str) -> Result<()> {
+        let stmt = self.conn.prepare_cached(sql)?;
+        if stmt.column_count() == 0 {
+            return Err(Error::InvalidQuery);
+        }
+        #[cfg(feature = "modern_sqlite")]
+        {
+            if !stmt.stmt.readonly() {
+                return Err(Error::InvalidQuery);
+            }
+        }
+        self.registered.insert(sql.to_owned());
+        Ok(())
+    }
+
+    /// Returns all the rows of `sql` with `params`, from the cache if `sql`
+    /// is registered and was already run with the same `params` since the
+    /// database last changed. Other queries are always run.
+    ///
+    /// Only positional parameters are supported.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if preparing or running the query fails.
+    pub fn query(&self, sql: &str, params: &[&dyn ToSql]) -> Result<CachedRows> {
+        if !self.registered.contains(sql) {
+            return self.run(sql, params);
+        }
+        let key = match params_key(params)? {
+            Some(key) => key,
+            None => return self.run(sql, params),
+        };
+        self.check_version()?;
+        if let Some(rows) = self.results.borrow().get(sql).and_then(|results| {
+            results
+                .iter()
+                .find(|(params, _)| *params == key)
+                .map(|(_, rows)| Rc::clone(rows))
+        }) {
+            return Ok(rows);
+        }
+        let rows = self.run(sql, params)?;
+        self.results
+            .borrow_mut()
+            .entry(sql.to_owned())
+            .or_default()
+            .push((key, Rc::clone(&rows)));
+        Ok(rows)
+    }
+
+    /// Forgets all memoized results.
+    #[inline]
+    pub fn invalidate(&self) {
+        self.results.borrow_mut().clear();
+    }
+
+    fn run(&self, sql: &str, params: &[&dyn ToSql]) -> Result<CachedRows> {
+        let mut stmt = self.conn.prepare_cached(sql)?;
+        let rows: Vec<Vec<Value>> = stmt.query(params)?.into_values().collect()?;
+        Ok(rows.into())
+    }
+
+    // Clears the cache if the database has changed since the last query.
+    fn check_version(&self) -> Result<()> {
+        let pragma = |name: &str| -> Result<i64> {
+            self.conn
+                .prepare_cached(name)?
+                .query_row([], |row| row.get(0))
+        };
+        let version = (
+            pragma("PRAGMA data_version")?,
+            pragma("PRAGMA schema_version")?,
+            self.conn.total_changes(),
+        );
+        if self.version.replace(Some(version)) != Some(version) {
+            self.invalidate();
+        }
+        Ok(())
+    }
+}
+
+// The values of `params`, or `None` if some can't be compared.
+fn params_key(params: &[&dyn ToSql]) -> Result<Option<Vec<Value>>> {
+    let mut key = Vec::with_capacity(params.len());
+    for param in params {
+        key.push(match param.to_sql()? {
+            ToSqlOutput::Borrowed(v) => v.into(),
+            ToSqlOutput::Owned(v) => v,
+            #[allow(unreachable_patterns)]
+            _ => return Ok(None),
+        });
+    }
+    Ok(Some(key))
+}
+
+#[cfg(test)]
+mod test {
+    use super::CachedQueries;
+    use crate::types::Value;
+    use crate::{Connection, Error, Result};
+
+    #[test]
+    fn test_cached_queries() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch("CREATE TABLE foo(x); INSERT INTO foo VALUES(1), (2);")?;
+        let sql = "SELECT count(*), random() FROM foo WHERE x > ?";
+        let mut queries = CachedQueries::new(&db);
+        queries.register(sql)?;
+
+        let first = queries.query(sql, &[&0])?;
+        assert_eq!(first[0][0], Value::Integer(2));
+        // The same rows, without running the query again.
+        assert_eq!(queries.query(sql, &[&0])?, first);
+        let other = queries.query(sql, &[&1])?;
+        assert_eq!(other[0][0], Value::Integer(1));
+
+        db.execute("INSERT INTO foo VALUES(3)", [])?;
+        let second = queries.query(sql, &[&0])?;
+        assert_eq!(second[0][0], Value::Integer(3));
+        assert_ne!(second, first);
+
+        queries.invalidate();
+        assert_ne!(queries.query(sql, &[&0])?, second);
+
+        // Unregistered queries are always run.
+        let unregistered = "SELECT random()";
+        assert_ne!(
+            queries.query(unregistered, &[])?,
+            queries.query(unregistered, &[])?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cached_queries_other_connection() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db3");
+        let db1 = Connection::open(&path)?;
+        let db2 = Connection::open(&path)?;
+        db1.execute_batch("CREATE TABLE foo(x); INSERT INTO foo VALUES(1);")?;
+        let sql = "SELECT sum(x) FROM foo";
+        let mut queries = CachedQueries::new(&db1);
+        queries.register(sql)?;
+        assert_eq!(queries.query(sql, &[])?[0][0], Value::Integer(1));
+        db2.execute("INSERT INTO foo VALUES(2)", [])?;
+        assert_eq!(queries.query(sql, &[])?[0][0], Value::Integer(3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_register_invalid() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch("CREATE TABLE foo(x)")?;
+        let mut queries = CachedQueries::new(&db);
+        assert_eq!(
+            queries.register("INSERT INTO foo VALUES(1)"),
+            Err(Error::InvalidQuery)
+        );
+        Ok(())
+    }
+}
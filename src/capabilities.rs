@@ -0,0 +1,201 @@
+use std::collections::BTreeSet;
+
+use crate::{Connection, Error, Result};
+
+// Optional built-in functions, probed when `pragma_function_list` is not
+// available (before SQLite 3.30.0).
+const OPTIONAL_FUNCTIONS: &[&str] = &[
+    "soundex",
+    "load_extension",
+    "sqlite_compileoption_used",
+    "json",
+    "json_extract",
+    "fts3_tokenizer",
+    "rtreenode",
+    "geopoly_json",
+    "sqlite_offset",
+    "acos",
+    "exp",
+    "ln",
+    "pi",
+    "pow",
+    "sin",
+    "sqrt",
+];
+
+// Modules implied by compile options, when `pragma_module_list` is not
+// available.
+const OPTION_MODULES: &[(&str, &[&str])] = &[
+    ("ENABLE_FTS3", &["fts3", "fts4", "fts4aux"]),
+    ("ENABLE_FTS4", &["fts3", "fts4", "fts4aux"]),
+    ("ENABLE_FTS5", &["fts5", "fts5vocab"]),
+    ("ENABLE_RTREE", &["rtree", "rtree_i32"]),
+    ("ENABLE_GEOPOLY", &["geopoly"]),
+    ("ENABLE_DBSTAT_VTAB", &["dbstat"]),
+    ("ENABLE_STMTVTAB", &["sqlite_stmt"]),
+];
+
+/// The functions, virtual table modules and compile options available to a
+/// connection, as reported by [`Connection::capabilities`].
+///
+/// Names are compared case-insensitively, and compile options without
+/// their `SQLITE_` prefix, e.g. `ENABLE_FTS5`.
+///
+/// ```rust,no_run
+/// # use rusqlite::{Connection, Result};
+/// fn soundex_sql(conn: &Connection) -> Result<&'static str> {
+///     Ok(if conn.capabilities()?.has_function("soundex") {
+///         "SELECT name FROM people WHERE soundex(name) = soundex(?)"
+///     } else {
+///         "SELECT name FROM people WHERE name = ? COLLATE NOCASE"
+///     })
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Capabilities {
+    functions: BTreeSet<String>,
+    modules: BTreeSet<String>,
+    compile_options: BTreeSet<String>,
+}
+
+impl Capabilities {
+    /// Returns whether the SQL function `name` is available, with any
+    /// number of arguments.
+    pub fn has_function(&self, name: &str) -> bool {
+        self.functions.contains(&name.to_ascii_lowercase())
+    }
+
+    /// Returns whether the virtual table module `name` is available.
+    pub fn has_module(&self, name: &str) -> bool {
+        self.modules.contains(&name.to_ascii_lowercase())
+    }
+
+    /// Returns whether SQLite was compiled with the option `name`, e.g.
+    /// `ENABLE_FTS5` or `THREADSAFE=1`. A name without a value matches any
+    /// value.
+    pub fn has_compile_option(&self, name: &str) -> bool {
+        let name = name.trim_start_matches("SQLITE_").to_ascii_uppercase();
+        if name.contains('=') {
+            return self.compile_options.contains(&name);
+        }
+        self.compile_options
+            .iter()
+            .any(|option| option.split('=').next() == Some(name.as_str()))
+    }
+
+    /// Returns the names of the available SQL functions, in lower case.
+    pub fn functions(&self) -> impl Iterator<Item = &str> {
+        self.functions.iter().map(String::as_str)
+    }
+
+    /// Returns the names of the available virtual table modules, in lower
+    /// case.
+    pub fn modules(&self) -> impl Iterator<Item = &str> {
+        self.modules.iter().map(String::as_str)
+    }
+
+    /// Returns the compile options, without their `SQLITE_` prefix.
+    pub fn compile_options(&self) -> impl Iterator<Item = &str> {
+        self.compile_options.iter().map(String::as_str)
+    }
+}
+
+impl Connection {
+    /// Reports the SQL functions, virtual table modules and compile options
+    /// available to this connection, so that queries can fall back when
+    /// linked against a SQLite without e.g. `soundex` or FTS5.
+    ///
+    /// The functions and modules include the ones registered by the
+    /// application at the time of the call. Before SQLite 3.30.0, which
+    /// cannot list them, only well-known optional functions are probed, and
+    /// modules are deduced from the compile options.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite calls fail.
+    pub fn capabilities(&self) -> Result<Capabilities> {
+        let mut capabilities = Capabilities::default();
+        // Fails with SQLITE_OMIT_COMPILEOPTION_DIAGS.
+        let _ = self.pragma_query(None, "compile_options", |row| {
+            capabilities.compile_options.insert(row.get(0)?);
+            Ok(())
+        });
+        match self.names("SELECT DISTINCT name FROM pragma_function_list") {
+            Ok(functions) => capabilities.functions = functions,
+            Err(_) => {
+                for &name in OPTIONAL_FUNCTIONS {
+                    if self.probe_function(name)? {
+                        capabilities.functions.insert(name.to_owned());
+                    }
+                }
+            }
+        }
+        match self.names("SELECT name FROM pragma_module_list") {
+            Ok(modules) => capabilities.modules = modules,
+            Err(_) => {
+                for &(option, modules) in OPTION_MODULES {
+                    if capabilities.has_compile_option(option) {
+                        capabilities
+                            .modules
+                            .extend(modules.iter().map(|&m| m.to_owned()));
+                    }
+                }
+            }
+        }
+        Ok(capabilities)
+    }
+
+    fn names(&self, sql: &str) -> Result<BTreeSet<String>> {
+        let mut stmt = self.prepare(sql)?;
+        let names = stmt.query_map([], |row| {
+            row.get::<_, String>(0).map(|s| s.to_ascii_lowercase())
+        })?;
+        names.collect()
+    }
+
+    // Whether a function `name` exists, whatever its number of arguments.
+    fn probe_function(&self, name: &str) -> Result<bool> {
+        match self.prepare(&format!("SELECT {}()", name)) {
+            Ok(_) => Ok(true),
+            Err(Error::SqliteFailure(_, Some(msg))) if msg.starts_with("no such function") => {
+                Ok(false)
+            }
+            // e.g. wrong number of arguments
+            Err(Error::SqliteFailure(..)) => Ok(true),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Connection, Result};
+
+    #[test]
+    fn test_capabilities() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        let capabilities = db.capabilities()?;
+        assert!(capabilities.has_function("abs"));
+        assert!(capabilities.has_function("SUBSTR"));
+        assert!(!capabilities.has_function("no_such_function"));
+        assert!(capabilities.has_compile_option("THREADSAFE"));
+        assert!(capabilities.has_compile_option("SQLITE_THREADSAFE"));
+        assert!(!capabilities.has_compile_option("THREADSAFE=42"));
+        assert!(capabilities.functions().count() > 10);
+        assert!(!capabilities.has_module("no_such_module"));
+        if cfg!(feature = "bundled") {
+            assert!(capabilities.has_function("soundex"));
+            assert!(capabilities.has_compile_option("SOUNDEX"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_probe_function() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        assert!(db.probe_function("abs")?);
+        assert!(db.probe_function("max")?);
+        assert!(!db.probe_function("no_such_function")?);
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+//! Hashes of rows and checksums of tables, to check that two copies of a
+//! table hold the same data without exporting both.
+//!
+//! Values are hashed from a canonical encoding of their SQLite value (type,
+//! then content), so the same rows give the same checksum on any platform and
+//! with any version of this crate. `1` and `1.0`, or `'1'` and `X'31'`, are
+//! different values.
+//!
+//! ```rust,no_run
+//! use rusqlite::checksum::table_checksum;
+//! use rusqlite::{Connection, Result};
+//!
+//! fn same_samples(primary: &Connection, replica: &Connection) -> Result<bool> {
+//!     Ok(table_checksum(primary, "samples")? == table_checksum(replica, "samples")?)
+//! }
+//! ```
+
+use crate::types::ValueRef;
+use crate::util::quote_identifier;
+use crate::{Connection, Result, Row};
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+// FNV-1a.
+struct Hasher(u64);
+
+impl Hasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ u64::from(b)).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_value(&mut self, value: ValueRef<'_>) {
+        match value {
+            ValueRef::Null => self.write(&[0]),
+            ValueRef::Integer(i) => {
+                self.write(&[1]);
+                self.write(&i.to_le_bytes());
+            }
+            ValueRef::Real(f) => {
+                self.write(&[2]);
+                // -0.0 and 0.0 compare equal in SQL.
+                let f = if f == 0.0 { 0.0 } else { f };
+                self.write(&f.to_bits().to_le_bytes());
+            }
+            ValueRef::Text(s) => {
+                self.write(&[3]);
+                self.write(&(s.len() as u64).to_le_bytes());
+                self.write(s);
+            }
+            ValueRef::Blob(b) => {
+                self.write(&[4]);
+                self.write(&(b.len() as u64).to_le_bytes());
+                self.write(b);
+            }
+        }
+    }
+}
+
+// The hash of `bytes`, e.g. of a database page.
+#[cfg(feature = "incremental_backup")]
+pub(crate) fn bytes_hash(bytes: &[u8]) -> u64 {
+    let mut hasher = Hasher(FNV_OFFSET);
+    hasher.write(bytes);
+    hasher.0
+}
+
+/// Returns the hash of the values of `row`, in order.
+///
+/// # Failure
+///
+/// Will return `Err` if a value cannot be read.
+pub fn row_hash(row: &Row<'_>) -> Result<u64> {
+    let mut hasher = Hasher(FNV_OFFSET);
+    for i in 0..row.column_count() {
+        hasher.write_value(row.get_ref(i)?);
+    }
+    Ok(hasher.0)
+}
+
+/// Returns the checksum of the rows of `table`, from their [`row_hash`] of
+/// all columns (`SELECT *`), which does not depend on the order of the rows.
+///
+/// Tables with the same rows, and with the same columns in the same order,
+/// have the same checksum; their row ids are not compared, unless they are
+/// a column (`INTEGER PRIMARY KEY`).
+///
+/// # Failure
+///
+/// Will return `Err` if `table` does not exist.
+pub fn table_checksum(conn: &Connection, table: &str) -> Result<u64> {
+    let mut stmt = conn.prepare(&format!("SELECT * FROM {}", quote_identifier(table)))?;
+    let mut rows = stmt.query([])?;
+    let mut sum = 0u64;
+    let mut count = 0u64;
+    while let Some(row) = rows.next()? {
+        // Mixed before summing, so that similar rows don't cancel out.
+        sum = sum.wrapping_add(mix(row_hash(row)?));
+        count += 1;
+    }
+    let mut hasher = Hasher(FNV_OFFSET);
+    hasher.write(&count.to_le_bytes());
+    hasher.write(&sum.to_le_bytes());
+    Ok(hasher.0)
+}
+
+// The SplitMix64 finalizer.
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{row_hash, table_checksum};
+    use crate::{Connection, Result};
+
+    fn hash(db: &Connection, sql: &str) -> Result<u64> {
+        db.query_row(sql, [], row_hash)
+    }
+
+    #[test]
+    fn test_row_hash() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        let a = hash(&db, "SELECT 1, 'a', NULL")?;
+        assert_eq!(a, hash(&db, "SELECT 1 AS x, 'a' AS y, NULL AS z")?);
+        for other in &[
+            "SELECT 1.0, 'a', NULL",
+            "SELECT 1, X'61', NULL",
+            "SELECT 1, 'a', ''",
+            "SELECT 'a', 1, NULL",
+            "SELECT 1, 'a'",
+        ] {
+            assert_ne!(a, hash(&db, other)?, "{}", other);
+        }
+        assert_eq!(hash(&db, "SELECT -0.0")?, hash(&db, "SELECT 0.0")?);
+        // Lengths are part of the encoding.
+        assert_ne!(
+            hash(&db, "SELECT 'ab', 'c'")?,
+            hash(&db, "SELECT 'a', 'bc'")?
+        );
+        // The encoding is stable.
+        assert_eq!(hash(&db, "SELECT NULL")?, 0xaf63_bd4c_8601_b7df);
+        Ok(())
+    }
+
+    #[test]
+    fn test_table_checksum() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE a (x, y);
+             INSERT INTO a VALUES (1, 'one'), (2, 'two'), (2, 'two');
+             CREATE TABLE \"b c\" (x, y);
+             INSERT INTO \"b c\" VALUES (2, 'two'), (1, 'one'), (2, 'two');
+             CREATE TABLE d (x, y);
+             INSERT INTO d VALUES (1, 'one'), (2, 'two');",
+        )?;
+        let a = table_checksum(&db, "a")?;
+        assert_eq!(a, table_checksum(&db, "b c")?);
+        assert_ne!(a, table_checksum(&db, "d")?);
+        db.execute("UPDATE \"b c\" SET y = 'TWO' WHERE rowid = 1", [])?;
+        assert_ne!(a, table_checksum(&db, "b c")?);
+        assert!(table_checksum(&db, "missing").is_err());
+        Ok(())
+    }
+}
@@ -23,6 +23,56 @@ impl Column<'_> {
     }
 }
 
+/// Where a column of a SQLite query comes from, as returned by
+/// [`Statement::column_metadata`].
+///
+/// The origin is only known for columns which are taken directly from a table
+/// column; for expressions, the database, table and origin names are `None`.
+#[cfg(feature = "column_metadata")]
+#[derive(Debug)]
+pub struct ColumnMetadata<'stmt> {
+    name: &'stmt str,
+    decl_type: Option<&'stmt str>,
+    database_name: Option<&'stmt str>,
+    table_name: Option<&'stmt str>,
+    origin_name: Option<&'stmt str>,
+}
+
+#[cfg(feature = "column_metadata")]
+impl ColumnMetadata<'_> {
+    /// Returns the name of the column in the result set.
+    #[inline]
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    /// Returns the declared type of the column (`None` for expression).
+    #[inline]
+    pub fn decl_type(&self) -> Option<&str> {
+        self.decl_type
+    }
+
+    /// Returns the name of the database (e.g. `main`, `temp` or an attached
+    /// database) the column comes from.
+    #[inline]
+    pub fn database_name(&self) -> Option<&str> {
+        self.database_name
+    }
+
+    /// Returns the name of the table the column comes from.
+    #[inline]
+    pub fn table_name(&self) -> Option<&str> {
+        self.table_name
+    }
+
+    /// Returns the name of the table column the column comes from, which may
+    /// differ from [`name`](ColumnMetadata::name) when it is aliased.
+    #[inline]
+    pub fn origin_name(&self) -> Option<&str> {
+        self.origin_name
+    }
+}
+
 impl Statement<'_> {
     /// Get all the column names in the result set of the prepared statement.
     pub fn column_names(&self) -> Vec<&str> {
@@ -106,6 +156,40 @@ impl Statement<'_> {
         }
         cols
     }
+
+    /// Returns the declared type and origin of a particular column in the
+    /// result set.
+    ///
+    /// ## Failure
+    ///
+    /// Returns an `Error::InvalidColumnIndex` if `col` is outside the valid
+    /// column range for this statement.
+    ///
+    /// Panics when any of the names is not valid UTF-8.
+    #[cfg(feature = "column_metadata")]
+    pub fn column_metadata(&self, col: usize) -> Result<ColumnMetadata<'_>> {
+        let name = self.column_name(col)?;
+        Ok(ColumnMetadata {
+            name,
+            decl_type: metadata_str(self.stmt.column_decltype(col)),
+            database_name: metadata_str(self.stmt.column_database_name(col)),
+            table_name: metadata_str(self.stmt.column_table_name(col)),
+            origin_name: metadata_str(self.stmt.column_origin_name(col)),
+        })
+    }
+
+    /// Returns the declared type and origin of every column in the result set.
+    #[cfg(feature = "column_metadata")]
+    pub fn columns_with_metadata(&self) -> Vec<ColumnMetadata<'_>> {
+        (0..self.column_count())
+            .map(|i| self.column_metadata(i).expect("Column out of bounds"))
+            .collect()
+    }
+}
+
+#[cfg(feature = "column_metadata")]
+fn metadata_str(s: Option<&std::ffi::CStr>) -> Option<&str> {
+    s.map(|s| str::from_utf8(s.to_bytes()).expect("Invalid UTF-8 sequence in column metadata"))
 }
 
 impl<'stmt> Rows<'stmt> {
@@ -139,6 +223,13 @@ impl<'stmt> Rows<'stmt> {
     pub fn columns(&self) -> Option<Vec<Column>> {
         self.stmt.map(Statement::columns)
     }
+
+    /// Returns the declared type and origin of every column of the Rows.
+    #[inline]
+    #[cfg(feature = "column_metadata")]
+    pub fn columns_with_metadata(&self) -> Option<Vec<ColumnMetadata<'_>>> {
+        self.stmt.map(Statement::columns_with_metadata)
+    }
 }
 
 impl<'stmt> Row<'stmt> {
@@ -172,6 +263,13 @@ impl<'stmt> Row<'stmt> {
     pub fn columns(&self) -> Vec<Column> {
         self.stmt.columns()
     }
+
+    /// Returns the declared type and origin of every column of the Row.
+    #[inline]
+    #[cfg(feature = "column_metadata")]
+    pub fn columns_with_metadata(&self) -> Vec<ColumnMetadata<'_>> {
+        self.stmt.columns_with_metadata()
+    }
 }
 
 #[cfg(test)]
@@ -234,4 +332,32 @@ mod test {
         }
         Ok(())
     }
+
+    #[test]
+    #[cfg(feature = "column_metadata")]
+    fn test_column_metadata() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch("CREATE TABLE foo(x INTEGER, y TEXT);")?;
+        let stmt = db.prepare("SELECT x AS renamed, y, 1 + 1 FROM foo")?;
+        let renamed = stmt.column_metadata(0)?;
+        assert_eq!(renamed.name(), "renamed");
+        assert_eq!(renamed.decl_type(), Some("INTEGER"));
+        assert_eq!(renamed.database_name(), Some("main"));
+        assert_eq!(renamed.table_name(), Some("foo"));
+        assert_eq!(renamed.origin_name(), Some("x"));
+
+        let columns = stmt.columns_with_metadata();
+        assert_eq!(columns.len(), 3);
+        assert_eq!(columns[1].origin_name(), Some("y"));
+        assert_eq!(columns[2].name(), "1 + 1");
+        assert_eq!(columns[2].decl_type(), None);
+        assert_eq!(columns[2].table_name(), None);
+        assert_eq!(columns[2].origin_name(), None);
+
+        assert_eq!(
+            stmt.column_metadata(3).unwrap_err(),
+            crate::Error::InvalidColumnIndex(3)
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,453 @@
+//! `feature = "column_encryption"` Encrypt the values of specific columns.
+//!
+//! Values are encrypted with AES-256-GCM before being bound, and stored as
+//! blobs; they are decrypted after being read, with the same [`ColumnKey`].
+//! Each value gets a random nonce, so encrypted columns can't be searched or
+//! indexed on their plaintext, and equal values are stored differently.
+//!
+//! The key is set on the connection, with [`Connection::set_column_key`], and
+//! used by [`Connection::encrypt_column`] and [`Connection::decrypt_column`]:
+//!
+//! ```rust,no_run
+//! use rusqlite::column_encryption::{ColumnKey, EncryptedColumn};
+//! use rusqlite::{Connection, Result};
+//!
+//! fn open(path: &str, key: ColumnKey) -> Result<Connection> {
+//!     let conn = Connection::open(path)?;
+//!     conn.set_column_key(Some(key));
+//!     Ok(conn)
+//! }
+//!
+//! fn add_patient(conn: &Connection, name: &str, nhs: String) -> Result<()> {
+//!     let nhs = conn.encrypt_column(&nhs)?;
+//!     conn.execute(
+//!         "INSERT INTO patient (name, nhs_number) VALUES (?, ?)",
+//!         rusqlite::params![name, nhs],
+//!     )?;
+//!     Ok(())
+//! }
+//!
+//! fn nhs_number(conn: &Connection, name: &str) -> Result<String> {
+//!     let nhs: EncryptedColumn<String> = conn.query_row(
+//!         "SELECT nhs_number FROM patient WHERE name = ?",
+//!         [name],
+//!         |row| row.get(0),
+//!     )?;
+//!     conn.decrypt_column(&nhs)
+//! }
+//! ```
+
+use std::convert::TryInto;
+use std::fmt;
+use std::marker::PhantomData;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+
+use crate::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, Value, ValueRef};
+use crate::util::quote_identifier;
+use crate::{Connection, Error, Result, Savepoint};
+
+// Format of the stored blobs: version, nonce, ciphertext and tag.
+const VERSION: u8 = 1;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// A 256-bit key used to encrypt and decrypt columns.
+#[derive(Clone)]
+pub struct ColumnKey {
+    cipher: Aes256Gcm,
+}
+
+impl ColumnKey {
+    /// Creates a key from its raw bytes.
+    #[inline]
+    pub fn from_bytes(bytes: &[u8; 32]) -> ColumnKey {
+        ColumnKey {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(bytes)),
+        }
+    }
+
+    /// Generates a random key, from the operating system's random number
+    /// generator.
+    #[inline]
+    pub fn generate() -> ColumnKey {
+        ColumnKey {
+            cipher: Aes256Gcm::new(&Aes256Gcm::generate_key(&mut OsRng)),
+        }
+    }
+
+    fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| Error::ToSqlConversionFailure("encryption failed".into()))?;
+        let mut blob = Vec::with_capacity(1 + NONCE_LEN + ciphertext.len());
+        blob.push(VERSION);
+        blob.extend_from_slice(&nonce);
+        blob.extend_from_slice(&ciphertext);
+        Ok(blob)
+    }
+
+    fn open(&self, blob: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Nonce::from_slice(&blob[1..=NONCE_LEN]);
+        self.cipher
+            .decrypt(nonce, &blob[1 + NONCE_LEN..])
+            .map_err(|_| Error::DecryptionError("wrong key or corrupted value".into()))
+    }
+}
+
+impl fmt::Debug for ColumnKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ColumnKey { .. }")
+    }
+}
+
+/// An encrypted value of type `T`, as stored in the database.
+///
+/// It is bound and read as a blob; use [`encrypt`](EncryptedColumn::encrypt)
+/// and [`decrypt`](EncryptedColumn::decrypt) to convert from and to `T`.
+pub struct EncryptedColumn<T> {
+    blob: Vec<u8>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: ToSql> EncryptedColumn<T> {
+    /// Encrypts `value` with `key`.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `value` cannot be converted to an SQLite value,
+    /// or is a zero blob or an array.
+    pub fn encrypt(key: &ColumnKey, value: &T) -> Result<EncryptedColumn<T>> {
+        let plaintext = match value.to_sql()? {
+            ToSqlOutput::Borrowed(v) => encode(v),
+            ToSqlOutput::Owned(ref v) => encode(v.into()),
+            #[allow(unreachable_patterns)]
+            _ => {
+                return Err(Error::ToSqlConversionFailure(
+                    "value cannot be encrypted".into(),
+                ))
+            }
+        };
+        Ok(EncryptedColumn {
+            blob: key.seal(&plaintext)?,
+            marker: PhantomData,
+        })
+    }
+}
+
+impl<T: FromSql> EncryptedColumn<T> {
+    /// Decrypts the value with `key`.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the value was encrypted with another key, was
+    /// modified, or cannot be converted to `T`.
+    pub fn decrypt(&self, key: &ColumnKey) -> Result<T> {
+        let plaintext = key.open(&self.blob)?;
+        let value =
+            decode(&plaintext).ok_or_else(|| Error::DecryptionError("invalid plaintext".into()))?;
+        T::column_result(value).map_err(|err| {
+            Error::DecryptionError(match err {
+                FromSqlError::Other(err) => err,
+                err => Box::new(err),
+            })
+        })
+    }
+}
+
+impl<T> EncryptedColumn<T> {
+    /// Returns the bytes stored in the database.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.blob
+    }
+}
+
+impl<T> Clone for EncryptedColumn<T> {
+    fn clone(&self) -> EncryptedColumn<T> {
+        EncryptedColumn {
+            blob: self.blob.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for EncryptedColumn<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptedColumn")
+            .field("len", &self.blob.len())
+            .finish()
+    }
+}
+
+impl<T> ToSql for EncryptedColumn<T> {
+    #[inline]
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.blob.as_slice()))
+    }
+}
+
+/// Only checks that the blob looks like an encrypted value; it is
+/// authenticated by [`EncryptedColumn::decrypt`].
+impl<T> FromSql for EncryptedColumn<T> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let blob = value.as_blob()?;
+        if blob.len() < 1 + NONCE_LEN + TAG_LEN || blob[0] != VERSION {
+            return Err(FromSqlError::InvalidType);
+        }
+        Ok(EncryptedColumn {
+            blob: blob.to_vec(),
+            marker: PhantomData,
+        })
+    }
+}
+
+// The plaintext of a value: its type, followed by its content.
+fn encode(value: ValueRef<'_>) -> Vec<u8> {
+    let (tag, content) = match value {
+        ValueRef::Null => (0, Vec::new()),
+        ValueRef::Integer(i) => (1, i.to_le_bytes().to_vec()),
+        ValueRef::Real(f) => (2, f.to_bits().to_le_bytes().to_vec()),
+        ValueRef::Text(s) => (3, s.to_vec()),
+        ValueRef::Blob(b) => (4, b.to_vec()),
+    };
+    let mut plaintext = Vec::with_capacity(1 + content.len());
+    plaintext.push(tag);
+    plaintext.extend_from_slice(&content);
+    plaintext
+}
+
+fn decode(plaintext: &[u8]) -> Option<ValueRef<'_>> {
+    let (&tag, content) = plaintext.split_first()?;
+    let word = || content.try_into().ok().map(u64::from_le_bytes);
+    Some(match tag {
+        0 if content.is_empty() => ValueRef::Null,
+        1 => ValueRef::Integer(word()? as i64),
+        2 => ValueRef::Real(f64::from_bits(word()?)),
+        3 => ValueRef::Text(content),
+        4 => ValueRef::Blob(content),
+        _ => return None,
+    })
+}
+
+impl Connection {
+    /// `feature = "column_encryption"` Sets or clears the key used by
+    /// [`encrypt_column`](Connection::encrypt_column) and
+    /// [`decrypt_column`](Connection::decrypt_column) on this connection.
+    #[inline]
+    pub fn set_column_key(&self, key: Option<ColumnKey>) {
+        self.db.borrow_mut().column_key = key;
+    }
+
+    /// `feature = "column_encryption"` Encrypts `value` with the key of the
+    /// connection.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if no key is set, or if `value` cannot be encrypted.
+    pub fn encrypt_column<T: ToSql>(&self, value: &T) -> Result<EncryptedColumn<T>> {
+        match self.db.borrow().column_key {
+            Some(ref key) => EncryptedColumn::encrypt(key, value),
+            None => Err(Error::ToSqlConversionFailure("no column key set".into())),
+        }
+    }
+
+    /// `feature = "column_encryption"` Decrypts `value` with the key of the
+    /// connection.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if no key is set, or if `value` cannot be decrypted.
+    pub fn decrypt_column<T: FromSql>(&self, value: &EncryptedColumn<T>) -> Result<T> {
+        match self.db.borrow().column_key {
+            Some(ref key) => value.decrypt(key),
+            None => Err(Error::DecryptionError("no column key set".into())),
+        }
+    }
+
+    /// `feature = "column_encryption"` Re-encrypts all the values of
+    /// `column` in `table` with `new_key`, and returns the number of values
+    /// re-encrypted. `NULL`s are left as they are.
+    ///
+    /// All values are re-encrypted, or none; this can be called in a
+    /// transaction. `table` must be a rowid table. The key of the connection
+    /// is left as it is.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if some value is not encrypted with `old_key`, or if
+    /// the underlying SQLite call fails.
+    pub fn rotate_column_key(
+        &self,
+        table: &str,
+        column: &str,
+        old_key: &ColumnKey,
+        new_key: &ColumnKey,
+    ) -> Result<usize> {
+        let sp = Savepoint::with_depth_and_name(self, 0, "_rusqlite_rotate_column_key")?;
+        let (table, column) = (quote_identifier(table), quote_identifier(column));
+        let select = format!(
+            "SELECT rowid, {1} FROM {0} WHERE {1} IS NOT NULL",
+            table, column
+        );
+        let update = format!("UPDATE {} SET {} = ?1 WHERE rowid = ?2", table, column);
+
+        let values = self
+            .prepare(&select)?
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, EncryptedColumn<Value>>(1)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        let mut stmt = self.prepare(&update)?;
+        for (rowid, value) in &values {
+            let blob = new_key.seal(&old_key.open(value.as_bytes())?)?;
+            stmt.execute(crate::params![blob, rowid])?;
+        }
+        sp.commit()?;
+        Ok(values.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ColumnKey, EncryptedColumn};
+    use crate::types::Value;
+    use crate::{Connection, Error, Result};
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let key = ColumnKey::generate();
+        let db = Connection::open_in_memory()?;
+        db.execute_batch("CREATE TABLE foo(x)")?;
+        let values = vec![
+            Value::Null,
+            Value::Integer(-42),
+            Value::Real(1.5),
+            Value::Text("secret".to_owned()),
+            Value::Blob(vec![0, 1, 2]),
+        ];
+        for value in &values {
+            let encrypted = EncryptedColumn::encrypt(&key, value)?;
+            db.execute("INSERT INTO foo VALUES (?)", [encrypted])?;
+        }
+        let mut stmt = db.prepare("SELECT x FROM foo ORDER BY rowid")?;
+        let decrypted = stmt
+            .query_map([], |row| row.get::<_, EncryptedColumn<Value>>(0))?
+            .map(|encrypted| encrypted?.decrypt(&key))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(decrypted, values);
+
+        // Equal values are stored differently.
+        let a = EncryptedColumn::encrypt(&key, &"same")?;
+        let b = EncryptedColumn::encrypt(&key, &"same")?;
+        assert_ne!(a.as_bytes(), b.as_bytes());
+
+        let name = EncryptedColumn::encrypt(&key, &Some("x".to_owned()))?;
+        let name: EncryptedColumn<Option<String>> =
+            db.query_row("SELECT ?", [name], |row| row.get(0))?;
+        assert_eq!(name.decrypt(&key)?, Some("x".to_owned()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_decrypt_failure() -> Result<()> {
+        let key = ColumnKey::from_bytes(&[7; 32]);
+        let encrypted = EncryptedColumn::encrypt(&key, &42i64)?;
+        assert!(matches!(
+            encrypted.decrypt(&ColumnKey::generate()),
+            Err(Error::DecryptionError(_))
+        ));
+
+        let db = Connection::open_in_memory()?;
+        let mut bytes = encrypted.as_bytes().to_vec();
+        *bytes.last_mut().unwrap() ^= 1;
+        let tampered: EncryptedColumn<i64> = db.query_row("SELECT ?", [bytes], |r| r.get(0))?;
+        assert!(matches!(
+            tampered.decrypt(&key),
+            Err(Error::DecryptionError(_))
+        ));
+
+        let not_text: EncryptedColumn<String> =
+            db.query_row("SELECT ?", [&encrypted], |r| r.get(0))?;
+        assert!(matches!(
+            not_text.decrypt(&key),
+            Err(Error::DecryptionError(_))
+        ));
+
+        let plain: Result<EncryptedColumn<i64>> = db.query_row("SELECT 42", [], |r| r.get(0));
+        assert!(matches!(plain, Err(Error::InvalidColumnType(..))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_connection_key() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        assert!(db.encrypt_column(&"x").is_err());
+        let key = ColumnKey::generate();
+        db.set_column_key(Some(key.clone()));
+        let encrypted = db.encrypt_column(&"x".to_owned())?;
+        assert_eq!(encrypted.decrypt(&key)?, "x");
+        assert_eq!(db.decrypt_column(&encrypted)?, "x");
+
+        // Each connection has its own key.
+        let other = Connection::open_in_memory()?;
+        assert!(matches!(
+            other.decrypt_column(&encrypted),
+            Err(Error::DecryptionError(_))
+        ));
+        other.set_column_key(Some(ColumnKey::generate()));
+        assert!(matches!(
+            other.decrypt_column(&encrypted),
+            Err(Error::DecryptionError(_))
+        ));
+        db.set_column_key(None);
+        assert!(db.decrypt_column(&encrypted).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotate_column_key() -> Result<()> {
+        let old_key = ColumnKey::generate();
+        let new_key = ColumnKey::generate();
+        let db = Connection::open_in_memory()?;
+        db.execute_batch("CREATE TABLE \"my table\"(id INTEGER PRIMARY KEY, x)")?;
+        for i in 0..3 {
+            db.execute(
+                "INSERT INTO \"my table\"(x) VALUES (?)",
+                [EncryptedColumn::encrypt(&old_key, &i)?],
+            )?;
+        }
+        db.execute("INSERT INTO \"my table\"(x) VALUES (NULL)", [])?;
+
+        assert_eq!(
+            db.rotate_column_key("my table", "x", &old_key, &new_key)?,
+            3
+        );
+        let mut stmt = db.prepare("SELECT x FROM \"my table\" WHERE x IS NOT NULL ORDER BY id")?;
+        let values = stmt
+            .query_map([], |row| row.get::<_, EncryptedColumn<i32>>(0))?
+            .map(|encrypted| encrypted?.decrypt(&new_key))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(values, vec![0, 1, 2]);
+
+        // Nothing is changed if a value can't be decrypted.
+        let other_key = ColumnKey::generate();
+        db.execute(
+            "INSERT INTO \"my table\"(x) VALUES (?)",
+            [EncryptedColumn::encrypt(&other_key, &3)?],
+        )?;
+        assert!(db
+            .rotate_column_key("my table", "x", &new_key, &old_key)
+            .is_err());
+        let first: EncryptedColumn<i32> =
+            db.query_row("SELECT x FROM \"my table\" WHERE id = 1", [], |r| r.get(0))?;
+        assert_eq!(first.decrypt(&new_key)?, 0);
+        Ok(())
+    }
+}
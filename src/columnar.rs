@@ -0,0 +1,200 @@
+//! Fetch the whole result of a query column by column.
+
+use crate::types::{Type, ValueRef};
+use crate::{Error, Params, Result, Statement};
+
+/// The values of one column of a query result, as returned by
+/// [`Statement::query_columnar`]. `NULL`s are `None`.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum ColumnValues {
+    /// Every value of the column is `NULL`; holds the number of rows.
+    Null(usize),
+    /// A column of integers.
+    Integer(Vec<Option<i64>>),
+    /// A column of floating point numbers.
+    Real(Vec<Option<f64>>),
+    /// A column of strings.
+    Text(Vec<Option<String>>),
+    /// A column of blobs.
+    Blob(Vec<Option<Vec<u8>>>),
+}
+
+impl ColumnValues {
+    /// Returns the number of rows in the column.
+    #[inline]
+    pub fn len(&self) -> usize {
+        match self {
+            ColumnValues::Null(n) => *n,
+            ColumnValues::Integer(v) => v.len(),
+            ColumnValues::Real(v) => v.len(),
+            ColumnValues::Text(v) => v.len(),
+            ColumnValues::Blob(v) => v.len(),
+        }
+    }
+
+    /// Returns `true` if the column has no rows.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the type of the column, `Type::Null` if every value is `NULL`.
+    #[inline]
+    pub fn data_type(&self) -> Type {
+        match self {
+            ColumnValues::Null(_) => Type::Null,
+            ColumnValues::Integer(_) => Type::Integer,
+            ColumnValues::Real(_) => Type::Real,
+            ColumnValues::Text(_) => Type::Text,
+            ColumnValues::Blob(_) => Type::Blob,
+        }
+    }
+
+    // Appends `value`, or returns the type which doesn't fit in this column.
+    fn push(&mut self, value: ValueRef<'_>) -> std::result::Result<(), Type> {
+        if let ColumnValues::Null(n) = *self {
+            if value == ValueRef::Null {
+                *self = ColumnValues::Null(n + 1);
+                return Ok(());
+            }
+            *self = ColumnValues::with_nulls(value.data_type(), n);
+        }
+        if let (ColumnValues::Integer(v), ValueRef::Real(_)) = (&*self, value) {
+            // Same conversion as `FromSql for f64`.
+            *self = ColumnValues::Real(v.iter().map(|i| i.map(|i| i as f64)).collect());
+        }
+        match (self, value) {
+            (ColumnValues::Integer(v), ValueRef::Null) => v.push(None),
+            (ColumnValues::Real(v), ValueRef::Null) => v.push(None),
+            (ColumnValues::Text(v), ValueRef::Null) => v.push(None),
+            (ColumnValues::Blob(v), ValueRef::Null) => v.push(None),
+            (ColumnValues::Integer(v), ValueRef::Integer(i)) => v.push(Some(i)),
+            (ColumnValues::Real(v), ValueRef::Real(f)) => v.push(Some(f)),
+            // Same conversion as `FromSql for f64`.
+            (ColumnValues::Real(v), ValueRef::Integer(i)) => v.push(Some(i as f64)),
+            (ColumnValues::Text(v), ValueRef::Text(_)) => {
+                v.push(Some(value.as_str().map_err(|_| Type::Text)?.to_owned()))
+            }
+            (ColumnValues::Blob(v), ValueRef::Blob(b)) => v.push(Some(b.to_vec())),
+            (_, value) => return Err(value.data_type()),
+        }
+        Ok(())
+    }
+
+    fn with_nulls(data_type: Type, n: usize) -> ColumnValues {
+        match data_type {
+            Type::Null => ColumnValues::Null(n),
+            Type::Integer => ColumnValues::Integer(vec![None; n]),
+            Type::Real => ColumnValues::Real(vec![None; n]),
+            Type::Text => ColumnValues::Text(vec![None; n]),
+            Type::Blob => ColumnValues::Blob(vec![None; n]),
+        }
+    }
+}
+
+impl Statement<'_> {
+    /// Execute the prepared statement and fetch all of its result rows at
+    /// once, column by column.
+    ///
+    /// This avoids the per-row overhead of [`query_map`](Statement::query_map)
+    /// for analytical queries over many rows. The type of each column is that
+    /// of its first non-`NULL` value, except that a column mixing integers and
+    /// reals is returned as reals; other values of a different type are an
+    /// error.
+    ///
+    /// ```rust,no_run
+    /// # use rusqlite::{ColumnValues, Connection, Result};
+    /// fn mean_length(conn: &Connection) -> Result<f64> {
+    ///     let mut stmt = conn.prepare("SELECT length FROM reads")?;
+    ///     let columns = stmt.query_columnar([])?;
+    ///     match &columns[0] {
+    ///         ColumnValues::Integer(lengths) => {
+    ///             let lengths: Vec<i64> = lengths.iter().flatten().copied().collect();
+    ///             Ok(lengths.iter().sum::<i64>() as f64 / lengths.len() as f64)
+    ///         }
+    ///         _ => Ok(0.0),
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// ## Failure
+    ///
+    /// Will return `Err` if binding parameters fails, if stepping the
+    /// statement fails, or with `Error::InvalidColumnType` if a column holds
+    /// values of incompatible types.
+    pub fn query_columnar<P: Params>(&mut self, params: P) -> Result<Vec<ColumnValues>> {
+        let n = self.column_count();
+        let mut columns = vec![ColumnValues::Null(0); n];
+        let mut rows = self.query(params)?;
+        while let Some(row) = rows.next()? {
+            for (idx, column) in columns.iter_mut().enumerate() {
+                column.push(row.get_ref_unwrap(idx)).map_err(|data_type| {
+                    Error::InvalidColumnType(idx, row.column_name(idx).unwrap().into(), data_type)
+                })?;
+            }
+        }
+        Ok(columns)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ColumnValues;
+    use crate::types::Type;
+    use crate::{Connection, Error, Result};
+
+    #[test]
+    fn test_query_columnar() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE foo(i INTEGER, r REAL, t TEXT, b BLOB, n);
+             INSERT INTO foo VALUES(NULL, 1, 'a', x'01', NULL);
+             INSERT INTO foo VALUES(2, 2.5, NULL, x'', NULL);
+             INSERT INTO foo VALUES(3, NULL, 'c', NULL, NULL);",
+        )?;
+        let mut stmt = db.prepare("SELECT * FROM foo ORDER BY rowid")?;
+        let columns = stmt.query_columnar([])?;
+        assert_eq!(
+            columns,
+            vec![
+                ColumnValues::Integer(vec![None, Some(2), Some(3)]),
+                ColumnValues::Real(vec![Some(1.0), Some(2.5), None]),
+                ColumnValues::Text(vec![Some("a".to_owned()), None, Some("c".to_owned())]),
+                ColumnValues::Blob(vec![Some(vec![1]), Some(vec![]), None]),
+                ColumnValues::Null(3),
+            ]
+        );
+        assert_eq!(columns[4].len(), 3);
+
+        let mut stmt = db.prepare("SELECT * FROM foo WHERE i > 10")?;
+        let columns = stmt.query_columnar([])?;
+        assert_eq!(columns.len(), 5);
+        assert!(columns.iter().all(ColumnValues::is_empty));
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_columnar_mixed_types() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        let mut stmt = db.prepare("SELECT NULL AS x UNION ALL SELECT 1 UNION ALL SELECT 'a'")?;
+        match stmt.query_columnar([]).unwrap_err() {
+            Error::InvalidColumnType(0, name, Type::Text) => assert_eq!(name, "x"),
+            err => panic!("Unexpected error {}", err),
+        }
+        // A column whose first value is an integer is promoted to reals.
+        let mut stmt = db.prepare("SELECT 1 AS x UNION ALL SELECT NULL UNION ALL SELECT 2.5")?;
+        assert_eq!(
+            stmt.query_columnar([])?[0],
+            ColumnValues::Real(vec![Some(1.0), None, Some(2.5)])
+        );
+        // A prepared statement can be queried again with other parameters.
+        let mut stmt = db.prepare("SELECT ?")?;
+        assert_eq!(
+            stmt.query_columnar([1])?[0],
+            ColumnValues::Integer(vec![Some(1)])
+        );
+        assert_eq!(stmt.query_columnar([2])?[0].data_type(), Type::Integer);
+        Ok(())
+    }
+}
@@ -20,7 +20,9 @@ pub enum DbConfig {
     /// Enable or disable the fts3_tokenizer() function which is part of the
     /// FTS3 full-text search engine extension.
     SQLITE_DBCONFIG_ENABLE_FTS3_TOKENIZER = 1004, // 3.12.0
-    //SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION = 1005,
+    /// Enable or disable loading extensions through the C API only, leaving
+    /// the `load_extension()` SQL function disabled.
+    SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION = 1005, // 3.13.0
     /// In WAL mode, enable or disable the checkpoint operation before closing
     /// the connection.
     SQLITE_DBCONFIG_NO_CKPT_ON_CLOSE = 1006, // 3.16.2
@@ -68,6 +70,9 @@ impl Connection {
     ///   whether triggers are disabled or enabled
     /// - SQLITE_DBCONFIG_ENABLE_FTS3_TOKENIZER: return `false` or `true` to
     ///   indicate whether fts3_tokenizer are disabled or enabled
+    /// - SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION: return `false` or `true` to
+    ///   indicate whether loading extensions through the C API is disabled or
+    ///   enabled
     /// - SQLITE_DBCONFIG_NO_CKPT_ON_CLOSE: return `false` to indicate
     ///   checkpoints-on-close are not disabled or `true` if they are
     /// - SQLITE_DBCONFIG_ENABLE_QPSG: return `false` or `true` to indicate
@@ -97,6 +102,8 @@ impl Connection {
     ///   enable triggers
     /// - SQLITE_DBCONFIG_ENABLE_FTS3_TOKENIZER: `false` to disable
     ///   fts3_tokenizer(), `true` to enable fts3_tokenizer()
+    /// - SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION: `false` to disable loading
+    ///   extensions through the C API, `true` to enable it
     /// - SQLITE_DBCONFIG_NO_CKPT_ON_CLOSE: `false` (the default) to enable
     ///   checkpoints-on-close, `true` to disable them
     /// - SQLITE_DBCONFIG_ENABLE_QPSG: `false` to disable the QPSG, `true` to
@@ -0,0 +1,426 @@
+//! Copy tables between databases.
+//!
+//! [`copy_table`] and [`copy_tables`] create the tables (and their indexes)
+//! in the target database if needed, and copy the rows in batches, each in a
+//! transaction, with a single prepared `INSERT`. The source and target can be
+//! separate connections, or two schemas of the same connection (e.g. `main`
+//! and an attached database).
+//!
+//! ```rust,no_run
+//! use rusqlite::copy::{copy_tables, CopyOptions};
+//! use rusqlite::{Connection, Result};
+//!
+//! fn export(src: &Connection, path: &str) -> Result<u64> {
+//!     let dst = Connection::open(path)?;
+//!     let mut options = CopyOptions::new()
+//!         .batch_size(10_000)
+//!         .progress(|p| eprintln!("{}: {}/{}", p.table, p.rows, p.total));
+//!     copy_tables(src, &dst, &["samples", "variants"], &mut options)
+//! }
+//! ```
+
+use std::fmt;
+
+use crate::types::ToSqlOutput;
+use crate::util::quote_identifier;
+use crate::{params_from_iter, Connection, DatabaseName, Error, Result, Savepoint};
+
+/// Progress of a copy, reported after each batch.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct Progress<'a> {
+    /// The table being copied.
+    pub table: &'a str,
+    /// The number of rows of `table` copied so far.
+    pub rows: u64,
+    /// The number of rows of `table` to copy.
+    pub total: u64,
+}
+
+/// How tables are copied.
+///
+/// By default, missing tables are created, rows are copied in batches of
+/// 1000, from and to the `main` schemas.
+pub struct CopyOptions<'a> {
+    source: DatabaseName<'a>,
+    target: DatabaseName<'a>,
+    create: bool,
+    batch_size: usize,
+    progress: Option<ProgressFn<'a>>,
+}
+
+type ProgressFn<'a> = Box<dyn FnMut(Progress<'_>) + 'a>;
+
+impl fmt::Debug for CopyOptions<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CopyOptions")
+            .field("source", &self.source)
+            .field("target", &self.target)
+            .field("create", &self.create)
+            .field("batch_size", &self.batch_size)
+            .finish()
+    }
+}
+
+impl Default for CopyOptions<'_> {
+    fn default() -> Self {
+        CopyOptions::new()
+    }
+}
+
+impl<'a> CopyOptions<'a> {
+    /// Creates the default options.
+    pub fn new() -> CopyOptions<'a> {
+        CopyOptions {
+            source: DatabaseName::Main,
+            target: DatabaseName::Main,
+            create: true,
+            batch_size: 1000,
+            progress: None,
+        }
+    }
+
+    /// Sets the schema the tables are copied from.
+    pub fn source(mut self, schema: DatabaseName<'a>) -> CopyOptions<'a> {
+        self.source = schema;
+        self
+    }
+
+    /// Sets the schema the tables are copied to.
+    pub fn target(mut self, schema: DatabaseName<'a>) -> CopyOptions<'a> {
+        self.target = schema;
+        self
+    }
+
+    /// Sets whether missing tables are created in the target, with the
+    /// indexes of the source. Otherwise, they must already exist.
+    pub fn create(mut self, create: bool) -> CopyOptions<'a> {
+        self.create = create;
+        self
+    }
+
+    /// Sets the number of rows copied per transaction.
+    pub fn batch_size(mut self, batch_size: usize) -> CopyOptions<'a> {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Sets a callback called after each batch.
+    pub fn progress<F>(mut self, progress: F) -> CopyOptions<'a>
+    where
+        F: FnMut(Progress<'_>) + 'a,
+    {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+}
+
+/// Copies the rows of `table` from `src` to `dst`, and returns their number.
+///
+/// Generated columns are not copied, but computed again in the target.
+///
+/// # Failure
+///
+/// Will return `Err` if `table` doesn't exist in `src`, or in `dst` if
+/// `create` is off, or if the underlying SQLite calls fail. Batches already
+/// copied are not rolled back, unless a transaction was open on `dst`.
+pub fn copy_table(
+    src: &Connection,
+    dst: &Connection,
+    table: &str,
+    options: &mut CopyOptions<'_>,
+) -> Result<u64> {
+    let source = schema(options.source);
+    let target = schema(options.target);
+    if options.create {
+        create_table(src, dst, &source, &target, table)?;
+    }
+
+    let mut columns = Vec::new();
+    src.pragma(Some(options.source), "table_info", &table, |row| {
+        columns.push(quote_identifier(&row.get::<_, String>(1)?));
+        Ok(())
+    })?;
+    if columns.is_empty() {
+        return Err(Error::SqliteFailure(
+            crate::ffi::Error::new(crate::ffi::SQLITE_ERROR),
+            Some(format!("no such table: {}.{}", source, table)),
+        ));
+    }
+    let columns = columns.join(", ");
+    let quoted = quote_identifier(table);
+    let total: i64 = src.query_row(
+        &format!("SELECT count(*) FROM {}.{}", source, quoted),
+        [],
+        |row| row.get(0),
+    )?;
+    let total = total as u64;
+
+    let mut select = src.prepare(&format!("SELECT {} FROM {}.{}", columns, source, quoted))?;
+    let column_count = select.column_count();
+    let mut insert = dst.prepare(&format!(
+        "INSERT INTO {}.{} ({}) VALUES ({})",
+        target,
+        quoted,
+        columns,
+        vec!["?"; column_count].join(", ")
+    ))?;
+    let mut rows = select.query([])?;
+    let mut copied = 0;
+    loop {
+        let sp = Savepoint::with_depth_and_name(dst, 0, "_rusqlite_copy")?;
+        let mut batch = 0;
+        while batch < options.batch_size {
+            let row = match rows.next()? {
+                Some(row) => row,
+                None => break,
+            };
+            let values = (0..column_count)
+                .map(|i| row.get_ref(i).map(ToSqlOutput::Borrowed))
+                .collect::<Result<Vec<_>>>()?;
+            insert.execute(params_from_iter(values))?;
+            batch += 1;
+        }
+        sp.commit()?;
+        copied += batch as u64;
+        if let Some(progress) = options.progress.as_mut().filter(|_| batch > 0) {
+            progress(Progress {
+                table,
+                rows: copied,
+                total,
+            });
+        }
+        if batch < options.batch_size {
+            return Ok(copied);
+        }
+    }
+}
+
+/// Copies `tables` from `src` to `dst`, referenced tables first, so that
+/// foreign key constraints are satisfied as rows are inserted, and returns
+/// the number of rows copied. Tables referencing each other are copied in
+/// the given order.
+///
+/// # Failure
+///
+/// Will return `Err` if any table cannot be copied: see [`copy_table`].
+pub fn copy_tables(
+    src: &Connection,
+    dst: &Connection,
+    tables: &[&str],
+    options: &mut CopyOptions<'_>,
+) -> Result<u64> {
+    let mut ordered = Vec::with_capacity(tables.len());
+    for table in tables {
+        visit(
+            src,
+            options.source,
+            tables,
+            table,
+            &mut Vec::new(),
+            &mut ordered,
+        )?;
+    }
+    let mut copied = 0;
+    for table in ordered {
+        copied += copy_table(src, dst, table, options)?;
+    }
+    Ok(copied)
+}
+
+// Appends `table` to `ordered`, after the tables among `tables` it
+// references.
+fn visit<'t>(
+    src: &Connection,
+    schema: DatabaseName<'_>,
+    tables: &[&'t str],
+    table: &'t str,
+    path: &mut Vec<&'t str>,
+    ordered: &mut Vec<&'t str>,
+) -> Result<()> {
+    if ordered.contains(&table) || path.contains(&table) {
+        return Ok(());
+    }
+    path.push(table);
+    let mut parents = Vec::new();
+    src.pragma(Some(schema), "foreign_key_list", &table, |row| {
+        parents.push(row.get::<_, String>(2)?);
+        Ok(())
+    })?;
+    for parent in parents {
+        if let Some(parent) = tables.iter().find(|t| t.eq_ignore_ascii_case(&parent)) {
+            visit(src, schema, tables, parent, path, ordered)?;
+        }
+    }
+    path.pop();
+    ordered.push(table);
+    Ok(())
+}
+
+// Creates `table`, and its indexes, in `target` if it doesn't exist, from
+// their definition in `source`.
+fn create_table(
+    src: &Connection,
+    dst: &Connection,
+    source: &str,
+    target: &str,
+    table: &str,
+) -> Result<()> {
+    let mut stmt = src.prepare(&format!(
+        "SELECT type, sql FROM {}.sqlite_master
+         WHERE tbl_name = ? AND type IN ('table', 'index') AND sql IS NOT NULL
+         ORDER BY type = 'index'",
+        source
+    ))?;
+    let mut rows = stmt.query([table])?;
+    while let Some(row) = rows.next()? {
+        let kind = row.get_ref(0)?.as_str()?;
+        let sql = row.get_ref(1)?.as_str()?;
+        if kind == "table" && table_exists(dst, target, table)? {
+            return Ok(());
+        }
+        if let Some(sql) = qualify(sql, target) {
+            dst.execute_batch(&sql)?;
+        }
+    }
+    Ok(())
+}
+
+fn table_exists(conn: &Connection, schema: &str, table: &str) -> Result<bool> {
+    conn.query_row(
+        &format!(
+            "SELECT count(*) FROM {}.sqlite_master WHERE type = 'table' AND name = ?",
+            schema
+        ),
+        [table],
+        |row| row.get(0),
+    )
+}
+
+// Rewrites a `CREATE` statement of `sqlite_master`, where SQLite normalizes
+// the leading keywords and strips any schema name, to create the object in
+// `schema`.
+fn qualify(sql: &str, schema: &str) -> Option<String> {
+    let prefix = ["CREATE TABLE ", "CREATE INDEX ", "CREATE UNIQUE INDEX "]
+        .iter()
+        .find(|prefix| sql.starts_with(*prefix))?;
+    Some(format!(
+        "{}IF NOT EXISTS {}.{}",
+        prefix,
+        schema,
+        &sql[prefix.len()..]
+    ))
+}
+
+fn schema(name: DatabaseName<'_>) -> String {
+    match name {
+        DatabaseName::Main => "main".to_owned(),
+        DatabaseName::Temp => "temp".to_owned(),
+        DatabaseName::Attached(s) => quote_identifier(s),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{copy_table, copy_tables, qualify, CopyOptions};
+    use crate::{Connection, DatabaseName, Result};
+
+    fn source() -> Result<Connection> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE samples (id INTEGER PRIMARY KEY, name TEXT NOT NULL);
+             CREATE UNIQUE INDEX samples_name ON samples (name);
+             CREATE TABLE \"my variants\" (
+                 sample INTEGER REFERENCES samples (id),
+                 pos INTEGER,
+                 data BLOB
+             );
+             CREATE INDEX variants_pos ON \"my variants\" (pos);
+             WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 25)
+             INSERT INTO samples SELECT i, 'sample' || i FROM n;
+             INSERT INTO \"my variants\" SELECT id, id * 10, x'00ff' FROM samples;
+             INSERT INTO \"my variants\" VALUES (NULL, NULL, NULL);",
+        )?;
+        Ok(db)
+    }
+
+    #[test]
+    fn test_qualify() {
+        assert_eq!(
+            qualify("CREATE TABLE \"a b\"(x)", "main").unwrap(),
+            "CREATE TABLE IF NOT EXISTS main.\"a b\"(x)"
+        );
+        assert_eq!(
+            qualify("CREATE UNIQUE INDEX i on t(x)", "\"aux\"").unwrap(),
+            "CREATE UNIQUE INDEX IF NOT EXISTS \"aux\".i on t(x)"
+        );
+        assert_eq!(
+            qualify("CREATE VIRTUAL TABLE t USING fts5(x)", "main"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_copy_tables() -> Result<()> {
+        let src = source()?;
+        let dst = Connection::open_in_memory()?;
+        dst.execute_batch("PRAGMA foreign_keys = ON")?;
+        let mut progress = Vec::new();
+        {
+            let mut options = CopyOptions::new()
+                .batch_size(10)
+                .progress(|p| progress.push((p.table.to_owned(), p.rows, p.total)));
+            // Referenced tables are copied first.
+            assert_eq!(
+                copy_tables(&src, &dst, &["my variants", "samples"], &mut options)?,
+                51
+            );
+        }
+        assert_eq!(
+            progress,
+            vec![
+                ("samples".to_owned(), 10, 25),
+                ("samples".to_owned(), 20, 25),
+                ("samples".to_owned(), 25, 25),
+                ("my variants".to_owned(), 10, 26),
+                ("my variants".to_owned(), 20, 26),
+                ("my variants".to_owned(), 26, 26),
+            ]
+        );
+
+        let indexes: i64 = dst.query_row(
+            "SELECT count(*) FROM sqlite_master WHERE type = 'index'",
+            [],
+            |r| r.get(0),
+        )?;
+        assert_eq!(indexes, 2);
+        let (pos, data): (Option<i64>, Vec<u8>) = dst.query_row(
+            "SELECT pos, data FROM \"my variants\" WHERE sample = 3",
+            [],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )?;
+        assert_eq!(pos, Some(30));
+        assert_eq!(data, vec![0, 255]);
+
+        // The tables exist now, and the unique index rejects duplicates.
+        assert!(copy_table(&src, &dst, "samples", &mut CopyOptions::new()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_attached() -> Result<()> {
+        let db = source()?;
+        db.execute_batch("ATTACH ':memory:' AS \"back up\"")?;
+        let mut options = CopyOptions::new().target(DatabaseName::Attached("back up"));
+        assert_eq!(copy_table(&db, &db, "samples", &mut options)?, 25);
+        let count: i64 =
+            db.query_row("SELECT count(*) FROM \"back up\".samples", [], |r| r.get(0))?;
+        assert_eq!(count, 25);
+
+        let mut options = CopyOptions::new().create(false);
+        let dst = Connection::open_in_memory()?;
+        assert!(copy_table(&db, &dst, "samples", &mut options).is_err());
+        assert!(copy_table(&db, &dst, "missing", &mut CopyOptions::new()).is_err());
+        Ok(())
+    }
+}
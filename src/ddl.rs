@@ -0,0 +1,763 @@
+//! Builders for `CREATE TABLE` and `CREATE INDEX` statements, for tools
+//! generating schemas at runtime.
+//!
+//! Identifiers are always quoted, and default values written as literals,
+//! so any name or value can be used. Expressions (`CHECK` constraints,
+//! generated columns, `WHERE` clauses of partial indexes...) are copied as
+//! is. The statements are rendered by their `Display` implementation; SQLite
+//! checks them when they are executed.
+//!
+//! ```rust
+//! # use rusqlite::{Connection, Result};
+//! use rusqlite::ddl::{Column, CreateIndex, CreateTable, IndexedColumn, References};
+//! use rusqlite::schema::DefaultValue;
+//!
+//! # fn main() -> Result<()> {
+//! let samples = CreateTable::new("samples")
+//!     .column(Column::new("id", "INTEGER").primary_key())
+//!     .column(Column::new("name", "TEXT").not_null().collate("NOCASE"))
+//!     .column(Column::new("added", "TEXT").default(DefaultValue::CurrentTimestamp));
+//! let calls = CreateTable::new("calls")
+//!     .if_not_exists()
+//!     .column(Column::new("sample_id", "INTEGER").references(References::new("samples")))
+//!     .column(Column::new("variant", "TEXT"))
+//!     .primary_key(&["sample_id", "variant"])
+//!     .without_rowid();
+//! let index = CreateIndex::new("calls_by_variant", "calls")
+//!     .column(IndexedColumn::new("variant").desc());
+//!
+//! let db = Connection::open_in_memory()?;
+//! db.execute_batch(&format!("{}; {}; {};", samples, calls, index))?;
+//! # Ok(())
+//! # }
+//! ```
+use std::fmt;
+
+use crate::schema::{DefaultValue, Generated};
+use crate::types::ValueRef;
+use crate::util::quote_identifier;
+
+// `"schema".` if any, followed by `"name"`.
+fn qualified(schema: &Option<String>, name: &str) -> String {
+    match schema {
+        Some(schema) => format!("{}.{}", quote_identifier(schema), quote_identifier(name)),
+        None => quote_identifier(name),
+    }
+}
+
+fn quote_list(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|name| quote_identifier(name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// An action of a foreign key, when the referenced row is updated or
+/// deleted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ForeignKeyAction {
+    /// `SET NULL`
+    SetNull,
+    /// `SET DEFAULT`
+    SetDefault,
+    /// `CASCADE`
+    Cascade,
+    /// `RESTRICT`
+    Restrict,
+    /// `NO ACTION`, the default.
+    NoAction,
+}
+
+impl ForeignKeyAction {
+    fn as_sql(self) -> &'static str {
+        match self {
+            ForeignKeyAction::SetNull => "SET NULL",
+            ForeignKeyAction::SetDefault => "SET DEFAULT",
+            ForeignKeyAction::Cascade => "CASCADE",
+            ForeignKeyAction::Restrict => "RESTRICT",
+            ForeignKeyAction::NoAction => "NO ACTION",
+        }
+    }
+}
+
+/// The `REFERENCES` clause of a foreign key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct References {
+    table: String,
+    columns: Vec<String>,
+    on_delete: Option<ForeignKeyAction>,
+    on_update: Option<ForeignKeyAction>,
+    deferred: bool,
+}
+
+impl References {
+    /// References the primary key of `table`.
+    pub fn new(table: &str) -> References {
+        References {
+            table: table.to_owned(),
+            columns: Vec::new(),
+            on_delete: None,
+            on_update: None,
+            deferred: false,
+        }
+    }
+
+    /// References `columns` of the table instead of its primary key.
+    #[must_use]
+    pub fn columns(mut self, columns: &[&str]) -> Self {
+        self.columns = columns.iter().map(|&c| c.to_owned()).collect();
+        self
+    }
+
+    /// Sets the action when the referenced row is deleted.
+    #[must_use]
+    pub fn on_delete(mut self, action: ForeignKeyAction) -> Self {
+        self.on_delete = Some(action);
+        self
+    }
+
+    /// Sets the action when the referenced key is updated.
+    #[must_use]
+    pub fn on_update(mut self, action: ForeignKeyAction) -> Self {
+        self.on_update = Some(action);
+        self
+    }
+
+    /// Makes the constraint `DEFERRABLE INITIALLY DEFERRED`: checked when
+    /// the transaction commits.
+    #[must_use]
+    pub fn deferred(mut self) -> Self {
+        self.deferred = true;
+        self
+    }
+}
+
+impl fmt::Display for References {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "REFERENCES {}", quote_identifier(&self.table))?;
+        if !self.columns.is_empty() {
+            write!(f, " ({})", quote_list(&self.columns))?;
+        }
+        if let Some(action) = self.on_delete {
+            write!(f, " ON DELETE {}", action.as_sql())?;
+        }
+        if let Some(action) = self.on_update {
+            write!(f, " ON UPDATE {}", action.as_sql())?;
+        }
+        if self.deferred {
+            f.write_str(" DEFERRABLE INITIALLY DEFERRED")?;
+        }
+        Ok(())
+    }
+}
+
+/// A column of a [`CreateTable`] statement, with its constraints.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Column {
+    name: String,
+    decl_type: String,
+    primary_key: bool,
+    autoincrement: bool,
+    not_null: bool,
+    unique: bool,
+    default: Option<DefaultValue>,
+    collation: Option<String>,
+    checks: Vec<String>,
+    references: Option<References>,
+    generated: Option<(String, Generated)>,
+}
+
+impl Column {
+    /// A column named `name`, declared with the type `decl_type`, or
+    /// without a type if it is empty.
+    pub fn new(name: &str, decl_type: &str) -> Column {
+        Column {
+            name: name.to_owned(),
+            decl_type: decl_type.to_owned(),
+            primary_key: false,
+            autoincrement: false,
+            not_null: false,
+            unique: false,
+            default: None,
+            collation: None,
+            checks: Vec::new(),
+            references: None,
+            generated: None,
+        }
+    }
+
+    /// Makes the column the primary key of the table. An `INTEGER PRIMARY
+    /// KEY` column is an alias of the rowid.
+    #[must_use]
+    pub fn primary_key(mut self) -> Self {
+        self.primary_key = true;
+        self
+    }
+
+    /// Makes the column an `INTEGER PRIMARY KEY AUTOINCREMENT`, whose values
+    /// are never reused. The type of the column must be `INTEGER`.
+    #[must_use]
+    pub fn autoincrement(mut self) -> Self {
+        self.primary_key = true;
+        self.autoincrement = true;
+        self
+    }
+
+    /// Adds a `NOT NULL` constraint.
+    #[must_use]
+    pub fn not_null(mut self) -> Self {
+        self.not_null = true;
+        self
+    }
+
+    /// Adds a `UNIQUE` constraint.
+    #[must_use]
+    pub fn unique(mut self) -> Self {
+        self.unique = true;
+        self
+    }
+
+    /// Sets the default value of the column.
+    #[must_use]
+    pub fn default(mut self, value: DefaultValue) -> Self {
+        self.default = Some(value);
+        self
+    }
+
+    /// Sets the collation of the column, e.g. `NOCASE`.
+    #[must_use]
+    pub fn collate(mut self, collation: &str) -> Self {
+        self.collation = Some(collation.to_owned());
+        self
+    }
+
+    /// Adds a `CHECK` constraint, with the expression `expr`.
+    #[must_use]
+    pub fn check(mut self, expr: &str) -> Self {
+        self.checks.push(expr.to_owned());
+        self
+    }
+
+    /// Makes the column a foreign key.
+    #[must_use]
+    pub fn references(mut self, references: References) -> Self {
+        self.references = Some(references);
+        self
+    }
+
+    /// Makes the column a generated column, computed from `expr`
+    /// (SQLite >= 3.31.0).
+    #[must_use]
+    pub fn generated(mut self, expr: &str, how: Generated) -> Self {
+        self.generated = Some((expr.to_owned(), how));
+        self
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&quote_identifier(&self.name))?;
+        if !self.decl_type.is_empty() {
+            write!(f, " {}", self.decl_type)?;
+        }
+        if self.primary_key {
+            f.write_str(" PRIMARY KEY")?;
+        }
+        if self.autoincrement {
+            f.write_str(" AUTOINCREMENT")?;
+        }
+        if self.not_null {
+            f.write_str(" NOT NULL")?;
+        }
+        if self.unique {
+            f.write_str(" UNIQUE")?;
+        }
+        for check in &self.checks {
+            write!(f, " CHECK ({})", check)?;
+        }
+        if let Some(default) = &self.default {
+            f.write_str(" DEFAULT ")?;
+            match default {
+                DefaultValue::Value(value) => {
+                    let mut literal = Vec::new();
+                    crate::dump::write_value(&mut literal, ValueRef::from(value))
+                        .map_err(|_| fmt::Error)?;
+                    f.write_str(&String::from_utf8_lossy(&literal))?;
+                }
+                DefaultValue::CurrentTime => f.write_str("CURRENT_TIME")?,
+                DefaultValue::CurrentDate => f.write_str("CURRENT_DATE")?,
+                DefaultValue::CurrentTimestamp => f.write_str("CURRENT_TIMESTAMP")?,
+                DefaultValue::Expression(expr) => write!(f, "({})", expr)?,
+            }
+        }
+        if let Some(collation) = &self.collation {
+            write!(f, " COLLATE {}", quote_identifier(collation))?;
+        }
+        if let Some(references) = &self.references {
+            write!(f, " {}", references)?;
+        }
+        if let Some((expr, how)) = &self.generated {
+            let how = match how {
+                Generated::Virtual => "VIRTUAL",
+                Generated::Stored => "STORED",
+            };
+            write!(f, " GENERATED ALWAYS AS ({}) {}", expr, how)?;
+        }
+        Ok(())
+    }
+}
+
+/// A `CREATE TABLE` statement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CreateTable {
+    schema: Option<String>,
+    name: String,
+    temporary: bool,
+    if_not_exists: bool,
+    columns: Vec<Column>,
+    constraints: Vec<String>,
+    without_rowid: bool,
+    strict: bool,
+}
+
+impl CreateTable {
+    /// Creates the table `name`, without columns yet.
+    pub fn new(name: &str) -> CreateTable {
+        CreateTable {
+            schema: None,
+            name: name.to_owned(),
+            temporary: false,
+            if_not_exists: false,
+            columns: Vec::new(),
+            constraints: Vec::new(),
+            without_rowid: false,
+            strict: false,
+        }
+    }
+
+    /// Creates the table in the database `schema`, e.g. an attached one.
+    #[must_use]
+    pub fn schema(mut self, schema: &str) -> Self {
+        self.schema = Some(schema.to_owned());
+        self
+    }
+
+    /// Creates a `TEMP` table.
+    #[must_use]
+    pub fn temporary(mut self) -> Self {
+        self.temporary = true;
+        self
+    }
+
+    /// Does nothing if the table already exists.
+    #[must_use]
+    pub fn if_not_exists(mut self) -> Self {
+        self.if_not_exists = true;
+        self
+    }
+
+    /// Adds a column.
+    #[must_use]
+    pub fn column(mut self, column: Column) -> Self {
+        self.columns.push(column);
+        self
+    }
+
+    /// Makes `columns` the primary key of the table.
+    #[must_use]
+    pub fn primary_key(mut self, columns: &[&str]) -> Self {
+        let constraint = format!("PRIMARY KEY ({})", quote_list(&owned(columns)));
+        self.constraints.push(constraint);
+        self
+    }
+
+    /// Adds a `UNIQUE` constraint on `columns`.
+    #[must_use]
+    pub fn unique(mut self, columns: &[&str]) -> Self {
+        let constraint = format!("UNIQUE ({})", quote_list(&owned(columns)));
+        self.constraints.push(constraint);
+        self
+    }
+
+    /// Adds a `CHECK` constraint, with the expression `expr`.
+    #[must_use]
+    pub fn check(mut self, expr: &str) -> Self {
+        self.constraints.push(format!("CHECK ({})", expr));
+        self
+    }
+
+    /// Makes `columns` a foreign key.
+    #[must_use]
+    pub fn foreign_key(mut self, columns: &[&str], references: References) -> Self {
+        let constraint = format!(
+            "FOREIGN KEY ({}) {}",
+            quote_list(&owned(columns)),
+            references
+        );
+        self.constraints.push(constraint);
+        self
+    }
+
+    /// Makes the table `WITHOUT ROWID`. It must have a primary key.
+    #[must_use]
+    pub fn without_rowid(mut self) -> Self {
+        self.without_rowid = true;
+        self
+    }
+
+    /// Makes the table `STRICT` (SQLite >= 3.37.0). The type of each column
+    /// must be `INT`, `INTEGER`, `REAL`, `TEXT`, `BLOB` or `ANY`.
+    #[must_use]
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+}
+
+fn owned(names: &[&str]) -> Vec<String> {
+    names.iter().map(|&name| name.to_owned()).collect()
+}
+
+impl fmt::Display for CreateTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CREATE ")?;
+        if self.temporary {
+            f.write_str("TEMP ")?;
+        }
+        f.write_str("TABLE ")?;
+        if self.if_not_exists {
+            f.write_str("IF NOT EXISTS ")?;
+        }
+        write!(f, "{} (", qualified(&self.schema, &self.name))?;
+        let definitions = self
+            .columns
+            .iter()
+            .map(ToString::to_string)
+            .chain(self.constraints.iter().cloned());
+        for (i, definition) in definitions.enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            f.write_str(&definition)?;
+        }
+        f.write_str(")")?;
+        let mut options = Vec::new();
+        if self.without_rowid {
+            options.push("WITHOUT ROWID");
+        }
+        if self.strict {
+            options.push("STRICT");
+        }
+        if !options.is_empty() {
+            write!(f, " {}", options.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A column, or an expression, of a [`CreateIndex`] statement.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexedColumn {
+    // Already quoted if it is a column.
+    sql: String,
+    collation: Option<String>,
+    desc: bool,
+}
+
+impl IndexedColumn {
+    /// Indexes the column `name`.
+    pub fn new(name: &str) -> IndexedColumn {
+        IndexedColumn {
+            sql: quote_identifier(name),
+            collation: None,
+            desc: false,
+        }
+    }
+
+    /// Indexes the value of the expression `expr` (SQLite >= 3.9.0).
+    pub fn expression(expr: &str) -> IndexedColumn {
+        IndexedColumn {
+            sql: format!("({})", expr),
+            collation: None,
+            desc: false,
+        }
+    }
+
+    /// Compares the values with `collation` rather than the collation of
+    /// the column.
+    #[must_use]
+    pub fn collate(mut self, collation: &str) -> Self {
+        self.collation = Some(collation.to_owned());
+        self
+    }
+
+    /// Sorts the values in descending order.
+    #[must_use]
+    pub fn desc(mut self) -> Self {
+        self.desc = true;
+        self
+    }
+}
+
+impl From<&str> for IndexedColumn {
+    #[inline]
+    fn from(name: &str) -> IndexedColumn {
+        IndexedColumn::new(name)
+    }
+}
+
+impl fmt::Display for IndexedColumn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.sql)?;
+        if let Some(collation) = &self.collation {
+            write!(f, " COLLATE {}", quote_identifier(collation))?;
+        }
+        if self.desc {
+            f.write_str(" DESC")?;
+        }
+        Ok(())
+    }
+}
+
+/// A `CREATE INDEX` statement.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CreateIndex {
+    schema: Option<String>,
+    name: String,
+    table: String,
+    unique: bool,
+    if_not_exists: bool,
+    columns: Vec<IndexedColumn>,
+    where_clause: Option<String>,
+}
+
+impl CreateIndex {
+    /// Creates the index `name` on `table`, without columns yet.
+    pub fn new(name: &str, table: &str) -> CreateIndex {
+        CreateIndex {
+            schema: None,
+            name: name.to_owned(),
+            table: table.to_owned(),
+            unique: false,
+            if_not_exists: false,
+            columns: Vec::new(),
+            where_clause: None,
+        }
+    }
+
+    /// Creates the index in the database `schema`, which holds the table.
+    #[must_use]
+    pub fn schema(mut self, schema: &str) -> Self {
+        self.schema = Some(schema.to_owned());
+        self
+    }
+
+    /// Creates a `UNIQUE` index.
+    #[must_use]
+    pub fn unique(mut self) -> Self {
+        self.unique = true;
+        self
+    }
+
+    /// Does nothing if the index already exists.
+    #[must_use]
+    pub fn if_not_exists(mut self) -> Self {
+        self.if_not_exists = true;
+        self
+    }
+
+    /// Adds a column, or an expression, to the index.
+    #[must_use]
+    pub fn column<C: Into<IndexedColumn>>(mut self, column: C) -> Self {
+        self.columns.push(column.into());
+        self
+    }
+
+    /// Makes the index partial: only the rows for which `expr` is true are
+    /// indexed.
+    #[must_use]
+    pub fn where_clause(mut self, expr: &str) -> Self {
+        self.where_clause = Some(expr.to_owned());
+        self
+    }
+}
+
+impl fmt::Display for CreateIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CREATE ")?;
+        if self.unique {
+            f.write_str("UNIQUE ")?;
+        }
+        f.write_str("INDEX ")?;
+        if self.if_not_exists {
+            f.write_str("IF NOT EXISTS ")?;
+        }
+        // The table is in the schema of the index.
+        write!(
+            f,
+            "{} ON {} (",
+            qualified(&self.schema, &self.name),
+            quote_identifier(&self.table)
+        )?;
+        for (i, column) in self.columns.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", column)?;
+        }
+        f.write_str(")")?;
+        if let Some(expr) = &self.where_clause {
+            write!(f, " WHERE {}", expr)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Column, CreateIndex, CreateTable, ForeignKeyAction, IndexedColumn, References};
+    use crate::schema::{DefaultValue, Generated};
+    use crate::types::Value;
+    use crate::{Connection, Result};
+
+    #[test]
+    fn test_create_table() -> Result<()> {
+        let table = CreateTable::new("sam\"ples")
+            .if_not_exists()
+            .column(Column::new("id", "INTEGER").autoincrement())
+            .column(
+                Column::new("name", "TEXT")
+                    .not_null()
+                    .unique()
+                    .collate("NOCASE"),
+            )
+            .column(Column::new("depth", "REAL").check("depth >= 0"))
+            .column(
+                Column::new("note", "")
+                    .default(DefaultValue::Value(Value::Text("it's".to_owned()))),
+            )
+            .column(Column::new("added", "TEXT").default(DefaultValue::CurrentTimestamp))
+            .check("length(name) > 0");
+        assert_eq!(
+            table.to_string(),
+            "CREATE TABLE IF NOT EXISTS \"sam\"\"ples\" (\"id\" INTEGER PRIMARY KEY AUTOINCREMENT, \
+             \"name\" TEXT NOT NULL UNIQUE COLLATE \"NOCASE\", \"depth\" REAL CHECK (depth >= 0), \
+             \"note\" DEFAULT 'it''s', \"added\" TEXT DEFAULT CURRENT_TIMESTAMP, \
+             CHECK (length(name) > 0))"
+        );
+
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(&table.to_string())?;
+        db.execute_batch(&table.to_string())?;
+        let columns = db.table_columns(None, "sam\"ples")?;
+        assert_eq!(columns.len(), 5);
+        assert!(columns[1].not_null);
+        assert_eq!(
+            columns[3].default_value(),
+            Some(DefaultValue::Value(Value::Text("it's".to_owned())))
+        );
+        db.execute("INSERT INTO \"sam\"\"ples\" (name) VALUES ('a')", [])?;
+        db.execute("INSERT INTO \"sam\"\"ples\" (name) VALUES ('A')", [])
+            .unwrap_err();
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_table_options() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            &CreateTable::new("samples")
+                .column(Column::new("id", "INTEGER").primary_key())
+                .column(Column::new("depth", "REAL").default(DefaultValue::Value(Value::Real(-1.))))
+                .to_string(),
+        )?;
+        let calls = CreateTable::new("calls")
+            .temporary()
+            .column(Column::new("sample_id", "INTEGER").not_null())
+            .column(Column::new("variant", "TEXT").not_null())
+            .primary_key(&["sample_id", "variant"])
+            .foreign_key(
+                &["sample_id"],
+                References::new("samples")
+                    .columns(&["id"])
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .deferred(),
+            )
+            .without_rowid();
+        assert_eq!(
+            calls.to_string(),
+            "CREATE TEMP TABLE \"calls\" (\"sample_id\" INTEGER NOT NULL, \
+             \"variant\" TEXT NOT NULL, PRIMARY KEY (\"sample_id\", \"variant\"), \
+             FOREIGN KEY (\"sample_id\") REFERENCES \"samples\" (\"id\") ON DELETE CASCADE \
+             DEFERRABLE INITIALLY DEFERRED) WITHOUT ROWID"
+        );
+        db.execute_batch(&calls.to_string())?;
+        let options = db.table_options(None, "calls")?.unwrap();
+        assert!(options.without_rowid);
+        let foreign_keys = db.foreign_keys(None, "calls")?;
+        assert_eq!(foreign_keys[0].on_delete, "CASCADE");
+
+        db.execute("INSERT INTO samples (id) VALUES (1)", [])?;
+        let depth: f64 = db.query_row("SELECT depth FROM samples", [], |r| r.get(0))?;
+        assert_eq!(depth, -1.);
+
+        let generated = Column::new("score", "INTEGER").generated("depth * 10", Generated::Stored);
+        assert_eq!(
+            generated.to_string(),
+            "\"score\" INTEGER GENERATED ALWAYS AS (depth * 10) STORED"
+        );
+        if crate::version_number() >= 3_031_000 {
+            db.execute_batch(
+                &CreateTable::new("scores")
+                    .column(Column::new("depth", "REAL"))
+                    .column(generated)
+                    .to_string(),
+            )?;
+            db.execute("INSERT INTO scores (depth) VALUES (1.5)", [])?;
+            let score: i64 = db.query_row("SELECT score FROM scores", [], |r| r.get(0))?;
+            assert_eq!(score, 15);
+        }
+
+        let strict = CreateTable::new("strict")
+            .column(Column::new("id", "INTEGER"))
+            .strict();
+        assert_eq!(
+            strict.to_string(),
+            "CREATE TABLE \"strict\" (\"id\" INTEGER) STRICT"
+        );
+        if crate::version_number() >= 3_037_000 {
+            db.execute_batch(&strict.to_string())?;
+            assert!(db.table_options(None, "strict")?.unwrap().strict);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_index() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch("ATTACH ':memory:' AS aux; CREATE TABLE aux.calls (variant, depth);")?;
+        let index = CreateIndex::new("calls_by_variant", "calls")
+            .schema("aux")
+            .unique()
+            .if_not_exists()
+            .column(IndexedColumn::new("variant").collate("NOCASE"))
+            .column(IndexedColumn::expression("depth / 2").desc())
+            .column("depth")
+            .where_clause("depth IS NOT NULL");
+        assert_eq!(
+            index.to_string(),
+            "CREATE UNIQUE INDEX IF NOT EXISTS \"aux\".\"calls_by_variant\" ON \"calls\" \
+             (\"variant\" COLLATE \"NOCASE\", (depth / 2) DESC, \"depth\") \
+             WHERE depth IS NOT NULL"
+        );
+        db.execute_batch(&index.to_string())?;
+        db.execute_batch(&index.to_string())?;
+        let indexes = db.table_indexes(Some(crate::DatabaseName::Attached("aux")), "calls")?;
+        assert_eq!(indexes.len(), 1);
+        assert!(indexes[0].unique);
+        assert!(indexes[0].partial);
+        Ok(())
+    }
+}
@@ -0,0 +1,401 @@
+//! Deserialize result rows with [`serde`](https://docs.rs/serde).
+
+use crate::types::ValueRef;
+use crate::{AndThenRows, Error, Params, Result, Row, Statement};
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+};
+use std::fmt::Display;
+
+impl Statement<'_> {
+    /// Execute the prepared statement, returning an iterator over the rows
+    /// deserialized as `T`.
+    ///
+    /// Columns are matched to struct fields by name, so the query should alias
+    /// columns to the names of the fields they fill. Tuples and sequences are
+    /// filled by column position instead.
+    ///
+    /// ```rust,no_run
+    /// # use rusqlite::{Connection, Result};
+    /// #[derive(serde::Deserialize)]
+    /// struct Person {
+    ///     id: i64,
+    ///     name: String,
+    ///     email: Option<String>,
+    /// }
+    ///
+    /// fn people(conn: &Connection) -> Result<Vec<Person>> {
+    ///     let mut stmt = conn.prepare("SELECT id, name, email FROM person")?;
+    ///     let rows = stmt.query_as::<Person, _>([])?;
+    ///     rows.collect()
+    /// }
+    /// ```
+    ///
+    /// ## Failure
+    ///
+    /// Will return `Err` if binding parameters fails. Each item is an `Err` if
+    /// the row cannot be deserialized as `T`; see [`Row::deserialize`].
+    #[inline]
+    pub fn query_as<T, P>(&mut self, params: P) -> Result<AndThenRows<'_, DeserializeRow<T>>>
+    where
+        T: DeserializeOwned,
+        P: Params,
+    {
+        self.query_and_then(params, deserialize_row as DeserializeRow<T>)
+    }
+}
+
+impl Row<'_> {
+    /// Deserialize this row as `T`, matching columns to struct fields by name.
+    ///
+    /// ## Failure
+    ///
+    /// Returns an `Error::InvalidColumnType` if the value of a column cannot be
+    /// converted to the type of its field, and an `Error::DeserializeError`
+    /// for any other mismatch between the row and `T` (e.g. a missing field).
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T> {
+        T::deserialize(RowDeserializer { row: self })
+    }
+}
+
+type DeserializeRow<T> = fn(&Row<'_>) -> Result<T>;
+
+fn deserialize_row<T: DeserializeOwned>(row: &Row<'_>) -> Result<T> {
+    row.deserialize()
+}
+
+impl de::Error for Error {
+    #[cold]
+    fn custom<T: Display>(msg: T) -> Error {
+        Error::DeserializeError(msg.to_string())
+    }
+}
+
+struct RowDeserializer<'a, 'stmt> {
+    row: &'a Row<'stmt>,
+}
+
+impl<'de> de::Deserializer<'de> for RowDeserializer<'_, '_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_map(Columns::new(self.row))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(Columns::new(self.row))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct enum identifier ignored_any
+    }
+}
+
+// Visits the columns of a row, either as a map keyed by column name or as a
+// sequence.
+struct Columns<'a, 'stmt> {
+    row: &'a Row<'stmt>,
+    idx: usize,
+    count: usize,
+}
+
+impl<'a, 'stmt> Columns<'a, 'stmt> {
+    fn new(row: &'a Row<'stmt>) -> Self {
+        Columns {
+            row,
+            idx: 0,
+            count: row.column_count(),
+        }
+    }
+
+    fn next_column(&mut self) -> Option<ColumnDeserializer<'a, 'stmt>> {
+        if self.idx >= self.count {
+            return None;
+        }
+        self.idx += 1;
+        Some(ColumnDeserializer {
+            row: self.row,
+            idx: self.idx - 1,
+        })
+    }
+}
+
+impl<'de> MapAccess<'de> for Columns<'_, '_> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.idx >= self.count {
+            return Ok(None);
+        }
+        let name = self.row.column_name(self.idx)?;
+        seed.deserialize(name.into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let column = self
+            .next_column()
+            .ok_or(Error::InvalidColumnIndex(self.idx))?;
+        column.deserialize_with(seed)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.count - self.idx)
+    }
+}
+
+impl<'de> SeqAccess<'de> for Columns<'_, '_> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        match self.next_column() {
+            Some(column) => column.deserialize_with(seed).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.count - self.idx)
+    }
+}
+
+// Deserializes the value of a single column. Conversions to Rust primitives go
+// through `FromSql`, so they behave (and fail) exactly like `Row::get`.
+struct ColumnDeserializer<'a, 'stmt> {
+    row: &'a Row<'stmt>,
+    idx: usize,
+}
+
+impl ColumnDeserializer<'_, '_> {
+    fn deserialize_with<'de, T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        let (row, idx) = (self.row, self.idx);
+        seed.deserialize(self).map_err(|err| match err {
+            // Say which column the message is about.
+            Error::DeserializeError(msg) => Error::DeserializeError(format!(
+                "{} (column {})",
+                msg,
+                row.column_name(idx).unwrap_or("?")
+            )),
+            err => err,
+        })
+    }
+
+    fn value(&self) -> Result<ValueRef<'_>> {
+        self.row.get_ref(self.idx)
+    }
+}
+
+macro_rules! deserialize_from_sql {
+    ($($method:ident => $visit:ident($t:ty),)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                visitor.$visit(self.row.get::<_, $t>(self.idx)?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ColumnDeserializer<'_, '_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value()? {
+            ValueRef::Null => visitor.visit_unit(),
+            ValueRef::Integer(i) => visitor.visit_i64(i),
+            ValueRef::Real(f) => visitor.visit_f64(f),
+            ValueRef::Text(s) => visitor.visit_str(std::str::from_utf8(s)?),
+            ValueRef::Blob(b) => visitor.visit_bytes(b),
+        }
+    }
+
+    deserialize_from_sql! {
+        deserialize_bool => visit_bool(bool),
+        deserialize_i8 => visit_i8(i8),
+        deserialize_i16 => visit_i16(i16),
+        deserialize_i32 => visit_i32(i32),
+        deserialize_i64 => visit_i64(i64),
+        deserialize_u8 => visit_u8(u8),
+        deserialize_u16 => visit_u16(u16),
+        deserialize_u32 => visit_u32(u32),
+        deserialize_u64 => visit_u64(u64),
+        deserialize_f32 => visit_f32(f32),
+        deserialize_f64 => visit_f64(f64),
+        deserialize_string => visit_string(String),
+        deserialize_byte_buf => visit_byte_buf(Vec<u8>),
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value()? {
+            ValueRef::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        // Only unit variants, stored as their name, can be read from a column.
+        let variant: String = self.row.get(self.idx)?;
+        visitor.visit_enum(variant.into_deserializer())
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 char unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::Type;
+    use crate::{Connection, Error, Result};
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Kind {
+        Admin,
+        User,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Person {
+        id: u32,
+        name: String,
+        email: Option<String>,
+        kind: Kind,
+        score: f64,
+        #[serde(default)]
+        missing: bool,
+    }
+
+    fn checked_memory_handle() -> Result<Connection> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE person (id INTEGER, name TEXT, email TEXT, kind TEXT, score REAL);
+             INSERT INTO person VALUES (1, 'alice', 'a@example.com', 'Admin', 1.5);
+             INSERT INTO person VALUES (2, 'bob', NULL, 'User', 2);",
+        )?;
+        Ok(db)
+    }
+
+    #[test]
+    fn test_query_as_struct() -> Result<()> {
+        let db = checked_memory_handle()?;
+        let mut stmt = db.prepare("SELECT * FROM person ORDER BY id")?;
+        let people = stmt
+            .query_as::<Person, _>([])?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            people,
+            vec![
+                Person {
+                    id: 1,
+                    name: "alice".to_owned(),
+                    email: Some("a@example.com".to_owned()),
+                    kind: Kind::Admin,
+                    score: 1.5,
+                    missing: false,
+                },
+                Person {
+                    id: 2,
+                    name: "bob".to_owned(),
+                    email: None,
+                    kind: Kind::User,
+                    score: 2.0,
+                    missing: false,
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_as_tuple() -> Result<()> {
+        let db = checked_memory_handle()?;
+        let mut stmt = db.prepare("SELECT id, name FROM person WHERE id = ?")?;
+        let row: (i64, String) = stmt.query_as([2])?.next().unwrap()?;
+        assert_eq!(row, (2, "bob".to_owned()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_errors() -> Result<()> {
+        let db = checked_memory_handle()?;
+        let mut stmt = db.prepare("SELECT name AS id, name FROM person")?;
+        match stmt.query_row([], |row| row.deserialize::<Person>()) {
+            Err(Error::InvalidColumnType(0, name, Type::Text)) => assert_eq!(name, "id"),
+            err => panic!("Unexpected result {:?}", err),
+        }
+
+        let mut stmt = db.prepare("SELECT id, name FROM person")?;
+        match stmt.query_row([], |row| row.deserialize::<Person>()) {
+            Err(Error::DeserializeError(msg)) => assert_eq!(msg, "missing field `kind`"),
+            err => panic!("Unexpected result {:?}", err),
+        }
+
+        let mut stmt = db.prepare("SELECT 'Guest' AS kind")?;
+        #[derive(Debug, Deserialize)]
+        struct OnlyKind {
+            #[allow(dead_code)]
+            kind: Kind,
+        }
+        match stmt.query_row([], |row| row.deserialize::<OnlyKind>()) {
+            Err(Error::DeserializeError(msg)) => assert!(msg.contains("(column kind)"), "{}", msg),
+            err => panic!("Unexpected result {:?}", err),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,220 @@
+//! `feature = "deterministic"` Reproducible query results, for snapshot
+//! tests.
+//!
+//! [`Connection::enable_deterministic_mode`] replaces, on a connection:
+//!
+//! * `random()` and `randomblob(N)` by functions drawing from a PRNG seeded
+//!   with a given seed, so that the same values are generated by each run;
+//! * `CURRENT_TIMESTAMP`, `CURRENT_DATE` and `CURRENT_TIME` by functions
+//!   reading a [`Clock`], which only changes when the test sets it;
+//!
+//! and enables the query planner stability guarantee (where supported), so
+//! that the plans, and the order of unordered results, don't depend on
+//! `ANALYZE` statistics.
+//!
+//! The date and time functions given `'now'` still read the system clock.
+//!
+//! ```rust
+//! # use rusqlite::{Connection, Result};
+//! # fn main() -> Result<()> {
+//! let db = Connection::open_in_memory()?;
+//! let clock = db.enable_deterministic_mode(42, 1_600_000_000)?;
+//! let now: String = db.query_row("SELECT CURRENT_TIMESTAMP", [], |r| r.get(0))?;
+//! assert_eq!(now, "2020-09-13 12:26:40");
+//! clock.advance(60);
+//! let now: String = db.query_row("SELECT CURRENT_TIME", [], |r| r.get(0))?;
+//! assert_eq!(now, "12:27:40");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::config::DbConfig;
+use crate::ffi;
+use crate::functions::FunctionFlags;
+use crate::{Connection, Error, Result};
+
+/// `feature = "deterministic"` The time seen by `CURRENT_TIMESTAMP` and
+/// friends on a connection in deterministic mode, in seconds since the Unix
+/// epoch (UTC).
+///
+/// Clones share the same time.
+#[derive(Clone, Debug)]
+pub struct Clock(Arc<AtomicI64>);
+
+impl Clock {
+    /// Returns the current time.
+    pub fn now(&self) -> i64 {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Sets the current time.
+    pub fn set(&self, unix_time: i64) {
+        self.0.store(unix_time, Ordering::SeqCst)
+    }
+
+    /// Moves the current time forward (or backward) by `seconds`.
+    pub fn advance(&self, seconds: i64) {
+        self.0.fetch_add(seconds, Ordering::SeqCst);
+    }
+}
+
+impl Connection {
+    /// `feature = "deterministic"` Makes `random()`, `randomblob()` and
+    /// `CURRENT_TIMESTAMP` (and friends) reproducible on this connection, see
+    /// the [module documentation](crate::deterministic), starting from
+    /// `seed` and `unix_time`, and returns the clock to set the time.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the functions cannot be replaced.
+    pub fn enable_deterministic_mode(&self, seed: u64, unix_time: i64) -> Result<Clock> {
+        let state = Arc::new(Mutex::new(seed));
+        let rng = state.clone();
+        self.create_scalar_function("random", 0, FunctionFlags::SQLITE_UTF8, move |_| {
+            Ok(next(&rng) as i64)
+        })?;
+        let rng = state;
+        self.create_scalar_function("randomblob", 1, FunctionFlags::SQLITE_UTF8, move |ctx| {
+            let len = ctx.get::<i64>(0).unwrap_or(0).max(1);
+            let max = unsafe {
+                ffi::sqlite3_limit(ctx.get_connection()?.handle(), ffi::SQLITE_LIMIT_LENGTH, -1)
+            };
+            if len > i64::from(max) {
+                return Err(Error::SqliteFailure(
+                    ffi::Error::new(ffi::SQLITE_TOOBIG),
+                    Some("string or blob too big".to_owned()),
+                ));
+            }
+            let len = len as usize;
+            let mut blob = Vec::with_capacity(len + 8);
+            while blob.len() < len {
+                blob.extend_from_slice(&next(&rng).to_le_bytes());
+            }
+            blob.truncate(len);
+            Ok(blob)
+        })?;
+
+        let clock = Clock(Arc::new(AtomicI64::new(unix_time)));
+        for &name in &["current_timestamp", "current_date", "current_time"] {
+            let time = clock.clone();
+            self.create_scalar_function(name, 0, FunctionFlags::SQLITE_UTF8, move |_| {
+                let now = time.now();
+                Ok(match name {
+                    "current_date" => format_date(now),
+                    "current_time" => format_time(now),
+                    _ => format!("{} {}", format_date(now), format_time(now)),
+                })
+            })?;
+        }
+
+        // Not supported before SQLite 3.20.0.
+        let _ = self.set_db_config(DbConfig::SQLITE_DBCONFIG_ENABLE_QPSG, true);
+        Ok(clock)
+    }
+}
+
+// SplitMix64.
+fn next(state: &Mutex<u64>) -> u64 {
+    let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn format_date(unix_time: i64) -> String {
+    // Days to civil date, from http://howardhinnant.github.io/date_algorithms.html
+    let z = unix_time.div_euclid(86400) + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn format_time(unix_time: i64) -> String {
+    let seconds = unix_time.rem_euclid(86400);
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_date, format_time};
+    use crate::{ffi, Connection, Error, ErrorCode, Result};
+
+    fn randoms(seed: u64) -> Result<(i64, Vec<u8>)> {
+        let db = Connection::open_in_memory()?;
+        db.enable_deterministic_mode(seed, 0)?;
+        db.query_row("SELECT random(), randomblob(12)", [], |r| {
+            Ok((r.get(0)?, r.get(1)?))
+        })
+    }
+
+    #[test]
+    fn test_random() -> Result<()> {
+        let (n, blob) = randoms(1)?;
+        assert_eq!(blob.len(), 12);
+        assert_eq!(randoms(1)?, (n, blob));
+        assert_ne!(randoms(2)?.0, n);
+        Ok(())
+    }
+
+    #[test]
+    fn test_randomblob_too_big() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.enable_deterministic_mode(1, 0)?;
+        unsafe { ffi::sqlite3_limit(db.handle(), ffi::SQLITE_LIMIT_LENGTH, 100) };
+        let len: i64 = db.query_row("SELECT length(randomblob(100))", [], |r| r.get(0))?;
+        assert_eq!(len, 100);
+        match db.query_row("SELECT randomblob(101)", [], |r| r.get::<_, Vec<u8>>(0)) {
+            Err(Error::SqliteFailure(err, _)) => assert_eq!(err.code, ErrorCode::TooBig),
+            r => panic!("unexpected {:?}", r),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_clock() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        let clock = db.enable_deterministic_mode(0, 951_782_400)?;
+        db.execute_batch(
+            "CREATE TABLE events (at TEXT DEFAULT CURRENT_TIMESTAMP, day TEXT DEFAULT CURRENT_DATE)",
+        )?;
+        db.execute("INSERT INTO events DEFAULT VALUES", [])?;
+        clock.advance(86400 + 3661);
+        db.execute("INSERT INTO events DEFAULT VALUES", [])?;
+        let mut stmt = db.prepare("SELECT at, day FROM events ORDER BY at")?;
+        let rows: Vec<(String, String)> = stmt
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?
+            .collect::<Result<_>>()?;
+        assert_eq!(
+            rows,
+            vec![
+                ("2000-02-29 00:00:00".to_owned(), "2000-02-29".to_owned()),
+                ("2000-03-01 01:01:01".to_owned(), "2000-03-01".to_owned()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(-1), "1969-12-31");
+        assert_eq!(format_time(-1), "23:59:59");
+        assert_eq!(format_date(253_402_300_799), "9999-12-31");
+    }
+}
@@ -0,0 +1,652 @@
+//! Compare databases row by row, like the `sqldiff` tool of SQLite.
+//!
+//! A [`Diff`] lists the changes turning the tables of one database into the
+//! ones of another, either as [`Change`]s or as the SQL statements making
+//! them. Rows are matched by primary key, or by rowid for tables without
+//! one, and both tables are scanned once, in key order, so that memory use
+//! doesn't depend on their size.
+//!
+//! ```rust,no_run
+//! use rusqlite::diff::Diff;
+//! use rusqlite::{Connection, Result};
+//!
+//! fn patch(old: &Connection, new: &Connection) -> Result<String> {
+//!     let mut sql = String::new();
+//!     Diff::new(old, new).for_each(|change| {
+//!         sql.push_str(&change.to_sql());
+//!         sql.push('\n');
+//!         Ok(())
+//!     })?;
+//!     Ok(sql)
+//! }
+//! ```
+//!
+//! Virtual tables and the internal `sqlite_` tables are left out. Tables
+//! which columns or primary keys differ are dropped and created again.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::dump::write_value;
+use crate::export::to_io;
+use crate::schema::TableColumn;
+use crate::types::{Value, ValueRef};
+use crate::util::quote_identifier;
+use crate::{ffi, Connection, DatabaseName, Error, Result, Savepoint};
+
+/// A change of a table, or of one of its rows. The values of rows are
+/// given as `(column, value)` pairs, where the rowid of a table without
+/// primary key is named `rowid`.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Change {
+    /// Create `table` with `sql`.
+    CreateTable {
+        /// The table
+        table: String,
+        /// The `CREATE TABLE` statement
+        sql: String,
+    },
+    /// Drop `table`.
+    DropTable {
+        /// The table
+        table: String,
+    },
+    /// Insert a row.
+    Insert {
+        /// The table
+        table: String,
+        /// The values of the columns of the row
+        values: Vec<(String, Value)>,
+    },
+    /// Delete the row with primary key `key`.
+    Delete {
+        /// The table
+        table: String,
+        /// The primary key of the row
+        key: Vec<(String, Value)>,
+    },
+    /// Update the columns of the row with primary key `key`.
+    Update {
+        /// The table
+        table: String,
+        /// The primary key of the row
+        key: Vec<(String, Value)>,
+        /// The new values of the changed columns
+        set: Vec<(String, Value)>,
+    },
+}
+
+impl Change {
+    /// Returns the table changed.
+    pub fn table(&self) -> &str {
+        match self {
+            Change::CreateTable { table, .. }
+            | Change::DropTable { table }
+            | Change::Insert { table, .. }
+            | Change::Delete { table, .. }
+            | Change::Update { table, .. } => table,
+        }
+    }
+
+    /// Writes the SQL statement making the change, ended by `;`.
+    pub fn write_sql<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            Change::CreateTable { sql, .. } => write!(writer, "{};", sql),
+            Change::DropTable { table } => {
+                write!(writer, "DROP TABLE {};", quote_identifier(table))
+            }
+            Change::Insert { table, values } => {
+                write!(writer, "INSERT INTO {}(", quote_identifier(table))?;
+                for (i, (column, _)) in values.iter().enumerate() {
+                    if i > 0 {
+                        writer.write_all(b",")?;
+                    }
+                    writer.write_all(quote_identifier(column).as_bytes())?;
+                }
+                writer.write_all(b") VALUES(")?;
+                for (i, (_, value)) in values.iter().enumerate() {
+                    if i > 0 {
+                        writer.write_all(b",")?;
+                    }
+                    write_value(writer, ValueRef::from(value))?;
+                }
+                writer.write_all(b");")
+            }
+            Change::Delete { table, key } => {
+                write!(writer, "DELETE FROM {}", quote_identifier(table))?;
+                write_where(writer, key)?;
+                writer.write_all(b";")
+            }
+            Change::Update { table, key, set } => {
+                write!(writer, "UPDATE {} SET ", quote_identifier(table))?;
+                for (i, (column, value)) in set.iter().enumerate() {
+                    if i > 0 {
+                        writer.write_all(b", ")?;
+                    }
+                    write!(writer, "{}=", quote_identifier(column))?;
+                    write_value(writer, ValueRef::from(value))?;
+                }
+                write_where(writer, key)?;
+                writer.write_all(b";")
+            }
+        }
+    }
+
+    /// Returns the SQL statement making the change, ended by `;`.
+    pub fn to_sql(&self) -> String {
+        let mut sql = Vec::new();
+        self.write_sql(&mut sql).unwrap();
+        String::from_utf8(sql).unwrap()
+    }
+}
+
+fn write_where<W: Write>(writer: &mut W, key: &[(String, Value)]) -> io::Result<()> {
+    for (i, (column, value)) in key.iter().enumerate() {
+        writer.write_all(if i == 0 { b" WHERE " } else { b" AND " })?;
+        writer.write_all(quote_identifier(column).as_bytes())?;
+        if *value == Value::Null {
+            writer.write_all(b" IS NULL")?;
+        } else {
+            writer.write_all(b"=")?;
+            write_value(writer, ValueRef::from(value))?;
+        }
+    }
+    Ok(())
+}
+
+/// Compares the tables of two databases.
+///
+/// By default, all the tables of the `main` databases are compared.
+pub struct Diff<'a> {
+    from: &'a Connection,
+    to: &'a Connection,
+    from_schema: DatabaseName<'a>,
+    to_schema: DatabaseName<'a>,
+    filter: Option<FilterFn<'a>>,
+}
+
+type FilterFn<'a> = Box<dyn Fn(&str) -> bool + 'a>;
+
+impl fmt::Debug for Diff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Diff")
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .field("from_schema", &self.from_schema)
+            .field("to_schema", &self.to_schema)
+            .finish()
+    }
+}
+
+// A table to compare: its `CREATE TABLE` statement, the columns compared,
+// with the rowid first for a table without primary key, and the indexes of
+// the columns of its primary key.
+struct Table {
+    sql: String,
+    columns: Vec<String>,
+    key: Vec<usize>,
+}
+
+impl<'a> Diff<'a> {
+    /// Creates a comparison of the `main` databases of `from` and `to`,
+    /// which can be the same connection.
+    pub fn new(from: &'a Connection, to: &'a Connection) -> Diff<'a> {
+        Diff {
+            from,
+            to,
+            from_schema: DatabaseName::Main,
+            to_schema: DatabaseName::Main,
+            filter: None,
+        }
+    }
+
+    /// Sets the database of `from` to compare, e.g. an attached database.
+    #[must_use]
+    pub fn from_schema(mut self, schema: DatabaseName<'a>) -> Diff<'a> {
+        self.from_schema = schema;
+        self
+    }
+
+    /// Sets the database of `to` to compare, e.g. an attached database.
+    #[must_use]
+    pub fn to_schema(mut self, schema: DatabaseName<'a>) -> Diff<'a> {
+        self.to_schema = schema;
+        self
+    }
+
+    /// Only compares the tables for which `filter` returns `true`.
+    #[must_use]
+    pub fn filter<F>(mut self, filter: F) -> Diff<'a>
+    where
+        F: Fn(&str) -> bool + 'a,
+    {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Calls `f` with each change, table by table in the order of their
+    /// names, and the rows of a table in the order of their keys.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite calls fail, or the first
+    /// error returned by `f`.
+    pub fn for_each<F>(&self, mut f: F) -> Result<()>
+    where
+        F: FnMut(Change) -> Result<()>,
+    {
+        // The rows are read in a single transaction on each side.
+        let _from_sp = Savepoint::with_depth_and_name(self.from, 0, "_rusqlite_diff")?;
+        let _to_sp = Savepoint::with_depth_and_name(self.to, 0, "_rusqlite_diff")?;
+        let mut names = table_names(self.from, self.from_schema)?;
+        names.extend(table_names(self.to, self.to_schema)?);
+        names.sort();
+        names.dedup();
+        for name in names {
+            if self.filter.as_ref().map(|f| f(&name)) != Some(false) {
+                self.diff_table(&name, &mut f)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls `f` with each change of `table`, as [`for_each`](Diff::for_each).
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite calls fail, or the first
+    /// error returned by `f`.
+    pub fn for_each_in_table<F>(&self, table: &str, mut f: F) -> Result<()>
+    where
+        F: FnMut(Change) -> Result<()>,
+    {
+        let _from_sp = Savepoint::with_depth_and_name(self.from, 0, "_rusqlite_diff")?;
+        let _to_sp = Savepoint::with_depth_and_name(self.to, 0, "_rusqlite_diff")?;
+        self.diff_table(table, &mut f)
+    }
+
+    /// Returns all the changes, as [`for_each`](Diff::for_each).
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite calls fail.
+    pub fn changes(&self) -> Result<Vec<Change>> {
+        let mut changes = Vec::new();
+        self.for_each(|change| {
+            changes.push(change);
+            Ok(())
+        })?;
+        Ok(changes)
+    }
+
+    /// Writes the SQL statements making the changes, one per line.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if writing fails, or if reading the databases
+    /// fails, in which case the error wraps the `rusqlite::Error`.
+    pub fn write_sql<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut write_error = None;
+        let read = self.for_each(|change| {
+            match change
+                .write_sql(&mut writer)
+                .and_then(|_| writer.write_all(b"\n"))
+            {
+                Ok(()) => Ok(()),
+                Err(err) => {
+                    write_error = Some(err);
+                    // Stops the comparison: the write error is returned.
+                    Err(Error::SqliteFailure(
+                        ffi::Error::new(ffi::SQLITE_ABORT),
+                        None,
+                    ))
+                }
+            }
+        });
+        if let Some(err) = write_error {
+            return Err(err);
+        }
+        read.map_err(to_io)?;
+        writer.flush()
+    }
+
+    fn diff_table(&self, name: &str, f: &mut dyn FnMut(Change) -> Result<()>) -> Result<()> {
+        let from = table(self.from, self.from_schema, name)?;
+        let to = table(self.to, self.to_schema, name)?;
+        let (from, to) = match (from, to) {
+            (None, None) => return Ok(()),
+            (Some(_), None) => {
+                return f(Change::DropTable {
+                    table: name.to_owned(),
+                })
+            }
+            (from, Some(to)) => {
+                let same =
+                    matches!(&from, Some(from) if from.columns == to.columns && from.key == to.key);
+                if !same {
+                    if from.is_some() {
+                        f(Change::DropTable {
+                            table: name.to_owned(),
+                        })?;
+                    }
+                    f(Change::CreateTable {
+                        table: name.to_owned(),
+                        sql: to.sql.clone(),
+                    })?;
+                    return self.scan(self.to, self.to_schema, name, &to, |values| {
+                        f(insert(name, &to, values))
+                    });
+                }
+                (from.unwrap(), to)
+            }
+        };
+
+        let select = |schema| select(schema, name, &from);
+        let mut from_stmt = self.from.prepare(&select(self.from_schema))?;
+        let mut to_stmt = self.to.prepare(&select(self.to_schema))?;
+        let mut from_rows = from_stmt.query([])?;
+        let mut to_rows = to_stmt.query([])?;
+        let n = from.columns.len();
+        let mut old = next_values(&mut from_rows, n)?;
+        let mut new = next_values(&mut to_rows, n)?;
+        loop {
+            let order = match (&old, &new) {
+                (None, None) => return Ok(()),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(old), Some(new)) => compare_keys(&from.key, old, new),
+            };
+            match order {
+                Ordering::Less => {
+                    f(Change::Delete {
+                        table: name.to_owned(),
+                        key: key(&from, old.as_ref().unwrap()),
+                    })?;
+                    old = next_values(&mut from_rows, n)?;
+                }
+                Ordering::Greater => {
+                    f(insert(name, &to, new.take().unwrap()))?;
+                    new = next_values(&mut to_rows, n)?;
+                }
+                Ordering::Equal => {
+                    let (old_values, new_values) = (old.take().unwrap(), new.take().unwrap());
+                    let set: Vec<(String, Value)> = new_values
+                        .into_iter()
+                        .zip(old_values.iter())
+                        .enumerate()
+                        .filter(|(_, (new, old))| new != *old)
+                        .map(|(i, (new, _))| (to.columns[i].clone(), new))
+                        .collect();
+                    if !set.is_empty() {
+                        f(Change::Update {
+                            table: name.to_owned(),
+                            key: key(&from, &old_values),
+                            set,
+                        })?;
+                    }
+                    old = next_values(&mut from_rows, n)?;
+                    new = next_values(&mut to_rows, n)?;
+                }
+            }
+        }
+    }
+
+    fn scan<F>(
+        &self,
+        conn: &Connection,
+        schema: DatabaseName<'_>,
+        name: &str,
+        table: &Table,
+        mut f: F,
+    ) -> Result<()>
+    where
+        F: FnMut(Vec<Value>) -> Result<()>,
+    {
+        let mut stmt = conn.prepare(&select(schema, name, table))?;
+        let mut rows = stmt.query([])?;
+        while let Some(values) = next_values(&mut rows, table.columns.len())? {
+            f(values)?;
+        }
+        Ok(())
+    }
+}
+
+fn table_names(conn: &Connection, schema: DatabaseName<'_>) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT name FROM {}.sqlite_master
+         WHERE type = 'table' AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\'
+         AND sql NOT LIKE 'CREATE VIRTUAL TABLE%'",
+        schema_name(schema)
+    ))?;
+    let names = stmt.query_map([], |row| row.get(0))?;
+    names.collect()
+}
+
+fn table(conn: &Connection, schema: DatabaseName<'_>, name: &str) -> Result<Option<Table>> {
+    let sql: Option<String> = {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT sql FROM {}.sqlite_master WHERE type = 'table' AND name = ?",
+            schema_name(schema)
+        ))?;
+        let mut rows = stmt.query([name])?;
+        match rows.next()? {
+            Some(row) => Some(row.get(0)?),
+            None => None,
+        }
+    };
+    let sql = match sql {
+        Some(sql) => sql,
+        None => return Ok(None),
+    };
+    let columns: Vec<TableColumn> = conn
+        .table_columns(Some(schema), name)?
+        .into_iter()
+        .filter(TableColumn::is_insertable)
+        .collect();
+    let mut key: Vec<usize> = (0..columns.len()).filter(|&i| columns[i].pk > 0).collect();
+    key.sort_by_key(|&i| columns[i].pk);
+    let mut columns: Vec<String> = columns.into_iter().map(|c| c.name).collect();
+    if key.is_empty() {
+        columns.insert(0, "rowid".to_owned());
+        key.push(0);
+    }
+    Ok(Some(Table { sql, columns, key }))
+}
+
+// The rows of `table`, ordered by key, in the binary collation compared by
+// `compare_keys`.
+fn select(schema: DatabaseName<'_>, name: &str, table: &Table) -> String {
+    let columns: Vec<String> = table.columns.iter().map(|c| quote_identifier(c)).collect();
+    let order: Vec<String> = table
+        .key
+        .iter()
+        .map(|&i| format!("{} COLLATE BINARY", columns[i]))
+        .collect();
+    format!(
+        "SELECT {} FROM {}.{} ORDER BY {}",
+        columns.join(", "),
+        schema_name(schema),
+        quote_identifier(name),
+        order.join(", ")
+    )
+}
+
+fn next_values(rows: &mut crate::Rows<'_>, n: usize) -> Result<Option<Vec<Value>>> {
+    match rows.next()? {
+        Some(row) => (0..n).map(|i| row.get(i)).collect::<Result<_>>().map(Some),
+        None => Ok(None),
+    }
+}
+
+fn insert(name: &str, table: &Table, values: Vec<Value>) -> Change {
+    Change::Insert {
+        table: name.to_owned(),
+        values: table.columns.iter().cloned().zip(values).collect(),
+    }
+}
+
+fn key(table: &Table, values: &[Value]) -> Vec<(String, Value)> {
+    table
+        .key
+        .iter()
+        .map(|&i| (table.columns[i].clone(), values[i].clone()))
+        .collect()
+}
+
+fn compare_keys(key: &[usize], a: &[Value], b: &[Value]) -> Ordering {
+    key.iter()
+        .map(|&i| compare(&a[i], &b[i]))
+        .find(|&order| order != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
+
+// Compares values as SQLite does with the binary collation: NULL first,
+// then numbers, text and blobs.
+fn compare(a: &Value, b: &Value) -> Ordering {
+    fn class(value: &Value) -> u8 {
+        match value {
+            Value::Null => 0,
+            Value::Integer(_) | Value::Real(_) => 1,
+            Value::Text(_) => 2,
+            Value::Blob(_) => 3,
+        }
+    }
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+        (Value::Integer(a), Value::Real(b)) => {
+            (*a as f64).partial_cmp(b).unwrap_or(Ordering::Equal)
+        }
+        (Value::Real(a), Value::Integer(b)) => {
+            a.partial_cmp(&(*b as f64)).unwrap_or(Ordering::Equal)
+        }
+        (Value::Real(a), Value::Real(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+        (Value::Text(a), Value::Text(b)) => a.as_bytes().cmp(b.as_bytes()),
+        (Value::Blob(a), Value::Blob(b)) => a.cmp(b),
+        _ => class(a).cmp(&class(b)),
+    }
+}
+
+fn schema_name(schema: DatabaseName<'_>) -> String {
+    match schema {
+        DatabaseName::Main => "main".to_owned(),
+        DatabaseName::Temp => "temp".to_owned(),
+        DatabaseName::Attached(s) => quote_identifier(s),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Change, Diff};
+    use crate::types::Value;
+    use crate::{Connection, DatabaseName, Result};
+
+    fn text(s: &str) -> Value {
+        Value::Text(s.to_owned())
+    }
+
+    #[test]
+    fn test_diff() -> Result<()> {
+        let old = Connection::open_in_memory()?;
+        old.execute_batch(
+            "CREATE TABLE foo (id INTEGER PRIMARY KEY, name TEXT, n);
+             INSERT INTO foo VALUES (1, 'a', 1), (2, 'b', 2), (3, 'c', 3);
+             CREATE TABLE bar (x, y);
+             INSERT INTO bar VALUES ('x', 1);
+             CREATE TABLE gone (x);",
+        )?;
+        let new = Connection::open_in_memory()?;
+        new.execute_batch(
+            "CREATE TABLE foo (id INTEGER PRIMARY KEY, name TEXT, n);
+             INSERT INTO foo VALUES (1, 'a', 1), (3, 'c''', 3), (4, NULL, X'00');
+             CREATE TABLE bar (x, y, z);
+             INSERT INTO bar VALUES ('x', 1, NULL);",
+        )?;
+
+        let changes = Diff::new(&old, &new).changes()?;
+        assert_eq!(
+            changes,
+            vec![
+                Change::DropTable {
+                    table: "bar".to_owned()
+                },
+                Change::CreateTable {
+                    table: "bar".to_owned(),
+                    sql: "CREATE TABLE bar (x, y, z)".to_owned()
+                },
+                Change::Insert {
+                    table: "bar".to_owned(),
+                    values: vec![
+                        ("rowid".to_owned(), Value::Integer(1)),
+                        ("x".to_owned(), text("x")),
+                        ("y".to_owned(), Value::Integer(1)),
+                        ("z".to_owned(), Value::Null),
+                    ]
+                },
+                Change::Delete {
+                    table: "foo".to_owned(),
+                    key: vec![("id".to_owned(), Value::Integer(2))]
+                },
+                Change::Update {
+                    table: "foo".to_owned(),
+                    key: vec![("id".to_owned(), Value::Integer(3))],
+                    set: vec![("name".to_owned(), text("c'"))]
+                },
+                Change::Insert {
+                    table: "foo".to_owned(),
+                    values: vec![
+                        ("id".to_owned(), Value::Integer(4)),
+                        ("name".to_owned(), Value::Null),
+                        ("n".to_owned(), Value::Blob(vec![0])),
+                    ]
+                },
+                Change::DropTable {
+                    table: "gone".to_owned()
+                },
+            ]
+        );
+
+        // Applying the statements makes the databases equal.
+        let mut sql = Vec::new();
+        Diff::new(&old, &new).write_sql(&mut sql).unwrap();
+        let sql = String::from_utf8(sql).unwrap();
+        assert!(sql.contains("UPDATE \"foo\" SET \"name\"='c''' WHERE \"id\"=3;\n"));
+        old.execute_batch(&sql)?;
+        assert_eq!(Diff::new(&old, &new).changes()?, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_attached() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "ATTACH ':memory:' AS other;
+             CREATE TABLE main.t (a TEXT, b INTEGER, c, PRIMARY KEY (b, a)) WITHOUT ROWID;
+             CREATE TABLE other.t (a TEXT, b INTEGER, c, PRIMARY KEY (b, a)) WITHOUT ROWID;
+             INSERT INTO main.t VALUES ('a', 1, 0), ('B', 1, 0), ('b', 1, 0), ('a', 2, 0);
+             INSERT INTO other.t VALUES ('a', 1, 0), ('b', 1, 1), ('c', 1, 0), ('a', 2, 0);
+             CREATE TABLE main.skipped (x);",
+        )?;
+        let diff = Diff::new(&db, &db)
+            .to_schema(DatabaseName::Attached("other"))
+            .filter(|table| table != "skipped");
+        let sql: Vec<String> = diff.changes()?.iter().map(Change::to_sql).collect();
+        assert_eq!(
+            sql,
+            vec![
+                "DELETE FROM \"t\" WHERE \"b\"=1 AND \"a\"='B';",
+                "UPDATE \"t\" SET \"c\"=1 WHERE \"b\"=1 AND \"a\"='b';",
+                "INSERT INTO \"t\"(\"a\",\"b\",\"c\") VALUES('c',1,0);",
+            ]
+        );
+        let mut n = 0;
+        diff.for_each_in_table("skipped", |change| {
+            assert_eq!(change.table(), "skipped");
+            n += 1;
+            Ok(())
+        })?;
+        assert_eq!(n, 1);
+        Ok(())
+    }
+}
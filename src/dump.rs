@@ -0,0 +1,459 @@
+//! Dump a database as SQL text, like the `.dump` command of the `sqlite3`
+//! shell.
+//!
+//! A [`Dump`] writes the statements recreating the schema and the rows of a
+//! database to an [`io::Write`], one row at a time, within a transaction:
+//! tables and their rows first, then indexes, triggers and views. The output
+//! can be run with [`Connection::execute_batch`] or the `sqlite3` shell.
+//!
+//! ```rust,no_run
+//! use std::fs::File;
+//! use std::io::{self, BufWriter};
+//! use rusqlite::dump::Dump;
+//! use rusqlite::Connection;
+//!
+//! fn export(conn: &Connection, path: &str) -> io::Result<()> {
+//!     let file = BufWriter::new(File::create(path)?);
+//!     Dump::new(conn)
+//!         .filter(|table| !table.starts_with("cache_"))
+//!         .write_to(file)
+//! }
+//! ```
+//!
+//! Virtual tables are written as their `CREATE VIRTUAL TABLE` statement
+//! only, and the statistics tables (`sqlite_stat1`, ...) are left out. The
+//! shadow tables of the FTS3, FTS4, FTS5 and R*Tree virtual tables, which
+//! hold their content, are created along with them, so only their rows are
+//! written, replacing the initial ones: the restored database must not be in
+//! [defensive](https://sqlite.org/c3ref/c_dbconfig_defensive.html) mode.
+
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::export::to_io;
+use crate::types::ValueRef;
+use crate::util::quote_identifier;
+use crate::{Connection, DatabaseName, Savepoint};
+
+/// Writes the schema and the rows of a database as SQL statements.
+///
+/// By default, all the tables of the `main` database, with their rows, are
+/// written.
+pub struct Dump<'a> {
+    conn: &'a Connection,
+    schema: DatabaseName<'a>,
+    data: bool,
+    filter: Option<FilterFn<'a>>,
+}
+
+type FilterFn<'a> = Box<dyn Fn(&str) -> bool + 'a>;
+
+impl fmt::Debug for Dump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dump")
+            .field("conn", &self.conn)
+            .field("schema", &self.schema)
+            .field("data", &self.data)
+            .finish()
+    }
+}
+
+impl<'a> Dump<'a> {
+    /// Creates a dump of the `main` database of `conn`.
+    pub fn new(conn: &'a Connection) -> Dump<'a> {
+        Dump {
+            conn,
+            schema: DatabaseName::Main,
+            data: true,
+            filter: None,
+        }
+    }
+
+    /// Sets the database to dump, e.g. an attached database. The statements
+    /// written are not qualified by its name.
+    #[must_use]
+    pub fn schema(mut self, schema: DatabaseName<'a>) -> Dump<'a> {
+        self.schema = schema;
+        self
+    }
+
+    /// Sets whether the rows are written, or only the schema.
+    #[must_use]
+    pub fn data(mut self, data: bool) -> Dump<'a> {
+        self.data = data;
+        self
+    }
+
+    /// Only writes the tables for which `filter` returns `true`, and the
+    /// indexes and triggers on them. Views are filtered by their name, and
+    /// shadow tables by the name of their virtual table.
+    #[must_use]
+    pub fn filter<F>(mut self, filter: F) -> Dump<'a>
+    where
+        F: Fn(&str) -> bool + 'a,
+    {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Writes the dump to `writer`.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if writing fails, or if reading the database fails,
+    /// in which case the error wraps the `rusqlite::Error`.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        // The rows are read in a single transaction, for a consistent dump.
+        let _sp = Savepoint::with_depth_and_name(self.conn, 0, "_rusqlite_dump").map_err(to_io)?;
+        let schema = quote_identifier(match self.schema {
+            DatabaseName::Main => "main",
+            DatabaseName::Temp => "temp",
+            DatabaseName::Attached(s) => s,
+        });
+        let mut all = Vec::new();
+        {
+            let mut stmt = self
+                .conn
+                .prepare(&format!(
+                    "SELECT type, name, tbl_name, sql FROM {}.sqlite_master
+                     WHERE sql NOT NULL ORDER BY rowid",
+                    schema
+                ))
+                .map_err(to_io)?;
+            let mut rows = stmt.query([]).map_err(to_io)?;
+            while let Some(row) = rows.next().map_err(to_io)? {
+                let object: (String, String, String, String) = (
+                    row.get(0).map_err(to_io)?,
+                    row.get(1).map_err(to_io)?,
+                    row.get(2).map_err(to_io)?,
+                    row.get(3).map_err(to_io)?,
+                );
+                all.push(object);
+            }
+        }
+        let virtual_tables: Vec<(&str, &[&str])> = all
+            .iter()
+            .filter(|(kind, ..)| kind == "table")
+            .filter_map(|(_, name, _, sql)| {
+                virtual_table_module(sql).map(|module| (name.as_str(), shadow_suffixes(&module)))
+            })
+            .collect();
+        // The virtual table of which `name` is a shadow table.
+        let owner = |name: &str| {
+            virtual_tables
+                .iter()
+                .find(|(table, suffixes)| {
+                    name.strip_prefix(table)
+                        .and_then(|s| s.strip_prefix('_'))
+                        .map_or(false, |suffix| suffixes.contains(&suffix))
+                })
+                .map(|(table, _)| *table)
+        };
+        let objects: Vec<_> = all
+            .iter()
+            .filter_map(|(kind, name, table, sql)| {
+                let owner = owner(name);
+                match self.filter {
+                    Some(ref f) if !f(owner.unwrap_or(table)) => None,
+                    _ => Some((kind, name, sql, owner)),
+                }
+            })
+            .collect();
+
+        writer.write_all(b"PRAGMA foreign_keys=OFF;\nBEGIN TRANSACTION;\n")?;
+        for (_, name, sql, owner) in objects.iter().filter(|o| o.0 == "table") {
+            if *name == "sqlite_sequence" {
+                // Created along with the first AUTOINCREMENT table.
+                writer.write_all(b"DELETE FROM sqlite_sequence;\n")?;
+            } else if name.starts_with("sqlite_") {
+                continue;
+            } else if owner.is_none() {
+                writeln!(writer, "{};", sql)?;
+            }
+            if self.data && virtual_table_module(sql).is_none() {
+                self.write_rows(&mut writer, &schema, name, owner.is_some())?;
+            }
+        }
+        for (_, _, sql, _) in objects.iter().filter(|o| o.0 != "table") {
+            writeln!(writer, "{};", sql)?;
+        }
+        writer.write_all(b"COMMIT;\n")?;
+        writer.flush()
+    }
+
+    // Writes the rows of `table`, replacing those with the same keys if
+    // `replace`.
+    fn write_rows<W: Write>(
+        &self,
+        writer: &mut W,
+        schema: &str,
+        table: &str,
+        replace: bool,
+    ) -> io::Result<()> {
+        // Generated columns are computed again when the rows are inserted.
+        let columns: Vec<String> = self
+            .conn
+            .insertable_columns(Some(self.schema), table)
+            .map_err(to_io)?
+            .iter()
+            .map(|c| quote_identifier(c))
+            .collect();
+        let table = quote_identifier(table);
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT {} FROM {}.{}",
+                columns.join(","),
+                schema,
+                table
+            ))
+            .map_err(to_io)?;
+        let mut rows = stmt.query([]).map_err(to_io)?;
+        while let Some(row) = rows.next().map_err(to_io)? {
+            write!(
+                writer,
+                "INSERT {}INTO {} VALUES(",
+                if replace { "OR REPLACE " } else { "" },
+                table
+            )?;
+            for i in 0..row.column_count() {
+                if i > 0 {
+                    writer.write_all(b",")?;
+                }
+                write_value(writer, row.get_ref_unwrap(i))?;
+            }
+            writer.write_all(b");\n")?;
+        }
+        Ok(())
+    }
+}
+
+// Returns the module of the virtual table created by `sql`, or `None` if it
+// creates an ordinary table.
+fn virtual_table_module(sql: &str) -> Option<String> {
+    if !matches!(sql.get(..20), Some(s) if s.eq_ignore_ascii_case("CREATE VIRTUAL TABLE")) {
+        return None;
+    }
+    let bytes = sql.as_bytes();
+    let mut quote = None;
+    for (i, &b) in bytes.iter().enumerate().skip(20) {
+        match quote {
+            Some(q) if b == q => quote = None,
+            Some(_) => {}
+            None => match b {
+                b'"' | b'\'' | b'`' => quote = Some(b),
+                b'[' => quote = Some(b']'),
+                _ if bytes[i - 1].is_ascii_whitespace()
+                    && bytes[i..]
+                        .get(..5)
+                        .map_or(false, |w| w.eq_ignore_ascii_case(b"USING")) =>
+                {
+                    let module = sql[i + 5..]
+                        .trim_start()
+                        .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                        .next()?;
+                    return Some(module.to_ascii_lowercase());
+                }
+                _ => {}
+            },
+        }
+    }
+    None
+}
+
+// The suffixes of the shadow tables which the virtual tables of `module`
+// create.
+fn shadow_suffixes(module: &str) -> &'static [&'static str] {
+    match module {
+        "fts5" => &["config", "content", "data", "docsize", "idx"],
+        "fts3" | "fts4" => &["content", "docsize", "segdir", "segments", "stat"],
+        "rtree" | "rtree_i32" | "geopoly" => &["node", "parent", "rowid"],
+        _ => &[],
+    }
+}
+
+pub(crate) fn write_value<W: Write>(writer: &mut W, value: ValueRef<'_>) -> io::Result<()> {
+    match value {
+        ValueRef::Null => writer.write_all(b"NULL"),
+        ValueRef::Integer(n) => write!(writer, "{}", n),
+        ValueRef::Real(f) if f.is_infinite() => {
+            writer.write_all(if f > 0. { b"1e999" } else { b"-1e999" })
+        }
+        // Debug formats reals with a decimal point or an exponent, so that
+        // they are read back as reals, and without loss.
+        ValueRef::Real(f) => write!(writer, "{:?}", f),
+        ValueRef::Text(s) => {
+            writer.write_all(b"'")?;
+            for (i, part) in s.split(|&b| b == b'\'').enumerate() {
+                if i > 0 {
+                    writer.write_all(b"''")?;
+                }
+                writer.write_all(part)?;
+            }
+            writer.write_all(b"'")
+        }
+        ValueRef::Blob(b) => {
+            writer.write_all(b"X'")?;
+            for byte in b {
+                write!(writer, "{:02X}", byte)?;
+            }
+            writer.write_all(b"'")
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Dump;
+    use crate::{Connection, DatabaseName, Result};
+
+    fn dump(dump: Dump<'_>) -> String {
+        let mut out = Vec::new();
+        dump.write_to(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_dump() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE foo (x, y TEXT);
+             INSERT INTO foo VALUES (1, 'it''s'), (2.5, NULL), (X'00FF', 'a
+b');
+             CREATE INDEX foo_y ON foo (y);",
+        )?;
+        assert_eq!(
+            dump(Dump::new(&db)),
+            "PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE foo (x, y TEXT);
+INSERT INTO \"foo\" VALUES(1,'it''s');
+INSERT INTO \"foo\" VALUES(2.5,NULL);
+INSERT INTO \"foo\" VALUES(X'00FF','a
+b');
+CREATE INDEX foo_y ON foo (y);
+COMMIT;
+"
+        );
+        assert_eq!(
+            dump(Dump::new(&db).data(false)),
+            "PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE foo (x, y TEXT);
+CREATE INDEX foo_y ON foo (y);
+COMMIT;
+"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_restore() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "ATTACH ':memory:' AS src;
+             CREATE TABLE src.parent (id INTEGER PRIMARY KEY AUTOINCREMENT, r REAL);
+             CREATE TABLE src.child (id INTEGER REFERENCES parent (id));
+             CREATE TABLE src.skipped (x);
+             CREATE VIEW src.ids AS SELECT id FROM parent;
+             CREATE TRIGGER src.child_insert AFTER INSERT ON child
+             BEGIN UPDATE parent SET r = r + 1 WHERE id = NEW.id; END;
+             INSERT INTO src.parent (r) VALUES (0.1), (1e300), (-1e999);
+             INSERT INTO src.child VALUES (2);
+             INSERT INTO src.skipped VALUES (1);",
+        )?;
+        let sql = dump(
+            Dump::new(&db)
+                .schema(DatabaseName::Attached("src"))
+                .filter(|table| table != "skipped"),
+        );
+
+        let restored = Connection::open_in_memory()?;
+        restored.execute_batch(&sql)?;
+        let compare = |sql: &str| -> Result<()> {
+            let expected: String =
+                db.query_row(&sql.replace("FROM ", "FROM src."), [], |r| r.get(0))?;
+            let actual: String = restored.query_row(sql, [], |r| r.get(0))?;
+            assert_eq!(expected, actual, "{}", sql);
+            Ok(())
+        };
+        compare("SELECT group_concat(id || ':' || quote(r)) FROM parent")?;
+        compare("SELECT group_concat(id) FROM ids")?;
+        compare("SELECT group_concat(id) FROM child")?;
+        compare("SELECT group_concat(name || ':' || seq) FROM sqlite_sequence")?;
+        compare("SELECT group_concat(name) FROM sqlite_master WHERE name != 'skipped'")?;
+        assert!(restored.prepare("SELECT * FROM skipped").is_err());
+
+        // The trigger was created after the rows were inserted.
+        restored.execute("INSERT INTO child VALUES (1)", [])?;
+        let r: f64 = restored.query_row("SELECT r FROM parent WHERE id = 1", [], |r| r.get(0))?;
+        assert!((r - 1.1).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_restore_shadow_tables() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE VIRTUAL TABLE docs USING fts5(body);
+             CREATE VIRTUAL TABLE \"boxes using\" USING rtree(id, x0, x1);
+             CREATE TABLE docs_extra (x);
+             INSERT INTO docs VALUES ('reads are assembled'), ('variants are called');
+             INSERT INTO \"boxes using\" VALUES (1, 0, 10), (2, 5, 15);
+             INSERT INTO docs_extra VALUES (1);",
+        )?;
+        let sql = dump(Dump::new(&db));
+        assert!(!sql.contains("CREATE TABLE 'docs_data'"), "{}", sql);
+        assert!(sql.contains("CREATE TABLE docs_extra"), "{}", sql);
+
+        let restored = Connection::open_in_memory()?;
+        restored.execute_batch(&sql)?;
+        let body: String =
+            restored.query_row("SELECT body FROM docs WHERE docs MATCH 'called'", [], |r| {
+                r.get(0)
+            })?;
+        assert_eq!(body, "variants are called");
+        restored.execute("INSERT INTO docs(docs) VALUES ('integrity-check')", [])?;
+        let ids: String = restored.query_row(
+            "SELECT group_concat(id) FROM \"boxes using\" WHERE x1 > 12",
+            [],
+            |r| r.get(0),
+        )?;
+        assert_eq!(ids, "2");
+        let n: i64 = restored.query_row("SELECT count(*) FROM docs_extra", [], |r| r.get(0))?;
+        assert_eq!(n, 1);
+
+        // Shadow tables follow their virtual table.
+        let sql = dump(Dump::new(&db).filter(|table| table != "docs"));
+        assert!(!sql.contains("docs_data"), "{}", sql);
+        assert!(sql.contains("boxes using_node"), "{}", sql);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_generated_columns() -> Result<()> {
+        if crate::version_number() < 3_031_000 {
+            return Ok(());
+        }
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE foo (x INTEGER, y AS (x * 2), z AS (x * 3) STORED, w TEXT);
+             INSERT INTO foo (x, w) VALUES (1, 'a'), (2, 'b');",
+        )?;
+        let sql = dump(Dump::new(&db));
+        assert!(
+            sql.contains("INSERT INTO \"foo\" VALUES(1,'a');"),
+            "{}",
+            sql
+        );
+
+        let restored = Connection::open_in_memory()?;
+        restored.execute_batch(&sql)?;
+        let rows: String =
+            restored.query_row("SELECT group_concat(x || y || z || w) FROM foo", [], |r| {
+                r.get(0)
+            })?;
+        assert_eq!(rows, "123a,246b");
+        Ok(())
+    }
+}
@@ -116,6 +116,78 @@ pub enum Error {
     /// return it if the blob has insufficient data.
     #[cfg(feature = "blob")]
     BlobSizeError,
+
+    /// Error returned by [`Row::deserialize`](crate::Row::deserialize) when
+    /// the row doesn't match the shape of the requested type.
+    #[cfg(feature = "serde")]
+    DeserializeError(String),
+
+    /// Error returned by
+    /// [`EncryptedColumn::decrypt`](crate::column_encryption::EncryptedColumn::decrypt)
+    /// when the value was encrypted with another key, was tampered with, or
+    /// cannot be converted to the requested type.
+    #[cfg(feature = "column_encryption")]
+    DecryptionError(Box<dyn error::Error + Send + Sync + 'static>),
+
+    /// Error returned when an API which needs rowids, e.g.
+    /// [`Connection::blob_open`](crate::Connection::blob_open), is used on a
+    /// `WITHOUT ROWID` table, named by the error.
+    WithoutRowid(String),
+
+    /// Error returned by
+    /// [`Connection::open_verified`](crate::Connection::open_verified) when
+    /// the `application_id` of the database is not the required one.
+    WrongApplication {
+        /// The required `application_id`.
+        expected: u32,
+        /// The `application_id` of the database.
+        found: u32,
+    },
+
+    /// Error returned by
+    /// [`Connection::open_verified`](crate::Connection::open_verified) when
+    /// the `user_version` of the database is older than the required one.
+    NeedsMigration {
+        /// The required `user_version`.
+        expected: i32,
+        /// The `user_version` of the database.
+        found: i32,
+    },
+
+    /// Error returned by
+    /// [`Connection::open_verified`](crate::Connection::open_verified) when
+    /// the `user_version` of the database is newer than the required one,
+    /// e.g. because it was migrated by a newer version of the program.
+    UnsupportedVersion {
+        /// The required `user_version`.
+        expected: i32,
+        /// The `user_version` of the database.
+        found: i32,
+    },
+
+    /// Error returned by
+    /// [`Connection::open_verified`](crate::Connection::open_verified) when
+    /// `PRAGMA quick_check` finds problems, which it lists.
+    Corrupt(Vec<String>),
+
+    /// Error returned by [`DatabaseUri::to_uri`](crate::uri::DatabaseUri::to_uri)
+    /// when its options cannot be combined, which it explains.
+    InvalidUri(String),
+
+    /// Error returned by
+    /// [`ShardManager::check_atomic_commit`](crate::shards::ShardManager::check_atomic_commit)
+    /// when a transaction over the shards would not be committed atomically,
+    /// with the reasons why.
+    #[cfg(feature = "shards")]
+    NonAtomicCommit(Vec<String>),
+
+    /// Error returned by [`WriteSerializer::submit`](crate::WriteSerializer::submit)
+    /// when the job panicked; its transaction was rolled back.
+    WriteJobPanicked,
+
+    /// Error returned by [`WriteSerializer::submit`](crate::WriteSerializer::submit)
+    /// when its writer thread is no longer running.
+    WriteSerializerStopped,
 }
 
 impl PartialEq for Error {
@@ -160,6 +232,45 @@ impl PartialEq for Error {
             }
             #[cfg(feature = "blob")]
             (Error::BlobSizeError, Error::BlobSizeError) => true,
+            #[cfg(feature = "serde")]
+            (Error::DeserializeError(s1), Error::DeserializeError(s2)) => s1 == s2,
+            (Error::WithoutRowid(s1), Error::WithoutRowid(s2)) => s1 == s2,
+            (
+                Error::WrongApplication {
+                    expected: e1,
+                    found: f1,
+                },
+                Error::WrongApplication {
+                    expected: e2,
+                    found: f2,
+                },
+            ) => e1 == e2 && f1 == f2,
+            (
+                Error::NeedsMigration {
+                    expected: e1,
+                    found: f1,
+                },
+                Error::NeedsMigration {
+                    expected: e2,
+                    found: f2,
+                },
+            )
+            | (
+                Error::UnsupportedVersion {
+                    expected: e1,
+                    found: f1,
+                },
+                Error::UnsupportedVersion {
+                    expected: e2,
+                    found: f2,
+                },
+            ) => e1 == e2 && f1 == f2,
+            (Error::Corrupt(p1), Error::Corrupt(p2)) => p1 == p2,
+            (Error::InvalidUri(s1), Error::InvalidUri(s2)) => s1 == s2,
+            #[cfg(feature = "shards")]
+            (Error::NonAtomicCommit(p1), Error::NonAtomicCommit(p2)) => p1 == p2,
+            (Error::WriteJobPanicked, Error::WriteJobPanicked) => true,
+            (Error::WriteSerializerStopped, Error::WriteSerializerStopped) => true,
             (..) => false,
         }
     }
@@ -277,6 +388,36 @@ impl fmt::Display for Error {
 
             #[cfg(feature = "blob")]
             Error::BlobSizeError => "Blob size is insufficient".fmt(f),
+            #[cfg(feature = "serde")]
+            Error::DeserializeError(ref msg) => write!(f, "Deserialization error: {}", msg),
+            #[cfg(feature = "column_encryption")]
+            Error::DecryptionError(ref err) => write!(f, "Decryption error: {}", err),
+            Error::WithoutRowid(ref table) => write!(f, "{} is a WITHOUT ROWID table", table),
+            Error::WrongApplication { expected, found } => write!(
+                f,
+                "Database has application_id {:#x} instead of {:#x}",
+                found, expected
+            ),
+            Error::NeedsMigration { expected, found } => write!(
+                f,
+                "Database has user_version {}, older than {}",
+                found, expected
+            ),
+            Error::UnsupportedVersion { expected, found } => write!(
+                f,
+                "Database has user_version {}, newer than {}",
+                found, expected
+            ),
+            Error::Corrupt(ref problems) => {
+                write!(f, "Database is corrupt: {}", problems.join("; "))
+            }
+            Error::InvalidUri(ref msg) => write!(f, "Invalid URI: {}", msg),
+            #[cfg(feature = "shards")]
+            Error::NonAtomicCommit(ref problems) => {
+                write!(f, "Commit would not be atomic: {}", problems.join("; "))
+            }
+            Error::WriteJobPanicked => write!(f, "Write job panicked"),
+            Error::WriteSerializerStopped => write!(f, "WriteSerializer is stopped"),
         }
     }
 }
@@ -324,6 +465,19 @@ impl error::Error for Error {
 
             #[cfg(feature = "blob")]
             Error::BlobSizeError => None,
+            #[cfg(feature = "serde")]
+            Error::DeserializeError(_) => None,
+            #[cfg(feature = "column_encryption")]
+            Error::DecryptionError(ref err) => Some(&**err),
+            Error::WithoutRowid(_)
+            | Error::WrongApplication { .. }
+            | Error::NeedsMigration { .. }
+            | Error::UnsupportedVersion { .. }
+            | Error::Corrupt(_)
+            | Error::InvalidUri(_) => None,
+            #[cfg(feature = "shards")]
+            Error::NonAtomicCommit(_) => None,
+            Error::WriteJobPanicked | Error::WriteSerializerStopped => None,
         }
     }
 }
@@ -340,7 +494,10 @@ pub unsafe fn error_from_handle(db: *mut ffi::sqlite3, code: c_int) -> Error {
     let message = if db.is_null() {
         None
     } else {
-        Some(errmsg_to_string(ffi::sqlite3_errmsg(db)))
+        let message = errmsg_to_string(ffi::sqlite3_errmsg(db));
+        #[cfg(feature = "trace")]
+        let message = crate::trace::redact_message(&message).into_owned();
+        Some(message)
     };
     error_from_sqlite_code(code, message)
 }
@@ -0,0 +1,194 @@
+//! `feature = "execution_stats"` Page cache and time statistics of statement
+//! executions, to tell I/O-bound queries from CPU-bound ones.
+//!
+//! ```rust,no_run
+//! # use rusqlite::{Connection, Result};
+//! # use std::time::Duration;
+//! fn purge(conn: &Connection) -> Result<usize> {
+//!     let mut stmt = conn.prepare("DELETE FROM events WHERE expired")?;
+//!     let (deleted, stats) = stmt.execute_with_stats([])?;
+//!     if stats.elapsed > Duration::from_secs(1) {
+//!         eprintln!(
+//!             "slow purge: {:?}, {} bytes read, {} bytes written",
+//!             stats.elapsed,
+//!             stats.bytes_read(),
+//!             stats.bytes_written()
+//!         );
+//!     }
+//!     Ok(deleted)
+//! }
+//! ```
+
+use std::os::raw::c_int;
+use std::time::{Duration, Instant};
+
+use crate::ffi;
+use crate::{Connection, Params, Result, Statement};
+
+/// The page cache activity and duration of an execution.
+///
+/// The counters are those of
+/// [`sqlite3_db_status`](https://sqlite.org/c3ref/c_dbstatus_options.html),
+/// for all the databases of the connection, so they include the activity of
+/// other statements stepped on the same connection meanwhile.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ExecutionStats {
+    /// The number of pages found in the page cache.
+    pub cache_hits: u64,
+    /// The number of pages read from the database files.
+    pub cache_misses: u64,
+    /// The number of pages written to the database files.
+    pub cache_writes: u64,
+    /// The page size of the `main` database, in bytes.
+    pub page_size: u64,
+    /// The wall-clock duration of the execution.
+    pub elapsed: Duration,
+}
+
+impl ExecutionStats {
+    /// Returns the number of bytes read from the database files, assuming
+    /// all their pages have the size of the `main` database ones.
+    pub fn bytes_read(&self) -> u64 {
+        self.cache_misses * self.page_size
+    }
+
+    /// Returns the number of bytes written to the database files, assuming
+    /// all their pages have the size of the `main` database ones.
+    pub fn bytes_written(&self) -> u64 {
+        self.cache_writes * self.page_size
+    }
+
+    /// Returns the proportion of pages found in the page cache, or `None`
+    /// if no page was accessed.
+    pub fn hit_ratio(&self) -> Option<f64> {
+        let accesses = self.cache_hits + self.cache_misses;
+        if accesses == 0 {
+            None
+        } else {
+            Some(self.cache_hits as f64 / accesses as f64)
+        }
+    }
+}
+
+// The counters sampled before and after an execution.
+#[derive(Clone, Copy)]
+struct Counters {
+    hits: i64,
+    misses: i64,
+    writes: i64,
+}
+
+impl Connection {
+    fn db_status(&self, op: c_int) -> Result<i64> {
+        let c = self.db.borrow();
+        let (mut current, mut highwater) = (0, 0);
+        unsafe {
+            check!(ffi::sqlite3_db_status(
+                c.db(),
+                op,
+                &mut current,
+                &mut highwater,
+                0
+            ));
+        }
+        Ok(i64::from(current))
+    }
+
+    fn counters(&self) -> Result<Counters> {
+        Ok(Counters {
+            hits: self.db_status(ffi::SQLITE_DBSTATUS_CACHE_HIT)?,
+            misses: self.db_status(ffi::SQLITE_DBSTATUS_CACHE_MISS)?,
+            writes: self.db_status(ffi::SQLITE_DBSTATUS_CACHE_WRITE)?,
+        })
+    }
+
+    /// `feature = "execution_stats"` Runs `f`, and returns its result with
+    /// the page cache activity of this connection while it ran.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `f` fails, or if the statistics cannot be read.
+    pub fn with_execution_stats<T, F>(&self, f: F) -> Result<(T, ExecutionStats)>
+    where
+        F: FnOnce(&Connection) -> Result<T>,
+    {
+        let page_size: i64 = self.pragma_query_value(None, "page_size", |row| row.get(0))?;
+        let before = self.counters()?;
+        let start = Instant::now();
+        let result = f(self)?;
+        let elapsed = start.elapsed();
+        let after = self.counters()?;
+        let delta = |a: i64, b: i64| (a - b).max(0) as u64;
+        Ok((
+            result,
+            ExecutionStats {
+                cache_hits: delta(after.hits, before.hits),
+                cache_misses: delta(after.misses, before.misses),
+                cache_writes: delta(after.writes, before.writes),
+                page_size: page_size as u64,
+                elapsed,
+            },
+        ))
+    }
+}
+
+impl Statement<'_> {
+    /// `feature = "execution_stats"` Executes the prepared statement like
+    /// [`execute`](Statement::execute), and returns the number of rows
+    /// changed with the statistics of the execution.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if binding parameters fails, if the executed
+    /// statement returns rows, or if the underlying SQLite call fails.
+    pub fn execute_with_stats<P: Params>(&mut self, params: P) -> Result<(usize, ExecutionStats)> {
+        let conn = self.conn;
+        conn.with_execution_stats(|_| self.execute(params))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Connection, Result};
+
+    #[test]
+    fn test_execution_stats() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db3");
+        let db = Connection::open(&path)?;
+        db.execute_batch(
+            "PRAGMA page_size = 1024;
+             CREATE TABLE foo (x BLOB);",
+        )?;
+
+        let mut stmt = db.prepare("INSERT INTO foo VALUES (zeroblob(100000))")?;
+        let (changed, stats) = stmt.execute_with_stats([])?;
+        assert_eq!(changed, 1);
+        assert_eq!(stats.page_size, 1024);
+        assert!(stats.cache_writes >= 98, "{:?}", stats);
+        assert!(stats.bytes_written() >= 100_000);
+
+        // A new connection reads the pages from the file.
+        drop(stmt);
+        drop(db);
+        let db = Connection::open(&path)?;
+        let (len, stats) = db.with_execution_stats(|db| {
+            db.query_row("SELECT sum(length(hex(x))) FROM foo", [], |r| {
+                r.get::<_, i64>(0)
+            })
+        })?;
+        assert_eq!(len, 200_000);
+        assert!(stats.cache_misses >= 98, "{:?}", stats);
+        assert_eq!(stats.cache_writes, 0);
+        assert!(stats.hit_ratio().unwrap() < 0.5);
+
+        let (_, stats) = db.with_execution_stats(|db| {
+            db.query_row("SELECT sum(length(hex(x))) FROM foo", [], |r| {
+                r.get::<_, i64>(0)
+            })
+        })?;
+        assert!(stats.hit_ratio().unwrap() > 0.9, "{:?}", stats);
+        Ok(())
+    }
+}
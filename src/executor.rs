@@ -0,0 +1,198 @@
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::{CachedStatement, Connection, Params, Result, Row, Savepoint, Statement, Transaction};
+
+/// Something that runs SQL: a [`Connection`], a [`Transaction`], a
+/// [`Savepoint`], or a wrapper of one of them.
+///
+/// Code written against this trait, rather than `Connection`, can be given a
+/// transaction by its caller, and an in-memory or recording implementation in
+/// unit tests. Implementations only need to provide
+/// [`connection`](DatabaseExecutor::connection); the other methods run on it
+/// by default, and can be overridden, e.g. to record the SQL run.
+///
+/// A connection pool's connection type implements it by returning the
+/// connection it wraps.
+///
+/// ```rust
+/// # use rusqlite::{Connection, DatabaseExecutor, Result};
+/// fn add_sample<E: DatabaseExecutor>(db: &E, name: &str) -> Result<i64> {
+///     db.execute("INSERT INTO samples (name) VALUES (?)", [name])?;
+///     Ok(db.connection().last_insert_rowid())
+/// }
+///
+/// # fn main() -> Result<()> {
+/// let mut db = Connection::open_in_memory()?;
+/// db.execute_batch("CREATE TABLE samples (id INTEGER PRIMARY KEY, name TEXT)")?;
+/// add_sample(&db, "a")?;
+/// let tx = db.transaction()?;
+/// add_sample(&tx, "b")?;
+/// tx.commit()?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait DatabaseExecutor {
+    /// The connection the SQL runs on.
+    fn connection(&self) -> &Connection;
+
+    /// See [`Connection::execute`].
+    #[inline]
+    fn execute<P: Params>(&self, sql: &str, params: P) -> Result<usize> {
+        self.connection().execute(sql, params)
+    }
+
+    /// See [`Connection::execute_batch`].
+    #[inline]
+    fn execute_batch(&self, sql: &str) -> Result<()> {
+        self.connection().execute_batch(sql)
+    }
+
+    /// See [`Connection::prepare`].
+    #[inline]
+    fn prepare(&self, sql: &str) -> Result<Statement<'_>> {
+        self.connection().prepare(sql)
+    }
+
+    /// See [`Connection::prepare_cached`].
+    #[inline]
+    fn prepare_cached(&self, sql: &str) -> Result<CachedStatement<'_>> {
+        self.connection().prepare_cached(sql)
+    }
+
+    /// See [`Connection::query_row`].
+    #[inline]
+    fn query_row<T, P, F>(&self, sql: &str, params: P, f: F) -> Result<T>
+    where
+        P: Params,
+        F: FnOnce(&Row<'_>) -> Result<T>,
+    {
+        self.connection().query_row(sql, params, f)
+    }
+}
+
+impl DatabaseExecutor for Connection {
+    #[inline]
+    fn connection(&self) -> &Connection {
+        self
+    }
+}
+
+impl DatabaseExecutor for Transaction<'_> {
+    #[inline]
+    fn connection(&self) -> &Connection {
+        self
+    }
+}
+
+impl DatabaseExecutor for Savepoint<'_> {
+    #[inline]
+    fn connection(&self) -> &Connection {
+        self
+    }
+}
+
+macro_rules! forward_executor {
+    ($($ty:ty),*) => {$(
+        impl<E: DatabaseExecutor + ?Sized> DatabaseExecutor for $ty {
+            #[inline]
+            fn connection(&self) -> &Connection {
+                (**self).connection()
+            }
+
+            #[inline]
+            fn execute<P: Params>(&self, sql: &str, params: P) -> Result<usize> {
+                (**self).execute(sql, params)
+            }
+
+            #[inline]
+            fn execute_batch(&self, sql: &str) -> Result<()> {
+                (**self).execute_batch(sql)
+            }
+
+            #[inline]
+            fn prepare(&self, sql: &str) -> Result<Statement<'_>> {
+                (**self).prepare(sql)
+            }
+
+            #[inline]
+            fn prepare_cached(&self, sql: &str) -> Result<CachedStatement<'_>> {
+                (**self).prepare_cached(sql)
+            }
+
+            #[inline]
+            fn query_row<T, P, F>(&self, sql: &str, params: P, f: F) -> Result<T>
+            where
+                P: Params,
+                F: FnOnce(&Row<'_>) -> Result<T>,
+            {
+                (**self).query_row(sql, params, f)
+            }
+        }
+    )*};
+}
+
+forward_executor!(&E, &mut E, Box<E>, Rc<E>, Arc<E>);
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+
+    use super::DatabaseExecutor;
+    use crate::{Connection, Params, Result, Statement};
+
+    fn insert<E: DatabaseExecutor>(db: &E, x: i64) -> Result<i64> {
+        db.execute("INSERT INTO foo VALUES (?)", [x])?;
+        db.query_row("SELECT sum(x) FROM foo", [], |r| r.get(0))
+    }
+
+    #[test]
+    fn test_implementations() -> Result<()> {
+        let mut db = Connection::open_in_memory()?;
+        db.execute_batch("CREATE TABLE foo (x INTEGER)")?;
+        assert_eq!(insert(&db, 1)?, 1);
+        assert_eq!(insert(&&db, 2)?, 3);
+        {
+            let mut tx = db.transaction()?;
+            assert_eq!(insert(&tx, 3)?, 6);
+            let sp = tx.savepoint()?;
+            assert_eq!(insert(&sp, 4)?, 10);
+        }
+        assert_eq!(insert(&Box::new(db), 5)?, 8);
+        Ok(())
+    }
+
+    // Records the SQL run, in a fresh in-memory database.
+    struct Recorder {
+        conn: Connection,
+        log: RefCell<Vec<String>>,
+    }
+
+    impl DatabaseExecutor for Recorder {
+        fn connection(&self) -> &Connection {
+            &self.conn
+        }
+
+        fn execute<P: Params>(&self, sql: &str, params: P) -> Result<usize> {
+            self.log.borrow_mut().push(sql.to_owned());
+            self.conn.execute(sql, params)
+        }
+
+        fn prepare(&self, sql: &str) -> Result<Statement<'_>> {
+            self.log.borrow_mut().push(sql.to_owned());
+            self.conn.prepare(sql)
+        }
+    }
+
+    #[test]
+    fn test_recorder() -> Result<()> {
+        let recorder = Recorder {
+            conn: Connection::open_in_memory()?,
+            log: RefCell::new(Vec::new()),
+        };
+        recorder.execute_batch("CREATE TABLE foo (x INTEGER)")?;
+        assert_eq!(insert(&recorder, 7)?, 7);
+        assert_eq!(*recorder.log.borrow(), vec!["INSERT INTO foo VALUES (?)"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+//! Stream query results as [CSV](https://tools.ietf.org/html/rfc4180).
+//!
+//! ```rust,no_run
+//! use std::fs::File;
+//! use std::io::{self, BufWriter};
+//! use rusqlite::export::csv::Writer;
+//! use rusqlite::Connection;
+//!
+//! fn dump(conn: &Connection, path: &str) -> io::Result<u64> {
+//!     let mut stmt = conn.prepare("SELECT * FROM samples").map_err(to_io)?;
+//!     let mut rows = stmt.query([]).map_err(to_io)?;
+//!     let file = BufWriter::new(File::create(path)?);
+//!     Writer::new(file).delimiter(b'\t').null("NA").write_rows(&mut rows)
+//! }
+//! # fn to_io(err: rusqlite::Error) -> io::Error { io::Error::new(io::ErrorKind::Other, err) }
+//! ```
+
+use std::io::{self, Write};
+
+use super::to_io;
+use crate::types::ValueRef;
+use crate::Rows;
+
+/// When to put a field between quotes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum QuoteStyle {
+    /// Only quote fields which contain the delimiter, the quote character, or
+    /// a line break, or which could be mistaken for `NULL`. This is the
+    /// default.
+    Necessary,
+    /// Quote every field, except `NULL`s.
+    Always,
+    /// Quote every field which is not a number or `NULL`.
+    NonNumeric,
+    /// Never quote fields. The output may not be parseable back.
+    Never,
+}
+
+/// Writes rows as CSV to an [`io::Write`].
+///
+/// By default, fields are separated by `,`, quoted with `"` only where
+/// necessary, `NULL` is written as an empty field, and a header line with
+/// the column names is written first.
+#[derive(Debug)]
+pub struct Writer<W: Write> {
+    writer: W,
+    delimiter: u8,
+    quote: u8,
+    quote_style: QuoteStyle,
+    null: String,
+    header: bool,
+    field: Vec<u8>,
+}
+
+impl<W: Write> Writer<W> {
+    /// Creates a CSV writer with the default settings.
+    pub fn new(writer: W) -> Writer<W> {
+        Writer {
+            writer,
+            delimiter: b',',
+            quote: b'"',
+            quote_style: QuoteStyle::Necessary,
+            null: String::new(),
+            header: true,
+            field: Vec::new(),
+        }
+    }
+
+    /// Sets the field delimiter, e.g. `b'\t'` for TSV.
+    #[must_use]
+    pub fn delimiter(mut self, delimiter: u8) -> Writer<W> {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Sets the quote character. Quote characters within a quoted field are
+    /// doubled.
+    #[must_use]
+    pub fn quote(mut self, quote: u8) -> Writer<W> {
+        self.quote = quote;
+        self
+    }
+
+    /// Sets when fields are quoted.
+    #[must_use]
+    pub fn quote_style(mut self, quote_style: QuoteStyle) -> Writer<W> {
+        self.quote_style = quote_style;
+        self
+    }
+
+    /// Sets how `NULL` is written, e.g. `"NA"` or `"\\N"`. It is never
+    /// quoted.
+    #[must_use]
+    pub fn null(mut self, null: &str) -> Writer<W> {
+        self.null = null.to_owned();
+        self
+    }
+
+    /// Sets whether a header line with the column names is written.
+    #[must_use]
+    pub fn header(mut self, header: bool) -> Writer<W> {
+        self.header = header;
+        self
+    }
+
+    /// Writes the header line (if enabled) and all the remaining rows of
+    /// `rows`, one per line, and returns the number of rows written.
+    ///
+    /// Reals are written as by `Display for f64`, and blobs as raw bytes.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if writing fails, or if stepping `rows` fails, in
+    /// which case the error wraps the `rusqlite::Error`.
+    pub fn write_rows(&mut self, rows: &mut Rows<'_>) -> io::Result<u64> {
+        if self.header {
+            if let Some(names) = rows.column_names() {
+                for (i, name) in names.iter().enumerate() {
+                    self.write_field(i, name.as_bytes(), false)?;
+                }
+                self.writer.write_all(b"\n")?;
+            }
+        }
+        let mut count = 0;
+        while let Some(row) = rows.next().map_err(to_io)? {
+            for i in 0..row.column_count() {
+                match row.get_ref_unwrap(i) {
+                    ValueRef::Null => {
+                        self.write_separator(i)?;
+                        self.writer.write_all(self.null.as_bytes())?;
+                    }
+                    ValueRef::Integer(n) => self.write_field(i, n.to_string().as_bytes(), true)?,
+                    ValueRef::Real(f) => self.write_field(i, f.to_string().as_bytes(), true)?,
+                    ValueRef::Text(s) | ValueRef::Blob(s) => self.write_field(i, s, false)?,
+                }
+            }
+            self.writer.write_all(b"\n")?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_separator(&mut self, i: usize) -> io::Result<()> {
+        if i > 0 {
+            self.writer.write_all(&[self.delimiter])?;
+        }
+        Ok(())
+    }
+
+    fn write_field(&mut self, i: usize, value: &[u8], numeric: bool) -> io::Result<()> {
+        self.write_separator(i)?;
+        let quoted = match self.quote_style {
+            QuoteStyle::Always => true,
+            QuoteStyle::NonNumeric => !numeric,
+            QuoteStyle::Never => false,
+            QuoteStyle::Necessary => {
+                value == self.null.as_bytes()
+                    || value.iter().any(|&b| {
+                        b == self.delimiter || b == self.quote || b == b'\n' || b == b'\r'
+                    })
+            }
+        };
+        if !quoted {
+            return self.writer.write_all(value);
+        }
+        self.field.clear();
+        self.field.push(self.quote);
+        for &b in value {
+            if b == self.quote {
+                self.field.push(b);
+            }
+            self.field.push(b);
+        }
+        self.field.push(self.quote);
+        self.writer.write_all(&self.field)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{QuoteStyle, Writer};
+    use crate::{Connection, Result};
+
+    fn export(db: &Connection, sql: &str, writer: Writer<Vec<u8>>) -> Result<String> {
+        let mut stmt = db.prepare(sql)?;
+        let mut rows = stmt.query([])?;
+        let mut writer = writer;
+        writer.write_rows(&mut rows).unwrap();
+        Ok(String::from_utf8(writer.into_inner().unwrap()).unwrap())
+    }
+
+    #[test]
+    fn test_write_rows() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE foo(i, r, t);
+             INSERT INTO foo VALUES(1, 2.5, 'plain');
+             INSERT INTO foo VALUES(NULL, -1.0, 'a,b');
+             INSERT INTO foo VALUES(3, NULL, 'say \"hi\"');
+             INSERT INTO foo VALUES(4, 0.0, '');
+             INSERT INTO foo VALUES(5, 1e3, 'two
+lines');",
+        )?;
+        let sql = "SELECT * FROM foo ORDER BY rowid";
+        assert_eq!(
+            export(&db, sql, Writer::new(Vec::new()))?,
+            "i,r,t\n1,2.5,plain\n,-1,\"a,b\"\n3,,\"say \"\"hi\"\"\"\n4,0,\"\"\n5,1000,\"two\nlines\"\n"
+        );
+        assert_eq!(
+            export(
+                &db,
+                "SELECT * FROM foo WHERE rowid < 5 ORDER BY rowid",
+                Writer::new(Vec::new())
+                    .delimiter(b'\t')
+                    .null("NA")
+                    .header(false)
+            )?,
+            "1\t2.5\tplain\nNA\t-1\ta,b\n3\tNA\t\"say \"\"hi\"\"\"\n4\t0\t\n"
+        );
+        assert_eq!(
+            export(
+                &db,
+                "SELECT i, t FROM foo WHERE i < 3",
+                Writer::new(Vec::new())
+                    .quote(b'\'')
+                    .quote_style(QuoteStyle::NonNumeric)
+            )?,
+            "'i','t'\n1,'plain'\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_rows_error() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        let mut stmt = db.prepare("SELECT abs(-9223372036854775807 - 1)")?;
+        let mut rows = stmt.query([])?;
+        let mut writer = Writer::new(Vec::new());
+        let err = writer.write_rows(&mut rows).unwrap_err();
+        assert!(err.get_ref().unwrap().is::<crate::Error>());
+        Ok(())
+    }
+}
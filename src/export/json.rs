@@ -0,0 +1,158 @@
+//! `feature = "serde_json"` Convert query results to JSON.
+//!
+//! Each row becomes an object keyed by column name. Integers and reals
+//! become numbers (non-finite reals become `null`), text becomes strings,
+//! blobs become base64 strings and `NULL` becomes `null`.
+
+use std::io::{self, Write};
+
+use serde_json::{Map, Number, Value};
+
+use super::to_io;
+use crate::types::ValueRef;
+use crate::{Error, Result, Row, Rows};
+
+impl Rows<'_> {
+    /// `feature = "serde_json"` Collect the remaining rows into a JSON array
+    /// of objects keyed by column names.
+    ///
+    /// ```rust,no_run
+    /// # use rusqlite::{Connection, Result};
+    /// fn people(conn: &Connection) -> Result<serde_json::Value> {
+    ///     let mut stmt = conn.prepare("SELECT id, name, photo FROM person")?;
+    ///     let mut rows = stmt.query([])?;
+    ///     rows.to_json_value()
+    /// }
+    /// ```
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if stepping fails, or if some text is not valid
+    /// UTF-8.
+    pub fn to_json_value(&mut self) -> Result<Value> {
+        let mut array = Vec::new();
+        while let Some(row) = self.next()? {
+            let mut object = Map::new();
+            for idx in 0..row.column_count() {
+                object.insert(row.column_name(idx)?.to_owned(), json_value(row, idx)?);
+            }
+            array.push(Value::Object(object));
+        }
+        Ok(Value::Array(array))
+    }
+
+    /// `feature = "serde_json"` Stream the remaining rows to `writer` as a
+    /// JSON array of objects keyed by column names, in column order, and
+    /// return the number of rows written.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if writing fails, or if stepping fails or some text
+    /// is not valid UTF-8, in which case the error wraps the
+    /// `rusqlite::Error`.
+    pub fn to_json_writer<W: Write>(&mut self, mut writer: W) -> io::Result<u64> {
+        let mut count = 0;
+        writer.write_all(b"[")?;
+        while let Some(row) = self.next().map_err(to_io)? {
+            if count > 0 {
+                writer.write_all(b",")?;
+            }
+            writer.write_all(b"{")?;
+            for idx in 0..row.column_count() {
+                if idx > 0 {
+                    writer.write_all(b",")?;
+                }
+                let name = row.column_name(idx).map_err(to_io)?;
+                serde_json::to_writer(&mut writer, name)?;
+                writer.write_all(b":")?;
+                let value = json_value(row, idx).map_err(to_io)?;
+                serde_json::to_writer(&mut writer, &value)?;
+            }
+            writer.write_all(b"}")?;
+            count += 1;
+        }
+        writer.write_all(b"]")?;
+        Ok(count)
+    }
+}
+
+fn json_value(row: &Row<'_>, idx: usize) -> Result<Value> {
+    Ok(match row.get_ref_unwrap(idx) {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::from(i),
+        ValueRef::Real(f) => Number::from_f64(f).map_or(Value::Null, Value::Number),
+        ValueRef::Text(s) => {
+            Value::String(std::str::from_utf8(s).map_err(Error::Utf8Error)?.to_owned())
+        }
+        ValueRef::Blob(b) => Value::String(base64(b)),
+    })
+}
+
+// Standard base64 (RFC 4648), with padding.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut s = String::with_capacity(bytes.len() / 3 * 4 + 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                s.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::base64;
+    use crate::{Connection, Result};
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        assert_eq!(base64(&[0xff, 0xfe, 0x00]), "//4A");
+    }
+
+    #[test]
+    fn test_to_json() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE foo(z, a);
+             INSERT INTO foo VALUES(1, 'x\"y');
+             INSERT INTO foo VALUES(2.5, x'666f6f');
+             INSERT INTO foo VALUES(NULL, NULL);",
+        )?;
+        let mut stmt = db.prepare("SELECT z, a FROM foo ORDER BY rowid")?;
+        let expected = json!([
+            {"z": 1, "a": "x\"y"},
+            {"z": 2.5, "a": "Zm9v"},
+            {"z": null, "a": null},
+        ]);
+        assert_eq!(stmt.query([])?.to_json_value()?, expected);
+
+        let mut out = Vec::new();
+        assert_eq!(stmt.query([])?.to_json_writer(&mut out).unwrap(), 3);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"[{"z":1,"a":"x\"y"},{"z":2.5,"a":"Zm9v"},{"z":null,"a":null}]"#
+        );
+
+        let mut out = Vec::new();
+        let mut stmt = db.prepare("SELECT 1 WHERE 0")?;
+        assert_eq!(stmt.query([])?.to_json_writer(&mut out).unwrap(), 0);
+        assert_eq!(out, b"[]");
+        Ok(())
+    }
+}
@@ -0,0 +1,15 @@
+//! Write query results to other formats.
+//!
+//! * [`csv`] streams rows as comma (or otherwise) separated values.
+//! * With `feature = "serde_json"`, [`Rows::to_json_value`](crate::Rows::to_json_value)
+//!   and [`Rows::to_json_writer`](crate::Rows::to_json_writer) convert rows to
+//!   JSON objects.
+
+pub mod csv;
+#[cfg(feature = "serde_json")]
+mod json;
+
+// Errors of the query, when the result is written to an `io::Write`.
+pub(crate) fn to_io(err: crate::Error) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, err)
+}
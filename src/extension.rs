@@ -0,0 +1,131 @@
+//! `feature = "extension_init"` Entry points of loadable extensions.
+//!
+//! The [`sqlite_extension_init`] attribute turns a function taking the
+//! loading `Connection` into the `sqlite3_<name>_init` entry point that
+//! `load_extension` looks up:
+//!
+//! ```rust,no_run
+//! use rusqlite::extension::sqlite_extension_init;
+//! use rusqlite::{Connection, Result};
+//!
+//! #[sqlite_extension_init]
+//! fn genomics(conn: &Connection) -> Result<()> {
+//!     conn.execute_batch("CREATE TEMP VIEW IF NOT EXISTS strands AS SELECT '+' UNION SELECT '-'")
+//! }
+//! ```
+//!
+//! The crate must be built as a `cdylib`. As rusqlite calls SQLite directly
+//! rather than through the `sqlite3_api_routines` table passed to the entry
+//! point, the extension must link the same SQLite library as the host
+//! (i.e. dynamically, without the `bundled` feature).
+//!
+//! Linked statically instead, the entry point can be registered with
+//! [`crate::auto_extension::register`] to initialize every new connection.
+//!
+//! # Several extensions in one library
+//!
+//! A crate can export any number of entry points, each loaded with its name
+//! as `entry_point`. Without one, SQLite looks up `sqlite3_extension_init`,
+//! then the name derived from the file name, as [`entry_point_name`] does:
+//! give the main extension one of these names so that it is found by
+//! default, e.g. for `libgenomics.so`:
+//!
+//! ```rust,no_run
+//! use rusqlite::extension::sqlite_extension_init;
+//! use rusqlite::{Connection, Result};
+//!
+//! #[sqlite_extension_init(name = "genomics", name = "extension")]
+//! fn genomics(conn: &Connection) -> Result<()> {
+//!     strands(conn)?;
+//!     conn.execute_batch("CREATE TEMP TABLE IF NOT EXISTS samples (name TEXT)")
+//! }
+//!
+//! // Loaded with `load_extension('libgenomics', 'sqlite3_strands_init')`.
+//! #[sqlite_extension_init]
+//! fn strands(conn: &Connection) -> Result<()> {
+//!     conn.execute_batch("CREATE TEMP VIEW IF NOT EXISTS strands AS SELECT '+' UNION SELECT '-'")
+//! }
+//! ```
+use std::os::raw::{c_char, c_int, c_void};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+
+use crate::ffi;
+use crate::util::SqliteMallocString;
+use crate::{Connection, Result};
+
+pub use rusqlite_macros::sqlite_extension_init;
+
+/// Returns the entry point which SQLite looks up in the extension at
+/// `path` when none is given, if there is no `sqlite3_extension_init`:
+/// `sqlite3_<name>_init`, where `<name>` is made of the letters of the
+/// file name, lowercased, up to its first `.` and without a `lib` prefix.
+///
+/// ```rust
+/// use rusqlite::extension::entry_point_name;
+///
+/// assert_eq!(entry_point_name("/usr/lib/libgenomics_v2.so"), "sqlite3_genomicsv_init");
+/// assert_eq!(entry_point_name("a日.so"), "sqlite3_a_init");
+/// ```
+pub fn entry_point_name<P: AsRef<Path>>(path: P) -> String {
+    let file_name = path
+        .as_ref()
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let mut name: &str = &file_name;
+    if name
+        .get(..3)
+        .map_or(false, |prefix| prefix.eq_ignore_ascii_case("lib"))
+    {
+        name = &name[3..];
+    }
+    let name: String = name
+        .chars()
+        .take_while(|&c| c != '.')
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    format!("sqlite3_{}_init", name)
+}
+
+/// Runs the initialization function of an extension on the connection
+/// loading it, reporting its error or panic through `pz_err_msg`.
+///
+/// Called by the entry points generated by [`sqlite_extension_init`].
+///
+/// # Safety
+///
+/// `db` must be a valid connection handle, and `pz_err_msg` either null or
+/// a valid pointer to an error message, as given by SQLite to the entry
+/// point.
+#[doc(hidden)]
+pub unsafe fn init_extension(
+    db: *mut ffi::sqlite3,
+    pz_err_msg: *mut *mut c_char,
+    _p_api: *const c_void,
+    init: fn(&Connection) -> Result<()>,
+) -> c_int {
+    let r = catch_unwind(AssertUnwindSafe(|| {
+        let conn = Connection::from_handle(db)?;
+        init(&conn)
+    }));
+    let message = match r {
+        Ok(Ok(())) => return ffi::SQLITE_OK,
+        Ok(Err(err)) => err.to_string(),
+        Err(payload) => {
+            let reason = payload
+                .downcast_ref::<&str>()
+                .map(|s| (*s).to_owned())
+                .or_else(|| payload.downcast_ref::<String>().cloned());
+            match reason {
+                Some(reason) => format!("unwinding panic: {}", reason),
+                None => "unwinding panic".to_owned(),
+            }
+        }
+    };
+    if !pz_err_msg.is_null() {
+        *pz_err_msg = SqliteMallocString::from_str(&message).into_raw();
+    }
+    ffi::SQLITE_ERROR
+}
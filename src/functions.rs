@@ -53,9 +53,13 @@
 //! }
 //! ```
 use std::any::Any;
+#[cfg(feature = "modern_sqlite")]
+use std::cell::Cell;
+#[cfg(feature = "modern_sqlite")]
+use std::ffi::CStr;
 use std::marker::PhantomData;
 use std::ops::Deref;
-use std::os::raw::{c_int, c_void};
+use std::os::raw::{c_int, c_uint, c_void};
 use std::panic::{catch_unwind, RefUnwindSafe, UnwindSafe};
 use std::ptr;
 use std::slice;
@@ -84,31 +88,77 @@ unsafe fn report_error(ctx: *mut sqlite3_context, err: &Error) {
         ffi::SQLITE_CONSTRAINT
     }
 
-    match *err {
-        Error::SqliteFailure(ref err, ref s) => {
-            ffi::sqlite3_result_error_code(ctx, err.extended_code);
-            if let Some(Ok(cstr)) = s.as_ref().map(|s| str_to_cstring(s)) {
-                ffi::sqlite3_result_error(ctx, cstr.as_ptr(), -1);
+    let code = match *err {
+        Error::SqliteFailure(ref err, _) => err.extended_code,
+        Error::UserFunctionError(ref err) => {
+            if let Some(err) = err.downcast_ref::<Error>() {
+                return report_error(ctx, err);
             }
-        }
-        _ => {
-            ffi::sqlite3_result_error_code(ctx, constraint_error_code());
-            if let Ok(cstr) = str_to_cstring(&err.to_string()) {
-                ffi::sqlite3_result_error(ctx, cstr.as_ptr(), -1);
+            match err.downcast_ref::<ffi::Error>() {
+                Some(err) => err.extended_code,
+                None => constraint_error_code(),
             }
         }
+        _ => constraint_error_code(),
+    };
+    let message = match *err {
+        Error::SqliteFailure(_, ref s) => s.clone(),
+        _ => Some(err.to_string()),
+    };
+    if let Some(Ok(cstr)) = message.map(|s| str_to_cstring(&s)) {
+        ffi::sqlite3_result_error(ctx, cstr.as_ptr(), -1);
     }
+    // After the message, which resets the code to SQLITE_ERROR.
+    ffi::sqlite3_result_error_code(ctx, code);
 }
 
 unsafe extern "C" fn free_boxed_value<T>(p: *mut c_void) {
     drop(Box::from_raw(p as *mut T));
 }
 
+// Sets the subtype given to `Context::set_result_subtype`, which has to be
+// done after setting the result.
+#[cfg(feature = "modern_sqlite")]
+unsafe fn set_result_subtype(ctx: *mut sqlite3_context, subtype: Option<c_uint>) {
+    if let Some(subtype) = subtype {
+        ffi::sqlite3_result_subtype(ctx, subtype);
+    }
+}
+
+#[cfg(not(feature = "modern_sqlite"))]
+unsafe fn set_result_subtype(_: *mut sqlite3_context, _: Option<c_uint>) {}
+
 /// `feature = "functions"` Context is a wrapper for the SQLite function
 /// evaluation context.
 pub struct Context<'a> {
     ctx: *mut sqlite3_context,
     args: &'a [*mut sqlite3_value],
+    #[cfg(feature = "modern_sqlite")]
+    result_subtype: Cell<Option<c_uint>>,
+}
+
+impl<'a> Context<'a> {
+    #[inline]
+    fn new(ctx: *mut sqlite3_context, args: &'a [*mut sqlite3_value]) -> Context<'a> {
+        Context {
+            ctx,
+            args,
+            #[cfg(feature = "modern_sqlite")]
+            result_subtype: Cell::new(None),
+        }
+    }
+
+    #[cfg(feature = "modern_sqlite")]
+    #[inline]
+    fn result_subtype(&self) -> Option<c_uint> {
+        self.result_subtype.get()
+    }
+
+    #[cfg(not(feature = "modern_sqlite"))]
+    #[inline]
+    fn result_subtype(&self) -> Option<c_uint> {
+        None
+    }
 }
 
 impl Context<'_> {
@@ -165,6 +215,54 @@ impl Context<'_> {
         unsafe { ValueRef::from_value(arg) }
     }
 
+    /// Returns the [subtype](https://sqlite.org/c3ref/value_subtype.html) of
+    /// the `idx`th argument, e.g. `'J'` for JSON returned by the JSON1
+    /// functions, or 0 if it has none.
+    ///
+    /// The function should be created with
+    /// [`FunctionFlags::SQLITE_SUBTYPE`].
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `idx` is greater than or equal to [`self.len()`](Context::len).
+    #[cfg(feature = "modern_sqlite")]
+    #[inline]
+    pub fn get_subtype(&self, idx: usize) -> c_uint {
+        let arg = self.args[idx];
+        unsafe { ffi::sqlite3_value_subtype(arg) }
+    }
+
+    /// Returns the value of the `idx`th argument if it is a
+    /// [`Pointer`](crate::types::Pointer) to a `T` with the type name
+    /// `type_name`, or `None` otherwise.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `idx` is greater than or equal to [`self.len()`](Context::len).
+    #[cfg(feature = "modern_sqlite")]
+    pub fn get_pointer<T: Any>(&self, idx: usize, type_name: &CStr) -> Option<&T> {
+        let arg = self.args[idx];
+        let p = unsafe { ffi::sqlite3_value_pointer(arg, type_name.as_ptr()) };
+        if p.is_null() {
+            None
+        } else {
+            unsafe { crate::types::Pointer::from_raw(p) }.downcast_ref()
+        }
+    }
+
+    /// Sets the [subtype](https://sqlite.org/c3ref/result_subtype.html) of
+    /// the result of a scalar function, or of the
+    /// [`finalize`](Aggregate::finalize) of an aggregate (see
+    /// [`WindowAggregate::value_subtype`] for the current value of a window
+    /// function), e.g. `'J'` to have
+    /// the JSON1 functions take a text result as JSON rather than as a
+    /// string. Only the lower 8 bits are kept.
+    #[cfg(feature = "modern_sqlite")]
+    #[inline]
+    pub fn set_result_subtype(&self, subtype: c_uint) {
+        self.result_subtype.set(Some(subtype));
+    }
+
     /// Fetch or insert the auxilliary data associated with a particular
     /// parameter. This is intended to be an easier-to-use way of fetching it
     /// compared to calling [`get_aux`](Context::get_aux) and [`set_aux`](Context::set_aux) separately.
@@ -298,6 +396,14 @@ where
     /// implementation should not delete any context.
     fn value(&self, _: Option<&A>) -> Result<T>;
 
+    /// Returns the [subtype](https://sqlite.org/c3ref/result_subtype.html) of
+    /// the current value, as [`Context::set_result_subtype`] sets that of the
+    /// final one, or `None`, the default, for no subtype. Ignored without
+    /// `feature = "modern_sqlite"`.
+    fn value_subtype(&self, _: Option<&A>) -> Option<c_uint> {
+        None
+    }
+
     /// Removes a row from the current window.
     fn inverse(&self, _: &mut Context<'_>, _: &mut A) -> Result<()>;
 }
@@ -340,12 +446,23 @@ impl Connection {
     ///
     /// `fn_name` is the name the function will be accessible from SQL.
     /// `n_arg` is the number of arguments to the function. Use `-1` for a
-    /// variable number. If the function always returns the same value
-    /// given the same input, `deterministic` should be `true`.
+    /// variable number. `flags` should include
+    /// [`SQLITE_DETERMINISTIC`](FunctionFlags::SQLITE_DETERMINISTIC) if the
+    /// function always returns the same value given the same input, and
+    /// either [`SQLITE_DIRECTONLY`](FunctionFlags::SQLITE_DIRECTONLY), if it
+    /// has side effects or reveals internal state and so must not be used from
+    /// the schema (views, triggers, indexes, ...), or
+    /// [`SQLITE_INNOCUOUS`](FunctionFlags::SQLITE_INNOCUOUS) otherwise.
     ///
     /// The function will remain available until the connection is closed or
     /// until it is explicitly removed via [`remove_function`](Connection::remove_function).
     ///
+    /// If the function returns [`Error::SqliteFailure`], or an
+    /// [`Error::UserFunctionError`] wrapping an [`ffi::Error`], the statement
+    /// fails with its (extended) error code and message; other errors fail
+    /// it with `SQLITE_CONSTRAINT_FUNCTION` (`SQLITE_CONSTRAINT` without
+    /// `modern_sqlite`) and their description.
+    ///
     /// # Example
     ///
     /// ```rust
@@ -474,13 +591,11 @@ impl InnerConnection {
             let r = catch_unwind(|| {
                 let boxed_f: *mut F = ffi::sqlite3_user_data(ctx) as *mut F;
                 assert!(!boxed_f.is_null(), "Internal error - null function pointer");
-                let ctx = Context {
-                    ctx,
-                    args: slice::from_raw_parts(argv, argc as usize),
-                };
-                (*boxed_f)(&ctx)
+                let ctx = Context::new(ctx, slice::from_raw_parts(argv, argc as usize));
+                let t = (*boxed_f)(&ctx);
+                (t, ctx.result_subtype())
             });
-            let t = match r {
+            let (t, subtype) = match r {
                 Err(_) => {
                     report_error(ctx, &Error::UnwindingPanic);
                     return;
@@ -490,7 +605,10 @@ impl InnerConnection {
             let t = t.as_ref().map(|t| ToSql::to_sql(t));
 
             match t {
-                Ok(Ok(ref value)) => set_result(ctx, value),
+                Ok(Ok(ref value)) => {
+                    set_result(ctx, value);
+                    set_result_subtype(ctx, subtype);
+                }
                 Ok(Err(err)) => report_error(ctx, &err),
                 Err(err) => report_error(ctx, err),
             }
@@ -626,10 +744,7 @@ unsafe extern "C" fn call_boxed_step<A, D, T>(
             !boxed_aggr.is_null(),
             "Internal error - null aggregate pointer"
         );
-        let mut ctx = Context {
-            ctx,
-            args: slice::from_raw_parts(argv, argc as usize),
-        };
+        let mut ctx = Context::new(ctx, slice::from_raw_parts(argv, argc as usize));
 
         if (*pac as *mut A).is_null() {
             *pac = Box::into_raw(Box::new((*boxed_aggr).init(&mut ctx)?));
@@ -674,10 +789,7 @@ unsafe extern "C" fn call_boxed_inverse<A, W, T>(
             !boxed_aggr.is_null(),
             "Internal error - null aggregate pointer"
         );
-        let mut ctx = Context {
-            ctx,
-            args: slice::from_raw_parts(argv, argc as usize),
-        };
+        let mut ctx = Context::new(ctx, slice::from_raw_parts(argv, argc as usize));
         (*boxed_aggr).inverse(&mut ctx, &mut **pac)
     });
     let r = match r {
@@ -719,10 +831,11 @@ where
             !boxed_aggr.is_null(),
             "Internal error - null aggregate pointer"
         );
-        let mut ctx = Context { ctx, args: &mut [] };
-        (*boxed_aggr).finalize(&mut ctx, a)
+        let mut ctx = Context::new(ctx, &[]);
+        let t = (*boxed_aggr).finalize(&mut ctx, a);
+        (t, ctx.result_subtype())
     });
-    let t = match r {
+    let (t, subtype) = match r {
         Err(_) => {
             report_error(ctx, &Error::UnwindingPanic);
             return;
@@ -731,7 +844,10 @@ where
     };
     let t = t.as_ref().map(|t| ToSql::to_sql(t));
     match t {
-        Ok(Ok(ref value)) => set_result(ctx, value),
+        Ok(Ok(ref value)) => {
+            set_result(ctx, value);
+            set_result_subtype(ctx, subtype);
+        }
         Ok(Err(err)) => report_error(ctx, &err),
         Err(err) => report_error(ctx, err),
     }
@@ -764,9 +880,9 @@ where
             !boxed_aggr.is_null(),
             "Internal error - null aggregate pointer"
         );
-        (*boxed_aggr).value(a)
+        ((*boxed_aggr).value(a), (*boxed_aggr).value_subtype(a))
     });
-    let t = match r {
+    let (t, subtype) = match r {
         Err(_) => {
             report_error(ctx, &Error::UnwindingPanic);
             return;
@@ -775,7 +891,10 @@ where
     };
     let t = t.as_ref().map(|t| ToSql::to_sql(t));
     match t {
-        Ok(Ok(ref value)) => set_result(ctx, value),
+        Ok(Ok(ref value)) => {
+            set_result(ctx, value);
+            set_result_subtype(ctx, subtype);
+        }
         Ok(Err(err)) => report_error(ctx, &err),
         Err(err) => report_error(ctx, err),
     }
@@ -785,12 +904,12 @@ where
 mod test {
     use regex::Regex;
     use std::f64::EPSILON;
-    use std::os::raw::c_double;
+    use std::os::raw::{c_double, c_uint};
 
     #[cfg(feature = "window")]
     use crate::functions::WindowAggregate;
     use crate::functions::{Aggregate, Context, FunctionFlags};
-    use crate::{Connection, Error, Result};
+    use crate::{ffi, Connection, Error, ErrorCode, Result};
 
     fn half(ctx: &Context<'_>) -> Result<c_double> {
         assert_eq!(ctx.len(), 1, "called with unexpected number of arguments");
@@ -887,6 +1006,32 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_error_code() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.create_scalar_function("fail", 1, FunctionFlags::SQLITE_UTF8, |ctx| {
+            Err::<i64, _>(match ctx.get::<i64>(0)? {
+                0 => Error::SqliteFailure(
+                    ffi::Error::new(ffi::SQLITE_TOOBIG),
+                    Some("region too large".to_owned()),
+                ),
+                1 => Error::UserFunctionError(Box::new(ffi::Error::new(ffi::SQLITE_RANGE))),
+                _ => Error::UserFunctionError("unknown contig".into()),
+            })
+        })?;
+        let fail = |n: i64| match db.query_row("SELECT fail(?)", [n], |r| r.get::<_, i64>(0)) {
+            Err(Error::SqliteFailure(err, msg)) => (err.code, msg.unwrap_or_default()),
+            r => panic!("unexpected {:?}", r),
+        };
+        assert_eq!(fail(0), (ErrorCode::TooBig, "region too large".to_owned()));
+        assert_eq!(fail(1).0, ErrorCode::ParameterOutOfRange);
+        assert_eq!(
+            fail(2),
+            (ErrorCode::ConstraintViolation, "unknown contig".to_owned())
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_varargs_function() -> Result<()> {
         let db = Connection::open_in_memory()?;
@@ -1020,6 +1165,68 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    #[cfg(feature = "bundled")]
+    fn test_directonly() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.create_scalar_function(
+            "secret",
+            0,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DIRECTONLY,
+            |_| Ok(42),
+        )?;
+        db.create_scalar_function(
+            "harmless",
+            0,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_INNOCUOUS,
+            |_| Ok(1),
+        )?;
+        db.execute_batch("CREATE VIEW v AS SELECT secret(); CREATE VIEW w AS SELECT harmless()")?;
+        let direct: i64 = db.query_row("SELECT secret()", [], |r| r.get(0))?;
+        assert_eq!(direct, 42);
+        let err = db
+            .query_row("SELECT * FROM v", [], |r| r.get::<_, i64>(0))
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("unsafe use of secret()"),
+            "{}",
+            err
+        );
+        let harmless: i64 = db.query_row("SELECT * FROM w", [], |r| r.get(0))?;
+        assert_eq!(harmless, 1);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "modern_sqlite")]
+    fn test_subtype() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        // Marks its argument as JSON, like json() without validation.
+        db.create_scalar_function("as_json", 1, FunctionFlags::SQLITE_UTF8, |ctx| {
+            ctx.set_result_subtype(u32::from(b'J'));
+            ctx.get::<String>(0)
+        })?;
+        db.create_scalar_function(
+            "subtype",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_SUBTYPE,
+            |ctx| Ok(ctx.get_subtype(0)),
+        )?;
+        let (plain, json): (String, String) = db.query_row(
+            "SELECT json_array('[1]'), json_array(as_json('[1]'))",
+            [],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )?;
+        assert_eq!(plain, r#"["[1]"]"#);
+        assert_eq!(json, "[[1]]");
+        let subtypes: (u32, u32) =
+            db.query_row("SELECT subtype(json('[1]')), subtype('[1]')", [], |r| {
+                Ok((r.get(0)?, r.get(1)?))
+            })?;
+        assert_eq!(subtypes, (u32::from(b'J'), 0));
+        Ok(())
+    }
+
     #[cfg(feature = "window")]
     impl WindowAggregate<i64, Option<i64>> for Sum {
         fn inverse(&self, ctx: &mut Context<'_>, sum: &mut i64) -> Result<()> {
@@ -1074,4 +1281,82 @@ mod test {
         assert_eq!(expected, results);
         Ok(())
     }
+
+    // The sums as JSON arrays, e.g. `[3]`.
+    #[cfg(all(feature = "window", feature = "modern_sqlite"))]
+    struct JsonSum;
+
+    #[cfg(all(feature = "window", feature = "modern_sqlite"))]
+    impl Aggregate<i64, String> for JsonSum {
+        fn init(&self, _: &mut Context<'_>) -> Result<i64> {
+            Ok(0)
+        }
+
+        fn step(&self, ctx: &mut Context<'_>, sum: &mut i64) -> Result<()> {
+            *sum += ctx.get::<i64>(0)?;
+            Ok(())
+        }
+
+        fn finalize(&self, ctx: &mut Context<'_>, sum: Option<i64>) -> Result<String> {
+            ctx.set_result_subtype(u32::from(b'J'));
+            Ok(format!("[{}]", sum.unwrap_or(0)))
+        }
+    }
+
+    #[cfg(all(feature = "window", feature = "modern_sqlite"))]
+    impl WindowAggregate<i64, String> for JsonSum {
+        fn inverse(&self, ctx: &mut Context<'_>, sum: &mut i64) -> Result<()> {
+            *sum -= ctx.get::<i64>(0)?;
+            Ok(())
+        }
+
+        fn value(&self, sum: Option<&i64>) -> Result<String> {
+            Ok(format!("[{}]", sum.copied().unwrap_or(0)))
+        }
+
+        fn value_subtype(&self, _: Option<&i64>) -> Option<c_uint> {
+            Some(u32::from(b'J'))
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "window", feature = "modern_sqlite"))]
+    fn test_window_subtype() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.create_window_function("json_sum", 1, FunctionFlags::SQLITE_UTF8, JsonSum)?;
+        let mut stmt = db.prepare(
+            "SELECT json_array(json_sum(x) OVER (ORDER BY x ROWS 1 PRECEDING))
+             FROM (SELECT 1 AS x UNION ALL SELECT 2 UNION ALL SELECT 3)",
+        )?;
+        let values: Vec<String> = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_>>()?;
+        assert_eq!(values, vec!["[[1]]", "[[3]]", "[[5]]"]);
+        let json: String = db.query_row("SELECT json_array(json_sum(1))", [], |r| r.get(0))?;
+        assert_eq!(json, "[[1]]");
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "window")]
+    fn test_window_range() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.create_window_function("sumint", 1, FunctionFlags::SQLITE_UTF8, Sum)?;
+        db.execute_batch(
+            "CREATE TABLE variants(pos INTEGER, depth INTEGER);
+             INSERT INTO variants VALUES (100, 1), (105, 2), (130, 4), (150, 8), (151, 16);",
+        )?;
+
+        // Rows enter and leave the frame by distance of position, not count.
+        let mut stmt = db.prepare(
+            "SELECT sumint(depth) OVER (
+                   ORDER BY pos RANGE BETWEEN 20 PRECEDING AND CURRENT ROW
+                 ) FROM variants ORDER BY pos",
+        )?;
+        let sums: Vec<i64> = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_>>()?;
+        assert_eq!(sums, vec![1, 3, 4, 12, 24]);
+        Ok(())
+    }
 }
@@ -0,0 +1,370 @@
+//! `feature = "fuzz"` Run untrusted SQL safely, for fuzz targets.
+//!
+//! A [`Harness`] wraps a connection restricted for untrusted SQL: an
+//! authorizer denies `ATTACH`, `DETACH` (and so `VACUUM`), the pragmas which could touch the
+//! file system or corrupt the database, and the functions reading or writing
+//! files or loading extensions; the [limits](crate::limits) are lowered; the
+//! memory used by SQLite can be capped; and each run is interrupted after a
+//! timeout. After a run, [`Harness::check_invariants`] checks that the
+//! database is still consistent.
+//!
+//! A [cargo-fuzz](https://github.com/rust-fuzz/cargo-fuzz) target can then be:
+//!
+//! ```rust,ignore
+//! #![no_main]
+//! libfuzzer_sys::fuzz_target!(|data: &[u8]| rusqlite::fuzz::run(data));
+//! ```
+
+use std::os::raw::{c_char, c_int, c_void};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::{ffi::CStr, ptr};
+
+use crate::ffi;
+use crate::limits::Limit;
+use crate::{Batch, Connection, Error, Result};
+
+// The pragmas which can be run: they only read or change the behavior of the
+// connection.
+const ALLOWED_PRAGMAS: &[&str] = &[
+    "case_sensitive_like",
+    "foreign_key_check",
+    "foreign_key_list",
+    "foreign_keys",
+    "index_info",
+    "index_list",
+    "index_xinfo",
+    "integrity_check",
+    "quick_check",
+    "recursive_triggers",
+    "reverse_unordered_selects",
+    "table_info",
+    "table_xinfo",
+];
+
+// The functions which access files or load code.
+const DENIED_FUNCTIONS: &[&str] = &[
+    "edit",
+    "fts3_tokenizer",
+    "load_extension",
+    "readfile",
+    "writefile",
+];
+
+/// `feature = "fuzz"` The restrictions of a [`Harness`].
+///
+/// By default, runs time out after 1 second, the memory SQLite may use is not
+/// capped, and the limits are lowered to keep statements small.
+#[derive(Clone, Debug)]
+pub struct FuzzOptions {
+    timeout: Duration,
+    memory_limit: i64,
+    limits: Vec<(Limit, i32)>,
+}
+
+impl Default for FuzzOptions {
+    fn default() -> Self {
+        FuzzOptions::new()
+    }
+}
+
+impl FuzzOptions {
+    /// Creates the default options.
+    pub fn new() -> FuzzOptions {
+        FuzzOptions {
+            timeout: Duration::from_secs(1),
+            memory_limit: 0,
+            limits: vec![
+                (Limit::SQLITE_LIMIT_LENGTH, 1_000_000),
+                (Limit::SQLITE_LIMIT_SQL_LENGTH, 100_000),
+                (Limit::SQLITE_LIMIT_COLUMN, 100),
+                (Limit::SQLITE_LIMIT_EXPR_DEPTH, 100),
+                (Limit::SQLITE_LIMIT_COMPOUND_SELECT, 10),
+                (Limit::SQLITE_LIMIT_VDBE_OP, 25_000),
+                (Limit::SQLITE_LIMIT_FUNCTION_ARG, 8),
+                (Limit::SQLITE_LIMIT_ATTACHED, 0),
+                (Limit::SQLITE_LIMIT_LIKE_PATTERN_LENGTH, 100),
+                (Limit::SQLITE_LIMIT_VARIABLE_NUMBER, 100),
+                (Limit::SQLITE_LIMIT_TRIGGER_DEPTH, 10),
+            ],
+        }
+    }
+
+    /// Sets the time after which a run is interrupted.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> FuzzOptions {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum number of bytes of memory SQLite may use, in the
+    /// whole process (see
+    /// [`sqlite3_hard_heap_limit64`](https://sqlite.org/c3ref/hard_heap_limit64.html)),
+    /// as long as the harness lives, or 0, the default, to leave it as is.
+    ///
+    /// The previous limit is restored when the harness is dropped, so the
+    /// harnesses with a memory limit should not overlap.
+    #[must_use]
+    pub fn memory_limit(mut self, bytes: i64) -> FuzzOptions {
+        self.memory_limit = bytes;
+        self
+    }
+
+    /// Sets a limit of the connection.
+    #[must_use]
+    pub fn limit(mut self, limit: Limit, value: i32) -> FuzzOptions {
+        self.limits.retain(|&(l, _)| l != limit);
+        self.limits.push((limit, value));
+        self
+    }
+}
+
+/// `feature = "fuzz"` A connection running untrusted SQL.
+#[derive(Debug)]
+pub struct Harness {
+    conn: Connection,
+    timeout: Duration,
+    deadline: Arc<Mutex<Instant>>,
+    // The heap limit to restore.
+    previous_memory_limit: Option<i64>,
+}
+
+impl Harness {
+    /// Creates a harness on a new in-memory database.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database cannot be opened.
+    pub fn new(options: &FuzzOptions) -> Result<Harness> {
+        Harness::open(Connection::open_in_memory()?, options)
+    }
+
+    /// Creates a harness on `conn`, e.g. a database with a schema to fuzz
+    /// queries against, restricting it.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the authorizer cannot be set.
+    pub fn open(conn: Connection, options: &FuzzOptions) -> Result<Harness> {
+        for &(limit, value) in &options.limits {
+            conn.set_limit(limit, value);
+        }
+        let previous_memory_limit = if options.memory_limit > 0 {
+            Some(unsafe { ffi::sqlite3_hard_heap_limit64(options.memory_limit) })
+        } else {
+            None
+        };
+        let deadline = Arc::new(Mutex::new(Instant::now() + options.timeout));
+        let expired = deadline.clone();
+        conn.progress_handler(
+            1000,
+            Some(move || Instant::now() > *expired.lock().unwrap_or_else(|e| e.into_inner())),
+        );
+        {
+            let c = conn.db.borrow();
+            check!(unsafe {
+                ffi::sqlite3_set_authorizer(c.db(), Some(authorize), ptr::null_mut())
+            });
+        }
+        Ok(Harness {
+            conn,
+            timeout: options.timeout,
+            deadline,
+            previous_memory_limit,
+        })
+    }
+
+    /// Runs the statements of `sql`, stepping through all their rows, and
+    /// stops at the first error.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if a statement is invalid, denied, fails, or times
+    /// out.
+    pub fn run(&self, sql: &str) -> Result<()> {
+        *self.deadline.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now() + self.timeout;
+        let mut batch = Batch::new(&self.conn, sql);
+        while let Some(mut stmt) = batch.next()? {
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                for i in 0..row.column_count() {
+                    row.get_ref(i)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Rolls back the transaction left open by runs, if any, and checks that
+    /// the database passes its integrity check.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err(Error::SqliteFailure)` with `SQLITE_CORRUPT` and the
+    /// problems found if the check fails.
+    pub fn check_invariants(&self) -> Result<()> {
+        if !self.conn.is_autocommit() {
+            self.conn.execute_batch("ROLLBACK")?;
+        }
+        let mut problems = Vec::new();
+        self.conn.pragma_query(None, "integrity_check", |row| {
+            problems.push(row.get::<_, String>(0)?);
+            Ok(())
+        })?;
+        if problems == ["ok"] {
+            Ok(())
+        } else {
+            Err(Error::SqliteFailure(
+                ffi::Error::new(ffi::SQLITE_CORRUPT),
+                Some(problems.join("\n")),
+            ))
+        }
+    }
+
+    /// The connection of the harness, still restricted.
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        if let Some(limit) = self.previous_memory_limit {
+            unsafe { ffi::sqlite3_hard_heap_limit64(limit) };
+        }
+    }
+}
+
+/// `feature = "fuzz"` Runs `data`, as SQL, on a fresh harness with the
+/// default options and a memory limit of 64 MiB, and panics if the database
+/// is left inconsistent. Errors of the SQL are ignored.
+pub fn run(data: &[u8]) {
+    let harness = Harness::new(&FuzzOptions::new().memory_limit(64 << 20)).expect("harness");
+    let _ = harness.run(&String::from_utf8_lossy(data));
+    if let Err(err) = harness.check_invariants() {
+        panic!("invariant violated after {:?}: {}", data, err);
+    }
+}
+
+unsafe extern "C" fn authorize(
+    _: *mut c_void,
+    action: c_int,
+    arg1: *const c_char,
+    arg2: *const c_char,
+    _: *const c_char,
+    _: *const c_char,
+) -> c_int {
+    let arg = |p: *const c_char| {
+        if p.is_null() {
+            ""
+        } else {
+            CStr::from_ptr(p).to_str().unwrap_or("")
+        }
+    };
+    let allowed = match action {
+        ffi::SQLITE_ATTACH | ffi::SQLITE_DETACH => false,
+        ffi::SQLITE_PRAGMA => ALLOWED_PRAGMAS
+            .iter()
+            .any(|p| p.eq_ignore_ascii_case(arg(arg1))),
+        ffi::SQLITE_FUNCTION => !DENIED_FUNCTIONS
+            .iter()
+            .any(|f| f.eq_ignore_ascii_case(arg(arg2))),
+        _ => true,
+    };
+    if allowed {
+        ffi::SQLITE_OK
+    } else {
+        ffi::SQLITE_DENY
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{FuzzOptions, Harness};
+    use crate::ffi;
+    use crate::limits::Limit;
+    use crate::{Connection, Error, ErrorCode, Result};
+
+    fn code(result: Result<()>) -> Option<ErrorCode> {
+        match result {
+            Err(Error::SqliteFailure(err, _)) => Some(err.code),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_restrictions() -> Result<()> {
+        let harness = Harness::new(&FuzzOptions::new())?;
+        harness.run("CREATE TABLE t (x); INSERT INTO t VALUES (1); SELECT * FROM t")?;
+        harness.run("PRAGMA table_info(t); PRAGMA foreign_keys = ON")?;
+        for sql in &[
+            "ATTACH 'file.db' AS other",
+            "PRAGMA writable_schema = ON",
+            "PRAGMA journal_mode = OFF",
+        ] {
+            assert_eq!(
+                code(harness.run(sql)),
+                Some(ErrorCode::AuthorizationForStatementDenied),
+                "{}",
+                sql
+            );
+        }
+        let err = harness.run("SELECT load_extension('x')").unwrap_err();
+        assert!(err.to_string().contains("not authorized"), "{}", err);
+        assert!(harness.run("VACUUM INTO 'copy.db'").is_err());
+        assert!(harness.run("SELECT 1, 2, 3, 4, 5, 6, 7, 8, 9").is_ok());
+        assert!(harness
+            .run("SELECT max(1, 2, 3, 4, 5, 6, 7, 8, 9)")
+            .is_err());
+        // Statements after an error are not run.
+        assert!(harness.run("SELEC 1; INSERT INTO t VALUES (2)").is_err());
+        harness.check_invariants()?;
+        let count: i64 = harness
+            .connection()
+            .query_row("SELECT count(*) FROM t", [], |r| r.get(0))?;
+        assert_eq!(count, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_timeout() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch("CREATE TABLE t (x)")?;
+        let options = FuzzOptions::new()
+            .timeout(Duration::from_millis(50))
+            .limit(Limit::SQLITE_LIMIT_VDBE_OP, 1000);
+        let harness = Harness::open(db, &options)?;
+        let infinite = "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c)
+                        SELECT count(*) FROM c";
+        assert_eq!(
+            code(harness.run(infinite)),
+            Some(ErrorCode::OperationInterrupted)
+        );
+        // The deadline is reset by each run.
+        harness.run("BEGIN; INSERT INTO t VALUES (1)")?;
+        harness.check_invariants()?;
+        assert!(harness.connection().is_autocommit());
+        Ok(())
+    }
+
+    // The only test changing the heap limit, which is global.
+    #[test]
+    fn test_run_memory_limit() -> Result<()> {
+        let limit = || unsafe { ffi::sqlite3_hard_heap_limit64(-1) };
+        let previous = limit();
+        let harness = Harness::new(&FuzzOptions::new())?;
+        assert_eq!(limit(), previous);
+        drop(harness);
+
+        let harness = Harness::new(&FuzzOptions::new().memory_limit(1 << 30))?;
+        assert_eq!(limit(), 1 << 30);
+        drop(harness);
+        assert_eq!(limit(), previous);
+
+        super::run(b"CREATE TABLE t (x PRIMARY KEY); INSERT INTO t VALUES (1), (1); \xff");
+        assert_eq!(limit(), previous);
+        Ok(())
+    }
+}
@@ -1,3 +1,7 @@
+#[cfg(feature = "statement_tracking")]
+use std::backtrace::Backtrace;
+#[cfg(feature = "statement_tracking")]
+use std::collections::HashMap;
 use std::ffi::CStr;
 use std::os::raw::{c_char, c_int};
 #[cfg(feature = "load_extension")]
@@ -10,6 +14,7 @@ use std::sync::{Arc, Mutex};
 use super::ffi;
 use super::str_for_sqlite;
 use super::{Connection, InterruptHandle, OpenFlags, Result};
+use crate::cache::StatementCache;
 use crate::error::{error_from_handle, error_from_sqlite_code, Error};
 use crate::raw_statement::RawStatement;
 use crate::statement::Statement;
@@ -33,6 +38,17 @@ pub struct InnerConnection {
     pub free_update_hook: Option<unsafe fn(*mut ::std::os::raw::c_void)>,
     #[cfg(feature = "hooks")]
     pub progress_handler: Option<Box<dyn FnMut() -> bool + Send>>,
+    // Where each statement was prepared, to report the ones still alive when
+    // closing fails. Keyed by statement pointer; entries for statements which
+    // have since been finalized are pruned lazily.
+    #[cfg(feature = "statement_tracking")]
+    statement_origins: HashMap<usize, Backtrace>,
+    // The key of `Connection::encrypt_column` and `decrypt_column`.
+    #[cfg(feature = "column_encryption")]
+    pub column_key: Option<crate::column_encryption::ColumnKey>,
+    // The messages logged while running `Connection::logged`.
+    #[cfg(feature = "trace")]
+    pub log_history: Option<Arc<Mutex<crate::trace::LogBuffer>>>,
     owned: bool,
 }
 
@@ -51,6 +67,12 @@ impl InnerConnection {
             free_update_hook: None,
             #[cfg(feature = "hooks")]
             progress_handler: None,
+            #[cfg(feature = "statement_tracking")]
+            statement_origins: HashMap::new(),
+            #[cfg(feature = "column_encryption")]
+            column_key: None,
+            #[cfg(feature = "trace")]
+            log_history: None,
             owned,
         }
     }
@@ -168,11 +190,71 @@ impl InnerConnection {
             if r.is_ok() {
                 *shared_handle = ptr::null_mut();
                 self.db = ptr::null_mut();
+                #[cfg(feature = "statement_tracking")]
+                self.statement_origins.clear();
             }
+            #[cfg(feature = "statement_tracking")]
+            let r = r.map_err(|e| self.describe_unfinalized_statements(e));
             r
         }
     }
 
+    #[cfg(feature = "statement_tracking")]
+    fn track_statement(&mut self, stmt: *mut ffi::sqlite3_stmt) {
+        if stmt.is_null() {
+            return;
+        }
+        self.statement_origins
+            .insert(stmt as usize, Backtrace::force_capture());
+        // Forget finalized statements each time the number of entries reaches
+        // a power of two (from 64), so that the live statements are listed
+        // once per doubling, whatever the number of finalized ones.
+        if self.statement_origins.len() >= 64 && self.statement_origins.len().is_power_of_two() {
+            let live = self.live_statements();
+            self.statement_origins
+                .retain(|stmt, _| live.contains(&(*stmt as *mut ffi::sqlite3_stmt)));
+        }
+    }
+
+    fn live_statements(&self) -> Vec<*mut ffi::sqlite3_stmt> {
+        let mut live = Vec::new();
+        unsafe {
+            let mut stmt = ffi::sqlite3_next_stmt(self.db, ptr::null_mut());
+            while !stmt.is_null() {
+                live.push(stmt);
+                stmt = ffi::sqlite3_next_stmt(self.db, stmt);
+            }
+        }
+        live
+    }
+
+    // Appends the SQL and origin of each statement still alive to the message
+    // of a failed close.
+    #[cfg(feature = "statement_tracking")]
+    #[cold]
+    fn describe_unfinalized_statements(&self, err: Error) -> Error {
+        use std::fmt::Write;
+
+        let (code, msg) = match err {
+            Error::SqliteFailure(code, msg) if code.code == ffi::ErrorCode::DatabaseBusy => {
+                (code, msg)
+            }
+            err => return err,
+        };
+        let mut msg = msg.unwrap_or_else(|| code.to_string());
+        for stmt in self.live_statements() {
+            let sql = unsafe { statement_sql(stmt) };
+            let _ = write!(msg, "\n\nunfinalized statement `{}`", sql);
+            match self.statement_origins.get(&(stmt as usize)) {
+                Some(origin) => {
+                    let _ = write!(msg, " prepared at:\n{}", origin);
+                }
+                None => msg.push_str(" (not prepared through rusqlite)"),
+            }
+        }
+        Error::SqliteFailure(code, Some(msg))
+    }
+
     #[inline]
     pub fn get_interrupt_handle(&self) -> InterruptHandle {
         InterruptHandle {
@@ -257,6 +339,8 @@ impl InnerConnection {
         // If the input text contains no SQL (if the input is an empty string or a
         // comment) then *ppStmt is set to NULL.
         let c_stmt: *mut ffi::sqlite3_stmt = c_stmt;
+        #[cfg(feature = "statement_tracking")]
+        self.track_statement(c_stmt);
         let c_tail: *const c_char = c_tail;
         let tail = if c_tail.is_null() {
             0
@@ -278,6 +362,11 @@ impl InnerConnection {
         unsafe { ffi::sqlite3_changes(self.db()) as usize }
     }
 
+    #[inline]
+    pub fn total_changes(&mut self) -> usize {
+        unsafe { ffi::sqlite3_total_changes(self.db()) as usize }
+    }
+
     #[inline]
     pub fn is_autocommit(&self) -> bool {
         unsafe { ffi::sqlite3_get_autocommit(self.db()) != 0 }
@@ -298,11 +387,38 @@ impl InnerConnection {
         false
     }
 
+    #[cfg(feature = "modern_sqlite")] // 3.8.6
+    pub fn busy_statements(&self) -> Vec<String> {
+        self.live_statements()
+            .into_iter()
+            .filter(|&stmt| unsafe { ffi::sqlite3_stmt_busy(stmt) != 0 })
+            .map(|stmt| unsafe { statement_sql(stmt) })
+            .collect()
+    }
+
+    pub fn unfinalized_statements(&self, cache: &StatementCache) -> Vec<String> {
+        self.live_statements()
+            .into_iter()
+            .filter(|&stmt| !cache.contains_stmt(stmt))
+            .map(|stmt| unsafe { statement_sql(stmt) })
+            .collect()
+    }
+
     #[cfg(not(feature = "hooks"))]
     #[inline]
     fn remove_hooks(&mut self) {}
 }
 
+// SAFETY: `stmt` must be a live statement.
+unsafe fn statement_sql(stmt: *mut ffi::sqlite3_stmt) -> String {
+    let sql = ffi::sqlite3_sql(stmt);
+    if sql.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(sql).to_string_lossy().into_owned()
+    }
+}
+
 impl Drop for InnerConnection {
     #[allow(unused_must_use)]
     #[inline]
@@ -0,0 +1,260 @@
+//! `feature = "kv"` A key-value store, for when SQL is not needed.
+//!
+//! Values are any type implementing [`serde::Serialize`] and
+//! [`serde::Deserialize`], stored as JSON text, keyed by strings.
+//!
+//! ```rust,no_run
+//! use rusqlite::kv::KvStore;
+//! use rusqlite::{Connection, Result};
+//!
+//! #[derive(serde::Serialize, serde::Deserialize)]
+//! struct Settings {
+//!     threads: usize,
+//!     reference: String,
+//! }
+//!
+//! fn load(conn: &Connection, user: &str) -> Result<Option<Settings>> {
+//!     let store = KvStore::new(conn)?;
+//!     store.get(&format!("settings/{}", user))
+//! }
+//! ```
+
+use std::convert::TryFrom;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::types::Type;
+use crate::util::quote_identifier;
+use crate::{params, Connection, Error, OptionalExtension, Result, Savepoint};
+
+/// A key-value store, in a table of a connection.
+///
+/// Each call is a transaction of its own, unless made from a
+/// [`batch`](KvStore::batch) or an enclosing transaction: batching many
+/// writes makes them much faster, especially in WAL mode, where each
+/// transaction appends to the log.
+#[derive(Debug)]
+pub struct KvStore<'conn> {
+    conn: &'conn Connection,
+    table: String,
+}
+
+impl KvStore<'_> {
+    /// Opens the store in the `rusqlite_kv` table, which is created if it
+    /// doesn't exist.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the table cannot be created.
+    #[inline]
+    pub fn new(conn: &Connection) -> Result<KvStore<'_>> {
+        KvStore::with_table(conn, "rusqlite_kv")
+    }
+
+    /// Opens the store in `table`, which is created if it doesn't exist.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the table cannot be created.
+    pub fn with_table<'conn>(conn: &'conn Connection, table: &str) -> Result<KvStore<'conn>> {
+        let table = quote_identifier(table);
+        // The primary key of a WITHOUT ROWID table is its only index, so
+        // lookups and prefix scans read a single b-tree.
+        conn.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                key TEXT PRIMARY KEY NOT NULL,
+                value TEXT NOT NULL
+            ) WITHOUT ROWID",
+            table
+        ))?;
+        Ok(KvStore { conn, table })
+    }
+
+    /// Returns the value of `key`, or `None` if it is not set.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the value cannot be deserialized as `V`, or if
+    /// the underlying SQLite call fails.
+    pub fn get<V: DeserializeOwned>(&self, key: &str) -> Result<Option<V>> {
+        let json: Option<String> = self
+            .conn
+            .prepare_cached(&format!("SELECT value FROM {} WHERE key = ?", self.table))?
+            .query_row([key], |row| row.get(0))
+            .optional()?;
+        json.map(|json| from_json(&json)).transpose()
+    }
+
+    /// Sets the value of `key`, replacing any previous value.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `value` cannot be serialized, or if the
+    /// underlying SQLite call fails.
+    pub fn put<V: Serialize + ?Sized>(&self, key: &str, value: &V) -> Result<()> {
+        let json = serde_json::to_string(value)
+            .map_err(|err| Error::ToSqlConversionFailure(Box::new(err)))?;
+        self.conn
+            .prepare_cached(&format!(
+                "INSERT OR REPLACE INTO {} (key, value) VALUES (?, ?)",
+                self.table
+            ))?
+            .execute(params![key, json])?;
+        Ok(())
+    }
+
+    /// Removes `key`, and returns whether it was set.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite call fails.
+    pub fn delete(&self, key: &str) -> Result<bool> {
+        self.conn
+            .prepare_cached(&format!("DELETE FROM {} WHERE key = ?", self.table))?
+            .execute([key])
+            .map(|n| n > 0)
+    }
+
+    /// Returns the keys starting with `prefix` and their values, ordered by
+    /// key.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if some value cannot be deserialized as `V`, or if
+    /// the underlying SQLite call fails.
+    pub fn scan_prefix<V: DeserializeOwned>(&self, prefix: &str) -> Result<Vec<(String, V)>> {
+        // A range, rather than LIKE or GLOB, so that the primary key is used.
+        let end = prefix_end(prefix);
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "SELECT key, value FROM {} WHERE key >= ?1{} ORDER BY key",
+            self.table,
+            if end.is_some() { " AND key < ?2" } else { "" }
+        ))?;
+        let mut rows = match end {
+            Some(end) => stmt.query(params![prefix, end])?,
+            None => stmt.query([prefix])?,
+        };
+        let mut entries = Vec::new();
+        while let Some(row) = rows.next()? {
+            let json = row.get_ref(1)?.as_str()?;
+            entries.push((row.get(0)?, from_json(json)?));
+        }
+        Ok(entries)
+    }
+
+    /// Runs `f` in a transaction (a savepoint, if one is already open), which
+    /// is committed if `f` returns `Ok`, and rolled back otherwise.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `f` does, or if the transaction cannot be
+    /// committed.
+    pub fn batch<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Self) -> Result<T>,
+    {
+        let sp = Savepoint::with_depth_and_name(self.conn, 0, "_rusqlite_kv_batch")?;
+        let value = f(self)?;
+        sp.commit()?;
+        Ok(value)
+    }
+}
+
+fn from_json<V: DeserializeOwned>(json: &str) -> Result<V> {
+    serde_json::from_str(json)
+        .map_err(|err| Error::FromSqlConversionFailure(0, Type::Text, Box::new(err)))
+}
+
+// The smallest string greater than all the strings starting with `prefix`,
+// or `None` if there is none.
+fn prefix_end(prefix: &str) -> Option<String> {
+    let mut chars: Vec<char> = prefix.chars().collect();
+    while let Some(c) = chars.pop() {
+        let next = (u32::from(c) + 1..=u32::from(char::MAX)).find_map(|c| char::try_from(c).ok());
+        if let Some(next) = next {
+            chars.push(next);
+            return Some(chars.into_iter().collect());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::{prefix_end, KvStore};
+    use crate::{Connection, Error, Result};
+
+    #[test]
+    fn test_prefix_end() {
+        assert_eq!(prefix_end(""), None);
+        assert_eq!(prefix_end("ab"), Some("ac".to_owned()));
+        assert_eq!(prefix_end("a\u{d7ff}"), Some("a\u{e000}".to_owned()));
+        assert_eq!(prefix_end("a\u{10ffff}"), Some("b".to_owned()));
+        assert_eq!(prefix_end("\u{10ffff}"), None);
+    }
+
+    #[test]
+    fn test_kv_store() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        let store = KvStore::new(&db)?;
+        assert_eq!(store.get::<i32>("a")?, None);
+        store.put("a", &1)?;
+        store.put("a", &2)?;
+        assert_eq!(store.get::<i32>("a")?, Some(2));
+        assert!(matches!(
+            store.get::<String>("a"),
+            Err(Error::FromSqlConversionFailure(..))
+        ));
+
+        let mut map = BTreeMap::new();
+        map.insert("x".to_owned(), vec![1.5, 2.0]);
+        store.put("map", &map)?;
+        assert_eq!(store.get("map")?, Some(map));
+
+        assert!(store.delete("a")?);
+        assert!(!store.delete("a")?);
+        assert_eq!(store.get::<i32>("a")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_prefix_and_batch() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        let store = KvStore::with_table(&db, "my kv")?;
+        store.batch(|store| {
+            for key in &["user/1", "user/2", "user0", "user", "use", "group/1"] {
+                store.put(key, key)?;
+            }
+            Ok(())
+        })?;
+        let users: Vec<(String, String)> = store.scan_prefix("user/")?;
+        assert_eq!(
+            users,
+            vec![
+                ("user/1".to_owned(), "user/1".to_owned()),
+                ("user/2".to_owned(), "user/2".to_owned())
+            ]
+        );
+        assert_eq!(store.scan_prefix::<String>("user")?.len(), 4);
+        assert_eq!(store.scan_prefix::<String>("")?.len(), 6);
+
+        // A failed batch is rolled back.
+        let err = store.batch(|store| {
+            store.delete("user/1")?;
+            store.get::<i32>("user/2")
+        });
+        assert!(err.is_err());
+        assert_eq!(store.get("user/1")?, Some("user/1".to_owned()));
+
+        let plan: String = db.query_row(
+            "EXPLAIN QUERY PLAN SELECT key, value FROM \"my kv\" WHERE key >= 'a' AND key < 'b'",
+            [],
+            |r| r.get(3),
+        )?;
+        assert!(plan.contains("PRIMARY KEY"), "{}", plan);
+        Ok(())
+    }
+}
@@ -52,6 +52,7 @@
 pub use libsqlite3_sys as ffi;
 
 use std::cell::RefCell;
+use std::cmp;
 use std::convert;
 use std::default::Default;
 use std::ffi::{CStr, CString};
@@ -63,69 +64,153 @@ use std::result;
 use std::str;
 use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::cache::StatementCache;
 use crate::inner_connection::{InnerConnection, BYPASS_SQLITE_INIT};
 use crate::raw_statement::RawStatement;
 use crate::types::ValueRef;
-
-pub use crate::cache::CachedStatement;
+use crate::util::SqlStr;
+
+pub use crate::analyze::{AnalyzeBudget, AnalyzeReport};
+#[cfg(feature = "arrow")]
+pub use crate::arrow::ArrowBatches;
+pub use crate::bulk::BatchProgress;
+pub use crate::busy_yield::{BusyBackoff, ExecuteYielding, NextYielding, Timer};
+pub use crate::cache::{CachedStatement, StatementCacheStats};
+pub use crate::cached_queries::{CachedQueries, CachedRows};
+pub use crate::capabilities::Capabilities;
 pub use crate::column::Column;
+#[cfg(feature = "column_metadata")]
+pub use crate::column::ColumnMetadata;
+pub use crate::columnar::ColumnValues;
 pub use crate::error::Error;
+pub use crate::executor::DatabaseExecutor;
 pub use crate::ffi::ErrorCode;
 #[cfg(feature = "hooks")]
 pub use crate::hooks::Action;
 #[cfg(feature = "load_extension")]
 pub use crate::load_extension_guard::LoadExtensionGuard;
+pub use crate::parameter_hints::ParameterHint;
 pub use crate::params::{params_from_iter, Params, ParamsFromIter};
-pub use crate::row::{AndThenRows, Map, MappedRows, Row, RowIndex, Rows};
+pub use crate::profile::OpenProfile;
+pub use crate::row::{AndThenRows, IntoValues, Map, MappedRows, Row, RowIndex, Rows};
+pub use crate::shutdown::ShutdownReport;
 pub use crate::statement::{Statement, StatementStatus};
-pub use crate::transaction::{DropBehavior, Savepoint, Transaction, TransactionBehavior};
+pub use crate::transaction::{
+    DropBehavior, ForeignKeyViolation, Savepoint, Transaction, TransactionBehavior,
+};
 pub use crate::types::ToSql;
+pub use crate::unit_of_work::{ScopeMetrics, UnitOfWork};
+pub use crate::verify::OpenRequirements;
 pub use crate::version::*;
+pub use crate::write_serializer::{WriteResult, WriteSerializer};
 
 #[macro_use]
 mod error;
 
+#[cfg(feature = "allocator")]
+pub mod allocator;
+mod analyze;
+#[cfg(feature = "arrow")]
+mod arrow;
+pub mod auto_extension;
 #[cfg(feature = "backup")]
 pub mod backup;
 #[cfg(feature = "blob")]
 pub mod blob;
+#[cfg(feature = "blob_store")]
+pub mod blob_store;
+mod bulk;
 mod busy;
+mod busy_yield;
 mod cache;
+mod cached_queries;
+mod capabilities;
+pub mod checksum;
 #[cfg(feature = "collation")]
 mod collation;
 mod column;
+#[cfg(feature = "column_encryption")]
+pub mod column_encryption;
+mod columnar;
 pub mod config;
 #[cfg(any(feature = "functions", feature = "vtab"))]
 mod context;
+pub mod copy;
+pub mod ddl;
+#[cfg(feature = "serde")]
+mod de;
+#[cfg(feature = "deterministic")]
+pub mod deterministic;
+pub mod diff;
+pub mod dump;
+#[cfg(feature = "execution_stats")]
+pub mod execution_stats;
+mod executor;
+pub mod export;
+#[cfg(feature = "extension_init")]
+pub mod extension;
 #[cfg(feature = "functions")]
 pub mod functions;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
 #[cfg(feature = "hooks")]
 mod hooks;
+#[cfg(feature = "incremental_backup")]
+pub mod incremental_backup;
 mod inner_connection;
+#[cfg(feature = "kv")]
+pub mod kv;
 #[cfg(feature = "limits")]
 pub mod limits;
 #[cfg(feature = "load_extension")]
 mod load_extension_guard;
+#[cfg(feature = "matviews")]
+pub mod matviews;
+mod parameter_hints;
 mod params;
+#[cfg(feature = "pcache")]
+pub mod pcache;
 mod pragma;
+mod profile;
 mod raw_statement;
+pub mod recording;
+#[cfg(feature = "session")]
+pub mod replication;
 mod row;
+#[cfg(feature = "row_policy")]
+pub mod row_policy;
+pub mod schema;
+#[cfg(feature = "search")]
+pub mod search;
+#[cfg(feature = "serialize")]
+pub mod serialize;
 #[cfg(feature = "session")]
 pub mod session;
+#[cfg(feature = "shards")]
+pub mod shards;
+mod shutdown;
+#[cfg(feature = "sqlar")]
+pub mod sqlar;
 mod statement;
+#[cfg(feature = "timeseries")]
+pub mod timeseries;
 #[cfg(feature = "trace")]
 pub mod trace;
 mod transaction;
 pub mod types;
+mod unit_of_work;
 mod unlock_notify;
+pub mod uri;
+mod verify;
 mod version;
 #[cfg(feature = "vtab")]
 pub mod vtab;
+mod write_serializer;
 
 pub(crate) mod util;
-pub(crate) use util::SmallCString;
 
 // Number of cached prepared statements we'll hold on to.
 const STATEMENT_CACHE_DEFAULT_CAPACITY: usize = 16;
@@ -235,8 +320,8 @@ unsafe fn errmsg_to_string(errmsg: *const c_char) -> String {
     String::from_utf8_lossy(c_slice).into_owned()
 }
 
-fn str_to_cstring(s: &str) -> Result<SmallCString> {
-    Ok(SmallCString::new(s)?)
+fn str_to_cstring(s: &str) -> Result<SqlStr> {
+    Ok(SqlStr::new(s)?)
 }
 
 /// Returns `Ok((string ptr, len as c_int, SQLITE_STATIC | SQLITE_TRANSIENT))`
@@ -310,9 +395,9 @@ pub const TEMP_DB: DatabaseName<'static> = DatabaseName::Temp;
 ))]
 impl DatabaseName<'_> {
     #[inline]
-    fn to_cstring(&self) -> Result<util::SmallCString> {
+    fn to_sql_str(self) -> Result<SqlStr> {
         use self::DatabaseName::{Attached, Main, Temp};
-        match *self {
+        match self {
             Main => str_to_cstring("main"),
             Temp => str_to_cstring("temp"),
             Attached(s) => str_to_cstring(s),
@@ -463,24 +548,16 @@ impl Connection {
     /// }
     /// ```
     ///
+    /// Use [`Batch`] instead if you need the number of rows changed by each
+    /// statement, or the position of the statement which failed.
+    ///
     /// # Failure
     ///
     /// Will return `Err` if `sql` cannot be converted to a C-compatible string
     /// or if the underlying SQLite call fails.
     pub fn execute_batch(&self, sql: &str) -> Result<()> {
-        let mut sql = sql;
-        while !sql.is_empty() {
-            let stmt = self.prepare(sql)?;
-            if !stmt.stmt.is_null() && stmt.step()? && cfg!(feature = "extra_check") {
-                // Some PRAGMA may return rows
-                return Err(Error::ExecuteReturnedResults);
-            }
-            let tail = stmt.stmt.tail();
-            if tail == 0 || tail >= sql.len() {
-                break;
-            }
-            sql = &sql[tail..];
-        }
+        let mut batch = Batch::new(self, sql);
+        while batch.execute_next()?.is_some() {}
         Ok(())
     }
 
@@ -557,6 +634,40 @@ impl Connection {
         })
     }
 
+    /// Convenience method to prepare and execute a single INSERT, UPDATE or
+    /// DELETE statement with a `RETURNING` clause, mapping `f` over the
+    /// returned rows.
+    ///
+    /// See [`Statement::execute_returning`] for details.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// # use rusqlite::{Connection, Result};
+    /// fn add_person(conn: &Connection, name: &str) -> Result<i64> {
+    ///     let ids = conn.execute_returning(
+    ///         "INSERT INTO people (name) VALUES (?) RETURNING id",
+    ///         [name],
+    ///         |row| row.get(0),
+    ///     )?;
+    ///     Ok(ids[0])
+    /// }
+    /// ```
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `sql` cannot be converted to a C-compatible string,
+    /// if the underlying SQLite call fails or if `f` fails.
+    pub fn execute_returning<T, P, F>(&self, sql: &str, params: P, f: F) -> Result<Vec<T>>
+    where
+        P: Params,
+        F: FnMut(&Row<'_>) -> Result<T>,
+    {
+        let mut stmt = self.prepare(sql)?;
+        stmt.check_no_tail()?;
+        stmt.execute_returning(params, f)
+    }
+
     /// Get the SQLite rowid of the most recent successful INSERT.
     ///
     /// Uses [sqlite3_last_insert_rowid](https://www.sqlite.org/c3ref/last_insert_rowid.html) under
@@ -696,7 +807,9 @@ impl Connection {
     ///
     /// # Failure
     ///
-    /// Will return `Err` if the underlying SQLite call fails.
+    /// Will return `Err` if the underlying SQLite call fails. If it fails with
+    /// `SQLITE_BUSY`, [`close_blockers`](Connection::close_blockers) tells what
+    /// is keeping the connection open.
     #[inline]
     pub fn close(self) -> Result<(), (Connection, Error)> {
         self.flush_prepared_statement_cache();
@@ -704,8 +817,62 @@ impl Connection {
         r.map_err(move |err| (self, err))
     }
 
+    /// Close the SQLite connection, retrying for up to `timeout` while it is
+    /// busy.
+    ///
+    /// Before each retry, any statement still running on the connection (e.g.
+    /// one stepped through the raw handle on another thread) is interrupted,
+    /// so that whoever owns it can finalize it.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite call fails with anything but
+    /// `SQLITE_BUSY`, or if the connection is still busy after `timeout`.
+    #[allow(clippy::result_large_err)] // Same error as `close`.
+    pub fn close_with_timeout(self, timeout: Duration) -> Result<(), (Connection, Error)> {
+        let deadline = Instant::now() + timeout;
+        let mut conn = self;
+        loop {
+            let (c, err) = match conn.close() {
+                Ok(()) => return Ok(()),
+                Err(e) => e,
+            };
+            let now = Instant::now();
+            let busy = matches!(
+                err,
+                Error::SqliteFailure(
+                    ffi::Error {
+                        code: ErrorCode::DatabaseBusy,
+                        ..
+                    },
+                    _
+                )
+            );
+            if !busy || now >= deadline {
+                return Err((c, err));
+            }
+            c.get_interrupt_handle().interrupt();
+            thread::sleep(cmp::min(deadline - now, Duration::from_millis(10)));
+            conn = c;
+        }
+    }
+
+    /// Return what currently prevents this connection from being closed.
+    ///
+    /// Statements in the prepared statement cache are not reported, as
+    /// closing finalizes them. Only unfinalized statements are reported:
+    /// SQLite has no way to list
+    /// open blobs or unfinished backups, and the ones created through
+    /// this crate borrow the connection, so they cannot outlive it.
+    pub fn close_blockers(&self) -> CloseBlockers {
+        CloseBlockers {
+            unfinalized_statements: self.db.borrow().unfinalized_statements(&self.cache),
+        }
+    }
+
     /// `feature = "load_extension"` Enable loading of SQLite extensions.
-    /// Strongly consider using `LoadExtensionGuard` instead of this function.
+    /// This also enables the `load_extension()` SQL function: strongly
+    /// consider using `LoadExtensionGuard` instead of this function.
     ///
     /// ## Example
     ///
@@ -833,6 +1000,14 @@ impl Connection {
         self.db.borrow_mut().changes()
     }
 
+    /// Return the total number of rows modified, inserted or deleted by all
+    /// completed INSERT, UPDATE or DELETE statements since the database
+    /// connection was opened.
+    #[inline]
+    fn total_changes(&self) -> usize {
+        self.db.borrow_mut().total_changes()
+    }
+
     /// Test for auto-commit mode.
     /// Autocommit mode is on by default.
     #[inline]
@@ -846,6 +1021,26 @@ impl Connection {
     pub fn is_busy(&self) -> bool {
         self.db.borrow().is_busy()
     }
+
+    /// Return the SQL text of the prepared statements which have been stepped
+    /// but not reset, i.e. whose [`Rows`] are still alive.
+    ///
+    /// Together with [`is_autocommit`](Connection::is_autocommit), this lets a
+    /// connection pool check that a connection handed back to it has neither
+    /// an open transaction nor an unfinished statement:
+    ///
+    /// ```rust,no_run
+    /// # use rusqlite::Connection;
+    /// fn check_idle(conn: &Connection) {
+    ///     assert!(conn.is_autocommit(), "transaction left open");
+    ///     let busy = conn.busy_statements();
+    ///     assert!(busy.is_empty(), "unfinished statements: {:?}", busy);
+    /// }
+    /// ```
+    #[cfg(feature = "modern_sqlite")] // 3.8.6
+    pub fn busy_statements(&self) -> Vec<String> {
+        self.db.borrow().busy_statements()
+    }
 }
 
 impl fmt::Debug for Connection {
@@ -873,17 +1068,41 @@ impl fmt::Debug for Connection {
 ///     Ok(())
 /// }
 /// ```
+///
+/// Statements can also be executed by the batch itself, which reports the
+/// number of rows changed by each one and, on failure, where in the script
+/// the failing statement starts:
+/// ```rust
+/// use rusqlite::{Batch, Connection, Result};
+///
+/// fn main() -> Result<()> {
+///     let conn = Connection::open_in_memory()?;
+///     let sql = "CREATE TABLE tbl (col); INSERT INTO tbl VALUES (1), (2); BOGUS;";
+///     let mut batch = Batch::new(&conn, sql);
+///     assert_eq!(batch.execute_next()?, Some(0));
+///     assert_eq!(batch.execute_next()?, Some(2));
+///     assert!(batch.execute_next().is_err());
+///     assert_eq!(batch.offset(), sql.find("BOGUS").unwrap());
+///     Ok(())
+/// }
+/// ```
 #[derive(Debug)]
 pub struct Batch<'conn, 'sql> {
     conn: &'conn Connection,
     sql: &'sql str,
     tail: usize,
+    offset: usize,
 }
 
 impl<'conn, 'sql> Batch<'conn, 'sql> {
     /// Constructor
     pub fn new(conn: &'conn Connection, sql: &'sql str) -> Batch<'conn, 'sql> {
-        Batch { conn, sql, tail: 0 }
+        Batch {
+            conn,
+            sql,
+            tail: 0,
+            offset: 0,
+        }
     }
 
     /// Iterates on each batch statements.
@@ -893,6 +1112,7 @@ impl<'conn, 'sql> Batch<'conn, 'sql> {
     pub fn next(&mut self) -> Result<Option<Statement<'conn>>> {
         while self.tail < self.sql.len() {
             let sql = &self.sql[self.tail..];
+            self.offset = self.tail + (sql.len() - sql.trim_start().len());
             let next = self.conn.prepare(sql)?;
             let tail = next.stmt.tail();
             if tail == 0 {
@@ -907,6 +1127,47 @@ impl<'conn, 'sql> Batch<'conn, 'sql> {
         }
         Ok(None)
     }
+
+    /// Prepares and executes the next statement of the batch.
+    ///
+    /// Returns the number of rows that were changed or inserted or deleted by
+    /// the statement (0 for statements other than INSERT, UPDATE or DELETE),
+    /// or `Ok(None)` when batch is completed.
+    ///
+    /// As with [`Connection::execute_batch`], a statement which returns rows
+    /// is only stepped once.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the statement cannot be prepared or if the
+    /// underlying SQLite call fails. [`offset`](Batch::offset) can then be
+    /// used to locate the failing statement.
+    pub fn execute_next(&mut self) -> Result<Option<usize>> {
+        let stmt = match self.next()? {
+            Some(stmt) => stmt,
+            None => return Ok(None),
+        };
+        let total_changes = self.conn.total_changes();
+        if stmt.step()? && cfg!(feature = "extra_check") {
+            // Some PRAGMA may return rows
+            return Err(Error::ExecuteReturnedResults);
+        }
+        if self.conn.total_changes() == total_changes {
+            Ok(Some(0))
+        } else {
+            Ok(Some(self.conn.changes()))
+        }
+    }
+
+    /// Byte offset in the batch SQL of the statement most recently returned
+    /// by [`next`](Batch::next) or executed by
+    /// [`execute_next`](Batch::execute_next), including one which failed.
+    ///
+    /// Leading whitespace is skipped, but leading comments are not.
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
 }
 
 impl<'conn> Iterator for Batch<'conn, '_> {
@@ -1003,6 +1264,25 @@ pub struct InterruptHandle {
 unsafe impl Send for InterruptHandle {}
 unsafe impl Sync for InterruptHandle {}
 
+/// What prevents a connection from being closed, as returned by
+/// [`Connection::close_blockers`].
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct CloseBlockers {
+    /// The SQL of each prepared statement which has not been finalized. This
+    /// includes statements prepared through the raw handle, or whose
+    /// [`Statement`] was leaked.
+    pub unfinalized_statements: Vec<String>,
+}
+
+impl CloseBlockers {
+    /// Returns `true` if nothing prevents the connection from being closed.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.unfinalized_statements.is_empty()
+    }
+}
+
 impl InterruptHandle {
     /// Interrupt the query currently executing on another thread. This will
     /// cause that query to fail with a `SQLITE3_INTERRUPT` error.
@@ -1016,7 +1296,7 @@ impl InterruptHandle {
 
 #[cfg(feature = "modern_sqlite")] // 3.7.10
 unsafe fn db_filename(db: *mut ffi::sqlite3) -> Option<PathBuf> {
-    let db_name = DatabaseName::Main.to_cstring().unwrap();
+    let db_name = DatabaseName::Main.to_sql_str().unwrap();
     let db_filename = ffi::sqlite3_db_filename(db, db_name.as_ptr());
     if db_filename.is_null() {
         None
@@ -1222,6 +1502,55 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_close_blockers() -> Result<()> {
+        let db = checked_memory_handle();
+        db.prepare_cached("SELECT 1")?;
+        assert!(db.close_blockers().is_empty());
+        std::mem::forget(db.prepare("SELECT 42")?);
+        assert_eq!(
+            db.close_blockers().unfinalized_statements,
+            vec!["SELECT 42"]
+        );
+
+        let start = Instant::now();
+        let (db, err) = db
+            .close_with_timeout(Duration::from_millis(50))
+            .unwrap_err();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        match err {
+            Error::SqliteFailure(err, _) => assert_eq!(err.code, ErrorCode::DatabaseBusy),
+            err => panic!("Unexpected error {}", err),
+        }
+
+        unsafe {
+            let stmt = ffi::sqlite3_next_stmt(db.handle(), std::ptr::null_mut());
+            assert_eq!(ffi::SQLITE_OK, ffi::sqlite3_finalize(stmt));
+        }
+        assert!(db.close_blockers().is_empty());
+        db.close_with_timeout(Duration::from_secs(1)).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "statement_tracking")]
+    fn test_close_reports_unfinalized_statements() -> Result<()> {
+        let db = checked_memory_handle();
+        std::mem::forget(db.prepare("SELECT 42")?);
+
+        let (db, err) = db.close().unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("unfinalized statement `SELECT 42` prepared at:"));
+        assert!(msg.contains("test_close_reports_unfinalized_statements"));
+
+        unsafe {
+            let stmt = ffi::sqlite3_next_stmt(db.handle(), std::ptr::null_mut());
+            assert_eq!(ffi::SQLITE_OK, ffi::sqlite3_finalize(stmt));
+        }
+        db.close().unwrap();
+        Ok(())
+    }
+
     #[test]
     fn test_open_with_flags() {
         for bad_flags in &[
@@ -1503,6 +1832,23 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    #[cfg(feature = "modern_sqlite")]
+    fn test_busy_statements() -> Result<()> {
+        let db = checked_memory_handle();
+        let mut idle = db.prepare("SELECT 1")?;
+        let mut stmt = db.prepare("PRAGMA schema_version")?;
+        assert!(db.busy_statements().is_empty());
+        idle.query_row([], |_| Ok(()))?;
+        {
+            let mut rows = stmt.query([])?;
+            rows.next()?;
+            assert_eq!(db.busy_statements(), vec!["PRAGMA schema_version"]);
+        }
+        assert!(db.busy_statements().is_empty());
+        Ok(())
+    }
+
     #[test]
     fn test_statement_debugging() -> Result<()> {
         let db = checked_memory_handle();
@@ -1907,6 +2253,33 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_batch_execute_next() -> Result<()> {
+        let db = checked_memory_handle();
+        let sql = r"
+             CREATE TABLE tbl (col);
+             INSERT INTO tbl VALUES (1), (2), (3);
+             -- comment
+             UPDATE tbl SET col = 0 WHERE col > 1;
+             CREATE INDEX idx ON tbl (col);
+             INSERT INTO missing VALUES (1);
+             ";
+        let mut batch = Batch::new(&db, sql);
+        assert_eq!(Some(0), batch.execute_next()?);
+        assert_eq!(sql.find("CREATE TABLE").unwrap(), batch.offset());
+        assert_eq!(Some(3), batch.execute_next()?);
+        assert_eq!(Some(2), batch.execute_next()?);
+        assert_eq!(sql.find("-- comment").unwrap(), batch.offset());
+        assert_eq!(Some(0), batch.execute_next()?);
+        assert!(batch.execute_next().is_err());
+        assert_eq!(sql.find("INSERT INTO missing").unwrap(), batch.offset());
+
+        let mut batch = Batch::new(&db, "DELETE FROM tbl;  ");
+        assert_eq!(Some(3), batch.execute_next()?);
+        assert_eq!(None, batch.execute_next()?);
+        Ok(())
+    }
+
     #[test]
     fn test_batch() -> Result<()> {
         let db = checked_memory_handle();
@@ -1,8 +1,13 @@
+use crate::config::DbConfig;
 use crate::{Connection, Result};
 
 /// `feature = "load_extension"` RAII guard temporarily enabling SQLite
 /// extensions to be loaded.
 ///
+/// Only [`Connection::load_extension`] is enabled: the `load_extension()`
+/// SQL function stays disabled, so that SQL run while the guard is alive
+/// cannot load extensions.
+///
 /// ## Example
 ///
 /// ```rust,no_run
@@ -16,16 +21,18 @@ use crate::{Connection, Result};
 /// ```
 pub struct LoadExtensionGuard<'conn> {
     conn: &'conn Connection,
+    was_enabled: bool,
 }
 
 impl LoadExtensionGuard<'_> {
-    /// Attempt to enable loading extensions. Loading extensions will be
-    /// disabled when this guard goes out of scope. Cannot be meaningfully
-    /// nested.
+    /// Attempt to enable loading extensions through the C API. Loading
+    /// extensions will be disabled again when this guard goes out of scope,
+    /// unless it was already enabled.
     #[inline]
     pub fn new(conn: &Connection) -> Result<LoadExtensionGuard<'_>> {
-        conn.load_extension_enable()
-            .map(|_| LoadExtensionGuard { conn })
+        let was_enabled = conn.db_config(DbConfig::SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION)?;
+        conn.set_db_config(DbConfig::SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION, true)
+            .map(|_| LoadExtensionGuard { conn, was_enabled })
     }
 }
 
@@ -33,6 +40,62 @@ impl LoadExtensionGuard<'_> {
 impl Drop for LoadExtensionGuard<'_> {
     #[inline]
     fn drop(&mut self) {
-        self.conn.load_extension_disable();
+        if !self.was_enabled {
+            self.conn
+                .set_db_config(DbConfig::SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LoadExtensionGuard;
+    use crate::config::DbConfig;
+    use crate::{Connection, Result};
+
+    fn is_enabled(db: &Connection) -> Result<bool> {
+        db.db_config(DbConfig::SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION)
+    }
+
+    fn disabled() -> Result<Connection> {
+        let db = Connection::open_in_memory()?;
+        // Builds with `SQLITE_ENABLE_LOAD_EXTENSION` enable it by default.
+        db.set_db_config(DbConfig::SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION, false)?;
+        Ok(db)
+    }
+
+    #[test]
+    fn test_guard_enables_c_api_only() -> Result<()> {
+        let db = disabled()?;
+        {
+            let _guard = LoadExtensionGuard::new(&db)?;
+            assert!(is_enabled(&db)?);
+            let err = db
+                .query_row("SELECT load_extension('missing')", [], |r| {
+                    r.get::<_, Option<String>>(0)
+                })
+                .unwrap_err();
+            assert!(err.to_string().contains("not authorized"), "{}", err);
+            let err = db.load_extension("missing", None).unwrap_err();
+            assert!(!err.to_string().contains("not authorized"), "{}", err);
+        }
+        assert!(!is_enabled(&db)?);
+        assert!(db
+            .load_extension("missing", None)
+            .unwrap_err()
+            .to_string()
+            .contains("not authorized"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_guards() -> Result<()> {
+        let db = disabled()?;
+        let outer = LoadExtensionGuard::new(&db)?;
+        drop(LoadExtensionGuard::new(&db)?);
+        assert!(is_enabled(&db)?);
+        drop(outer);
+        assert!(!is_enabled(&db)?);
+        Ok(())
     }
 }
@@ -0,0 +1,473 @@
+//! `feature = "matviews"` Materialized views: tables holding the result of a
+//! query, refreshed on demand.
+//!
+//! A [`MaterializedView`] is a real table, created from its query (or from
+//! its own `CREATE TABLE` statement, e.g. to give it a primary key), and the
+//! query refreshing it. [`MaterializedViews`] tracks when each view was last
+//! refreshed, and whether it is stale:
+//! * if its sources have an "updated at" column, when a row of a source has
+//!   been updated after the latest one seen by the last refresh (deleted
+//!   rows are not seen);
+//! * otherwise, when anything has been written to the database since the
+//!   last refresh, by this connection (other than by refreshing views) or by
+//!   another one (see
+//!   [`PRAGMA data_version`](https://sqlite.org/pragma.html#pragma_data_version)).
+//!
+//! A view is refreshed in full, by running its query again, or, if it has a
+//! delta query and sources, incrementally, by upserting (`INSERT OR
+//! REPLACE`) the rows of the delta query for the rows of its sources updated
+//! since the last refresh.
+//!
+//! Views are read-only by convention: changes written to them are lost on
+//! the next full refresh.
+//!
+//! ```rust,no_run
+//! use rusqlite::matviews::{MaterializedView, MaterializedViews};
+//! use rusqlite::{Connection, Result};
+//!
+//! fn sample_counts(conn: &Connection) -> Result<()> {
+//!     let mut views = MaterializedViews::new(conn)?;
+//!     views.define(
+//!         MaterializedView::new(
+//!             "sample_counts",
+//!             "SELECT project, count(*) AS n FROM samples GROUP BY project",
+//!         )
+//!         .table("CREATE TABLE sample_counts (project TEXT PRIMARY KEY, n INTEGER)")
+//!         .source("samples", "updated_at")
+//!         .incremental(
+//!             "SELECT project, count(*) FROM samples WHERE project IN \
+//!              (SELECT project FROM samples WHERE updated_at > :since) GROUP BY project",
+//!         ),
+//!     )?;
+//!     views.refresh("sample_counts")?;
+//!     Ok(())
+//! }
+//! ```
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use crate::types::Value;
+use crate::util::quote_identifier;
+use crate::{
+    ffi, named_params, params, Connection, DatabaseName, Error, OptionalExtension, Result,
+    Savepoint,
+};
+
+const METADATA_TABLE: &str = "_rusqlite_matviews";
+
+/// `feature = "matviews"` The definition of a materialized view.
+#[derive(Clone, Debug)]
+pub struct MaterializedView {
+    name: String,
+    query: String,
+    table: Option<String>,
+    // (table, "updated at" column)
+    sources: Vec<(String, String)>,
+    delta: Option<String>,
+}
+
+impl MaterializedView {
+    /// A view named `name`, holding the rows of `query`.
+    pub fn new(name: &str, query: &str) -> MaterializedView {
+        MaterializedView {
+            name: name.to_owned(),
+            query: query.to_owned(),
+            table: None,
+            sources: Vec::new(),
+            delta: None,
+        }
+    }
+
+    /// Creates the table of the view with the `CREATE TABLE` statement
+    /// `sql`, rather than from the columns of its query. Incremental
+    /// refreshes need a primary key or unique constraint to replace rows.
+    #[must_use]
+    pub fn table(mut self, sql: &str) -> Self {
+        self.table = Some(sql.to_owned());
+        self
+    }
+
+    /// Adds the table `table` to the sources of the view, with the column
+    /// `updated_at` holding when its rows were last inserted or updated
+    /// (in any unit, as long as later updates have larger values).
+    #[must_use]
+    pub fn source(mut self, table: &str, updated_at: &str) -> Self {
+        self.sources.push((table.to_owned(), updated_at.to_owned()));
+        self
+    }
+
+    /// Refreshes the view incrementally, when it has sources, with the rows
+    /// of `query` for the rows of the sources updated after its `:since`
+    /// parameter. Rows of the view with the same key are replaced.
+    #[must_use]
+    pub fn incremental(mut self, query: &str) -> Self {
+        self.delta = Some(query.to_owned());
+        self
+    }
+}
+
+/// `feature = "matviews"` How a view was refreshed by
+/// [`MaterializedViews::refresh`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Refresh {
+    /// The view wasn't stale.
+    Fresh,
+    /// The rows of the delta query were upserted.
+    Incremental,
+    /// The query was run again.
+    Full,
+}
+
+/// `feature = "matviews"` The materialized views of a connection.
+#[derive(Debug)]
+pub struct MaterializedViews<'conn> {
+    conn: &'conn Connection,
+    views: HashMap<String, MaterializedView>,
+    // PRAGMA data_version and the changes of the connection, not counting
+    // those of the refreshes, after the last refresh of views without
+    // sources.
+    versions: RefCell<HashMap<String, (i64, usize)>>,
+    // The changes made by the refreshes, so that refreshing a view doesn't
+    // make the others stale.
+    own_changes: Cell<usize>,
+}
+
+impl<'conn> MaterializedViews<'conn> {
+    /// Manages the materialized views of `conn`, creating the table
+    /// recording their refreshes if needed.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the table cannot be created.
+    pub fn new(conn: &'conn Connection) -> Result<MaterializedViews<'conn>> {
+        conn.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (name TEXT PRIMARY KEY, watermark, refreshed INTEGER NOT NULL)",
+            METADATA_TABLE
+        ))?;
+        Ok(MaterializedViews {
+            conn,
+            views: HashMap::new(),
+            versions: RefCell::new(HashMap::new()),
+            own_changes: Cell::new(0),
+        })
+    }
+
+    /// Defines `view`, creating its (empty, and stale) table if it doesn't
+    /// exist. A view defined again, e.g. by another run of the program, keeps
+    /// its table and its last refresh.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the table cannot be created.
+    pub fn define(&mut self, view: MaterializedView) -> Result<()> {
+        if !self.table_exists(&view.name)? {
+            match view.table {
+                Some(ref sql) => self.conn.execute_batch(sql)?,
+                None => self.conn.execute_batch(&format!(
+                    "CREATE TABLE {} AS SELECT * FROM ({}) LIMIT 0",
+                    quote_identifier(&view.name),
+                    view.query
+                ))?,
+            }
+            self.untracked(|| {
+                self.conn.execute(
+                    &format!("DELETE FROM {} WHERE name = ?", METADATA_TABLE),
+                    [&view.name],
+                )
+            })?;
+        }
+        self.views.insert(view.name.clone(), view);
+        Ok(())
+    }
+
+    /// Returns whether the view `name` is stale: it has never been
+    /// refreshed, or its sources changed since.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `name` is not defined, or the sources cannot be
+    /// read.
+    pub fn is_stale(&self, name: &str) -> Result<bool> {
+        let view = self.view(name)?;
+        let watermark = match self.last_refresh(name)? {
+            Some(watermark) => watermark,
+            None => return Ok(true),
+        };
+        if view.sources.is_empty() {
+            let version = self.version()?;
+            return Ok(self.versions.borrow().get(name) != Some(&version));
+        }
+        let current = self.watermark(view)?;
+        self.conn.query_row(
+            "SELECT ?1 IS NOT NULL AND (?2 IS NULL OR ?1 > ?2)",
+            params![current, watermark],
+            |row| row.get(0),
+        )
+    }
+
+    /// Refreshes the view `name` if it is stale, incrementally if possible,
+    /// and returns how.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `name` is not defined, or a query fails; the
+    /// view is left as it was then.
+    pub fn refresh(&self, name: &str) -> Result<Refresh> {
+        if !self.is_stale(name)? {
+            return Ok(Refresh::Fresh);
+        }
+        let view = self.view(name)?;
+        match (&view.delta, self.last_refresh(name)?) {
+            (Some(delta), Some(since)) if !view.sources.is_empty() && since != Value::Null => {
+                self.untracked(|| {
+                    let sp = Savepoint::with_depth_and_name(self.conn, 0, "_rusqlite_matview")?;
+                    let watermark = self.watermark(view)?;
+                    self.conn
+                        .prepare(&format!(
+                            "INSERT OR REPLACE INTO {} {}",
+                            quote_identifier(&view.name),
+                            delta
+                        ))?
+                        .execute(named_params! {":since": since})?;
+                    self.record(view, watermark)?;
+                    sp.commit()
+                })?;
+                Ok(Refresh::Incremental)
+            }
+            _ => {
+                self.refresh_full(name)?;
+                Ok(Refresh::Full)
+            }
+        }
+    }
+
+    /// Refreshes the view `name` by running its query again, stale or not.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `name` is not defined, or the query fails; the
+    /// view is left as it was then.
+    pub fn refresh_full(&self, name: &str) -> Result<()> {
+        let view = self.view(name)?;
+        self.untracked(|| {
+            let sp = Savepoint::with_depth_and_name(self.conn, 0, "_rusqlite_matview")?;
+            let watermark = self.watermark(view)?;
+            self.conn.execute_batch(&format!(
+                "DELETE FROM {table}; INSERT INTO {table} {query};",
+                table = quote_identifier(&view.name),
+                query = view.query
+            ))?;
+            self.record(view, watermark)?;
+            sp.commit()
+        })?;
+        if view.sources.is_empty() {
+            let version = self.version()?;
+            self.versions
+                .borrow_mut()
+                .insert(view.name.clone(), version);
+        }
+        Ok(())
+    }
+
+    /// Refreshes the stale views, and returns their number.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` on the first view failing to refresh; the views
+    /// before it stay refreshed.
+    pub fn refresh_all(&self) -> Result<usize> {
+        let mut names: Vec<&String> = self.views.keys().collect();
+        names.sort();
+        let mut refreshed = 0;
+        for name in names {
+            if self.refresh(name)? != Refresh::Fresh {
+                refreshed += 1;
+            }
+        }
+        Ok(refreshed)
+    }
+
+    fn view(&self, name: &str) -> Result<&MaterializedView> {
+        self.views.get(name).ok_or_else(|| {
+            Error::SqliteFailure(
+                ffi::Error::new(ffi::SQLITE_MISUSE),
+                Some(format!("no materialized view named {}", name)),
+            )
+        })
+    }
+
+    fn table_exists(&self, table: &str) -> Result<bool> {
+        self.conn.query_row(
+            "SELECT count(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = ?",
+            [table],
+            |row| row.get(0),
+        )
+    }
+
+    // The watermark recorded by the last refresh of `name`, if any.
+    fn last_refresh(&self, name: &str) -> Result<Option<Value>> {
+        self.conn
+            .query_row(
+                &format!("SELECT watermark FROM {} WHERE name = ?", METADATA_TABLE),
+                [name],
+                |row| row.get(0),
+            )
+            .optional()
+    }
+
+    // The latest "updated at" value of the sources of `view`.
+    fn watermark(&self, view: &MaterializedView) -> Result<Value> {
+        if view.sources.is_empty() {
+            return Ok(Value::Null);
+        }
+        let maxima: Vec<String> = view
+            .sources
+            .iter()
+            .map(|(table, column)| {
+                format!(
+                    "SELECT max({}) AS m FROM {}",
+                    quote_identifier(column),
+                    quote_identifier(table)
+                )
+            })
+            .collect();
+        self.conn.query_row(
+            &format!("SELECT max(m) FROM ({})", maxima.join(" UNION ALL ")),
+            [],
+            |row| row.get(0),
+        )
+    }
+
+    fn version(&self) -> Result<(i64, usize)> {
+        let data_version =
+            self.conn
+                .pragma_query_value(Some(DatabaseName::Main), "data_version", |row| row.get(0))?;
+        Ok((
+            data_version,
+            self.conn.total_changes() - self.own_changes.get(),
+        ))
+    }
+
+    // Runs `f`, adding the changes it makes, even if it fails, to those of
+    // the refreshes.
+    fn untracked<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let before = self.conn.total_changes();
+        let result = f();
+        self.own_changes
+            .set(self.own_changes.get() + self.conn.total_changes() - before);
+        result
+    }
+
+    fn record(&self, view: &MaterializedView, watermark: Value) -> Result<()> {
+        self.conn
+            .execute(
+                &format!(
+                    "INSERT OR REPLACE INTO {} (name, watermark, refreshed) VALUES (?, ?, strftime('%s', 'now'))",
+                    METADATA_TABLE
+                ),
+                params![view.name, watermark],
+            )
+            .map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MaterializedView, MaterializedViews, Refresh};
+    use crate::{Connection, Result};
+
+    fn counts(db: &Connection) -> Result<Vec<(String, i64)>> {
+        let mut stmt = db.prepare("SELECT project, n FROM counts ORDER BY project")?;
+        let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+        rows.collect()
+    }
+
+    fn setup() -> Result<Connection> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE samples (id INTEGER PRIMARY KEY, project TEXT, updated_at INTEGER);
+             INSERT INTO samples VALUES (1, 'a', 1), (2, 'a', 1), (3, 'b', 2);",
+        )?;
+        Ok(db)
+    }
+
+    const QUERY: &str = "SELECT project, count(*) AS n FROM samples GROUP BY project";
+
+    #[test]
+    fn test_incremental() -> Result<()> {
+        let db = setup()?;
+        let mut views = MaterializedViews::new(&db)?;
+        views.define(
+            MaterializedView::new("counts", QUERY)
+                .table("CREATE TABLE counts (project TEXT PRIMARY KEY, n INTEGER)")
+                .source("samples", "updated_at")
+                .incremental(
+                    "SELECT project, count(*) FROM samples WHERE project IN \
+                     (SELECT project FROM samples WHERE updated_at > :since) GROUP BY project",
+                ),
+        )?;
+        assert!(views.is_stale("counts")?);
+        assert!(counts(&db)?.is_empty());
+        assert_eq!(views.refresh("counts")?, Refresh::Full);
+        assert_eq!(counts(&db)?, vec![("a".into(), 2), ("b".into(), 1)]);
+        assert!(!views.is_stale("counts")?);
+        assert_eq!(views.refresh("counts")?, Refresh::Fresh);
+
+        db.execute_batch("INSERT INTO samples VALUES (4, 'b', 3), (5, 'c', 3)")?;
+        assert!(views.is_stale("counts")?);
+        // Only b and c are recounted.
+        db.execute_batch("UPDATE counts SET n = 0 WHERE project = 'a'")?;
+        assert_eq!(views.refresh_all()?, 1);
+        assert_eq!(
+            counts(&db)?,
+            vec![("a".into(), 0), ("b".into(), 2), ("c".into(), 1)]
+        );
+
+        // Defined again, by another run.
+        let mut views = MaterializedViews::new(&db)?;
+        views.define(MaterializedView::new("counts", QUERY).source("samples", "updated_at"))?;
+        assert!(!views.is_stale("counts")?);
+        views.refresh_full("counts")?;
+        assert_eq!(
+            counts(&db)?,
+            vec![("a".into(), 2), ("b".into(), 2), ("c".into(), 1)]
+        );
+        assert!(views.refresh("missing").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_data_version() -> Result<()> {
+        let db = setup()?;
+        let mut views = MaterializedViews::new(&db)?;
+        views.define(MaterializedView::new("counts", QUERY))?;
+        assert_eq!(views.refresh("counts")?, Refresh::Full);
+        assert_eq!(counts(&db)?, vec![("a".into(), 2), ("b".into(), 1)]);
+        assert_eq!(views.refresh("counts")?, Refresh::Fresh);
+        db.execute_batch("DELETE FROM samples WHERE project = 'b'")?;
+        assert!(views.is_stale("counts")?);
+        assert_eq!(views.refresh("counts")?, Refresh::Full);
+        assert_eq!(counts(&db)?, vec![("a".into(), 2)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_refresh_all_converges() -> Result<()> {
+        let db = setup()?;
+        let mut views = MaterializedViews::new(&db)?;
+        views.define(MaterializedView::new("counts", QUERY))?;
+        views.define(MaterializedView::new(
+            "projects",
+            "SELECT DISTINCT project FROM samples",
+        ))?;
+        assert_eq!(views.refresh_all()?, 2);
+        // Refreshing one view doesn't make the other stale.
+        assert_eq!(views.refresh_all()?, 0);
+        db.execute_batch("INSERT INTO samples VALUES (4, 'c', 3)")?;
+        assert_eq!(views.refresh_all()?, 2);
+        assert_eq!(views.refresh_all()?, 0);
+        Ok(())
+    }
+}
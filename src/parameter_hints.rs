@@ -0,0 +1,562 @@
+use std::collections::HashMap;
+
+use crate::types::Type;
+use crate::{Result, Statement};
+
+/// The likely type of a parameter of a statement, as returned by
+/// [`Statement::parameter_hints`].
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct ParameterHint {
+    /// The name of the parameter, including its prefix (e.g. `:id`), if it is
+    /// named.
+    pub name: Option<String>,
+    /// The table and column the parameter is compared to, or assigned to.
+    pub column: Option<(String, String)>,
+    /// The declared type of the column, or the type the parameter is `CAST`
+    /// to.
+    pub decl_type: Option<String>,
+    /// The type of values for the column's affinity, unless it is `NUMERIC`
+    /// (which takes integers or reals) or unknown.
+    pub value_type: Option<Type>,
+}
+
+impl Statement<'_> {
+    /// Infers the likely type of each parameter of the statement, from the
+    /// declared type of the column it is compared to (`col = ?`,
+    /// `? < col`, `col IN (?, ?)`, `col BETWEEN ? AND ?`, `col LIKE ?`), or
+    /// assigned to (`SET col = ?`, `INSERT INTO t (col) VALUES (?)`), or from
+    /// a `CAST(? AS type)`. The hint of the parameter `?NNN` is at index
+    /// `NNN - 1`.
+    ///
+    /// This is a best effort, based on the tokens of the SQL rather than on
+    /// its syntax tree: parameters in other contexts get an empty hint.
+    ///
+    /// ```rust,no_run
+    /// # use rusqlite::{types::Type, Connection, Result};
+    /// fn hints(conn: &Connection) -> Result<Vec<Option<Type>>> {
+    ///     let stmt = conn.prepare("SELECT * FROM samples WHERE id = ? AND name LIKE ?")?;
+    ///     let hints = stmt.parameter_hints()?;
+    ///     Ok(hints.into_iter().map(|hint| hint.value_type).collect())
+    /// }
+    /// ```
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the schema of a table cannot be read.
+    pub fn parameter_hints(&self) -> Result<Vec<ParameterHint>> {
+        let count = self.parameter_count();
+        let mut hints: Vec<ParameterHint> = (1..=count)
+            .map(|i| ParameterHint {
+                name: self.stmt.bind_parameter_name(i).map(str::to_owned),
+                column: None,
+                decl_type: None,
+                value_type: None,
+            })
+            .collect();
+        let sql = match self.stmt.sql() {
+            Some(sql) => String::from_utf8_lossy(sql.to_bytes()).into_owned(),
+            None => return Ok(hints),
+        };
+        let tokens = tokenize(&sql);
+        let mut schema = Schema {
+            stmt: self,
+            tables: HashMap::new(),
+        };
+        let aliases = schema.aliases(&tokens)?;
+
+        let mut next = 1;
+        for (p, token) in tokens.iter().enumerate() {
+            let index = match token {
+                Token::Param(name) => {
+                    let index = if name == "?" {
+                        next
+                    } else if let Some(number) = name.strip_prefix('?') {
+                        number.parse().unwrap_or(0)
+                    } else {
+                        self.stmt.bind_parameter_index(name).unwrap_or(0)
+                    };
+                    next = next.max(index + 1);
+                    index
+                }
+                _ => continue,
+            };
+            if index == 0 || index > count || hints[index - 1].decl_type.is_some() {
+                continue;
+            }
+            let hint = &mut hints[index - 1];
+            if let Some(decl_type) = cast_type(&tokens, p) {
+                hint.value_type = affinity(&decl_type);
+                hint.decl_type = Some(decl_type);
+                continue;
+            }
+            let target = match compared_column(&tokens, p).or_else(|| inserted_column(&tokens, p)) {
+                Some(target) => target,
+                None => continue,
+            };
+            if let Some((table, column, decl_type)) = schema.resolve(&aliases, target)? {
+                hint.value_type = affinity(&decl_type);
+                hint.column = Some((table, column));
+                hint.decl_type = Some(decl_type).filter(|t| !t.is_empty());
+            }
+        }
+        Ok(hints)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    // An identifier or keyword, and whether it is quoted.
+    Word(String, bool),
+    Param(String),
+    Op(String),
+    Literal,
+}
+
+impl Token {
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self, Token::Word(w, false) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn is_op(&self, op: &str) -> bool {
+        matches!(self, Token::Op(o) if o == op)
+    }
+
+    fn word(&self) -> Option<&str> {
+        match self {
+            Token::Word(w, _) => Some(w),
+            _ => None,
+        }
+    }
+}
+
+fn tokenize(sql: &str) -> Vec<Token> {
+    let bytes = sql.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        let start = i;
+        i += 1;
+        match b {
+            b if b.is_ascii_whitespace() => {}
+            b'-' if bytes.get(i) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i) == Some(&b'*') => {
+                i = sql[i..].find("*/").map_or(bytes.len(), |end| i + end + 2);
+            }
+            b'\'' | b'"' | b'`' | b'[' => {
+                let close = if b == b'[' { b']' } else { b };
+                let mut text = Vec::new();
+                while i < bytes.len() {
+                    if bytes[i] == close {
+                        // Doubled quotes stand for one.
+                        if close != b']' && bytes.get(i + 1) == Some(&close) {
+                            i += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    text.push(bytes[i]);
+                    i += 1;
+                }
+                i += 1;
+                tokens.push(if b == b'\'' {
+                    Token::Literal
+                } else {
+                    Token::Word(String::from_utf8_lossy(&text).into_owned(), true)
+                });
+            }
+            b'?' | b':' | b'@' | b'$' => {
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                tokens.push(Token::Param(sql[start..i].to_owned()));
+            }
+            b if b.is_ascii_digit()
+                || (b == b'.' && matches!(bytes.get(i), Some(b) if b.is_ascii_digit())) =>
+            {
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'.') {
+                    i += 1;
+                }
+                tokens.push(Token::Literal);
+            }
+            b if b.is_ascii_alphabetic() || b == b'_' || b >= 0x80 => {
+                while i < bytes.len()
+                    && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_' || bytes[i] >= 0x80)
+                {
+                    i += 1;
+                }
+                tokens.push(Token::Word(sql[start..i].to_owned(), false));
+            }
+            _ => {
+                if let Some(op) = ["==", "!=", "<>", "<=", ">=", "||", "<<", ">>"]
+                    .iter()
+                    .find(|op| bytes[start..].starts_with(op.as_bytes()))
+                {
+                    i = start + op.len();
+                }
+                tokens.push(Token::Op(sql[start..i].to_owned()));
+            }
+        }
+    }
+    tokens
+}
+
+// A possibly qualified column name, ending at `end` (inclusive).
+fn column_at(tokens: &[Token], end: usize) -> Option<(Option<&str>, &str)> {
+    let column = tokens.get(end)?.word()?;
+    if end >= 2 && tokens[end - 1].is_op(".") {
+        if let Some(qualifier) = tokens[end - 2].word() {
+            return Some((Some(qualifier), column));
+        }
+    }
+    Some((None, column))
+}
+
+fn is_comparison(token: &Token) -> bool {
+    match token {
+        Token::Op(op) => ["=", "==", "!=", "<>", "<", "<=", ">", ">="].contains(&op.as_str()),
+        _ => ["LIKE", "GLOB", "REGEXP", "MATCH", "IS"]
+            .iter()
+            .any(|k| token.is_keyword(k)),
+    }
+}
+
+// The column compared to the parameter at `p`.
+fn compared_column(tokens: &[Token], p: usize) -> Option<(Option<&str>, &str)> {
+    let mut before = p.checked_sub(1)?;
+    // `IS NOT ?`, `NOT LIKE ?`
+    if tokens[before].is_keyword("NOT") {
+        before = before.checked_sub(1)?;
+    }
+    if is_comparison(&tokens[before]) {
+        let mut end = before.checked_sub(1)?;
+        if tokens[end].is_keyword("NOT") {
+            end = end.checked_sub(1)?;
+        }
+        return column_at(tokens, end);
+    }
+    // `col BETWEEN ? AND ?`
+    if tokens[before].is_keyword("AND") && before >= 2 && tokens[before - 2].is_keyword("BETWEEN") {
+        before -= 2;
+    }
+    if tokens[before].is_keyword("BETWEEN") {
+        let mut end = before.checked_sub(1)?;
+        if tokens[end].is_keyword("NOT") {
+            end = end.checked_sub(1)?;
+        }
+        return column_at(tokens, end);
+    }
+    // `col IN (?, ?)`
+    let mut open = before;
+    while open >= 2 && tokens[open].is_op(",") && matches!(tokens[open - 1], Token::Param(_)) {
+        open -= 2;
+    }
+    if tokens[open].is_op("(") && open >= 1 && tokens[open - 1].is_keyword("IN") {
+        let mut end = open.checked_sub(2)?;
+        if tokens[end].is_keyword("NOT") {
+            end = end.checked_sub(1)?;
+        }
+        return column_at(tokens, end);
+    }
+    // `? = col`
+    if matches!(tokens.get(p + 1), Some(t) if is_comparison(t)) {
+        let mut start = p + 2;
+        if matches!(tokens.get(start), Some(t) if t.is_keyword("NOT")) {
+            start += 1;
+        }
+        let end = if matches!(tokens.get(start + 1), Some(t) if t.is_op(".")) {
+            start + 2
+        } else {
+            start
+        };
+        return column_at(tokens, end);
+    }
+    None
+}
+
+// The column of `INSERT INTO t (col, ...) VALUES (..., ?, ...)` the parameter
+// at `p` is inserted into.
+fn inserted_column(tokens: &[Token], p: usize) -> Option<(Option<&str>, &str)> {
+    // The position of the parameter in its tuple.
+    let mut depth = 0;
+    let mut position = 0;
+    let mut open = p;
+    loop {
+        open = open.checked_sub(1)?;
+        match &tokens[open] {
+            t if t.is_op(")") => depth += 1,
+            t if t.is_op("(") && depth > 0 => depth -= 1,
+            t if t.is_op("(") => break,
+            t if t.is_op(",") && depth == 0 => position += 1,
+            _ => {}
+        }
+    }
+    // Skip the previous tuples.
+    let mut values = open.checked_sub(1)?;
+    while tokens[values].is_op(",") {
+        let mut depth = 0;
+        loop {
+            values = values.checked_sub(1)?;
+            match &tokens[values] {
+                t if t.is_op(")") => depth += 1,
+                t if t.is_op("(") => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        values = values.checked_sub(1)?;
+    }
+    if !tokens[values].is_keyword("VALUES") || !tokens[values.checked_sub(1)?].is_op(")") {
+        return None;
+    }
+    // The column list.
+    let close = values - 1;
+    let open = (0..close).rev().find(|&i| tokens[i].is_op("("))?;
+    let column = tokens[open + 1..close]
+        .split(|t| t.is_op(","))
+        .nth(position)?
+        .first()?
+        .word()?;
+    let table = column_at(tokens, open.checked_sub(1)?)?.1;
+    Some((Some(table), column))
+}
+
+// The type in `CAST(? AS type)`.
+fn cast_type(tokens: &[Token], p: usize) -> Option<String> {
+    if p < 2 || !tokens[p - 1].is_op("(") || !tokens[p - 2].is_keyword("CAST") {
+        return None;
+    }
+    if !tokens.get(p + 1)?.is_keyword("AS") {
+        return None;
+    }
+    let words: Vec<&str> = tokens[p + 2..].iter().map_while(Token::word).collect();
+    if words.is_empty() {
+        None
+    } else {
+        Some(words.join(" "))
+    }
+}
+
+struct Schema<'a, 'conn> {
+    stmt: &'a Statement<'conn>,
+    // The tables, by lowercase name.
+    tables: HashMap<String, Option<Table>>,
+}
+
+// The name of a table, and the names and declared types of its columns.
+type Table = (String, Vec<(String, String)>);
+
+impl Schema<'_, '_> {
+    fn table(&mut self, name: &str) -> Result<Option<&Table>> {
+        let key = name.to_lowercase();
+        if !self.tables.contains_key(&key) {
+            let mut columns = Vec::new();
+            self.stmt.conn.pragma(None, "table_info", &name, |row| {
+                columns.push((
+                    row.get(1)?,
+                    row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                ));
+                Ok(())
+            })?;
+            let table = if columns.is_empty() {
+                None
+            } else {
+                Some((name.to_owned(), columns))
+            };
+            self.tables.insert(key.clone(), table);
+        }
+        Ok(self.tables[&key].as_ref())
+    }
+
+    // The tables of the statement, by lowercase name or alias, in order.
+    fn aliases(&mut self, tokens: &[Token]) -> Result<Vec<(String, String)>> {
+        const NOT_ALIASES: &[&str] = &[
+            "CROSS",
+            "DEFAULT",
+            "EXCEPT",
+            "FULL",
+            "GROUP",
+            "HAVING",
+            "INDEXED",
+            "INNER",
+            "INTERSECT",
+            "JOIN",
+            "LEFT",
+            "LIMIT",
+            "NATURAL",
+            "NOT",
+            "ON",
+            "ORDER",
+            "OUTER",
+            "RETURNING",
+            "RIGHT",
+            "SELECT",
+            "SET",
+            "UNION",
+            "USING",
+            "VALUES",
+            "WHERE",
+            "WINDOW",
+        ];
+        let mut aliases = Vec::new();
+        for (i, token) in tokens.iter().enumerate() {
+            let name = match token.word() {
+                Some(name) if !matches!(tokens.get(i + 1), Some(t) if t.is_op(".")) => name,
+                _ => continue,
+            };
+            let table = match self.table(name)? {
+                Some((table, _)) => table.clone(),
+                None => continue,
+            };
+            aliases.push((table.to_lowercase(), table.clone()));
+            let alias = match (tokens.get(i + 1), tokens.get(i + 2)) {
+                (Some(t), Some(Token::Word(alias, _))) if t.is_keyword("AS") => alias,
+                (Some(Token::Word(alias, quoted)), _)
+                    if *quoted || !NOT_ALIASES.iter().any(|k| alias.eq_ignore_ascii_case(k)) =>
+                {
+                    alias
+                }
+                _ => continue,
+            };
+            aliases.push((alias.to_lowercase(), table));
+        }
+        Ok(aliases)
+    }
+
+    // The table, column and declared type of a column of the statement.
+    fn resolve(
+        &mut self,
+        aliases: &[(String, String)],
+        (qualifier, column): (Option<&str>, &str),
+    ) -> Result<Option<(String, String, String)>> {
+        let candidates: Vec<String> = match qualifier {
+            Some(q) => {
+                let q = q.to_lowercase();
+                aliases
+                    .iter()
+                    .filter(|(alias, _)| *alias == q)
+                    .map(|(_, table)| table.clone())
+                    .take(1)
+                    .collect()
+            }
+            None => aliases.iter().map(|(_, table)| table.clone()).collect(),
+        };
+        for table in candidates {
+            if let Some((table, columns)) = self.table(&table)? {
+                if let Some((name, decl_type)) =
+                    columns.iter().find(|(c, _)| c.eq_ignore_ascii_case(column))
+                {
+                    return Ok(Some((table.clone(), name.clone(), decl_type.clone())));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+// The type of values for the affinity of a declared type, unless it is
+// NUMERIC.
+fn affinity(decl_type: &str) -> Option<Type> {
+    let decl_type = decl_type.to_ascii_uppercase();
+    if decl_type.contains("INT") {
+        Some(Type::Integer)
+    } else if decl_type.contains("CHAR") || decl_type.contains("CLOB") || decl_type.contains("TEXT")
+    {
+        Some(Type::Text)
+    } else if decl_type.contains("BLOB") {
+        Some(Type::Blob)
+    } else if decl_type.contains("REAL") || decl_type.contains("FLOA") || decl_type.contains("DOUB")
+    {
+        Some(Type::Real)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::Type;
+    use crate::{Connection, Result};
+
+    fn hints(db: &Connection, sql: &str) -> Result<Vec<(Option<String>, Option<Type>)>> {
+        let stmt = db.prepare(sql)?;
+        Ok(stmt
+            .parameter_hints()?
+            .into_iter()
+            .map(|h| (h.column.map(|(t, c)| format!("{}.{}", t, c)), h.value_type))
+            .collect())
+    }
+
+    fn column(name: &str, value_type: Type) -> (Option<String>, Option<Type>) {
+        (Some(name.to_owned()), Some(value_type))
+    }
+
+    #[test]
+    fn test_parameter_hints() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE samples (id INTEGER PRIMARY KEY, name TEXT, score DOUBLE, data, \"the date\" DATE);
+             CREATE TABLE variants (sample_id INTEGER, pos INT, ref VARCHAR(1), qual REAL);",
+        )?;
+        let id = column("samples.id", Type::Integer);
+        let name = column("samples.name", Type::Text);
+        let score = column("samples.score", Type::Real);
+        assert_eq!(
+            hints(
+                &db,
+                "SELECT * FROM samples WHERE id = ? AND ? < score AND name NOT LIKE ?"
+            )?,
+            vec![id.clone(), score.clone(), name.clone()]
+        );
+        assert_eq!(
+            hints(
+                &db,
+                "SELECT * FROM samples s JOIN variants AS v ON v.sample_id = s.id
+                 WHERE v.pos BETWEEN :start AND :end AND s.id IN (?5, ?) AND v.ref = 'A'"
+            )?,
+            vec![
+                column("variants.pos", Type::Integer),
+                column("variants.pos", Type::Integer),
+                (None, None),
+                (None, None),
+                id.clone(),
+                id.clone(),
+            ]
+        );
+        assert_eq!(
+            hints(
+                &db,
+                "INSERT INTO samples (name, \"the date\", data) VALUES (?, ?, ?), (lower(?), ?, 0)"
+            )?,
+            vec![
+                name.clone(),
+                (Some("samples.the date".to_owned()), None),
+                (Some("samples.data".to_owned()), None),
+                (None, None),
+                (Some("samples.the date".to_owned()), None),
+            ]
+        );
+        assert_eq!(
+            hints(
+                &db,
+                "UPDATE samples SET score = ?, name = name || ? WHERE id = CAST(? AS INTEGER)"
+            )?,
+            vec![score, (None, None), (None, Some(Type::Integer))]
+        );
+        let stmt = db.prepare("SELECT * FROM samples WHERE \"the date\" > :since")?;
+        let hint = &stmt.parameter_hints()?[0];
+        assert_eq!(hint.name.as_deref(), Some(":since"));
+        assert_eq!(hint.decl_type.as_deref(), Some("DATE"));
+        assert_eq!(hint.value_type, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,377 @@
+//! `feature = "pcache"` Plug a Rust [page cache](https://sqlite.org/c3ref/pcache_methods2.html)
+//! implementation into SQLite.
+//!
+//! SQLite creates one [`PageCache`] per open database file, through the
+//! [`PageCacheFactory`] given to [`config_page_cache`]. The cache owns
+//! [`Page`]s, each identified by a key (the page number), and hands them out
+//! to SQLite from [`PageCache::fetch`].
+//!
+//! ```rust,no_run
+//! use std::collections::HashMap;
+//! use rusqlite::pcache::{self, CreateFlag, Page, PageCache, PageCacheFactory};
+//! # use rusqlite::Result;
+//!
+//! struct Unbounded {
+//!     page_size: usize,
+//!     extra_size: usize,
+//!     pages: HashMap<u32, Page>,
+//! }
+//!
+//! unsafe impl PageCache for Unbounded {
+//!     fn page_count(&self) -> usize {
+//!         self.pages.len()
+//!     }
+//!
+//!     fn fetch(&mut self, key: u32, create: CreateFlag) -> Option<&Page> {
+//!         if create == CreateFlag::Never && !self.pages.contains_key(&key) {
+//!             return None;
+//!         }
+//!         let (page_size, extra_size) = (self.page_size, self.extra_size);
+//!         Some(self.pages.entry(key).or_insert_with(|| Page::new(page_size, extra_size)))
+//!     }
+//!
+//!     fn unpin(&mut self, key: u32, discard: bool) {
+//!         if discard {
+//!             self.pages.remove(&key);
+//!         }
+//!     }
+//!
+//!     fn rekey(&mut self, old_key: u32, new_key: u32) {
+//!         let page = self.pages.remove(&old_key).unwrap();
+//!         self.pages.insert(new_key, page);
+//!     }
+//!
+//!     fn truncate(&mut self, limit: u32) {
+//!         self.pages.retain(|&key, _| key < limit);
+//!     }
+//! }
+//!
+//! struct Factory;
+//!
+//! impl PageCacheFactory for Factory {
+//!     fn create(&self, page_size: usize, extra_size: usize, _: bool) -> Box<dyn PageCache> {
+//!         Box::new(Unbounded { page_size, extra_size, pages: HashMap::new() })
+//!     }
+//! }
+//!
+//! fn main() -> Result<()> {
+//!     // Before any other use of SQLite:
+//!     unsafe { pcache::config_page_cache(Factory) }?;
+//!     let conn = rusqlite::Connection::open_in_memory()?;
+//!     Ok(())
+//! }
+//! ```
+
+use std::alloc::{self, Layout};
+use std::mem;
+use std::os::raw::{c_int, c_uint, c_void};
+use std::ptr::{self, NonNull};
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::error::error_from_sqlite_code;
+use crate::{ffi, Error, Result};
+
+/// `feature = "pcache"` Creates the page caches of SQLite.
+pub trait PageCacheFactory: Send + Sync + 'static {
+    /// Creates the cache of a database file, whose pages are made of
+    /// `page_size` bytes for SQLite's data, plus `extra_size` bytes of extra
+    /// storage (see [`Page::new`]).
+    ///
+    /// A cache which is not `purgeable` (used for in-memory databases) must
+    /// never discard an unpinned page on its own: it is the only copy.
+    fn create(&self, page_size: usize, extra_size: usize, purgeable: bool) -> Box<dyn PageCache>;
+}
+
+/// `feature = "pcache"` The page cache of one database file.
+///
+/// A page is pinned from the time it is returned by
+/// [`fetch`](PageCache::fetch) until [`unpin`](PageCache::unpin) is called
+/// for it.
+///
+/// # Safety
+///
+/// SQLite keeps using the memory of the pages it fetched, so implementations
+/// must ensure that:
+///
+/// * a pinned page is neither dropped nor replaced, and so stays at the same
+///   address, until it is unpinned by [`unpin`](PageCache::unpin) or
+///   [`truncate`](PageCache::truncate);
+/// * [`fetch`](PageCache::fetch) returns the same [`Page`] for a key for as
+///   long as that page is in the cache, and a page is only ever held under one
+///   key.
+pub unsafe trait PageCache: Send {
+    /// Sets the suggested maximum number of pages, which may be ignored.
+    fn set_cache_size(&mut self, _pages: usize) {}
+
+    /// Returns the number of pages in the cache, pinned or not.
+    fn page_count(&self) -> usize;
+
+    /// Returns the page with the given key, pinning it. If there is none,
+    /// creates a page according to `create`, or returns `None`.
+    ///
+    /// An existing page must be returned with its content intact. A new page
+    /// must either come from [`Page::new`], or be an unpinned page of the
+    /// cache on which [`Page::reset`] was called.
+    fn fetch(&mut self, key: u32, create: CreateFlag) -> Option<&Page>;
+
+    /// Unpins the page with the given key. If `discard` is `true`, the page
+    /// must be removed from the cache; otherwise it may be kept for later
+    /// fetches, or removed at the discretion of the cache.
+    fn unpin(&mut self, key: u32, discard: bool);
+
+    /// Changes the key of a page. Any other page with key `new_key` is
+    /// discarded.
+    fn rekey(&mut self, old_key: u32, new_key: u32);
+
+    /// Discards all pages with a key greater than or equal to `limit`. Any of
+    /// them which is pinned is to be unpinned implicitly.
+    fn truncate(&mut self, limit: u32);
+
+    /// Releases as much memory as possible, e.g. by dropping unpinned pages.
+    fn shrink(&mut self) {}
+}
+
+/// `feature = "pcache"` How hard [`PageCache::fetch`] should try to create a
+/// missing page.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CreateFlag {
+    /// Do not create a page.
+    Never,
+    /// Create a page if it is easy to do, e.g. without exceeding the cache
+    /// size; otherwise return `None`.
+    IfEasy,
+    /// Create a page, unless memory is exhausted.
+    Always,
+}
+
+#[repr(C)]
+struct PageHeader {
+    // Must be first: SQLite sees a pointer to it.
+    page: ffi::sqlite3_pcache_page,
+    key: c_uint,
+}
+
+/// `feature = "pcache"` A page of a [`PageCache`].
+///
+/// Its memory stays at the same address for as long as the `Page` is alive,
+/// even if the `Page` itself is moved.
+pub struct Page {
+    header: NonNull<PageHeader>,
+    layout: Layout,
+}
+
+unsafe impl Send for Page {}
+
+impl Page {
+    /// Allocates a page of `page_size` bytes plus `extra_size` bytes of extra
+    /// storage, with the sizes given to [`PageCacheFactory::create`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if allocating the page fails.
+    pub fn new(page_size: usize, extra_size: usize) -> Page {
+        let size = mem::size_of::<PageHeader>() + page_size + extra_size;
+        let layout = Layout::from_size_align(size, mem::align_of::<PageHeader>()).unwrap();
+        unsafe {
+            let base = alloc::alloc_zeroed(layout);
+            let header = match NonNull::new(base as *mut PageHeader) {
+                Some(header) => header,
+                None => alloc::handle_alloc_error(layout),
+            };
+            let buf = base.add(mem::size_of::<PageHeader>());
+            ptr::write(
+                header.as_ptr(),
+                PageHeader {
+                    page: ffi::sqlite3_pcache_page {
+                        pBuf: buf as *mut c_void,
+                        pExtra: buf.add(page_size) as *mut c_void,
+                    },
+                    key: 0,
+                },
+            );
+            Page { header, layout }
+        }
+    }
+
+    /// Clears the extra storage of the page, so that it can be returned by
+    /// [`PageCache::fetch`] for another key.
+    pub fn reset(&mut self) {
+        let offset = self.extra_offset();
+        unsafe {
+            let header = self.header.as_ref();
+            ptr::write_bytes(
+                header.page.pExtra as *mut u8,
+                0,
+                self.layout.size() - offset,
+            );
+        }
+    }
+
+    /// Returns the total number of bytes allocated for the page.
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.layout.size()
+    }
+
+    fn extra_offset(&self) -> usize {
+        unsafe {
+            let header = self.header.as_ref();
+            header.page.pExtra as usize - self.header.as_ptr() as usize
+        }
+    }
+}
+
+impl Drop for Page {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.header.as_ptr() as *mut u8, self.layout) }
+    }
+}
+
+type Factory = Box<dyn PageCacheFactory>;
+
+// Set once by `config_page_cache`, and never freed: SQLite may use it until
+// the process exits.
+static FACTORY: AtomicPtr<Factory> = AtomicPtr::new(ptr::null_mut());
+
+/// `feature = "pcache"` Make SQLite use page caches created by `factory`,
+/// with `SQLITE_CONFIG_PCACHE2`.
+///
+/// This can only be done once per process, before SQLite is initialized, i.e.
+/// before the first connection is opened.
+///
+/// # Failure
+///
+/// Will return `Err` if a page cache has already been configured, or with
+/// `SQLITE_MISUSE` if SQLite is already initialized.
+///
+/// # Safety
+///
+/// This function is not threadsafe. No other SQLite calls may be made while
+/// `config_page_cache` is running, and multiple threads may not call it
+/// simultaneously.
+pub unsafe fn config_page_cache<F: PageCacheFactory>(factory: F) -> Result<()> {
+    let boxed: *mut Factory = Box::into_raw(Box::new(Box::new(factory)));
+    if FACTORY
+        .compare_exchange(ptr::null_mut(), boxed, Ordering::AcqRel, Ordering::Acquire)
+        .is_err()
+    {
+        drop(Box::from_raw(boxed));
+        return Err(Error::SqliteFailure(
+            ffi::Error::new(ffi::SQLITE_MISUSE),
+            Some("SQLite page cache already configured".to_owned()),
+        ));
+    }
+
+    let methods = ffi::sqlite3_pcache_methods2 {
+        iVersion: 1,
+        pArg: ptr::null_mut(),
+        xInit: Some(x_init),
+        xShutdown: Some(x_shutdown),
+        xCreate: Some(x_create),
+        xCachesize: Some(x_cachesize),
+        xPagecount: Some(x_pagecount),
+        xFetch: Some(x_fetch),
+        xUnpin: Some(x_unpin),
+        xRekey: Some(x_rekey),
+        xTruncate: Some(x_truncate),
+        xDestroy: Some(x_destroy),
+        xShrink: Some(x_shrink),
+    };
+    // SQLite copies `methods`.
+    let rc = ffi::sqlite3_config(ffi::SQLITE_CONFIG_PCACHE2, &methods);
+    if rc == ffi::SQLITE_OK {
+        Ok(())
+    } else {
+        // SQLite will never call us, so a later attempt can be made.
+        FACTORY.store(ptr::null_mut(), Ordering::Release);
+        drop(Box::from_raw(boxed));
+        Err(error_from_sqlite_code(rc, None))
+    }
+}
+
+// What SQLite sees as a `sqlite3_pcache`.
+type Cache = Box<dyn PageCache>;
+
+#[inline]
+unsafe fn cache<'a>(p: *mut ffi::sqlite3_pcache) -> &'a mut Cache {
+    &mut *(p as *mut Cache)
+}
+
+#[inline]
+unsafe fn page_key(p: *mut ffi::sqlite3_pcache_page) -> c_uint {
+    (*(p as *mut PageHeader)).key
+}
+
+extern "C" fn x_init(_: *mut c_void) -> c_int {
+    ffi::SQLITE_OK
+}
+
+extern "C" fn x_shutdown(_: *mut c_void) {}
+
+unsafe extern "C" fn x_create(
+    page_size: c_int,
+    extra_size: c_int,
+    purgeable: c_int,
+) -> *mut ffi::sqlite3_pcache {
+    let factory = &*FACTORY.load(Ordering::Acquire);
+    let cache = factory.create(page_size as usize, extra_size as usize, purgeable != 0);
+    Box::into_raw(Box::new(cache)) as *mut ffi::sqlite3_pcache
+}
+
+unsafe extern "C" fn x_cachesize(p: *mut ffi::sqlite3_pcache, pages: c_int) {
+    cache(p).set_cache_size(pages.max(0) as usize);
+}
+
+unsafe extern "C" fn x_pagecount(p: *mut ffi::sqlite3_pcache) -> c_int {
+    cache(p).page_count() as c_int
+}
+
+unsafe extern "C" fn x_fetch(
+    p: *mut ffi::sqlite3_pcache,
+    key: c_uint,
+    create: c_int,
+) -> *mut ffi::sqlite3_pcache_page {
+    let create = match create {
+        0 => CreateFlag::Never,
+        1 => CreateFlag::IfEasy,
+        _ => CreateFlag::Always,
+    };
+    match cache(p).fetch(key, create) {
+        Some(page) => {
+            let header = page.header.as_ptr();
+            (*header).key = key;
+            header as *mut ffi::sqlite3_pcache_page
+        }
+        None => ptr::null_mut(),
+    }
+}
+
+unsafe extern "C" fn x_unpin(
+    p: *mut ffi::sqlite3_pcache,
+    page: *mut ffi::sqlite3_pcache_page,
+    discard: c_int,
+) {
+    cache(p).unpin(page_key(page), discard != 0);
+}
+
+unsafe extern "C" fn x_rekey(
+    p: *mut ffi::sqlite3_pcache,
+    page: *mut ffi::sqlite3_pcache_page,
+    old_key: c_uint,
+    new_key: c_uint,
+) {
+    cache(p).rekey(old_key, new_key);
+    (*(page as *mut PageHeader)).key = new_key;
+}
+
+unsafe extern "C" fn x_truncate(p: *mut ffi::sqlite3_pcache, limit: c_uint) {
+    cache(p).truncate(limit);
+}
+
+unsafe extern "C" fn x_destroy(p: *mut ffi::sqlite3_pcache) {
+    drop(Box::from_raw(p as *mut Cache));
+}
+
+unsafe extern "C" fn x_shrink(p: *mut ffi::sqlite3_pcache) {
+    cache(p).shrink();
+}
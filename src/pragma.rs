@@ -1,11 +1,12 @@
 //! Pragma helpers
 
+use std::ffi::CStr;
 use std::ops::Deref;
 
 use crate::error::Error;
 use crate::ffi;
 use crate::types::{ToSql, ToSqlOutput, ValueRef};
-use crate::{Connection, DatabaseName, Result, Row};
+use crate::{Connection, DatabaseName, Result, Row, SqlStr};
 
 pub struct Sql {
     buf: String,
@@ -24,7 +25,7 @@ impl Sql {
         self.push_keyword("PRAGMA")?;
         self.push_space();
         if let Some(schema_name) = schema_name {
-            self.push_schema_name(schema_name);
+            self.push_schema_name(schema_name)?;
             self.push_dot();
         }
         self.push_keyword(pragma_name)
@@ -42,19 +43,21 @@ impl Sql {
         }
     }
 
-    pub fn push_schema_name(&mut self, schema_name: DatabaseName<'_>) {
+    pub fn push_schema_name(&mut self, schema_name: DatabaseName<'_>) -> Result<()> {
         match schema_name {
             DatabaseName::Main => self.buf.push_str("main"),
             DatabaseName::Temp => self.buf.push_str("temp"),
-            DatabaseName::Attached(s) => self.push_identifier(s),
+            DatabaseName::Attached(s) => self.push_identifier(s)?,
         };
+        Ok(())
     }
 
-    pub fn push_identifier(&mut self, s: &str) {
+    pub fn push_identifier(&mut self, s: &str) -> Result<()> {
         if is_identifier(s) {
             self.buf.push_str(s);
+            Ok(())
         } else {
-            self.wrap_and_escape(s, '"');
+            self.push_formatted(IDENTIFIER_FORMAT, s)
         }
     }
 
@@ -77,6 +80,13 @@ impl Sql {
                     Some(format!("Unsupported value \"{:?}\"", value)),
                 ));
             }
+            #[cfg(feature = "modern_sqlite")]
+            ToSqlOutput::Pointer(_) => {
+                return Err(Error::SqliteFailure(
+                    ffi::Error::new(ffi::SQLITE_MISUSE),
+                    Some(format!("Unsupported value \"{:?}\"", value)),
+                ));
+            }
         };
         match value {
             ValueRef::Integer(i) => {
@@ -87,7 +97,7 @@ impl Sql {
             }
             ValueRef::Text(s) => {
                 let s = std::str::from_utf8(s)?;
-                self.push_string_literal(s);
+                self.push_string_literal(s)?;
             }
             _ => {
                 return Err(Error::SqliteFailure(
@@ -99,8 +109,8 @@ impl Sql {
         Ok(())
     }
 
-    pub fn push_string_literal(&mut self, s: &str) {
-        self.wrap_and_escape(s, '\'');
+    pub fn push_string_literal(&mut self, s: &str) -> Result<()> {
+        self.push_formatted(STRING_LITERAL_FORMAT, s)
     }
 
     pub fn push_int(&mut self, i: i64) {
@@ -135,20 +145,33 @@ impl Sql {
         &self.buf
     }
 
-    fn wrap_and_escape(&mut self, s: &str, quote: char) {
-        self.buf.push(quote);
-        let chars = s.chars();
-        for ch in chars {
-            // escape `quote` by doubling it
-            if ch == quote {
-                self.buf.push(ch);
+    // Quote `s` with SQLite's own printf, so that escaping is exactly what
+    // SQLite would do itself. (`sqlite3_str_appendf` would save a copy, but
+    // requires SQLite 3.24.)
+    fn push_formatted(&mut self, format: &CStr, s: &str) -> Result<()> {
+        let s = SqlStr::new(s)?;
+        unsafe {
+            let quoted = ffi::sqlite3_mprintf(format.as_ptr(), s.as_cstr().as_ptr());
+            if quoted.is_null() {
+                return Err(Error::SqliteFailure(
+                    ffi::Error::new(ffi::SQLITE_NOMEM),
+                    None,
+                ));
             }
-            self.buf.push(ch)
+            // The input is UTF-8 and only ASCII quotes are added.
+            let res = CStr::from_ptr(quoted)
+                .to_str()
+                .map(|q| self.buf.push_str(q));
+            ffi::sqlite3_free(quoted.cast());
+            res.map_err(Error::from)
         }
-        self.buf.push(quote);
     }
 }
 
+// `%w` doubles double quotes and `%q` doubles single quotes.
+const IDENTIFIER_FORMAT: &CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"\"%w\"\0") };
+const STRING_LITERAL_FORMAT: &CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"'%q'\0") };
+
 impl Deref for Sql {
     type Target = str;
 
@@ -281,6 +304,38 @@ impl Connection {
         sql.push_value(pragma_value)?;
         self.query_row(&sql, [], f)
     }
+
+    /// Query the `application_id` of the main database: a number which
+    /// identifies the file format of an application, `0` by default.
+    ///
+    /// It is stored at offset 68 of the database header, and is shown by
+    /// `file(1)` if registered in its magic file.
+    pub fn application_id(&self) -> Result<u32> {
+        let id: i32 = self.pragma_query_value(None, "application_id", |row| row.get(0))?;
+        Ok(id as u32)
+    }
+
+    /// Set the `application_id` of the main database.
+    ///
+    /// The whole range of `u32` is accepted; the value is stored as the
+    /// signed 32-bit integer with the same bits.
+    pub fn set_application_id(&self, id: u32) -> Result<()> {
+        self.pragma_update(None, "application_id", &(id as i32))
+    }
+
+    /// Query the `user_version` of the main database: a number free for use
+    /// by applications, typically the version of their schema, `0` by
+    /// default.
+    ///
+    /// Unlike `schema_version`, it is not changed by SQLite.
+    pub fn user_version(&self) -> Result<i32> {
+        self.pragma_query_value(None, "user_version", |row| row.get(0))
+    }
+
+    /// Set the `user_version` of the main database.
+    pub fn set_user_version(&self, version: i32) -> Result<()> {
+        self.pragma_update(None, "user_version", &version)
+    }
 }
 
 fn is_identifier(s: &str) -> bool {
@@ -405,6 +460,20 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn application_id_and_user_version() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        assert_eq!(0, db.application_id()?);
+        assert_eq!(0, db.user_version()?);
+        db.set_application_id(0xffff_fffe)?;
+        db.set_user_version(-3)?;
+        assert_eq!(0xffff_fffe, db.application_id()?);
+        assert_eq!(-3, db.user_version()?);
+        let id: i64 = db.pragma_query_value(None, "application_id", |row| row.get(0))?;
+        assert_eq!(-2, id);
+        Ok(())
+    }
+
     #[test]
     fn is_identifier() {
         assert!(pragma::is_identifier("full"));
@@ -416,17 +485,27 @@ mod test {
     #[test]
     fn double_quote() {
         let mut sql = Sql::new();
-        sql.push_schema_name(DatabaseName::Attached(r#"schema";--"#));
+        sql.push_schema_name(DatabaseName::Attached(r#"schema";--"#))
+            .unwrap();
         assert_eq!(r#""schema"";--""#, sql.as_str());
     }
 
     #[test]
     fn wrap_and_escape() {
         let mut sql = Sql::new();
-        sql.push_string_literal("value'; --");
+        sql.push_string_literal("value'; --").unwrap();
         assert_eq!("'value''; --'", sql.as_str());
     }
 
+    #[test]
+    fn escape_non_ascii() {
+        let mut sql = Sql::new();
+        sql.push_string_literal("\u{1F600} '%s'").unwrap();
+        sql.push_identifier(r#"a "b""#).unwrap();
+        assert_eq!(r#"'😀 ''%s'''"a ""b""""#, sql.as_str());
+        sql.push_string_literal("nul\0").unwrap_err();
+    }
+
     #[test]
     fn locking_mode() -> Result<()> {
         let db = Connection::open_in_memory()?;
@@ -0,0 +1,145 @@
+use std::path::Path;
+
+use crate::{Connection, OpenFlags, Result};
+
+/// A vetted combination of settings applied to a connection when it is
+/// opened with [`Connection::open_with_profile`], instead of a block of
+/// pragmas copied from project to project.
+///
+/// No profile turns `synchronous` off or disables the journal, which can
+/// corrupt the database on a crash.
+///
+/// ```rust,no_run
+/// # use rusqlite::{Connection, OpenProfile, Result};
+/// fn open_ledger() -> Result<Connection> {
+///     Connection::open_with_profile("ledger.db", OpenProfile::Durable)
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OpenProfile {
+    /// No committed transaction is lost, even on power failure: WAL journal
+    /// with `synchronous = FULL`, and foreign keys enforced.
+    Durable,
+    /// Faster writes, at the cost of losing the last transactions on power
+    /// failure, but never corrupting the database: WAL journal with
+    /// `synchronous = NORMAL`, foreign keys enforced, a 64 MiB cache, 256 MiB
+    /// of memory-mapped I/O, and temporary tables in memory.
+    Fast,
+    /// Read-only connection for large queries: opened read-only, with
+    /// `query_only`, a 256 MiB cache, 1 GiB of memory-mapped I/O, and
+    /// temporary tables in memory. The journal mode is left to the writers.
+    ReadOnlyAnalytics,
+}
+
+impl OpenProfile {
+    /// Returns the flags the database is opened with.
+    pub fn flags(self) -> OpenFlags {
+        match self {
+            OpenProfile::Durable | OpenProfile::Fast => OpenFlags::default(),
+            OpenProfile::ReadOnlyAnalytics => {
+                OpenFlags::SQLITE_OPEN_READ_ONLY
+                    | OpenFlags::SQLITE_OPEN_URI
+                    | OpenFlags::SQLITE_OPEN_NO_MUTEX
+            }
+        }
+    }
+
+    /// Returns the pragmas applied, in order, as name and value.
+    pub fn pragmas(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            OpenProfile::Durable => &[
+                ("journal_mode", "WAL"),
+                ("synchronous", "FULL"),
+                ("foreign_keys", "ON"),
+            ],
+            OpenProfile::Fast => &[
+                ("journal_mode", "WAL"),
+                ("synchronous", "NORMAL"),
+                ("foreign_keys", "ON"),
+                ("cache_size", "-65536"),
+                ("mmap_size", "268435456"),
+                ("temp_store", "MEMORY"),
+            ],
+            OpenProfile::ReadOnlyAnalytics => &[
+                ("query_only", "ON"),
+                ("cache_size", "-262144"),
+                ("mmap_size", "1073741824"),
+                ("temp_store", "MEMORY"),
+            ],
+        }
+    }
+}
+
+impl Connection {
+    /// Opens the database at `path` with the flags of `profile`, and applies
+    /// its pragmas.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the database cannot be opened, or if a pragma
+    /// fails, e.g. because the database is locked while switching to WAL.
+    pub fn open_with_profile<P: AsRef<Path>>(path: P, profile: OpenProfile) -> Result<Connection> {
+        let conn = Connection::open_with_flags(path, profile.flags())?;
+        conn.apply_profile(profile)?;
+        Ok(conn)
+    }
+
+    /// Applies the pragmas of `profile` to this connection.
+    ///
+    /// In-memory and temporary databases keep their `memory` or `delete`
+    /// journal mode.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if a pragma fails.
+    pub fn apply_profile(&self, profile: OpenProfile) -> Result<()> {
+        for &(name, value) in profile.pragmas() {
+            // `journal_mode` returns the new mode, which is not checked.
+            let mut stmt = self.prepare(&format!("PRAGMA {} = {}", name, value))?;
+            let mut rows = stmt.query([])?;
+            while rows.next()?.is_some() {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::OpenProfile;
+    use crate::types::FromSql;
+    use crate::{Connection, Result};
+
+    fn pragma<T: FromSql>(db: &Connection, name: &str) -> Result<T> {
+        db.pragma_query_value(None, name, |row| row.get(0))
+    }
+
+    #[test]
+    fn test_open_with_profile() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db3");
+        let db = Connection::open_with_profile(&path, OpenProfile::Fast)?;
+        assert_eq!(pragma::<String>(&db, "journal_mode")?, "wal");
+        assert_eq!(pragma::<i64>(&db, "synchronous")?, 1);
+        assert_eq!(pragma::<i64>(&db, "foreign_keys")?, 1);
+        assert_eq!(pragma::<i64>(&db, "cache_size")?, -65536);
+        db.execute_batch("CREATE TABLE foo (x INTEGER)")?;
+
+        let durable = Connection::open_with_profile(&path, OpenProfile::Durable)?;
+        assert_eq!(pragma::<i64>(&durable, "synchronous")?, 2);
+
+        let ro = Connection::open_with_profile(&path, OpenProfile::ReadOnlyAnalytics)?;
+        assert_eq!(pragma::<i64>(&ro, "query_only")?, 1);
+        assert_eq!(pragma::<String>(&ro, "journal_mode")?, "wal");
+        assert!(ro.execute("INSERT INTO foo VALUES (1)", []).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_profile_in_memory() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.apply_profile(OpenProfile::Durable)?;
+        assert_eq!(pragma::<String>(&db, "journal_mode")?, "memory");
+        Ok(())
+    }
+}
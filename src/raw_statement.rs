@@ -3,6 +3,7 @@ use super::unlock_notify;
 use super::StatementStatus;
 #[cfg(feature = "modern_sqlite")]
 use crate::util::SqliteMallocString;
+use std::cell::Ref;
 use std::ffi::CStr;
 use std::os::raw::c_int;
 use std::ptr;
@@ -71,7 +72,7 @@ impl RawStatement {
     }
 
     #[inline]
-    #[cfg(feature = "column_decltype")]
+    #[cfg(any(feature = "column_decltype", feature = "arrow"))]
     pub fn column_decltype(&self, idx: usize) -> Option<&CStr> {
         unsafe {
             let decltype = ffi::sqlite3_column_decltype(self.ptr, idx as c_int);
@@ -83,6 +84,24 @@ impl RawStatement {
         }
     }
 
+    #[inline]
+    #[cfg(feature = "column_metadata")]
+    pub fn column_database_name(&self, idx: usize) -> Option<&CStr> {
+        unsafe { nullable_cstr(ffi::sqlite3_column_database_name(self.ptr, idx as c_int)) }
+    }
+
+    #[inline]
+    #[cfg(feature = "column_metadata")]
+    pub fn column_table_name(&self, idx: usize) -> Option<&CStr> {
+        unsafe { nullable_cstr(ffi::sqlite3_column_table_name(self.ptr, idx as c_int)) }
+    }
+
+    #[inline]
+    #[cfg(feature = "column_metadata")]
+    pub fn column_origin_name(&self, idx: usize) -> Option<&CStr> {
+        unsafe { nullable_cstr(ffi::sqlite3_column_origin_name(self.ptr, idx as c_int)) }
+    }
+
     #[inline]
     pub fn column_name(&self, idx: usize) -> Option<&CStr> {
         let idx = idx as c_int;
@@ -133,6 +152,18 @@ impl RawStatement {
         unsafe { ffi::sqlite3_bind_parameter_count(self.ptr) as usize }
     }
 
+    #[inline]
+    pub fn bind_parameter_name(&self, index: usize) -> Option<&str> {
+        unsafe {
+            let name = ffi::sqlite3_bind_parameter_name(self.ptr, index as c_int);
+            if name.is_null() {
+                None
+            } else {
+                CStr::from_ptr(name).to_str().ok()
+            }
+        }
+    }
+
     #[inline]
     pub fn bind_parameter_index(&self, name: &str) -> Option<usize> {
         self.cache.get_or_insert_with(name, |param_cstr| {
@@ -144,6 +175,22 @@ impl RawStatement {
         })
     }
 
+    /// Returns the indices of all of `names`, or the first name which is not
+    /// a parameter of this statement.
+    #[inline]
+    pub fn bind_parameter_indices<'a, I>(&self, names: I) -> Result<Ref<'_, [usize]>, &'a str>
+    where
+        I: Iterator<Item = &'a str> + Clone,
+    {
+        self.cache
+            .get_or_insert_set(names, |name| self.bind_parameter_index(name))
+    }
+
+    #[inline]
+    pub fn set_parameter_index_cache_capacity(&self, capacity: usize) {
+        self.cache.set_capacity(capacity)
+    }
+
     #[inline]
     pub fn clear_bindings(&self) -> c_int {
         unsafe { ffi::sqlite3_clear_bindings(self.ptr) }
@@ -172,11 +219,17 @@ impl RawStatement {
 
     // does not work for PRAGMA
     #[inline]
-    #[cfg(all(feature = "extra_check", feature = "modern_sqlite"))] // 3.7.4
+    #[cfg(feature = "modern_sqlite")] // 3.7.4
     pub fn readonly(&self) -> bool {
         unsafe { ffi::sqlite3_stmt_readonly(self.ptr) != 0 }
     }
 
+    #[inline]
+    #[cfg(feature = "modern_sqlite")] // 3.7.10
+    pub fn is_busy(&self) -> bool {
+        unsafe { ffi::sqlite3_stmt_busy(self.ptr) != 0 }
+    }
+
     #[inline]
     #[cfg(feature = "modern_sqlite")] // 3.14.0
     pub(crate) fn expanded_sql(&self) -> Option<SqliteMallocString> {
@@ -206,3 +259,15 @@ impl Drop for RawStatement {
         self.finalize_();
     }
 }
+
+// The returned string is owned by the statement (and only valid until its
+// next step or finalization).
+#[cfg(feature = "column_metadata")]
+#[inline]
+unsafe fn nullable_cstr<'a>(p: *const std::os::raw::c_char) -> Option<&'a CStr> {
+    if p.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(p))
+    }
+}
@@ -0,0 +1,358 @@
+//! Record the statements run on a connection, and replay them.
+//!
+//! A [`RecordingConnection`] runs statements on a connection, and records
+//! each statement, its parameters, and its outcome (number of changed rows,
+//! result rows, or error) in a recording, a SQLite database file. [`replay`]
+//! runs the recorded statements again on another database, e.g. a fresh one,
+//! and reports those whose outcome differs: a data bug seen by a customer
+//! can be reproduced from the recording of their session, without their
+//! database.
+//!
+//! ```rust,no_run
+//! use rusqlite::recording::{replay, RecordingConnection};
+//! use rusqlite::{Connection, Result};
+//!
+//! fn reproduce(recording: &str) -> Result<()> {
+//!     let db = Connection::open_in_memory()?;
+//!     for mismatch in replay(recording, &db)? {
+//!         println!("statement {} ({}) differs: {:?}", mismatch.seq, mismatch.sql, mismatch);
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use std::cell::Cell;
+use std::path::Path;
+
+use crate::types::{ToSqlOutput, Value};
+use crate::{params, params_from_iter, Connection, Result, ToSql};
+
+/// The outcome of a recorded statement.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Outcome {
+    /// The number of rows changed by [`RecordingConnection::execute`].
+    Changes(usize),
+    /// The rows returned by [`RecordingConnection::query`].
+    Rows(Vec<Vec<Value>>),
+    /// [`RecordingConnection::execute_batch`] succeeded.
+    Done,
+    /// The message of the error returned.
+    Error(String),
+}
+
+/// A recorded statement whose outcome differs when replayed.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Mismatch {
+    /// The position of the statement in the recording, from 1.
+    pub seq: i64,
+    /// The SQL of the statement.
+    pub sql: String,
+    /// The recorded outcome.
+    pub expected: Outcome,
+    /// The outcome of the replay.
+    pub actual: Outcome,
+}
+
+/// A connection recording the statements it runs, see the
+/// [module documentation](crate::recording).
+#[derive(Debug)]
+pub struct RecordingConnection {
+    conn: Connection,
+    log: Connection,
+    seq: Cell<i64>,
+}
+
+impl RecordingConnection {
+    /// Records the statements run on `conn` to the recording at `path`, which
+    /// is created if needed, or appended to.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the recording cannot be opened or created.
+    pub fn new<P: AsRef<Path>>(conn: Connection, path: P) -> Result<RecordingConnection> {
+        let log = Connection::open(path)?;
+        log.execute_batch(
+            "CREATE TABLE IF NOT EXISTS statements (
+                 seq INTEGER PRIMARY KEY,
+                 kind TEXT NOT NULL,
+                 sql TEXT NOT NULL,
+                 changes INTEGER,
+                 error TEXT
+             );
+             CREATE TABLE IF NOT EXISTS params (
+                 seq INTEGER, idx INTEGER, value, PRIMARY KEY (seq, idx)
+             ) WITHOUT ROWID;
+             CREATE TABLE IF NOT EXISTS results (
+                 seq INTEGER, row INTEGER, col INTEGER, value, PRIMARY KEY (seq, row, col)
+             ) WITHOUT ROWID;",
+        )?;
+        let seq = log.query_row("SELECT ifnull(max(seq), 0) FROM statements", [], |r| {
+            r.get(0)
+        })?;
+        Ok(RecordingConnection {
+            conn,
+            log,
+            seq: Cell::new(seq),
+        })
+    }
+
+    /// The connection, to run statements which are not recorded.
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Returns the connection, closing the recording.
+    pub fn into_inner(self) -> Connection {
+        self.conn
+    }
+
+    /// Runs and records [`Connection::execute`].
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the statement fails, or cannot be recorded.
+    pub fn execute(&self, sql: &str, params: &[&dyn ToSql]) -> Result<usize> {
+        let values = to_values(params)?;
+        let result = self.conn.execute(sql, params_from_iter(&values));
+        let outcome = match &result {
+            Ok(changes) => Outcome::Changes(*changes),
+            Err(err) => Outcome::Error(err.to_string()),
+        };
+        self.record("execute", sql, &values, &outcome)?;
+        result
+    }
+
+    /// Runs and records [`Connection::execute_batch`].
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if a statement fails, or if the batch cannot be
+    /// recorded.
+    pub fn execute_batch(&self, sql: &str) -> Result<()> {
+        let result = self.conn.execute_batch(sql);
+        let outcome = match &result {
+            Ok(()) => Outcome::Done,
+            Err(err) => Outcome::Error(err.to_string()),
+        };
+        self.record("batch", sql, &[], &outcome)?;
+        result
+    }
+
+    /// Runs a query, and records and returns its rows.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the query fails, or cannot be recorded.
+    pub fn query(&self, sql: &str, params: &[&dyn ToSql]) -> Result<Vec<Vec<Value>>> {
+        let values = to_values(params)?;
+        let result = query(&self.conn, sql, &values);
+        let outcome = match &result {
+            Ok(rows) => Outcome::Rows(rows.clone()),
+            Err(err) => Outcome::Error(err.to_string()),
+        };
+        self.record("query", sql, &values, &outcome)?;
+        result
+    }
+
+    fn record(&self, kind: &str, sql: &str, values: &[Value], outcome: &Outcome) -> Result<()> {
+        let seq = self.seq.get() + 1;
+        let tx = self.log.unchecked_transaction()?;
+        let (changes, error) = match outcome {
+            Outcome::Changes(changes) => (Some(*changes as i64), None),
+            Outcome::Error(err) => (None, Some(err.as_str())),
+            Outcome::Rows(_) | Outcome::Done => (None, None),
+        };
+        tx.execute(
+            "INSERT INTO statements (seq, kind, sql, changes, error) VALUES (?, ?, ?, ?, ?)",
+            params![seq, kind, sql, changes, error],
+        )?;
+        {
+            let mut insert = tx.prepare_cached("INSERT INTO params VALUES (?, ?, ?)")?;
+            for (idx, value) in values.iter().enumerate() {
+                insert.execute(params![seq, idx as i64, value])?;
+            }
+        }
+        if let Outcome::Rows(rows) = outcome {
+            let mut insert = tx.prepare_cached("INSERT INTO results VALUES (?, ?, ?, ?)")?;
+            for (row, values) in rows.iter().enumerate() {
+                for (col, value) in values.iter().enumerate() {
+                    insert.execute(params![seq, row as i64, col as i64, value])?;
+                }
+            }
+        }
+        tx.commit()?;
+        self.seq.set(seq);
+        Ok(())
+    }
+}
+
+/// Runs the statements of the recording at `path` on `conn`, in order, and
+/// returns those whose outcome differs from the recorded one.
+///
+/// Only the rows of queries, the number of rows changed, and whether a
+/// statement failed are compared, not error messages.
+///
+/// # Failure
+///
+/// Will return `Err` if the recording cannot be read.
+pub fn replay<P: AsRef<Path>>(path: P, conn: &Connection) -> Result<Vec<Mismatch>> {
+    let log = Connection::open(path)?;
+    let mut statements =
+        log.prepare("SELECT seq, kind, sql, changes, error FROM statements ORDER BY seq")?;
+    let mut params = log.prepare("SELECT value FROM params WHERE seq = ? ORDER BY idx")?;
+    let mut results =
+        log.prepare("SELECT row, value FROM results WHERE seq = ? ORDER BY row, col")?;
+    let mut mismatches = Vec::new();
+    let mut rows = statements.query([])?;
+    while let Some(row) = rows.next()? {
+        let seq: i64 = row.get(0)?;
+        let kind: String = row.get(1)?;
+        let sql: String = row.get(2)?;
+        let values = params
+            .query_map([seq], |r| r.get(0))?
+            .collect::<Result<Vec<Value>>>()?;
+        let expected = match (
+            row.get::<_, Option<String>>(4)?,
+            row.get::<_, Option<i64>>(3)?,
+        ) {
+            (Some(err), _) => Outcome::Error(err),
+            (None, Some(changes)) => Outcome::Changes(changes as usize),
+            (None, None) if kind == "batch" => Outcome::Done,
+            (None, None) => {
+                let mut expected: Vec<Vec<Value>> = Vec::new();
+                let mut rows = results.query([seq])?;
+                while let Some(r) = rows.next()? {
+                    let row = r.get::<_, i64>(0)? as usize;
+                    if expected.len() <= row {
+                        expected.push(Vec::new());
+                    }
+                    expected[row].push(r.get(1)?);
+                }
+                Outcome::Rows(expected)
+            }
+        };
+        let result = match kind.as_str() {
+            "execute" => conn
+                .execute(&sql, params_from_iter(&values))
+                .map(Outcome::Changes),
+            "batch" => conn.execute_batch(&sql).map(|_| Outcome::Done),
+            _ => query(conn, &sql, &values).map(Outcome::Rows),
+        };
+        let actual = result.unwrap_or_else(|err| Outcome::Error(err.to_string()));
+        let same = match (&expected, &actual) {
+            (Outcome::Error(_), Outcome::Error(_)) => true,
+            (expected, actual) => expected == actual,
+        };
+        if !same {
+            mismatches.push(Mismatch {
+                seq,
+                sql,
+                expected,
+                actual,
+            });
+        }
+    }
+    Ok(mismatches)
+}
+
+fn to_values(params: &[&dyn ToSql]) -> Result<Vec<Value>> {
+    params
+        .iter()
+        .map(|param| match param.to_sql()? {
+            ToSqlOutput::Borrowed(value) => Ok(value.into()),
+            ToSqlOutput::Owned(value) => Ok(value),
+            #[cfg(feature = "blob")]
+            ToSqlOutput::ZeroBlob(len) => Ok(Value::Blob(vec![0; len as usize])),
+            #[cfg(feature = "array")]
+            ToSqlOutput::Array(_) => Err(crate::Error::ToSqlConversionFailure(
+                "arrays cannot be recorded".into(),
+            )),
+            #[cfg(feature = "modern_sqlite")]
+            ToSqlOutput::Pointer(_) => Err(crate::Error::ToSqlConversionFailure(
+                "pointers cannot be recorded".into(),
+            )),
+        })
+        .collect()
+}
+
+fn query(conn: &Connection, sql: &str, values: &[Value]) -> Result<Vec<Vec<Value>>> {
+    let mut stmt = conn.prepare(sql)?;
+    let columns = stmt.column_count();
+    let mut rows = stmt.query(params_from_iter(values))?;
+    let mut result = Vec::new();
+    while let Some(row) = rows.next()? {
+        result.push(
+            (0..columns)
+                .map(|i| row.get(i))
+                .collect::<Result<Vec<Value>>>()?,
+        );
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::tempdir;
+
+    use super::{replay, Outcome, RecordingConnection};
+    use crate::types::Value;
+    use crate::{Connection, Result};
+
+    #[test]
+    fn test_record_replay() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("session.db");
+        let db = RecordingConnection::new(Connection::open_in_memory()?, &path)?;
+        db.execute_batch("CREATE TABLE foo (x INTEGER, y BLOB)")?;
+        assert_eq!(
+            db.execute("INSERT INTO foo VALUES (?, ?)", &[&1, &vec![0u8, 1]])?,
+            1
+        );
+        assert_eq!(db.execute("INSERT INTO foo VALUES (?, NULL)", &[&2.5])?, 1);
+        assert!(db.execute("INSERT INTO bar VALUES (1)", &[]).is_err());
+        let rows = db.query("SELECT x, y FROM foo WHERE x > ?", &[&0])?;
+        assert_eq!(
+            rows,
+            vec![
+                vec![Value::Integer(1), Value::Blob(vec![0, 1])],
+                vec![Value::Real(2.5), Value::Null],
+            ]
+        );
+        // Not recorded.
+        db.connection().execute("DELETE FROM foo", [])?;
+        drop(db);
+
+        // Appends to the recording.
+        let db = RecordingConnection::new(Connection::open_in_memory()?, &path)?;
+        db.execute_batch("CREATE TABLE foo (x INTEGER, y BLOB)")?;
+        drop(db);
+
+        // The same statements have the same outcome on a fresh database,
+        // except the last one, since the table now exists.
+        let fresh = Connection::open_in_memory()?;
+        let mismatches = replay(&path, &fresh)?;
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].seq, 6);
+        assert_eq!(mismatches[0].expected, Outcome::Done);
+        assert!(matches!(mismatches[0].actual, Outcome::Error(_)));
+
+        // A database with other data gives other results.
+        let other = Connection::open_in_memory()?;
+        other.execute_batch(
+            "CREATE TABLE foo (x INTEGER, y BLOB); INSERT INTO foo VALUES (3, NULL)",
+        )?;
+        let mismatches = replay(&path, &other)?;
+        assert_eq!(mismatches[0].seq, 1);
+        assert_eq!(mismatches[1].seq, 5);
+        assert_eq!(
+            mismatches[1].expected,
+            Outcome::Rows(vec![
+                vec![Value::Integer(1), Value::Blob(vec![0, 1])],
+                vec![Value::Real(2.5), Value::Null],
+            ])
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,364 @@
+//! `feature = "session"` Apply a stream of changesets to a replica.
+//!
+//! The primary records its changes with a [`Session`](crate::session::Session)
+//! and sends each changeset with a sequence number, e.g. over a channel, or
+//! appended to a file with [`write_frame`]. On the replica, a [`Replicator`]
+//! applies them in order, each in a savepoint together with its sequence
+//! number, which is recorded in a metadata table: after a crash or restart,
+//! changesets already applied are skipped, and the replica resumes from
+//! [`Replicator::applied_seq`].
+//!
+//! ```rust,no_run
+//! use std::fs::File;
+//! use std::io::BufReader;
+//!
+//! use rusqlite::replication::{ConflictPolicy, FrameReader, Replicator};
+//! use rusqlite::{Connection, Result};
+//!
+//! fn catch_up(replica: &Connection, log: &str) -> Result<u64> {
+//!     let file = File::open(log).expect("changeset log");
+//!     Replicator::new(replica, "primary")?
+//!         .conflict_policy(ConflictPolicy::Overwrite)
+//!         .run(FrameReader::new(BufReader::new(file)))
+//! }
+//! ```
+
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+
+use crate::session::{ConflictAction, ConflictType};
+use crate::{ffi, Connection, Error, OptionalExtension, Result, Savepoint};
+
+const METADATA_TABLE: &str = "_rusqlite_replication";
+
+/// `feature = "session"` What to do when a change of a changeset conflicts
+/// with the replica, e.g. when the row to update has been changed locally.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConflictPolicy {
+    /// Fail, leaving the changeset and its sequence number unapplied.
+    Abort,
+    /// Keep the replica's row, and skip the change.
+    Skip,
+    /// Replace the replica's row by the one of the change where possible
+    /// (the row exists with other values, or its primary key is taken), and
+    /// skip the change otherwise (the row to update or delete is missing).
+    Overwrite,
+}
+
+impl ConflictPolicy {
+    fn action(self, conflict: ConflictType) -> ConflictAction {
+        match (self, conflict) {
+            (ConflictPolicy::Abort, _) => ConflictAction::SQLITE_CHANGESET_ABORT,
+            (ConflictPolicy::Overwrite, ConflictType::SQLITE_CHANGESET_DATA)
+            | (ConflictPolicy::Overwrite, ConflictType::SQLITE_CHANGESET_CONFLICT) => {
+                ConflictAction::SQLITE_CHANGESET_REPLACE
+            }
+            // REPLACE is a misuse for the other conflicts.
+            _ => ConflictAction::SQLITE_CHANGESET_OMIT,
+        }
+    }
+}
+
+/// `feature = "session"` Applies the changesets of a stream to a connection,
+/// and keeps track of the last one applied.
+#[derive(Debug)]
+pub struct Replicator<'conn> {
+    conn: &'conn Connection,
+    stream: String,
+    policy: ConflictPolicy,
+}
+
+impl Replicator<'_> {
+    /// Creates a replicator for the stream named `stream` (a connection can
+    /// replicate several streams), creating its metadata table if needed.
+    /// Conflicts abort by default.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the metadata table cannot be created.
+    pub fn new<'conn>(conn: &'conn Connection, stream: &str) -> Result<Replicator<'conn>> {
+        conn.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (stream TEXT PRIMARY KEY, seq INTEGER NOT NULL)",
+            METADATA_TABLE
+        ))?;
+        Ok(Replicator {
+            conn,
+            stream: stream.to_owned(),
+            policy: ConflictPolicy::Abort,
+        })
+    }
+
+    /// Sets how conflicts are resolved.
+    #[must_use]
+    pub fn conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Returns the sequence number of the last changeset applied, or `None`
+    /// if none has been.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the metadata table cannot be read.
+    pub fn applied_seq(&self) -> Result<Option<u64>> {
+        self.conn
+            .query_row(
+                &format!("SELECT seq FROM {} WHERE stream = ?", METADATA_TABLE),
+                [&self.stream],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()
+            .map(|seq| seq.map(|seq| seq as u64))
+    }
+
+    /// Applies `changeset` (as produced by
+    /// [`Session::changeset_strm`](crate::session::Session::changeset_strm)),
+    /// with its sequence number `seq`, unless a changeset with the same or a
+    /// later sequence number has already been applied. Returns whether it
+    /// was applied.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the changeset is invalid, or a conflict aborts
+    /// it; nothing is applied then.
+    pub fn apply(&self, seq: u64, changeset: &[u8]) -> Result<bool> {
+        if matches!(self.applied_seq()?, Some(applied) if seq <= applied) {
+            return Ok(false);
+        }
+        let policy = self.policy;
+        let sp = Savepoint::with_depth_and_name(self.conn, 0, "_rusqlite_replication")?;
+        self.conn.apply_strm(
+            &mut &changeset[..],
+            None::<fn(&str) -> bool>,
+            move |conflict, _| policy.action(conflict),
+        )?;
+        self.conn.execute(
+            &format!(
+                "INSERT OR REPLACE INTO {} (stream, seq) VALUES (?, ?)",
+                METADATA_TABLE
+            ),
+            crate::params![self.stream, seq as i64],
+        )?;
+        sp.commit()?;
+        Ok(true)
+    }
+
+    /// Applies the changesets of `source`, e.g. `receiver.iter().map(Ok)`
+    /// for a channel, or a [`FrameReader`] for a file, until it ends, and
+    /// returns the number of changesets applied.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` on the first error of `source` or of
+    /// [`apply`](Replicator::apply); the changesets before it stay applied.
+    pub fn run<I>(&self, source: I) -> Result<u64>
+    where
+        I: IntoIterator<Item = Result<(u64, Vec<u8>)>>,
+    {
+        let mut applied = 0;
+        for frame in source {
+            let (seq, changeset) = frame?;
+            if self.apply(seq, &changeset)? {
+                applied += 1;
+            }
+        }
+        Ok(applied)
+    }
+}
+
+/// `feature = "session"` Appends a changeset and its sequence number to a
+/// stream read by [`FrameReader`]: the sequence number (8 bytes) and the
+/// length of the changeset (4 bytes), little-endian, then the changeset.
+pub fn write_frame<W: Write>(mut output: W, seq: u64, changeset: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(changeset.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "changeset too large"))?;
+    output.write_all(&seq.to_le_bytes())?;
+    output.write_all(&len.to_le_bytes())?;
+    output.write_all(changeset)
+}
+
+/// `feature = "session"` Reads the changesets written by [`write_frame`].
+///
+/// Changesets larger than the [maximum frame size](FrameReader::max_frame_size),
+/// 64 MiB by default, are rejected, so that a corrupt length cannot make it
+/// allocate unbounded memory.
+#[derive(Debug)]
+pub struct FrameReader<R> {
+    input: R,
+    max_frame_size: usize,
+}
+
+impl<R: Read> FrameReader<R> {
+    /// Reads frames from `input`.
+    pub fn new(input: R) -> FrameReader<R> {
+        FrameReader {
+            input,
+            max_frame_size: 64 << 20,
+        }
+    }
+
+    /// Sets the size, in bytes, of the largest changeset accepted.
+    #[must_use]
+    pub fn max_frame_size(mut self, bytes: usize) -> FrameReader<R> {
+        self.max_frame_size = bytes;
+        self
+    }
+
+    fn read_frame(&mut self) -> io::Result<Option<(u64, Vec<u8>)>> {
+        let mut header = [0u8; 12];
+        // A stream ends between frames.
+        let mut read = 0;
+        while read < header.len() {
+            match self.input.read(&mut header[read..])? {
+                0 if read == 0 => return Ok(None),
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                n => read += n,
+            }
+        }
+        let mut seq = [0u8; 8];
+        seq.copy_from_slice(&header[..8]);
+        let mut len = [0u8; 4];
+        len.copy_from_slice(&header[8..]);
+        let len = u32::from_le_bytes(len) as usize;
+        if len > self.max_frame_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("frame of {} bytes exceeds the maximum size", len),
+            ));
+        }
+        // Grown as the bytes are read, in case the length is wrong.
+        let mut changeset = Vec::new();
+        (&mut self.input)
+            .take(len as u64)
+            .read_to_end(&mut changeset)?;
+        if changeset.len() < len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(Some((u64::from_le_bytes(seq), changeset)))
+    }
+}
+
+impl<R: Read> Iterator for FrameReader<R> {
+    type Item = Result<(u64, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_frame()
+            .map_err(|err| {
+                Error::SqliteFailure(
+                    ffi::Error::new(ffi::SQLITE_IOERR),
+                    Some(format!("cannot read changeset frame: {}", err)),
+                )
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{write_frame, ConflictPolicy, FrameReader, Replicator};
+    use crate::session::Session;
+    use crate::{Connection, Result};
+
+    const SCHEMA: &str = "CREATE TABLE foo (id INTEGER PRIMARY KEY, v TEXT)";
+
+    // The changesets of running each statement on a fresh primary.
+    fn changesets(statements: &[&str]) -> Result<Vec<Vec<u8>>> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(SCHEMA)?;
+        let mut changesets = Vec::new();
+        for sql in statements {
+            let mut session = Session::new(&db)?;
+            session.attach(None)?;
+            db.execute_batch(sql)?;
+            let mut changeset = Vec::new();
+            session.changeset_strm(&mut changeset)?;
+            changesets.push(changeset);
+        }
+        Ok(changesets)
+    }
+
+    fn values(db: &Connection) -> Result<Vec<(i64, String)>> {
+        let mut stmt = db.prepare("SELECT id, v FROM foo ORDER BY id")?;
+        let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+        rows.collect()
+    }
+
+    #[test]
+    fn test_resume() -> Result<()> {
+        let changesets = changesets(&[
+            "INSERT INTO foo VALUES (1, 'a'), (2, 'b')",
+            "UPDATE foo SET v = 'c' WHERE id = 2",
+            "DELETE FROM foo WHERE id = 1",
+        ])?;
+        let mut log = Vec::new();
+        for (seq, changeset) in changesets.iter().enumerate() {
+            write_frame(&mut log, seq as u64 + 1, changeset).unwrap();
+        }
+
+        let replica = Connection::open_in_memory()?;
+        replica.execute_batch(SCHEMA)?;
+        let replicator = Replicator::new(&replica, "primary")?;
+        assert_eq!(replicator.applied_seq()?, None);
+        assert!(replicator.apply(1, &changesets[0])?);
+        assert_eq!(replicator.applied_seq()?, Some(1));
+
+        // The whole log, after a restart: the first changeset is skipped.
+        let replicator = Replicator::new(&replica, "primary")?;
+        assert_eq!(replicator.run(FrameReader::new(&log[..]))?, 2);
+        assert_eq!(replicator.applied_seq()?, Some(3));
+        assert_eq!(values(&replica)?, vec![(2, "c".to_owned())]);
+        assert_eq!(replicator.run(FrameReader::new(&log[..]))?, 0);
+
+        // A truncated log.
+        assert!(FrameReader::new(&log[..log.len() - 1])
+            .last()
+            .unwrap()
+            .is_err());
+        // A frame too large.
+        let largest = changesets.iter().map(Vec::len).max().unwrap();
+        assert_eq!(
+            FrameReader::new(&log[..]).max_frame_size(largest).count(),
+            3
+        );
+        let err = FrameReader::new(&log[..])
+            .max_frame_size(largest - 1)
+            .find_map(Result::err)
+            .unwrap();
+        assert!(
+            err.to_string().contains("exceeds the maximum size"),
+            "{}",
+            err
+        );
+        let mut corrupt = Vec::new();
+        write_frame(&mut corrupt, 1, &[]).unwrap();
+        corrupt[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(FrameReader::new(&corrupt[..]).next().unwrap().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_conflicts() -> Result<()> {
+        let changesets = changesets(&[
+            "INSERT INTO foo VALUES (1, 'a')",
+            "UPDATE foo SET v = 'b' WHERE id = 1",
+        ])?;
+        let replica = Connection::open_in_memory()?;
+        replica.execute_batch(SCHEMA)?;
+        replica.execute_batch("INSERT INTO foo VALUES (1, 'local')")?;
+
+        let replicator = Replicator::new(&replica, "primary")?;
+        assert!(replicator.apply(1, &changesets[0]).is_err());
+        assert_eq!(replicator.applied_seq()?, None);
+
+        let replicator = replicator.conflict_policy(ConflictPolicy::Skip);
+        assert!(replicator.apply(1, &changesets[0])?);
+        assert_eq!(values(&replica)?, vec![(1, "local".to_owned())]);
+
+        let replicator = replicator.conflict_policy(ConflictPolicy::Overwrite);
+        assert!(replicator.apply(2, &changesets[1])?);
+        assert_eq!(values(&replica)?, vec![(1, "b".to_owned())]);
+        Ok(())
+    }
+}
@@ -2,8 +2,8 @@ use fallible_iterator::FallibleIterator;
 use fallible_streaming_iterator::FallibleStreamingIterator;
 use std::convert;
 
-use super::{Error, Result, Statement};
-use crate::types::{FromSql, FromSqlError, ValueRef};
+use super::{Error, ErrorCode, Result, Statement};
+use crate::types::{FromSql, FromSqlError, Value, ValueRef};
 
 /// An handle for the resulting rows of a query.
 #[must_use = "Rows is lazy and will do nothing unless consumed"]
@@ -67,6 +67,24 @@ impl<'stmt> Rows<'stmt> {
         MappedRows { rows: self, map: f }
     }
 
+    /// Convert this `Rows` to an [`IntoValues`], which implements
+    /// `FallibleIterator` over owned copies of the values of each row, so
+    /// that results compose with the usual combinators.
+    /// ```rust,no_run
+    /// use fallible_iterator::FallibleIterator;
+    /// # use rusqlite::{Result, Statement, types::Value};
+    /// fn non_null_rows(stmt: &mut Statement) -> Result<Vec<Vec<Value>>> {
+    ///     let rows = stmt.query([])?;
+    ///     rows.into_values()
+    ///         .filter(|values| Ok(values[0] != Value::Null))
+    ///         .collect()
+    /// }
+    /// ```
+    #[inline]
+    pub fn into_values(self) -> IntoValues<'stmt> {
+        IntoValues { rows: self }
+    }
+
     /// Map over this `Rows` with a fallible function, converting it to a
     /// [`AndThenRows`], which implements `Iterator` (instead of
     /// `FallibleStreamingIterator`).
@@ -97,6 +115,48 @@ impl<'stmt> Rows<'stmt> {
     }
 }
 
+impl<'stmt> Rows<'stmt> {
+    /// Like `advance`, but returns `Ok(false)`, without resetting the
+    /// statement, if it is busy and `yield_on_busy` is set, so that the step
+    /// can be retried.
+    pub(crate) fn advance_unless_busy(&mut self, yield_on_busy: bool) -> Result<bool> {
+        match self.stmt.map(Statement::step) {
+            Some(Err(Error::SqliteFailure(err, _)))
+                if yield_on_busy && err.code == ErrorCode::DatabaseBusy =>
+            {
+                Ok(false)
+            }
+            step => self.finish_step(step).map(|_| true),
+        }
+    }
+
+    /// Resets the statement, ending the rows, when a busy step is not
+    /// retried.
+    pub(crate) fn abandon(&mut self) {
+        self.reset();
+        self.row = None;
+    }
+
+    fn finish_step(&mut self, step: Option<Result<bool>>) -> Result<()> {
+        match (self.stmt, step) {
+            (Some(stmt), Some(Ok(true))) => {
+                self.row = Some(Row { stmt });
+                Ok(())
+            }
+            (_, Some(Err(e))) => {
+                self.reset();
+                self.row = None;
+                Err(e)
+            }
+            _ => {
+                self.reset();
+                self.row = None;
+                Ok(())
+            }
+        }
+    }
+}
+
 impl Drop for Rows<'_> {
     #[inline]
     fn drop(&mut self) {
@@ -127,6 +187,23 @@ where
     }
 }
 
+/// An iterator over the values of the resulting rows of a query, each row
+/// copied to a `Vec<Value>`.
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct IntoValues<'stmt> {
+    rows: Rows<'stmt>,
+}
+
+impl FallibleIterator for IntoValues<'_> {
+    type Error = Error;
+    type Item = Vec<Value>;
+
+    #[inline]
+    fn next(&mut self) -> Result<Option<Vec<Value>>> {
+        Ok(self.rows.next()?.map(Row::to_values))
+    }
+}
+
 /// An iterator over the mapped resulting rows of a query.
 ///
 /// `F` is used to tranform the _streaming_ iterator into a _standard_ iterator.
@@ -201,28 +278,8 @@ impl<'stmt> FallibleStreamingIterator for Rows<'stmt> {
 
     #[inline]
     fn advance(&mut self) -> Result<()> {
-        match self.stmt {
-            Some(ref stmt) => match stmt.step() {
-                Ok(true) => {
-                    self.row = Some(Row { stmt });
-                    Ok(())
-                }
-                Ok(false) => {
-                    self.reset();
-                    self.row = None;
-                    Ok(())
-                }
-                Err(e) => {
-                    self.reset();
-                    self.row = None;
-                    Err(e)
-                }
-            },
-            None => {
-                self.row = None;
-                Ok(())
-            }
-        }
+        let step = self.stmt.map(Statement::step);
+        self.finish_step(step)
     }
 
     #[inline]
@@ -305,6 +362,23 @@ impl<'stmt> Row<'stmt> {
     /// it can be somewhat difficult to use, and most callers will be better
     /// served by [`get`](Row::get) or [`get_unwrap`](Row::get_unwrap).
     ///
+    /// Text and blobs are borrowed straight from SQLite's buffer, so scanning
+    /// a large value this way doesn't allocate:
+    ///
+    /// ```rust,no_run
+    /// # use rusqlite::{Connection, Result};
+    /// fn count_gc(conn: &Connection) -> Result<usize> {
+    ///     let mut stmt = conn.prepare("SELECT seq FROM reads")?;
+    ///     let mut rows = stmt.query([])?;
+    ///     let mut count = 0;
+    ///     while let Some(row) = rows.next()? {
+    ///         let seq: &[u8] = row.get_ref(0)?.as_blob()?;
+    ///         count += seq.iter().filter(|&&b| b == b'G' || b == b'C').count();
+    ///     }
+    ///     Ok(count)
+    /// }
+    /// ```
+    ///
     /// ## Failure
     ///
     /// Returns an `Error::InvalidColumnIndex` if `idx` is outside the valid
@@ -340,6 +414,12 @@ impl<'stmt> Row<'stmt> {
         self.get_ref(idx).unwrap()
     }
 
+    fn to_values(&self) -> Vec<Value> {
+        (0..self.stmt.column_count())
+            .map(|idx| Value::from(self.stmt.value_ref(idx)))
+            .collect()
+    }
+
     /// Renamed to [`get_ref`](Row::get_ref).
     #[deprecated = "Use [`get_ref`](Row::get_ref) instead."]
     #[inline]
@@ -429,6 +509,57 @@ mod tests {
     #![allow(clippy::redundant_closure)] // false positives due to lifetime issues; clippy issue #5594
     use crate::{Connection, Result};
 
+    #[test]
+    fn test_get_ref_borrows() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        let mut stmt = conn.prepare("SELECT zeroblob(1000000), 'text', NULL")?;
+        let mut rows = stmt.query([])?;
+        let row = rows.next()?.unwrap();
+        let blob = row.get_ref(0)?.as_blob()?;
+        assert_eq!(blob.len(), 1_000_000);
+        // Both borrows point into the same SQLite buffer.
+        assert_eq!(blob.as_ptr(), row.get_ref(0)?.as_blob()?.as_ptr());
+        assert_eq!(row.get_ref(1)?.as_str_or_null()?, Some("text"));
+        assert_eq!(row.get_ref(2)?.as_str_or_null()?, None);
+        assert_eq!(row.get_ref(2)?.as_blob_or_null()?, None);
+        assert!(row.get_ref(1)?.as_blob_or_null().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rows_fallible_iterator() -> Result<()> {
+        use crate::types::Value;
+        use fallible_iterator::FallibleIterator;
+
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch(
+            "CREATE TABLE test (a INTEGER, b TEXT);
+             INSERT INTO test VALUES (1, 'one'), (2, NULL), (3, 'three');",
+        )?;
+        let mut stmt = conn.prepare("SELECT a, b FROM test ORDER BY a")?;
+        let rows: Vec<Vec<Value>> = stmt.query([])?.into_values().collect()?;
+        assert_eq!(
+            rows,
+            vec![
+                vec![Value::Integer(1), Value::Text("one".to_owned())],
+                vec![Value::Integer(2), Value::Null],
+                vec![Value::Integer(3), Value::Text("three".to_owned())],
+            ]
+        );
+
+        let non_null = stmt
+            .query([])?
+            .into_values()
+            .filter(|values| Ok(values[1] != Value::Null))
+            .count()?;
+        assert_eq!(non_null, 2);
+
+        let mut rows = stmt.query([])?.into_values().skip(2);
+        assert_eq!(rows.next()?.map(|v| v[0].clone()), Some(Value::Integer(3)));
+        assert_eq!(rows.next()?, None);
+        Ok(())
+    }
+
     #[test]
     fn test_try_from_row_for_tuple_1() -> Result<()> {
         use crate::ToSql;
@@ -0,0 +1,245 @@
+//! `feature = "row_policy"` Soft deletion and optimistic versioning of rows.
+//!
+//! A table following these policies has two more columns, added by
+//! [`add_columns`]:
+//!
+//! * `deleted_at`, `NULL` for live rows, and the Unix time of their deletion
+//!   for soft-deleted rows, which queries should skip, with
+//!   [`live!`](crate::live).
+//! * `version`, incremented by each update, which is only applied if the
+//!   version is still the one read: if another writer updated the row in
+//!   between, the update is rejected instead of silently overwriting its
+//!   changes. [`versioned_update!`](crate::versioned_update) builds such
+//!   updates, to be run with [`execute_versioned`].
+//!
+//! The macros build the SQL at compile time, so that the policies are
+//! applied the same way everywhere, and statements can be cached.
+//!
+//! ```rust,no_run
+//! use rusqlite::row_policy::execute_versioned;
+//! use rusqlite::{live, params, versioned_update, Connection, Result};
+//!
+//! fn rename(conn: &Connection, id: i64, name: &str) -> Result<()> {
+//!     let version: i64 = conn.query_row(
+//!         concat!("SELECT version FROM samples WHERE id = ? AND ", live!("samples")),
+//!         [id],
+//!         |row| row.get(0),
+//!     )?;
+//!     // ... compute the new name ...
+//!     execute_versioned(
+//!         conn,
+//!         versioned_update!("samples", "id", "name = ?"),
+//!         params![name, id, version],
+//!     )
+//! }
+//! ```
+
+use crate::util::quote_identifier;
+use crate::{params, Connection, Error, Params, Result, ToSql};
+
+/// `feature = "row_policy"` Expands to the condition selecting the live (not
+/// soft-deleted) rows of `table`, as a string literal.
+///
+/// ```rust
+/// # use rusqlite::live;
+/// assert_eq!(live!("samples"), "samples.deleted_at IS NULL");
+/// ```
+#[macro_export]
+macro_rules! live {
+    ($table:literal) => {
+        concat!($table, ".deleted_at IS NULL")
+    };
+}
+
+/// `feature = "row_policy"` Expands to an `UPDATE` of the live row of
+/// `table` whose `key` column matches, setting `set` and incrementing its
+/// version, if its version is unchanged, as a string literal.
+///
+/// The parameters are those of `set`, which must be positional (`?` or
+/// `?NNN`), then the key and the expected version.
+///
+/// ```rust
+/// # use rusqlite::versioned_update;
+/// assert_eq!(
+///     versioned_update!("samples", "id", "name = ?"),
+///     "UPDATE samples SET name = ?, version = version + 1 \
+///      WHERE id = ? AND version = ? AND deleted_at IS NULL"
+/// );
+/// ```
+#[macro_export]
+macro_rules! versioned_update {
+    ($table:literal, $key:literal, $set:literal) => {
+        concat!(
+            "UPDATE ",
+            $table,
+            " SET ",
+            $set,
+            ", version = version + 1 WHERE ",
+            $key,
+            " = ? AND version = ? AND deleted_at IS NULL"
+        )
+    };
+}
+
+/// Adds the `deleted_at` and `version` columns to `table`, if it doesn't
+/// have them yet, and a partial index of its live rows by `key`.
+///
+/// # Failure
+///
+/// Will return `Err` if the table cannot be altered.
+pub fn add_columns(conn: &Connection, table: &str, key: &str) -> Result<()> {
+    let mut columns = Vec::new();
+    conn.pragma(None, "table_info", &table, |row| {
+        columns.push(row.get::<_, String>(1)?);
+        Ok(())
+    })?;
+    let quoted = quote_identifier(table);
+    if !columns.iter().any(|c| c.eq_ignore_ascii_case("deleted_at")) {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN deleted_at INTEGER",
+            quoted
+        ))?;
+    }
+    if !columns.iter().any(|c| c.eq_ignore_ascii_case("version")) {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN version INTEGER NOT NULL DEFAULT 0",
+            quoted
+        ))?;
+    }
+    conn.execute_batch(&format!(
+        "CREATE INDEX IF NOT EXISTS {} ON {} ({}) WHERE deleted_at IS NULL",
+        quote_identifier(&format!("{}_live", table)),
+        quoted,
+        quote_identifier(key)
+    ))
+}
+
+/// Soft-deletes the row of `table` whose `key` column is `value`, and returns
+/// whether a live row was deleted. The version of the row is incremented.
+///
+/// # Failure
+///
+/// Will return `Err` if the underlying SQLite call fails.
+pub fn soft_delete<V: ToSql>(conn: &Connection, table: &str, key: &str, value: V) -> Result<bool> {
+    conn.prepare_cached(&format!(
+        "UPDATE {} SET deleted_at = CAST(strftime('%s', 'now') AS INTEGER),
+         version = version + 1 WHERE {} = ? AND deleted_at IS NULL",
+        quote_identifier(table),
+        quote_identifier(key)
+    ))?
+    .execute(params![value])
+    .map(|n| n > 0)
+}
+
+/// Restores the soft-deleted row of `table` whose `key` column is `value`,
+/// and returns whether a row was restored. The version of the row is
+/// incremented.
+///
+/// # Failure
+///
+/// Will return `Err` if the underlying SQLite call fails.
+pub fn restore<V: ToSql>(conn: &Connection, table: &str, key: &str, value: V) -> Result<bool> {
+    conn.prepare_cached(&format!(
+        "UPDATE {} SET deleted_at = NULL, version = version + 1
+         WHERE {} = ? AND deleted_at IS NOT NULL",
+        quote_identifier(table),
+        quote_identifier(key)
+    ))?
+    .execute(params![value])
+    .map(|n| n > 0)
+}
+
+/// Deletes for good the rows of `table` soft-deleted before `before` (a Unix
+/// time), and returns their number.
+///
+/// # Failure
+///
+/// Will return `Err` if the underlying SQLite call fails.
+pub fn purge(conn: &Connection, table: &str, before: i64) -> Result<usize> {
+    conn.prepare_cached(&format!(
+        "DELETE FROM {} WHERE deleted_at < ?",
+        quote_identifier(table)
+    ))?
+    .execute([before])
+}
+
+/// Executes a [`versioned_update!`](crate::versioned_update), and checks that
+/// it updated one row.
+///
+/// # Failure
+///
+/// Will return `Err(Error::StatementChangedRows(0))` if the row was not
+/// updated: it was updated by another writer since its version was read, or
+/// it was deleted. Will return `Err` if the underlying SQLite call fails.
+pub fn execute_versioned<P: Params>(conn: &Connection, sql: &str, params: P) -> Result<()> {
+    match conn.prepare_cached(sql)?.execute(params)? {
+        1 => Ok(()),
+        changes => Err(Error::StatementChangedRows(changes)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{add_columns, execute_versioned, purge, restore, soft_delete};
+    use crate::{params, Connection, Error, Result};
+
+    fn checked_memory_handle() -> Result<Connection> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE samples (id INTEGER PRIMARY KEY, name TEXT);
+             INSERT INTO samples (name) VALUES ('a'), ('b');",
+        )?;
+        add_columns(&db, "samples", "id")?;
+        // Idempotent.
+        add_columns(&db, "samples", "id")?;
+        Ok(db)
+    }
+
+    fn names(db: &Connection) -> Result<Vec<String>> {
+        let mut stmt = db.prepare(concat!(
+            "SELECT name FROM samples WHERE ",
+            live!("samples"),
+            " ORDER BY id"
+        ))?;
+        let rows = stmt.query_map([], |r| r.get(0))?;
+        rows.collect()
+    }
+
+    #[test]
+    fn test_soft_delete() -> Result<()> {
+        let db = checked_memory_handle()?;
+        assert!(soft_delete(&db, "samples", "id", 1)?);
+        assert!(!soft_delete(&db, "samples", "id", 1)?);
+        assert_eq!(names(&db)?, vec!["b"]);
+        assert!(restore(&db, "samples", "id", 1)?);
+        assert!(!restore(&db, "samples", "id", 1)?);
+        assert_eq!(names(&db)?, vec!["a", "b"]);
+
+        assert!(soft_delete(&db, "samples", "id", 2)?);
+        assert_eq!(purge(&db, "samples", 0)?, 0);
+        assert_eq!(purge(&db, "samples", i64::MAX)?, 1);
+        let count: i64 = db.query_row("SELECT count(*) FROM samples", [], |r| r.get(0))?;
+        assert_eq!(count, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_versioned_update() -> Result<()> {
+        let db = checked_memory_handle()?;
+        let update = versioned_update!("samples", "id", "name = ?");
+        execute_versioned(&db, update, params!["a2", 1, 0])?;
+        // A writer which read version 0 conflicts.
+        let err = execute_versioned(&db, update, params!["a3", 1, 0]).unwrap_err();
+        assert_eq!(err, Error::StatementChangedRows(0));
+        execute_versioned(&db, update, params!["a3", 1, 1])?;
+        assert_eq!(names(&db)?, vec!["a3", "b"]);
+
+        // Deleted rows are not updated.
+        soft_delete(&db, "samples", "id", 2)?;
+        let version: i64 =
+            db.query_row("SELECT version FROM samples WHERE id = 2", [], |r| r.get(0))?;
+        assert_eq!(version, 1);
+        assert!(execute_versioned(&db, update, params!["b2", 2, 1]).is_err());
+        Ok(())
+    }
+}
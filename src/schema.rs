@@ -0,0 +1,775 @@
+//! Typed schema introspection, from the
+//! [pragma functions](https://sqlite.org/pragma.html#pragfunc) where the
+//! SQLite version has them (e.g. `pragma_table_xinfo(?)`), and from the
+//! classic `PRAGMA` statements otherwise.
+//!
+//! Pragma functions can also be joined in larger queries, e.g. to list the
+//! columns of all tables at once, as [`Connection::schema_columns`] does;
+//! check [`has_pragma_functions`] before writing such queries.
+//!
+//! ```rust
+//! # use rusqlite::{Connection, Result};
+//! # fn main() -> Result<()> {
+//! let db = Connection::open_in_memory()?;
+//! db.execute_batch("CREATE TABLE samples (id INTEGER PRIMARY KEY, name TEXT NOT NULL)")?;
+//! let columns = db.table_columns(None, "samples")?;
+//! assert_eq!(columns[1].name, "name");
+//! assert!(columns[1].not_null);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::types::Value;
+use crate::util::quote_identifier;
+use crate::{Connection, DatabaseName, Error, OptionalExtension, Result, Row};
+
+/// Returns whether the SQLite library has table-valued pragma functions
+/// (SQLite >= 3.16.0), which can be used in `SELECT` statements, e.g.
+/// `SELECT * FROM sqlite_master, pragma_index_list(sqlite_master.name)`.
+#[inline]
+pub fn has_pragma_functions() -> bool {
+    crate::version_number() >= 3_016_000
+}
+
+// PRAGMA table_xinfo: 3.26.0
+fn has_table_xinfo() -> bool {
+    crate::version_number() >= 3_026_000
+}
+
+/// A column of a table, as returned by [`Connection::table_columns`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TableColumn {
+    /// The index of the column in the table.
+    pub cid: i64,
+    /// The name of the column.
+    pub name: String,
+    /// The declared type of the column, empty if none.
+    pub decl_type: String,
+    /// Whether the column is `NOT NULL`.
+    pub not_null: bool,
+    /// The SQL text of the default value of the column, if any.
+    pub default: Option<String>,
+    /// The (1-based) index of the column in the primary key, 0 if it is not
+    /// part of it.
+    pub pk: i64,
+    /// 0 for an ordinary column, 1 for a hidden column of a virtual table,
+    /// 2 for a `VIRTUAL` generated column, 3 for a `STORED` generated
+    /// column. Always 0 with SQLite < 3.26.0, which only lists ordinary
+    /// columns.
+    pub hidden: i64,
+}
+
+impl TableColumn {
+    /// Returns how the column is generated, `None` if it is not a generated
+    /// column (SQLite >= 3.31.0).
+    #[inline]
+    pub fn generated(&self) -> Option<Generated> {
+        match self.hidden {
+            2 => Some(Generated::Virtual),
+            3 => Some(Generated::Stored),
+            _ => None,
+        }
+    }
+
+    /// Returns whether a value can be inserted in the column: generated
+    /// columns, and hidden columns of virtual tables, are left out of
+    /// `INSERT` statements.
+    #[inline]
+    pub fn is_insertable(&self) -> bool {
+        self.hidden == 0
+    }
+
+    /// Returns the default value of the column, parsed from its SQL text,
+    /// or `None` if it has no default (its default is then `NULL`).
+    #[inline]
+    pub fn default_value(&self) -> Option<DefaultValue> {
+        self.default.as_deref().map(DefaultValue::parse)
+    }
+
+    // The columns of PRAGMA table_info, and hidden of table_xinfo.
+    fn from_row(row: &Row<'_>, offset: usize, hidden: bool) -> Result<TableColumn> {
+        Ok(TableColumn {
+            cid: row.get(offset)?,
+            name: row.get(offset + 1)?,
+            decl_type: row.get(offset + 2)?,
+            not_null: row.get(offset + 3)?,
+            default: row.get(offset + 4)?,
+            pk: row.get(offset + 5)?,
+            hidden: if hidden { row.get(offset + 6)? } else { 0 },
+        })
+    }
+}
+
+/// How a generated column is computed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Generated {
+    /// `GENERATED ALWAYS AS (...) VIRTUAL`: computed when read.
+    Virtual,
+    /// `GENERATED ALWAYS AS (...) STORED`: computed when written, and stored.
+    Stored,
+}
+
+/// The default value of a column, as returned by
+/// [`TableColumn::default_value`].
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum DefaultValue {
+    /// A literal: `NULL`, a number, a string, a blob, or `TRUE` and `FALSE`
+    /// (the integers 1 and 0).
+    Value(Value),
+    /// `CURRENT_TIME`: the time, as `HH:MM:SS`, of the insertion.
+    CurrentTime,
+    /// `CURRENT_DATE`: the date, as `YYYY-MM-DD`, of the insertion.
+    CurrentDate,
+    /// `CURRENT_TIMESTAMP`: the date and time, as `YYYY-MM-DD HH:MM:SS`, of
+    /// the insertion.
+    CurrentTimestamp,
+    /// Any other expression, e.g. `datetime('now', 'localtime')`, without
+    /// its enclosing parentheses, evaluated on each insertion.
+    Expression(String),
+}
+
+impl DefaultValue {
+    // Parses the `dflt_value` of `PRAGMA table_info`.
+    fn parse(sql: &str) -> DefaultValue {
+        let sql = sql.trim();
+        match sql.to_ascii_uppercase().as_str() {
+            "NULL" => return DefaultValue::Value(Value::Null),
+            "TRUE" => return DefaultValue::Value(Value::Integer(1)),
+            "FALSE" => return DefaultValue::Value(Value::Integer(0)),
+            "CURRENT_TIME" => return DefaultValue::CurrentTime,
+            "CURRENT_DATE" => return DefaultValue::CurrentDate,
+            "CURRENT_TIMESTAMP" => return DefaultValue::CurrentTimestamp,
+            _ => {}
+        }
+        parse_literal(sql)
+            .map(DefaultValue::Value)
+            .unwrap_or_else(|| DefaultValue::Expression(sql.to_owned()))
+    }
+}
+
+// A string, blob or number literal.
+fn parse_literal(sql: &str) -> Option<Value> {
+    let bytes = sql.as_bytes();
+    if bytes.len() >= 2 && bytes[0] == b'\'' && bytes[bytes.len() - 1] == b'\'' {
+        let inner = &sql[1..sql.len() - 1];
+        // A quote must be doubled.
+        if inner.replace("''", "").contains('\'') {
+            return None;
+        }
+        return Some(Value::Text(inner.replace("''", "'")));
+    }
+    if bytes.len() >= 3
+        && (bytes[0] == b'x' || bytes[0] == b'X')
+        && bytes[1] == b'\''
+        && bytes[bytes.len() - 1] == b'\''
+    {
+        let hex = &sql[2..sql.len() - 1];
+        return hex
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| match pair.len() {
+                2 => u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok(),
+                _ => None,
+            })
+            .collect::<Option<Vec<u8>>>()
+            .map(Value::Blob);
+    }
+    parse_number(sql)
+}
+
+fn parse_number(sql: &str) -> Option<Value> {
+    let (negative, unsigned) = match sql.as_bytes().first()? {
+        b'-' => (true, &sql[1..]),
+        b'+' => (false, &sql[1..]),
+        _ => (false, sql),
+    };
+    let first = *unsigned.as_bytes().first()?;
+    if !(first.is_ascii_digit() || first == b'.') {
+        return None;
+    }
+    if let Some(hex) = unsigned
+        .strip_prefix("0x")
+        .or_else(|| unsigned.strip_prefix("0X"))
+    {
+        // Hexadecimal literals are the bits of a 64-bit integer.
+        let i = u64::from_str_radix(hex, 16).ok()? as i64;
+        return Some(Value::Integer(if negative { i.wrapping_neg() } else { i }));
+    }
+    if unsigned.bytes().all(|b| b.is_ascii_digit()) {
+        if let Ok(i) = sql.trim_start_matches('+').parse::<i64>() {
+            return Some(Value::Integer(i));
+        }
+    }
+    if !unsigned
+        .bytes()
+        .all(|b| b.is_ascii_digit() || b"+-.eE".contains(&b))
+    {
+        return None;
+    }
+    sql.trim_start_matches('+')
+        .parse::<f64>()
+        .ok()
+        .map(Value::Real)
+}
+
+/// The options of a table, as returned by [`Connection::table_options`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TableOptions {
+    /// Whether the table is `WITHOUT ROWID`: its rows have no rowid, and
+    /// APIs needing one (e.g. `last_insert_rowid`, or incremental blob I/O)
+    /// cannot be used with it.
+    pub without_rowid: bool,
+    /// Whether the table is `STRICT` (SQLite >= 3.37.0): values must match
+    /// the types of the columns, or inserts and updates fail.
+    pub strict: bool,
+}
+
+impl TableOptions {
+    // The options after the closing parenthesis of a CREATE TABLE statement.
+    fn parse(sql: &str) -> TableOptions {
+        let mut options = TableOptions::default();
+        let tail = match sql.rfind(')') {
+            Some(i) => &sql[i + 1..],
+            None => return options,
+        };
+        for option in tail.split(',') {
+            let words: Vec<&str> = option.split_whitespace().collect();
+            match words.as_slice() {
+                [without, rowid]
+                    if without.eq_ignore_ascii_case("WITHOUT")
+                        && rowid.eq_ignore_ascii_case("ROWID") =>
+                {
+                    options.without_rowid = true
+                }
+                [strict] if strict.trim_end_matches(';').eq_ignore_ascii_case("STRICT") => {
+                    options.strict = true
+                }
+                _ => {}
+            }
+        }
+        options
+    }
+}
+
+/// A column of a foreign key, as returned by [`Connection::foreign_keys`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ForeignKey {
+    /// The index of the foreign key in the table.
+    pub id: i64,
+    /// The index of the column in the foreign key.
+    pub seq: i64,
+    /// The parent table.
+    pub table: String,
+    /// The column of the child table.
+    pub from: String,
+    /// The referenced column of the parent table, `None` for its primary
+    /// key.
+    pub to: Option<String>,
+    /// The `ON UPDATE` action, e.g. `NO ACTION` or `CASCADE`.
+    pub on_update: String,
+    /// The `ON DELETE` action.
+    pub on_delete: String,
+}
+
+/// An index of a table, as returned by [`Connection::table_indexes`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TableIndex {
+    /// The name of the index.
+    pub name: String,
+    /// Whether the index is `UNIQUE`.
+    pub unique: bool,
+    /// How the index was created: `c` by `CREATE INDEX`, `u` by a `UNIQUE`
+    /// constraint, `pk` by a `PRIMARY KEY` constraint.
+    pub origin: String,
+    /// Whether the index is partial.
+    pub partial: bool,
+}
+
+impl Connection {
+    /// Returns the columns of `table`, in `schema` (or in the first schema
+    /// having it if `None`), including the hidden and generated columns when
+    /// the SQLite version lists them (>= 3.26.0).
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite calls fail. A missing
+    /// table has no columns.
+    pub fn table_columns(
+        &self,
+        schema: Option<DatabaseName<'_>>,
+        table: &str,
+    ) -> Result<Vec<TableColumn>> {
+        if has_table_xinfo() {
+            pragma_function_rows(
+                self,
+                "SELECT cid, name, type, \"notnull\", dflt_value, pk, hidden FROM pragma_table_xinfo",
+                schema,
+                table,
+                |row| TableColumn::from_row(row, 0, true),
+            )
+        } else {
+            let mut columns = Vec::new();
+            self.pragma(schema, "table_info", &table, |row| {
+                columns.push(TableColumn::from_row(row, 0, false)?);
+                Ok(())
+            })?;
+            Ok(columns)
+        }
+    }
+
+    /// Returns the options of `table`, in `schema` (or in the first schema
+    /// having it if `None`), or `None` if there is no such table.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite calls fail.
+    pub fn table_options(
+        &self,
+        schema: Option<DatabaseName<'_>>,
+        table: &str,
+    ) -> Result<Option<TableOptions>> {
+        let schemas = match schema {
+            Some(schema) => vec![schema_name(&schema).to_owned()],
+            None => {
+                let mut schemas = Vec::new();
+                self.pragma_query(None, "database_list", |row| {
+                    schemas.push(row.get(1)?);
+                    Ok(())
+                })?;
+                // temp is searched first.
+                schemas.sort_by_key(|s: &String| s != "temp");
+                schemas
+            }
+        };
+        for schema in schemas {
+            let sql: Option<Option<String>> = self
+                .query_row(
+                    &format!(
+                        "SELECT sql FROM {}.sqlite_master WHERE type = 'table' AND name = ?",
+                        quote_identifier(&schema)
+                    ),
+                    [table],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            if let Some(sql) = sql {
+                return Ok(Some(TableOptions::parse(sql.as_deref().unwrap_or(""))));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns an [`Error::WithoutRowid`] if `table` is a `WITHOUT ROWID`
+    /// table, for APIs needing rowids.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite calls fail.
+    pub fn check_rowid(&self, schema: Option<DatabaseName<'_>>, table: &str) -> Result<()> {
+        match self.table_options(schema, table)? {
+            Some(options) if options.without_rowid => Err(Error::WithoutRowid(table.to_owned())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the names of the columns of `table` which values can be
+    /// inserted in, in order: all but its generated columns.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite calls fail.
+    pub fn insertable_columns(
+        &self,
+        schema: Option<DatabaseName<'_>>,
+        table: &str,
+    ) -> Result<Vec<String>> {
+        Ok(self
+            .table_columns(schema, table)?
+            .into_iter()
+            .filter(TableColumn::is_insertable)
+            .map(|column| column.name)
+            .collect())
+    }
+
+    /// Returns the columns of the foreign keys of `table`, in `schema` (or
+    /// in the first schema having it if `None`).
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite calls fail.
+    pub fn foreign_keys(
+        &self,
+        schema: Option<DatabaseName<'_>>,
+        table: &str,
+    ) -> Result<Vec<ForeignKey>> {
+        let foreign_key = |row: &Row<'_>| {
+            Ok(ForeignKey {
+                id: row.get(0)?,
+                seq: row.get(1)?,
+                table: row.get(2)?,
+                from: row.get(3)?,
+                to: row.get(4)?,
+                on_update: row.get(5)?,
+                on_delete: row.get(6)?,
+            })
+        };
+        if has_pragma_functions() {
+            pragma_function_rows(
+                self,
+                "SELECT id, seq, \"table\", \"from\", \"to\", on_update, on_delete FROM pragma_foreign_key_list",
+                schema,
+                table,
+                foreign_key,
+            )
+        } else {
+            let mut foreign_keys = Vec::new();
+            self.pragma(schema, "foreign_key_list", &table, |row| {
+                foreign_keys.push(foreign_key(row)?);
+                Ok(())
+            })?;
+            Ok(foreign_keys)
+        }
+    }
+
+    /// Returns the indexes of `table`, in `schema` (or in the first schema
+    /// having it if `None`).
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite calls fail.
+    pub fn table_indexes(
+        &self,
+        schema: Option<DatabaseName<'_>>,
+        table: &str,
+    ) -> Result<Vec<TableIndex>> {
+        let index = |row: &Row<'_>| {
+            Ok(TableIndex {
+                name: row.get(1)?,
+                unique: row.get(2)?,
+                origin: row.get(3)?,
+                partial: row.get(4)?,
+            })
+        };
+        if has_pragma_functions() {
+            pragma_function_rows(
+                self,
+                "SELECT seq, name, \"unique\", origin, partial FROM pragma_index_list",
+                schema,
+                table,
+                index,
+            )
+        } else {
+            let mut indexes = Vec::new();
+            self.pragma(schema, "index_list", &table, |row| {
+                indexes.push(index(row)?);
+                Ok(())
+            })?;
+            Ok(indexes)
+        }
+    }
+
+    /// Returns the columns of all tables of `schema` (`main` if `None`), as
+    /// `(table, column)` pairs ordered by table name, with a single query
+    /// joining `sqlite_master` and `pragma_table_xinfo` where available.
+    /// Internal tables (`sqlite_%`) are skipped.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite calls fail.
+    pub fn schema_columns(
+        &self,
+        schema: Option<DatabaseName<'_>>,
+    ) -> Result<Vec<(String, TableColumn)>> {
+        let schema = schema.unwrap_or(DatabaseName::Main);
+        let master = format!("{}.sqlite_master", quote_identifier(schema_name(&schema)));
+        let tables = "m.type = 'table' AND m.name NOT LIKE 'sqlite\\_%' ESCAPE '\\'";
+        if has_table_xinfo() {
+            let mut stmt = self.prepare(&format!(
+                "SELECT m.name, p.cid, p.name, p.type, p.\"notnull\", p.dflt_value, p.pk, p.hidden \
+                 FROM {} AS m, pragma_table_xinfo(m.name, ?) AS p WHERE {} ORDER BY m.name, p.cid",
+                master, tables
+            ))?;
+            let rows = stmt.query_map([schema_name(&schema)], |row| {
+                Ok((row.get(0)?, TableColumn::from_row(row, 1, true)?))
+            })?;
+            rows.collect()
+        } else {
+            let mut stmt = self.prepare(&format!(
+                "SELECT m.name FROM {} AS m WHERE {} ORDER BY m.name",
+                master, tables
+            ))?;
+            let names = stmt
+                .query_map([], |row| row.get(0))?
+                .collect::<Result<Vec<String>>>()?;
+            let mut columns = Vec::new();
+            for name in names {
+                for column in self.table_columns(Some(schema), &name)? {
+                    columns.push((name.clone(), column));
+                }
+            }
+            Ok(columns)
+        }
+    }
+}
+
+// The rows of `select` from a pragma function, called with `arg` and the
+// schema name.
+fn pragma_function_rows<T, F>(
+    conn: &Connection,
+    select: &str,
+    schema: Option<DatabaseName<'_>>,
+    arg: &str,
+    f: F,
+) -> Result<Vec<T>>
+where
+    F: FnMut(&Row<'_>) -> Result<T>,
+{
+    let rows = match schema {
+        Some(schema) => {
+            let mut stmt = conn.prepare(&format!("{}(?, ?)", select))?;
+            let rows = stmt.query_map([arg, schema_name(&schema)], f)?;
+            rows.collect()
+        }
+        None => {
+            let mut stmt = conn.prepare(&format!("{}(?)", select))?;
+            let rows = stmt.query_map([arg], f)?;
+            rows.collect()
+        }
+    };
+    rows
+}
+
+fn schema_name<'a>(schema: &DatabaseName<'a>) -> &'a str {
+    match *schema {
+        DatabaseName::Main => "main",
+        DatabaseName::Temp => "temp",
+        DatabaseName::Attached(s) => s,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DefaultValue, ForeignKey, Generated, TableColumn, TableIndex, TableOptions};
+    use crate::types::Value;
+    use crate::Error;
+    use crate::{Connection, DatabaseName, Result};
+
+    fn db() -> Result<Connection> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE project (id INTEGER PRIMARY KEY, name TEXT UNIQUE);
+             CREATE TABLE sample (
+                 id INTEGER PRIMARY KEY,
+                 project INTEGER NOT NULL REFERENCES project ON DELETE CASCADE,
+                 name TEXT DEFAULT 'unnamed'
+             );
+             CREATE INDEX sample_name ON sample (name) WHERE name IS NOT NULL;",
+        )?;
+        Ok(db)
+    }
+
+    #[test]
+    fn test_table_columns() -> Result<()> {
+        let db = db()?;
+        let columns = db.table_columns(None, "sample")?;
+        assert_eq!(
+            columns[1],
+            TableColumn {
+                cid: 1,
+                name: "project".to_owned(),
+                decl_type: "INTEGER".to_owned(),
+                not_null: true,
+                default: None,
+                pk: 0,
+                hidden: 0,
+            }
+        );
+        assert_eq!(columns[0].pk, 1);
+        assert_eq!(columns[2].default.as_deref(), Some("'unnamed'"));
+        assert_eq!(
+            db.table_columns(Some(DatabaseName::Main), "sample")?,
+            columns
+        );
+        assert!(db
+            .table_columns(Some(DatabaseName::Temp), "sample")?
+            .is_empty());
+        assert!(db.table_columns(None, "missing")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_values() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE t (
+                 a DEFAULT -1,
+                 b DEFAULT 'it''s',
+                 c DEFAULT (datetime('now', 'localtime')),
+                 d DEFAULT x'0aFF',
+                 e DEFAULT 1.5e3,
+                 f DEFAULT CURRENT_TIMESTAMP,
+                 g DEFAULT NULL,
+                 h DEFAULT (2),
+                 i DEFAULT +0x10,
+                 j NOT NULL,
+                 k DEFAULT 9223372036854775808
+             )",
+        )?;
+        let defaults: Vec<_> = db
+            .table_columns(None, "t")?
+            .iter()
+            .map(TableColumn::default_value)
+            .collect();
+        assert_eq!(
+            defaults,
+            [
+                Some(DefaultValue::Value(Value::Integer(-1))),
+                Some(DefaultValue::Value(Value::Text("it's".to_owned()))),
+                Some(DefaultValue::Expression(
+                    "datetime('now', 'localtime')".to_owned()
+                )),
+                Some(DefaultValue::Value(Value::Blob(vec![0x0a, 0xff]))),
+                Some(DefaultValue::Value(Value::Real(1500.0))),
+                Some(DefaultValue::CurrentTimestamp),
+                Some(DefaultValue::Value(Value::Null)),
+                Some(DefaultValue::Value(Value::Integer(2))),
+                Some(DefaultValue::Value(Value::Integer(16))),
+                None,
+                Some(DefaultValue::Value(Value::Real(
+                    9_223_372_036_854_775_808.0
+                ))),
+            ]
+        );
+        assert_eq!(
+            DefaultValue::parse("'a' || 'b'"),
+            DefaultValue::Expression("'a' || 'b'".to_owned())
+        );
+        assert_eq!(
+            DefaultValue::parse("1 + 2"),
+            DefaultValue::Expression("1 + 2".to_owned())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_generated_columns() -> Result<()> {
+        if crate::version_number() < 3_031_000 {
+            return Ok(());
+        }
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE t (a INTEGER, b AS (a * 2), c INTEGER AS (a * 3) STORED, d TEXT)",
+        )?;
+        let generated: Vec<Option<Generated>> = db
+            .table_columns(None, "t")?
+            .iter()
+            .map(TableColumn::generated)
+            .collect();
+        assert_eq!(
+            generated,
+            vec![
+                None,
+                Some(Generated::Virtual),
+                Some(Generated::Stored),
+                None
+            ]
+        );
+        assert_eq!(db.insertable_columns(None, "t")?, vec!["a", "d"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_table_options() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE a (x INTEGER PRIMARY KEY);
+             CREATE TABLE b (x TEXT PRIMARY KEY, y) WITHOUT ROWID;
+             CREATE TEMP TABLE a (x TEXT PRIMARY KEY) without  rowid;",
+        )?;
+        let options = |schema, table| db.table_options(schema, table);
+        assert_eq!(
+            options(Some(DatabaseName::Main), "a")?,
+            Some(TableOptions::default())
+        );
+        assert!(options(None, "a")?.unwrap().without_rowid);
+        assert!(options(None, "b")?.unwrap().without_rowid);
+        assert_eq!(options(None, "missing")?, None);
+        assert_eq!(
+            db.check_rowid(None, "b"),
+            Err(Error::WithoutRowid("b".to_owned()))
+        );
+        db.check_rowid(Some(DatabaseName::Main), "a")?;
+
+        assert_eq!(
+            TableOptions::parse("CREATE TABLE c (x INT) STRICT, WITHOUT ROWID"),
+            TableOptions {
+                without_rowid: true,
+                strict: true
+            }
+        );
+        assert_eq!(
+            TableOptions::parse("CREATE TABLE \"strict\" (x INT DEFAULT (1))"),
+            TableOptions::default()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_foreign_keys_and_indexes() -> Result<()> {
+        let db = db()?;
+        assert_eq!(
+            db.foreign_keys(None, "sample")?,
+            vec![ForeignKey {
+                id: 0,
+                seq: 0,
+                table: "project".to_owned(),
+                from: "project".to_owned(),
+                to: None,
+                on_update: "NO ACTION".to_owned(),
+                on_delete: "CASCADE".to_owned(),
+            }]
+        );
+        assert_eq!(
+            db.table_indexes(None, "sample")?,
+            vec![TableIndex {
+                name: "sample_name".to_owned(),
+                unique: false,
+                origin: "c".to_owned(),
+                partial: true,
+            }]
+        );
+        let indexes = db.table_indexes(None, "project")?;
+        assert_eq!(indexes.len(), 1);
+        assert!(indexes[0].unique);
+        assert_eq!(indexes[0].origin, "u");
+        Ok(())
+    }
+
+    #[test]
+    fn test_schema_columns() -> Result<()> {
+        let db = db()?;
+        let columns: Vec<(String, String)> = db
+            .schema_columns(None)?
+            .into_iter()
+            .map(|(table, column)| (table, column.name))
+            .collect();
+        let expected: Vec<(String, String)> = [
+            ("project", "id"),
+            ("project", "name"),
+            ("sample", "id"),
+            ("sample", "project"),
+            ("sample", "name"),
+        ]
+        .iter()
+        .map(|&(t, c)| (t.to_owned(), c.to_owned()))
+        .collect();
+        assert_eq!(columns, expected);
+        Ok(())
+    }
+}
@@ -0,0 +1,437 @@
+//! `feature = "search"` Full-text search of a table, with
+//! [FTS5](https://sqlite.org/fts5.html).
+//!
+//! A [`SearchIndex`] is an external content FTS5 table, which indexes some
+//! text columns of a source table without copying them.
+//!
+//! ```rust,no_run
+//! use rusqlite::search::SearchIndex;
+//! use rusqlite::{Connection, Result};
+//!
+//! fn find_samples(conn: &Connection, query: &str) -> Result<Vec<String>> {
+//!     let index = SearchIndex::builder("sample_search", "sample")
+//!         .column("name", 10.0)
+//!         .column("notes", 1.0)
+//!         .tokenizer("porter unicode61")
+//!         .create(conn)?;
+//!     let mut names = Vec::new();
+//!     for hit in index.search(query, 10)? {
+//!         names.push(conn.query_row(
+//!             "SELECT name FROM sample WHERE rowid = ?",
+//!             [hit.rowid],
+//!             |row| row.get(0),
+//!         )?);
+//!     }
+//!     Ok(names)
+//! }
+//! ```
+
+use crate::types::ValueRef;
+use crate::util::quote_identifier;
+use crate::{ffi, params, Connection, Error, Result, Savepoint};
+
+// Wrapped around matches by `highlight()`, to find their offsets. These bytes
+// never occur in UTF-8 text.
+const MATCH_START: u8 = 0xFF;
+const MATCH_END: u8 = 0xFE;
+
+/// How the index is kept in sync with its source table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SyncMode {
+    /// Triggers on the source table update the index. This is the default.
+    Triggers,
+    /// The index is only updated by [`SearchIndex::rebuild`].
+    Manual,
+}
+
+/// Builds a [`SearchIndex`], with [`SearchIndex::builder`].
+#[derive(Clone, Debug)]
+pub struct SearchIndexBuilder {
+    name: String,
+    source: String,
+    columns: Vec<(String, f64)>,
+    tokenizer: Option<String>,
+    sync: SyncMode,
+}
+
+impl SearchIndexBuilder {
+    /// Indexes `column` of the source table. Matches in it are weighted by
+    /// `weight` in the scores.
+    #[must_use]
+    pub fn column(mut self, column: &str, weight: f64) -> SearchIndexBuilder {
+        self.columns.push((column.to_owned(), weight));
+        self
+    }
+
+    /// Sets the [tokenizer](https://sqlite.org/fts5.html#tokenizers), e.g.
+    /// `"porter unicode61"`. The default is `unicode61`.
+    #[must_use]
+    pub fn tokenizer(mut self, tokenizer: &str) -> SearchIndexBuilder {
+        self.tokenizer = Some(tokenizer.to_owned());
+        self
+    }
+
+    /// Sets how the index is kept in sync with the source table.
+    #[must_use]
+    pub fn sync(mut self, sync: SyncMode) -> SearchIndexBuilder {
+        self.sync = sync;
+        self
+    }
+
+    /// Creates the FTS5 table (and triggers) if it doesn't exist, populating
+    /// it from the source table, and returns the index.
+    ///
+    /// The source table must be a rowid table, and the columns must not
+    /// change as long as the index exists.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if no column was added, if a weight is not finite,
+    /// or if the underlying SQLite call fails, e.g. if FTS5 is not available.
+    pub fn create(self, conn: &Connection) -> Result<SearchIndex<'_>> {
+        if let Some((column, weight)) = self.columns.iter().find(|(_, w)| !w.is_finite()) {
+            return Err(Error::SqliteFailure(
+                ffi::Error::new(ffi::SQLITE_MISUSE),
+                Some(format!("invalid weight {} of column {}", weight, column)),
+            ));
+        }
+        let index = SearchIndex {
+            conn,
+            table: quote_identifier(&self.name),
+            weights: self
+                .columns
+                .iter()
+                .map(|(_, weight)| weight.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            columns: self.columns.len(),
+        };
+        let columns: Vec<String> = self
+            .columns
+            .iter()
+            .map(|(c, _)| quote_identifier(c))
+            .collect();
+        let prefixed = |prefix: &str| -> Vec<String> {
+            columns
+                .iter()
+                .map(|c| format!("{}.{}", prefix, c))
+                .collect()
+        };
+        let (table, source) = (&index.table, quote_identifier(&self.source));
+        let mut sql = String::new();
+        let exists = conn
+            .prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?")?
+            .exists([&self.name])?;
+        sql.push_str(&format!(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS {} USING fts5({}, content={}, content_rowid=rowid{});",
+            table,
+            columns.join(", "),
+            quote_literal(&self.source),
+            self.tokenizer
+                .as_ref()
+                .map(|t| format!(", tokenize={}", quote_literal(t)))
+                .unwrap_or_default(),
+        ));
+        if self.sync == SyncMode::Triggers {
+            let insert = format!(
+                "INSERT INTO {}(rowid, {}) VALUES (new.rowid, {});",
+                table,
+                columns.join(", "),
+                prefixed("new").join(", ")
+            );
+            let delete = format!(
+                "INSERT INTO {0}({0}, rowid, {1}) VALUES ('delete', old.rowid, {2});",
+                table,
+                columns.join(", "),
+                prefixed("old").join(", ")
+            );
+            for (suffix, event, body) in &[
+                ("ai", "INSERT", insert.clone()),
+                ("ad", "DELETE", delete.clone()),
+                ("au", "UPDATE", format!("{} {}", delete, insert)),
+            ] {
+                sql.push_str(&format!(
+                    "CREATE TRIGGER IF NOT EXISTS {} AFTER {} ON {} BEGIN {} END;",
+                    quote_identifier(&format!("{}_{}", self.name, suffix)),
+                    event,
+                    source,
+                    body
+                ));
+            }
+        }
+        let sp = Savepoint::with_depth_and_name(conn, 0, "_rusqlite_search")?;
+        conn.execute_batch(&sql)?;
+        if !exists {
+            index.rebuild()?;
+        }
+        sp.commit()?;
+        Ok(index)
+    }
+}
+
+/// A full-text index of some columns of a table.
+#[derive(Debug)]
+pub struct SearchIndex<'conn> {
+    conn: &'conn Connection,
+    table: String,
+    weights: String,
+    columns: usize,
+}
+
+/// A row matching a search.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct SearchHit {
+    /// The rowid of the row in the source table.
+    pub rowid: i64,
+    /// How well the row matches, according to the BM25 algorithm and the
+    /// column weights: greater is better.
+    pub score: f64,
+    /// Where the matches are in the row.
+    pub offsets: Vec<MatchOffset>,
+}
+
+/// Where a phrase of a search matches.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MatchOffset {
+    /// The index of the column, in the order they were added to the index.
+    pub column: usize,
+    /// The offset of the first byte of the match in the column text.
+    pub start: usize,
+    /// The offset of the byte following the match in the column text.
+    pub end: usize,
+}
+
+impl SearchIndex<'_> {
+    /// Starts building an index named `name` of the `source` table.
+    #[inline]
+    pub fn builder(name: &str, source: &str) -> SearchIndexBuilder {
+        SearchIndexBuilder {
+            name: name.to_owned(),
+            source: source.to_owned(),
+            columns: Vec::new(),
+            tokenizer: None,
+            sync: SyncMode::Triggers,
+        }
+    }
+
+    /// Returns the best `limit` rows matching `query`, an
+    /// [FTS5 query](https://sqlite.org/fts5.html#full_text_query_syntax),
+    /// best first.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `query` is invalid, or if the underlying SQLite
+    /// call fails.
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        let highlights: Vec<String> = (0..self.columns)
+            .map(|i| {
+                format!(
+                    "highlight({}, {}, CAST(X'{:02X}' AS TEXT), CAST(X'{:02X}' AS TEXT))",
+                    self.table, i, MATCH_START, MATCH_END
+                )
+            })
+            .collect();
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "SELECT rowid, -bm25({0}, {1}), {2} FROM {0} WHERE {0} MATCH ?1
+             ORDER BY bm25({0}, {1}) LIMIT ?2",
+            self.table,
+            self.weights,
+            highlights.join(", ")
+        ))?;
+        let mut rows = stmt.query(params![query, limit as i64])?;
+        let mut hits = Vec::new();
+        while let Some(row) = rows.next()? {
+            let mut offsets = Vec::new();
+            for column in 0..self.columns {
+                if let ValueRef::Text(text) = row.get_ref(2 + column)? {
+                    match_offsets(column, text, &mut offsets);
+                }
+            }
+            hits.push(SearchHit {
+                rowid: row.get(0)?,
+                score: row.get(1)?,
+                offsets,
+            });
+        }
+        Ok(hits)
+    }
+
+    /// Reindexes the whole source table. With [`SyncMode::Manual`], this
+    /// must be called after the source table changes.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite call fails.
+    pub fn rebuild(&self) -> Result<()> {
+        self.command("rebuild")
+    }
+
+    /// Merges the b-trees of the index, to make searches faster.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite call fails.
+    pub fn optimize(&self) -> Result<()> {
+        self.command("optimize")
+    }
+
+    fn command(&self, command: &str) -> Result<()> {
+        self.conn.execute(
+            &format!("INSERT INTO {0}({0}) VALUES (?)", self.table),
+            [command],
+        )?;
+        Ok(())
+    }
+}
+
+// Appends the offsets of the matches wrapped in markers in `highlighted`.
+fn match_offsets(column: usize, highlighted: &[u8], offsets: &mut Vec<MatchOffset>) {
+    let mut pos = 0;
+    let mut start = 0;
+    for &b in highlighted {
+        match b {
+            MATCH_START => start = pos,
+            MATCH_END => offsets.push(MatchOffset {
+                column,
+                start,
+                end: pos,
+            }),
+            _ => pos += 1,
+        }
+    }
+}
+
+fn quote_literal(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MatchOffset, SearchIndex, SyncMode};
+    use crate::{Connection, Result};
+
+    fn checked_memory_handle() -> Result<Connection> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "CREATE TABLE doc(id INTEGER PRIMARY KEY, title TEXT, body TEXT);
+             INSERT INTO doc VALUES (1, 'Genome assembly', 'Reads are assembled into contigs.');
+             INSERT INTO doc VALUES (2, 'Variant calling', 'Variants are called against the genome.');",
+        )?;
+        Ok(db)
+    }
+
+    #[test]
+    fn test_search_with_triggers() -> Result<()> {
+        let db = checked_memory_handle()?;
+        let index = SearchIndex::builder("doc search", "doc")
+            .column("title", 10.0)
+            .column("body", 1.0)
+            .create(&db)?;
+
+        // Existing rows are indexed, and the title weighs more.
+        let hits = index.search("genome", 10)?;
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].rowid, 1);
+        assert!(hits[0].score > hits[1].score);
+        assert_eq!(
+            hits[0].offsets,
+            vec![MatchOffset {
+                column: 0,
+                start: 0,
+                end: 6
+            }]
+        );
+        assert_eq!(
+            hits[1].offsets,
+            vec![MatchOffset {
+                column: 1,
+                start: 32,
+                end: 38
+            }]
+        );
+
+        db.execute(
+            "INSERT INTO doc(title, body) VALUES ('Café', 'genome café')",
+            [],
+        )?;
+        let hits = index.search("café", 10)?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].rowid, 3);
+        assert_eq!(hits[0].offsets[1].start, 7);
+        assert_eq!(hits[0].offsets[1].end, 12);
+        assert_eq!(index.search("genome", 1)?.len(), 1);
+
+        db.execute("UPDATE doc SET title = 'Assembly' WHERE id = 1", [])?;
+        db.execute("DELETE FROM doc WHERE id = 2", [])?;
+        assert_eq!(index.search("genome", 10)?.len(), 1);
+        assert_eq!(index.search("assembly", 10)?[0].rowid, 1);
+
+        // Creating it again opens it.
+        let index = SearchIndex::builder("doc search", "doc")
+            .column("title", 10.0)
+            .column("body", 1.0)
+            .create(&db)?;
+        index.optimize()?;
+        assert_eq!(index.search("genome", 10)?.len(), 1);
+        assert!(index.search("\"unterminated", 10).is_err());
+        assert!(SearchIndex::builder("empty", "doc").create(&db).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_control_characters() -> Result<()> {
+        let db = checked_memory_handle()?;
+        db.execute(
+            "INSERT INTO doc(title, body) VALUES ('\u{1}\u{2}', '\u{1}genome\u{2} \u{1}reads')",
+            [],
+        )?;
+        let index = SearchIndex::builder("doc_search", "doc")
+            .column("body", 1.0)
+            .create(&db)?;
+        let hits = index.search("reads", 10)?;
+        assert_eq!(hits.len(), 2);
+        let hit = hits.iter().find(|hit| hit.rowid == 3).unwrap();
+        assert_eq!(
+            hit.offsets,
+            vec![MatchOffset {
+                column: 0,
+                start: 10,
+                end: 15
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_non_finite_weight() -> Result<()> {
+        let db = checked_memory_handle()?;
+        for weight in &[f64::NAN, f64::INFINITY] {
+            assert!(SearchIndex::builder("doc_search", "doc")
+                .column("body", *weight)
+                .create(&db)
+                .is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_manual_sync() -> Result<()> {
+        let db = checked_memory_handle()?;
+        let index = SearchIndex::builder("doc_search", "doc")
+            .column("body", 1.0)
+            .tokenizer("porter")
+            .sync(SyncMode::Manual)
+            .create(&db)?;
+        // Stemmed.
+        assert_eq!(index.search("call", 10)?.len(), 1);
+
+        db.execute("INSERT INTO doc(body) VALUES ('calling')", [])?;
+        assert_eq!(index.search("call", 10)?.len(), 1);
+        index.rebuild()?;
+        assert_eq!(index.search("call", 10)?.len(), 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,243 @@
+//! `feature = "serialize"` Serialize a database to bytes, and open a database
+//! from bytes.
+//!
+//! [`Connection::serialize`] copies a database to a `Vec<u8>`, in the format
+//! of a database file, and [`Connection::deserialize`] replaces a database of
+//! a connection by an in-memory copy of such bytes.
+//!
+//! [`embedded_db!`](crate::embedded_db) embeds a database file in the
+//! binary, which makes for hermetic and fast test fixtures: each test gets a
+//! copy of the database, which it can modify without affecting the others.
+//! [`EmbeddedDb::sql`] instead builds the database from a SQL script, at
+//! runtime, once per process:
+//!
+//! ```rust,ignore
+//! use rusqlite::embedded_db;
+//! use rusqlite::serialize::EmbeddedDb;
+//!
+//! static VARIANTS: EmbeddedDb = EmbeddedDb::sql(include_str!("fixtures/variants.sql"));
+//!
+//! #[test]
+//! fn test_annotate() -> rusqlite::Result<()> {
+//!     // A fresh in-memory copy of the database, for each test.
+//!     let db = embedded_db!("fixtures/variants.db")?;
+//!     let other = VARIANTS.open()?;
+//!     // ...
+//!     # Ok(())
+//! }
+//! ```
+
+use std::os::raw::c_uint;
+use std::sync::{Arc, Mutex};
+use std::{ptr, slice};
+
+use crate::error::error_from_sqlite_code;
+use crate::ffi;
+use crate::{Connection, DatabaseName, Result};
+
+impl Connection {
+    /// `feature = "serialize"` Returns the content of the `schema` database,
+    /// as it would be in a database file.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `schema` is not a database of this connection, or
+    /// if the copy cannot be allocated.
+    pub fn serialize(&self, schema: DatabaseName<'_>) -> Result<Vec<u8>> {
+        let schema = schema.to_sql_str()?;
+        let c = self.db.borrow_mut();
+        let mut size = 0;
+        let data = unsafe { ffi::sqlite3_serialize(c.db(), schema.as_ptr(), &mut size, 0) };
+        if data.is_null() {
+            // A database which was never written to has no content.
+            let exists = unsafe { !ffi::sqlite3_db_filename(c.db(), schema.as_ptr()).is_null() };
+            if size == 0 && exists {
+                return Ok(Vec::new());
+            }
+            return Err(error_from_sqlite_code(ffi::SQLITE_ERROR, None));
+        }
+        let bytes = unsafe { slice::from_raw_parts(data, size as usize) }.to_vec();
+        unsafe { ffi::sqlite3_free(data as *mut _) };
+        Ok(bytes)
+    }
+
+    /// `feature = "serialize"` Replaces the `schema` database by an in-memory
+    /// copy of `data`, the content of a database file, which can grow unless
+    /// `read_only` is set.
+    ///
+    /// The statements cached by this connection are discarded.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `schema` is not a database of this connection, or
+    /// is in use, or if the copy cannot be allocated. Invalid `data` is only
+    /// reported when the database is read.
+    pub fn deserialize(
+        &mut self,
+        schema: DatabaseName<'_>,
+        data: &[u8],
+        read_only: bool,
+    ) -> Result<()> {
+        let schema = schema.to_sql_str()?;
+        self.flush_prepared_statement_cache();
+        let mut c = self.db.borrow_mut();
+        // SQLite takes ownership of a buffer of its allocator.
+        let buf = unsafe { ffi::sqlite3_malloc64(data.len().max(1) as ffi::sqlite3_uint64) };
+        if buf.is_null() {
+            return Err(error_from_sqlite_code(ffi::SQLITE_NOMEM, None));
+        }
+        unsafe { ptr::copy_nonoverlapping(data.as_ptr(), buf as *mut u8, data.len()) };
+        let flags = ffi::SQLITE_DESERIALIZE_FREEONCLOSE
+            | if read_only {
+                ffi::SQLITE_DESERIALIZE_READONLY
+            } else {
+                ffi::SQLITE_DESERIALIZE_RESIZEABLE
+            };
+        let rc = unsafe {
+            ffi::sqlite3_deserialize(
+                c.db(),
+                schema.as_ptr(),
+                buf as *mut u8,
+                data.len() as ffi::sqlite3_int64,
+                data.len() as ffi::sqlite3_int64,
+                flags as c_uint,
+            )
+        };
+        // On failure, the buffer is freed by SQLite.
+        c.decode_result(rc)
+    }
+}
+
+/// `feature = "serialize"` A database embedded in the binary, see
+/// [`embedded_db!`](crate::embedded_db).
+#[derive(Debug)]
+pub struct EmbeddedDb {
+    source: Source,
+    // The image built from the SQL script, once.
+    image: Mutex<Option<Arc<[u8]>>>,
+}
+
+#[derive(Debug)]
+enum Source {
+    Sql(&'static str),
+    Image(&'static [u8]),
+}
+
+impl EmbeddedDb {
+    /// A database created by running the `sql` script, the first time it is
+    /// opened in the process. Errors of the script are only reported then.
+    pub const fn sql(sql: &'static str) -> EmbeddedDb {
+        EmbeddedDb {
+            source: Source::Sql(sql),
+            image: Mutex::new(None),
+        }
+    }
+
+    /// A database with the content `image`, that of a database file.
+    pub const fn image(image: &'static [u8]) -> EmbeddedDb {
+        EmbeddedDb {
+            source: Source::Image(image),
+            image: Mutex::new(None),
+        }
+    }
+
+    /// Opens an in-memory copy of the database, which can be modified without
+    /// affecting other copies.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the SQL script fails, or if the underlying SQLite
+    /// calls fail.
+    pub fn open(&self) -> Result<Connection> {
+        let mut db = Connection::open_in_memory()?;
+        match self.source {
+            Source::Image(image) => db.deserialize(DatabaseName::Main, image, false)?,
+            Source::Sql(sql) => {
+                let image = {
+                    let mut cache = self.image.lock().unwrap_or_else(|err| err.into_inner());
+                    match &*cache {
+                        Some(image) => image.clone(),
+                        None => {
+                            let builder = Connection::open_in_memory()?;
+                            builder.execute_batch(sql)?;
+                            let image: Arc<[u8]> = builder.serialize(DatabaseName::Main)?.into();
+                            *cache = Some(image.clone());
+                            image
+                        }
+                    }
+                };
+                db.deserialize(DatabaseName::Main, &image, false)?;
+            }
+        }
+        Ok(db)
+    }
+}
+
+/// `feature = "serialize"` Opens an in-memory copy of a database file
+/// embedded in the binary, and returns a `Result<Connection>`.
+///
+/// The path of the file is relative to the current file, like for
+/// [`include_bytes!`]. To build the database from a SQL script, use
+/// [`EmbeddedDb::sql`](crate::serialize::EmbeddedDb::sql) instead.
+///
+/// ```rust,ignore
+/// let db = rusqlite::embedded_db!("fixtures/variants.db")?;
+/// ```
+#[macro_export]
+macro_rules! embedded_db {
+    ($path:literal) => {{
+        static DB: $crate::serialize::EmbeddedDb =
+            $crate::serialize::EmbeddedDb::image(include_bytes!($path));
+        DB.open()
+    }};
+}
+
+#[cfg(test)]
+mod test {
+    use super::EmbeddedDb;
+    use crate::{Connection, DatabaseName, Result};
+
+    #[test]
+    fn test_serialize_deserialize() -> Result<()> {
+        let src = Connection::open_in_memory()?;
+        assert!(src.serialize(DatabaseName::Main)?.is_empty());
+        src.execute_batch("CREATE TABLE foo (x); INSERT INTO foo VALUES (42);")?;
+        let image = src.serialize(DatabaseName::Main)?;
+        assert_eq!(&image[..16], b"SQLite format 3\0");
+        assert!(src.serialize(DatabaseName::Attached("missing")).is_err());
+
+        let mut dst = Connection::open_in_memory()?;
+        dst.deserialize(DatabaseName::Main, &image, false)?;
+        dst.execute("INSERT INTO foo VALUES (43)", [])?;
+        let sum: i64 = dst.query_row("SELECT sum(x) FROM foo", [], |r| r.get(0))?;
+        assert_eq!(sum, 85);
+
+        dst.execute_batch("ATTACH ':memory:' AS ro")?;
+        dst.deserialize(DatabaseName::Attached("ro"), &image, true)?;
+        let x: i64 = dst.query_row("SELECT x FROM ro.foo", [], |r| r.get(0))?;
+        assert_eq!(x, 42);
+        assert!(dst.execute("INSERT INTO ro.foo VALUES (1)", []).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_embedded_db() -> Result<()> {
+        static FIXTURE: EmbeddedDb =
+            EmbeddedDb::sql("CREATE TABLE foo (x); INSERT INTO foo VALUES (1);");
+        let a = FIXTURE.open()?;
+        a.execute("INSERT INTO foo VALUES (2)", [])?;
+        // Each copy is independent.
+        let b = FIXTURE.open()?;
+        let count: i64 = b.query_row("SELECT count(*) FROM foo", [], |r| r.get(0))?;
+        assert_eq!(count, 1);
+
+        let image = a.serialize(DatabaseName::Main)?.into_boxed_slice();
+        let image = EmbeddedDb::image(Box::leak(image)).open()?;
+        let count: i64 = image.query_row("SELECT count(*) FROM foo", [], |r| r.get(0))?;
+        assert_eq!(count, 2);
+
+        static BROKEN: EmbeddedDb = EmbeddedDb::sql("CREATE TABLE");
+        assert!(BROKEN.open().is_err());
+        Ok(())
+    }
+}
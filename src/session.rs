@@ -40,7 +40,7 @@ impl Session<'_> {
         db: &'conn Connection,
         name: DatabaseName<'_>,
     ) -> Result<Session<'conn>> {
-        let name = name.to_cstring()?;
+        let name = name.to_sql_str()?;
 
         let db = db.db.borrow_mut().db;
 
@@ -161,7 +161,7 @@ impl Session<'_> {
 
     /// Load the difference between tables.
     pub fn diff(&mut self, from: DatabaseName<'_>, table: &str) -> Result<()> {
-        let from = from.to_cstring()?;
+        let from = from.to_sql_str()?;
         let table = str_to_cstring(table)?;
         let table = table.as_ptr();
         unsafe {
@@ -585,6 +585,72 @@ impl Drop for Changegroup {
     }
 }
 
+/// `feature = "session"` Used to rebase a changeset on the changes applied
+/// locally, as recorded by [`Connection::apply_with_rebase`], so that the
+/// remote peers which apply it resolve conflicts as the local database did.
+pub struct Rebaser {
+    r: *mut ffi::sqlite3_rebaser,
+}
+
+impl Rebaser {
+    /// Create a new rebaser.
+    #[inline]
+    pub fn new() -> Result<Self> {
+        let mut r = ptr::null_mut();
+        check!(unsafe { ffi::sqlite3rebaser_create(&mut r) });
+        Ok(Rebaser { r })
+    }
+
+    /// Add the rebase buffer returned by
+    /// [`Connection::apply_with_rebase`].
+    #[inline]
+    pub fn configure(&mut self, rebase: &[u8]) -> Result<()> {
+        check!(unsafe {
+            ffi::sqlite3rebaser_configure(
+                self.r,
+                rebase.len() as c_int,
+                rebase.as_ptr() as *const c_void,
+            )
+        });
+        Ok(())
+    }
+
+    /// Rebase a changeset
+    #[inline]
+    pub fn rebase(&mut self, cs: &Changeset) -> Result<Changeset> {
+        let mut n = 0;
+        let mut output: *mut c_void = ptr::null_mut();
+        check!(unsafe { ffi::sqlite3rebaser_rebase(self.r, cs.n, cs.cs, &mut n, &mut output) });
+        Ok(Changeset { cs: output, n })
+    }
+
+    /// Rebase the changeset read from `input`, and write it to `output`.
+    #[inline]
+    pub fn rebase_strm(&mut self, input: &mut dyn Read, output: &mut dyn Write) -> Result<()> {
+        let input_ref = &input;
+        let output_ref = &output;
+        check!(unsafe {
+            ffi::sqlite3rebaser_rebase_strm(
+                self.r,
+                Some(x_input),
+                input_ref as *const &mut dyn Read as *mut c_void,
+                Some(x_output),
+                output_ref as *const &mut dyn Write as *mut c_void,
+            )
+        });
+        Ok(())
+    }
+}
+
+impl Drop for Rebaser {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe {
+            ffi::sqlite3rebaser_delete(self.r);
+        }
+    }
+}
+
 impl Connection {
     /// `feature = "session"` Apply a changeset to a database
     pub fn apply<F, C>(&self, cs: &Changeset, filter: Option<F>, conflict: C) -> Result<()>
@@ -620,6 +686,53 @@ impl Connection {
         Ok(())
     }
 
+    /// `feature = "session"` Apply a changeset to a database, and return the
+    /// rebase buffer describing how conflicts were resolved, for a
+    /// [`Rebaser`].
+    pub fn apply_with_rebase<F, C>(
+        &self,
+        cs: &Changeset,
+        filter: Option<F>,
+        conflict: C,
+    ) -> Result<Vec<u8>>
+    where
+        F: Fn(&str) -> bool + Send + RefUnwindSafe + 'static,
+        C: Fn(ConflictType, ChangesetItem) -> ConflictAction + Send + RefUnwindSafe + 'static,
+    {
+        let db = self.db.borrow_mut().db;
+
+        let filtered = filter.is_some();
+        let tuple = &mut (filter, conflict);
+        let mut rebase: *mut c_void = ptr::null_mut();
+        let mut n = 0;
+        let rc = unsafe {
+            ffi::sqlite3changeset_apply_v2(
+                db,
+                cs.n,
+                cs.cs,
+                if filtered {
+                    Some(call_filter::<F, C>)
+                } else {
+                    None
+                },
+                Some(call_conflict::<F, C>),
+                tuple as *mut (Option<F>, C) as *mut c_void,
+                &mut rebase,
+                &mut n,
+                0,
+            )
+        };
+        let buffer = if rebase.is_null() {
+            Vec::new()
+        } else {
+            let buffer = unsafe { from_raw_parts(rebase as *const u8, n as usize) }.to_vec();
+            unsafe { ffi::sqlite3_free(rebase) };
+            buffer
+        };
+        check!(rc);
+        Ok(buffer)
+    }
+
     /// `feature = "session"` Apply a changeset to a database
     pub fn apply_strm<F, C>(
         &self,
@@ -779,7 +892,7 @@ mod test {
     use std::io::Read;
     use std::sync::atomic::{AtomicBool, Ordering};
 
-    use super::{Changeset, ChangesetIter, ConflictAction, ConflictType, Session};
+    use super::{Changeset, ChangesetIter, ConflictAction, ConflictType, Rebaser, Session};
     use crate::hooks::Action;
     use crate::{Connection, Result};
 
@@ -906,6 +1019,29 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_rebaser() -> Result<()> {
+        let changeset = one_changeset()?;
+
+        let db = Connection::open_in_memory()?;
+        db.execute_batch("CREATE TABLE foo(t TEXT PRIMARY KEY NOT NULL);")?;
+        db.execute("INSERT INTO foo (t) VALUES (?);", ["bar"])?;
+
+        let rebase = db.apply_with_rebase(
+            &changeset,
+            None::<fn(&str) -> bool>,
+            |_conflict_type, _item| ConflictAction::SQLITE_CHANGESET_REPLACE,
+        )?;
+        assert!(!rebase.is_empty());
+
+        let mut rebaser = Rebaser::new()?;
+        rebaser.configure(&rebase)?;
+        let rebased = rebaser.rebase(&changeset)?;
+        let mut iter = rebased.iter()?;
+        assert!(iter.next()?.is_some());
+        Ok(())
+    }
+
     #[test]
     fn test_session_empty() -> Result<()> {
         let db = Connection::open_in_memory()?;
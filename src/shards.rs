@@ -0,0 +1,381 @@
+//! `feature = "shards"` Split a dataset across database files, attached on
+//! demand.
+//!
+//! A [`ShardManager`] maps logical shards (e.g. chromosomes or months) to
+//! database files, and attaches them to its connection as they are used. As
+//! SQLite limits the number of attached databases
+//! ([`SQLITE_LIMIT_ATTACHED`](crate::limits::Limit::SQLITE_LIMIT_ATTACHED), 10
+//! by default), the least recently used shards are detached to make room.
+//!
+//! Queries name the shard's schema with a `{shard}` placeholder, which is
+//! replaced by the (quoted) schema name the shard is attached as.
+//!
+//! SQLite commits a transaction writing to several attached databases
+//! atomically, with a super-journal, under some conditions checked by
+//! [`ShardManager::check_atomic_commit`]; [`ShardManager::atomic`] runs such
+//! a transaction over a set of shards.
+//!
+//! ```rust,no_run
+//! use rusqlite::shards::ShardManager;
+//! use rusqlite::{Connection, Result};
+//!
+//! fn variant_count(conn: &Connection, chromosome: &str) -> Result<i64> {
+//!     let shards = ShardManager::new(conn, |chr| format!("variants-{}.db", chr).into());
+//!     shards
+//!         .prepare(chromosome, "SELECT count(*) FROM {shard}.variants")?
+//!         .query_row([], |row| row.get(0))
+//! }
+//! ```
+
+use std::cell::RefCell;
+use std::fmt;
+use std::path::PathBuf;
+
+use hashlink::LinkedHashMap;
+
+use crate::limits::Limit;
+use crate::util::quote_identifier;
+use crate::{Connection, DatabaseName, Error, Result, Statement, Transaction, TransactionBehavior};
+
+/// Attaches the database files of shards to a connection, on demand.
+pub struct ShardManager<'conn> {
+    conn: &'conn Connection,
+    path: Box<dyn Fn(&str) -> PathBuf + 'conn>,
+    // Attached shards, from the least to the most recently used.
+    attached: RefCell<LinkedHashMap<String, ()>>,
+}
+
+impl fmt::Debug for ShardManager<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShardManager")
+            .field("conn", &self.conn)
+            .field("attached", &self.attached())
+            .finish()
+    }
+}
+
+impl<'conn> ShardManager<'conn> {
+    /// Creates a manager attaching each shard from the file `path(shard)`,
+    /// which is created if it doesn't exist.
+    pub fn new<F>(conn: &'conn Connection, path: F) -> ShardManager<'conn>
+    where
+        F: Fn(&str) -> PathBuf + 'conn,
+    {
+        ShardManager {
+            conn,
+            path: Box::new(path),
+            attached: RefCell::new(LinkedHashMap::new()),
+        }
+    }
+
+    /// Attaches `shard`, if it isn't already, detaching the least recently
+    /// used shard if the limit of attached databases is reached, and returns
+    /// the quoted schema name it is attached as.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the path of the shard is not valid UTF-8, if the
+    /// limit is reached and no shard can be detached (e.g. because all are
+    /// in use by a statement or a transaction), or if the underlying SQLite
+    /// call fails.
+    pub fn attach(&self, shard: &str) -> Result<String> {
+        let schema = schema(shard);
+        let mut attached = self.attached.borrow_mut();
+        if attached.remove(shard).is_some() {
+            // Now the most recently used.
+            attached.insert(shard.to_owned(), ());
+            return Ok(schema);
+        }
+        let path = (self.path)(shard);
+        let path = path
+            .to_str()
+            .ok_or_else(|| Error::InvalidPath(path.clone()))?;
+        let limit = self.conn.limit(Limit::SQLITE_LIMIT_ATTACHED) as usize;
+        while self.attached_databases()? >= limit {
+            let lru = match attached.front() {
+                Some((lru, _)) => lru.clone(),
+                None => break, // ATTACH reports the error.
+            };
+            self.conn.execute("DETACH ?", [schema_name(&lru)])?;
+            attached.remove(&lru);
+        }
+        self.conn
+            .execute("ATTACH ? AS ?", [path, schema_name(shard).as_str()])?;
+        attached.insert(shard.to_owned(), ());
+        Ok(schema)
+    }
+
+    /// Attaches `shard`, and prepares `sql` with its `{shard}` placeholders
+    /// replaced by the shard's schema name.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the shard cannot be attached, or if `sql` cannot
+    /// be prepared.
+    pub fn prepare(&self, shard: &str, sql: &str) -> Result<Statement<'conn>> {
+        let schema = self.attach(shard)?;
+        self.conn.prepare(&sql.replace("{shard}", &schema))
+    }
+
+    /// Detaches `shard`, and returns whether it was attached.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the shard is in use, or if the underlying SQLite
+    /// call fails.
+    pub fn detach(&self, shard: &str) -> Result<bool> {
+        let mut attached = self.attached.borrow_mut();
+        if !attached.contains_key(shard) {
+            return Ok(false);
+        }
+        self.conn.execute("DETACH ?", [schema_name(shard)])?;
+        attached.remove(shard);
+        Ok(true)
+    }
+
+    /// Detaches all the shards.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if a shard is in use, or if the underlying SQLite
+    /// call fails.
+    pub fn detach_all(&self) -> Result<()> {
+        for shard in self.attached() {
+            self.detach(&shard)?;
+        }
+        Ok(())
+    }
+
+    /// Attaches `shards`, and checks that a transaction writing to them and
+    /// to the main database is committed atomically: either all or none of
+    /// the databases have its changes, even after a crash. This requires
+    /// that
+    ///
+    /// * the main database is a file (the super-journal is named after it),
+    ///   and so are the shards;
+    /// * all the databases use a rollback journal: their `journal_mode` is
+    ///   `DELETE`, `TRUNCATE` or `PERSIST` (not `WAL`, `MEMORY` or `OFF`);
+    /// * their `synchronous` setting is not `OFF`;
+    /// * all the shards can be attached at the same time.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err(Error::NonAtomicCommit)` with the problems found if
+    /// the commit would not be atomic, or `Err` if a shard cannot be
+    /// attached.
+    pub fn check_atomic_commit(&self, shards: &[&str]) -> Result<()> {
+        for shard in shards {
+            self.attach(shard)?;
+        }
+        let mut problems = Vec::new();
+        let mut schemas = vec![("main".to_owned(), "main database".to_owned())];
+        for shard in shards {
+            if self.attached.borrow().contains_key(*shard) {
+                schemas.push((schema_name(shard), format!("shard {}", shard)));
+            } else {
+                problems.push(format!("shard {} is not attached: too many shards", shard));
+            }
+        }
+        for (schema, name) in &schemas {
+            let db = DatabaseName::Attached(schema);
+            let file: Option<String> = self.conn.query_row(
+                "SELECT file FROM pragma_database_list WHERE name = ?",
+                [schema],
+                |row| row.get(0),
+            )?;
+            if !matches!(file, Some(file) if !file.is_empty()) {
+                problems.push(format!("{} is not a file", name));
+            }
+            let mode: String = self
+                .conn
+                .pragma_query_value(Some(db), "journal_mode", |row| row.get(0))?;
+            if !["delete", "truncate", "persist"].contains(&mode.to_ascii_lowercase().as_str()) {
+                problems.push(format!("{} uses journal_mode {}", name, mode));
+            }
+            let synchronous: i64 =
+                self.conn
+                    .pragma_query_value(Some(db), "synchronous", |row| row.get(0))?;
+            if synchronous == 0 {
+                problems.push(format!("{} uses synchronous OFF", name));
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::NonAtomicCommit(problems))
+        }
+    }
+
+    /// Checks that a transaction over the main database and `shards` is
+    /// atomic (see [`check_atomic_commit`](ShardManager::check_atomic_commit)),
+    /// and runs `f` in such a transaction, started with `BEGIN IMMEDIATE` so
+    /// that it holds the write locks of all the databases. The transaction
+    /// is committed if `f` returns `Ok`, and rolled back otherwise.
+    ///
+    /// `f` can only use the main database and `shards`: other shards cannot
+    /// be attached during a transaction.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the commit would not be atomic, if the
+    /// transaction cannot be started or committed, or the error of `f`.
+    pub fn atomic<T, F>(&self, shards: &[&str], f: F) -> Result<T>
+    where
+        F: FnOnce(&ShardManager<'conn>) -> Result<T>,
+    {
+        self.check_atomic_commit(shards)?;
+        let tx = Transaction::new_unchecked(self.conn, TransactionBehavior::Immediate)?;
+        let result = f(self)?;
+        tx.commit()?;
+        Ok(result)
+    }
+
+    /// Returns the attached shards, from the least to the most recently used.
+    pub fn attached(&self) -> Vec<String> {
+        self.attached.borrow().keys().cloned().collect()
+    }
+
+    // The number of attached databases, including those not attached by this
+    // manager.
+    fn attached_databases(&self) -> Result<usize> {
+        let mut count = 0;
+        self.conn.pragma_query(None, "database_list", |row| {
+            let name = row.get_ref(1)?;
+            if name.as_str()? != "main" && name.as_str()? != "temp" {
+                count += 1;
+            }
+            Ok(())
+        })?;
+        Ok(count)
+    }
+}
+
+fn schema_name(shard: &str) -> String {
+    format!("shard_{}", shard)
+}
+
+fn schema(shard: &str) -> String {
+    quote_identifier(&schema_name(shard))
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use tempfile::tempdir;
+
+    use super::ShardManager;
+    use crate::limits::Limit;
+    use crate::{Connection, DatabaseName, Error, Result};
+
+    fn count(shards: &ShardManager<'_>, shard: &str) -> Result<i64> {
+        shards
+            .prepare(shard, "SELECT count(*) FROM {shard}.variants")?
+            .query_row([], |r| r.get(0))
+    }
+
+    #[test]
+    fn test_attach_on_demand() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db = Connection::open_in_memory()?;
+        db.set_limit(Limit::SQLITE_LIMIT_ATTACHED, 3);
+        db.execute_batch("ATTACH ':memory:' AS other")?;
+        let path = dir.path().to_owned();
+        let shards = ShardManager::new(&db, move |chr| path.join(format!("{}.db", chr)));
+
+        for (i, chr) in ["chr1", "chr\"2", "chrX"].iter().enumerate() {
+            shards
+                .prepare(chr, "CREATE TABLE {shard}.variants (pos INTEGER)")?
+                .execute([])?;
+            for pos in 0..=i {
+                shards
+                    .prepare(chr, "INSERT INTO {shard}.variants VALUES (?)")?
+                    .execute([pos as i64])?;
+            }
+        }
+        // Only two shards fit besides `other`.
+        assert_eq!(shards.attached(), vec!["chr\"2", "chrX"]);
+        assert!(Path::new(&dir.path().join("chr1.db")).exists());
+
+        assert_eq!(count(&shards, "chr1")?, 1);
+        assert_eq!(shards.attached(), vec!["chrX", "chr1"]);
+        assert_eq!(count(&shards, "chrX")?, 3);
+        assert_eq!(count(&shards, "chr\"2")?, 2);
+        assert_eq!(shards.attached(), vec!["chrX", "chr\"2"]);
+
+        assert!(shards.detach("chrX")?);
+        assert!(!shards.detach("chrX")?);
+        shards.detach_all()?;
+        assert!(shards.attached().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_shard_in_use() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.set_limit(Limit::SQLITE_LIMIT_ATTACHED, 1);
+        let shards = ShardManager::new(&db, |_| ":memory:".into());
+        shards.attach("a")?;
+        db.execute_batch("BEGIN; CREATE TABLE shard_a.t (x)")?;
+        // `a` cannot be detached while a transaction uses it.
+        assert!(shards.attach("b").is_err());
+        assert_eq!(shards.attached(), vec!["a"]);
+        db.execute_batch("COMMIT")?;
+        shards.attach("b")?;
+        assert_eq!(shards.attached(), vec!["b"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_atomic() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db = Connection::open(dir.path().join("main.db"))?;
+        let path = dir.path().to_owned();
+        let shards = ShardManager::new(&db, move |chr| path.join(format!("{}.db", chr)));
+        for chr in &["chr1", "chr2"] {
+            shards
+                .prepare(chr, "CREATE TABLE {shard}.variants (pos INTEGER)")?
+                .execute([])?;
+        }
+        let insert = |shards: &ShardManager<'_>| -> Result<()> {
+            for chr in &["chr1", "chr2"] {
+                shards
+                    .prepare(chr, "INSERT INTO {shard}.variants VALUES (1)")?
+                    .execute([])?;
+            }
+            Ok(())
+        };
+
+        let err = shards.atomic(&["chr1", "chr2"], |shards| {
+            insert(shards)?;
+            Err::<(), _>(Error::QueryReturnedNoRows)
+        });
+        assert_eq!(err, Err(Error::QueryReturnedNoRows));
+        assert_eq!((count(&shards, "chr1")?, count(&shards, "chr2")?), (0, 0));
+        shards.atomic(&["chr1", "chr2"], insert)?;
+        assert_eq!((count(&shards, "chr1")?, count(&shards, "chr2")?), (1, 1));
+
+        let schema = Some(DatabaseName::Attached("shard_chr2"));
+        db.pragma_update_and_check(schema, "journal_mode", &"WAL", |_| Ok(()))?;
+        assert_eq!(
+            shards.check_atomic_commit(&["chr1", "chr2"]),
+            Err(Error::NonAtomicCommit(vec![
+                "shard chr2 uses journal_mode wal".to_owned()
+            ]))
+        );
+
+        let db = Connection::open_in_memory()?;
+        db.set_limit(Limit::SQLITE_LIMIT_ATTACHED, 1);
+        let path = dir.path().to_owned();
+        let shards = ShardManager::new(&db, move |chr| path.join(format!("{}.db", chr)));
+        assert_eq!(
+            shards.check_atomic_commit(&["chr1", "chr3"]),
+            Err(Error::NonAtomicCommit(vec![
+                "shard chr1 is not attached: too many shards".to_owned(),
+                "main database is not a file".to_owned(),
+                "main database uses journal_mode memory".to_owned(),
+            ]))
+        );
+        Ok(())
+    }
+}
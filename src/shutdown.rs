@@ -0,0 +1,178 @@
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+use crate::{CloseBlockers, Connection, Error};
+
+/// What [`Connection::shutdown`] left undone.
+#[derive(Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct ShutdownReport {
+    /// Whether statements still running at the deadline were interrupted.
+    pub interrupted: bool,
+    /// The number of frames of the write-ahead log which could not be
+    /// copied to the database, e.g. because of readers on other
+    /// connections, or `-1` if checkpointing failed. `0` when the database is
+    /// not in WAL mode.
+    pub wal_frames_left: i64,
+    /// Why checkpointing failed.
+    pub checkpoint_error: Option<Error>,
+    /// Why closing the connection failed.
+    pub close_error: Option<Error>,
+    /// What prevented closing the connection.
+    pub close_blockers: CloseBlockers,
+}
+
+impl Connection {
+    /// Shut the connection down by `deadline`: checkpoint the write-ahead
+    /// log and truncate it, waiting for other connections until the
+    /// deadline, then close the connection.
+    ///
+    /// The connection is consumed, so no new work can be started through
+    /// it. Any statement still running at the deadline, including the
+    /// checkpoint or one stepped through the raw handle on another thread,
+    /// is interrupted; closing is retried until the deadline, as
+    /// [`close_with_timeout`](Connection::close_with_timeout) does.
+    ///
+    /// To wait for other connections, the busy handler of the connection is
+    /// replaced by a [`busy_timeout`](Connection::busy_timeout) lasting until
+    /// the deadline. SQLite cannot return the previous handler, so it is not
+    /// restored: set it again before using a connection returned in `Err`.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err`, with the connection, if it could not be closed by
+    /// the deadline. Both the result and the error report what was left
+    /// undone.
+    #[allow(clippy::result_large_err)] // Returns the connection, as `close`.
+    pub fn shutdown(
+        self,
+        deadline: Instant,
+    ) -> Result<ShutdownReport, (Connection, ShutdownReport)> {
+        let mut report = ShutdownReport::default();
+
+        let (done, cancelled) = mpsc::channel::<()>();
+        let handle = self.get_interrupt_handle();
+        let watchdog = thread::spawn(move || {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let timed_out = matches!(
+                cancelled.recv_timeout(timeout),
+                Err(mpsc::RecvTimeoutError::Timeout)
+            );
+            if timed_out {
+                handle.interrupt();
+            }
+            timed_out
+        });
+
+        let checkpoint = self
+            .busy_timeout(deadline.saturating_duration_since(Instant::now()))
+            .and_then(|_| {
+                self.query_row("PRAGMA main.wal_checkpoint(TRUNCATE)", [], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, i64>(2)?,
+                    ))
+                })
+            });
+        match checkpoint {
+            Ok((busy, log, checkpointed)) => {
+                if busy != 0 && log > 0 {
+                    report.wal_frames_left = log - checkpointed.max(0);
+                }
+            }
+            Err(err) => {
+                report.wal_frames_left = -1;
+                report.checkpoint_error = Some(err);
+            }
+        }
+        drop(done);
+        report.interrupted = watchdog.join().unwrap_or(false);
+
+        match self.close_with_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(()) => Ok(report),
+            Err((conn, err)) => {
+                report.close_error = Some(err);
+                report.close_blockers = conn.close_blockers();
+                Err((conn, report))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::{ffi, Connection, Error, ErrorCode, Result};
+
+    #[test]
+    fn test_shutdown_truncates_wal() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shutdown.db3");
+        let db = Connection::open(&path)?;
+        db.pragma_update_and_check(None, "journal_mode", &"WAL", |_| Ok(()))?;
+        db.pragma_update_and_check(None, "wal_autocheckpoint", &0, |_| Ok(()))?;
+        db.execute_batch(
+            "CREATE TABLE samples (name TEXT);
+             INSERT INTO samples VALUES ('NA12878');",
+        )?;
+        let reader = Connection::open(&path)?;
+
+        let report = db
+            .shutdown(Instant::now() + Duration::from_secs(5))
+            .unwrap();
+        assert!(!report.interrupted);
+        assert_eq!(report.wal_frames_left, 0);
+        assert_eq!(report.checkpoint_error, None);
+        // Closing the last connection removes the log.
+        let wal = std::fs::metadata(dir.path().join("shutdown.db3-wal"));
+        assert_eq!(wal.map_or(0, |wal| wal.len()), 0);
+        let count: i64 = reader.query_row("SELECT count(*) FROM samples", [], |r| r.get(0))?;
+        assert_eq!(count, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shutdown_reports_leftovers() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shutdown.db3");
+        let db = Connection::open(&path)?;
+        db.pragma_update_and_check(None, "journal_mode", &"WAL", |_| Ok(()))?;
+        db.pragma_update_and_check(None, "wal_autocheckpoint", &0, |_| Ok(()))?;
+        db.execute_batch("CREATE TABLE samples (name TEXT);")?;
+        // A reader keeps the frames written after its snapshot in the log.
+        let reader = Connection::open(&path)?;
+        reader.execute_batch("BEGIN")?;
+        reader.query_row("SELECT count(*) FROM samples", [], |r| r.get::<_, i64>(0))?;
+        db.execute("INSERT INTO samples VALUES ('NA12878')", [])?;
+        std::mem::forget(db.prepare("SELECT 42")?);
+
+        let start = Instant::now();
+        let (db, report) = db
+            .shutdown(Instant::now() + Duration::from_millis(100))
+            .unwrap_err();
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert_ne!(report.wal_frames_left, 0);
+        match report.close_error {
+            Some(Error::SqliteFailure(err, _)) => assert_eq!(err.code, ErrorCode::DatabaseBusy),
+            err => panic!("Unexpected error {:?}", err),
+        }
+        assert_eq!(
+            report.close_blockers.unfinalized_statements,
+            vec!["SELECT 42"]
+        );
+
+        unsafe {
+            let stmt = ffi::sqlite3_next_stmt(db.handle(), std::ptr::null_mut());
+            assert_eq!(ffi::SQLITE_OK, ffi::sqlite3_finalize(stmt));
+        }
+        reader.execute_batch("COMMIT")?;
+        let report = db
+            .shutdown(Instant::now() + Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(report.wal_frames_left, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,626 @@
+//! `feature = "sqlar"` Read and write [SQLite Archives](https://sqlite.org/sqlar.html),
+//! the archive format of the `-A` option of the `sqlite3` shell.
+//!
+//! An archive is a database with a `sqlar` table, which has a row per file,
+//! directory or symbolic link. The contents of files are stored compressed
+//! with zlib when it makes them smaller, as the shell does; an [`Archive`]
+//! compresses them with `miniz_oxide`, unless given another compression
+//! function.
+//!
+//! ```rust,no_run
+//! use std::path::Path;
+//! use rusqlite::sqlar::Archive;
+//! use rusqlite::Connection;
+//!
+//! fn unpack(archive: &Path, dir: &Path) -> std::io::Result<()> {
+//!     let conn = Connection::open(archive).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+//!     let archive = Archive::new(&conn).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+//!     archive.extract_all(dir)
+//! }
+//! ```
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Component, Path};
+use std::time::UNIX_EPOCH;
+
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use miniz_oxide::inflate::decompress_to_vec_zlib_with_limit;
+
+use crate::export::to_io;
+use crate::{ffi, params, Connection, DatabaseName, Error, OptionalExtension, Result, Savepoint};
+
+const S_IFMT: u32 = 0o170_000;
+const S_IFDIR: u32 = 0o040_000;
+const S_IFLNK: u32 = 0o120_000;
+const S_IFREG: u32 = 0o100_000;
+
+/// The kind of an archive entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryKind {
+    /// A file
+    File,
+    /// A directory
+    Directory,
+    /// A symbolic link
+    Symlink,
+}
+
+/// An entry of an archive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Entry {
+    /// The path of the entry, relative, with `/` separators.
+    pub name: String,
+    /// The Unix mode of the entry: its kind and permissions.
+    pub mode: u32,
+    /// The modification time, in seconds since the Unix epoch.
+    pub mtime: i64,
+    /// The size of a file, `0` for a directory, and `-1` for a symbolic
+    /// link.
+    pub size: i64,
+}
+
+impl Entry {
+    /// Returns the kind of the entry.
+    pub fn kind(&self) -> EntryKind {
+        match self.mode & S_IFMT {
+            S_IFDIR => EntryKind::Directory,
+            S_IFLNK => EntryKind::Symlink,
+            _ if self.size == -1 => EntryKind::Symlink,
+            _ => EntryKind::File,
+        }
+    }
+
+    /// Returns the permission bits of the mode.
+    pub fn permissions(&self) -> u32 {
+        self.mode & 0o7777
+    }
+}
+
+/// A function compressing data to a zlib stream, as `compress` of zlib.
+pub type CompressFn = fn(&[u8]) -> Vec<u8>;
+
+// The default compression, at the default level of zlib.
+fn compress_zlib(data: &[u8]) -> Vec<u8> {
+    compress_to_vec_zlib(data, 6)
+}
+
+/// The `sqlar` table of a database.
+#[derive(Debug)]
+pub struct Archive<'conn> {
+    conn: &'conn Connection,
+    compress: CompressFn,
+}
+
+impl<'conn> Archive<'conn> {
+    /// Opens the archive in the `main` database of `conn`, creating its
+    /// table if needed.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the table cannot be created.
+    pub fn new(conn: &'conn Connection) -> Result<Archive<'conn>> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS sqlar(
+                 name TEXT PRIMARY KEY,
+                 mode INT,
+                 mtime INT,
+                 sz INT,
+                 data BLOB
+             )",
+        )?;
+        Ok(Archive {
+            conn,
+            compress: compress_zlib,
+        })
+    }
+
+    /// Sets the function compressing the contents of the files added, in
+    /// place of `miniz_oxide` at the default level. Compressed contents are
+    /// only stored when smaller, so a function returning its input as is
+    /// disables compression.
+    #[must_use]
+    pub fn compress(mut self, compress: CompressFn) -> Archive<'conn> {
+        self.compress = compress;
+        self
+    }
+
+    /// Returns the entries of the archive, ordered by name.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite calls fail.
+    pub fn entries(&self) -> Result<Vec<Entry>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name, mode, mtime, sz FROM sqlar ORDER BY name")?;
+        let entries = stmt.query_map([], |row| {
+            Ok(Entry {
+                name: row.get(0)?,
+                mode: row.get(1)?,
+                mtime: row.get(2)?,
+                size: row.get(3)?,
+            })
+        })?;
+        entries.collect()
+    }
+
+    /// Returns the entry `name`, if any.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite calls fail.
+    pub fn entry(&self, name: &str) -> Result<Option<Entry>> {
+        self.conn
+            .query_row(
+                "SELECT name, mode, mtime, sz FROM sqlar WHERE name = ?",
+                [name],
+                |row| {
+                    Ok(Entry {
+                        name: row.get(0)?,
+                        mode: row.get(1)?,
+                        mtime: row.get(2)?,
+                        size: row.get(3)?,
+                    })
+                },
+            )
+            .optional()
+    }
+
+    /// Adds, or replaces, the file `name` with `data`.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite calls fail.
+    pub fn add_file(&self, name: &str, permissions: u32, mtime: i64, data: &[u8]) -> Result<()> {
+        let compressed = (self.compress)(data);
+        let stored = if compressed.len() < data.len() {
+            &compressed
+        } else {
+            data
+        };
+        self.conn.execute(
+            "REPLACE INTO sqlar (name, mode, mtime, sz, data) VALUES (?, ?, ?, ?, ?)",
+            params![
+                name,
+                S_IFREG | permissions & 0o7777,
+                mtime,
+                data.len() as i64,
+                stored
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Adds, or replaces, the directory `name`.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite calls fail.
+    pub fn add_dir(&self, name: &str, permissions: u32, mtime: i64) -> Result<()> {
+        self.conn.execute(
+            "REPLACE INTO sqlar (name, mode, mtime, sz, data) VALUES (?, ?, ?, 0, NULL)",
+            params![name, S_IFDIR | permissions & 0o7777, mtime],
+        )?;
+        Ok(())
+    }
+
+    /// Adds, or replaces, the symbolic link `name` to `target`.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite calls fail.
+    pub fn add_symlink(&self, name: &str, target: &str, mtime: i64) -> Result<()> {
+        self.conn.execute(
+            "REPLACE INTO sqlar (name, mode, mtime, sz, data) VALUES (?, ?, ?, -1, ?)",
+            params![name, S_IFLNK | 0o777, mtime, target.as_bytes()],
+        )?;
+        Ok(())
+    }
+
+    /// Removes the entry `name`, and returns whether it existed.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite calls fail.
+    pub fn remove(&self, name: &str) -> Result<bool> {
+        Ok(self
+            .conn
+            .execute("DELETE FROM sqlar WHERE name = ?", [name])?
+            > 0)
+    }
+
+    /// Returns the contents of the file `name`, or the target of the
+    /// symbolic link `name`.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if there is no entry `name`
+    /// ([`Error::QueryReturnedNoRows`]), if its contents cannot be
+    /// uncompressed, or if the underlying SQLite calls fail.
+    pub fn read(&self, name: &str) -> Result<Vec<u8>> {
+        let (size, data): (i64, Option<Vec<u8>>) =
+            self.conn
+                .query_row("SELECT sz, data FROM sqlar WHERE name = ?", [name], |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })?;
+        let data = data.unwrap_or_default();
+        if size < 0 || data.len() as i64 == size {
+            return Ok(data);
+        }
+        // The limit bounds the output to the size recorded, however large.
+        match decompress_to_vec_zlib_with_limit(&data, size as usize) {
+            Ok(out) if out.len() as i64 == size => Ok(out),
+            _ => Err(corrupt(name)),
+        }
+    }
+
+    /// Writes the contents of the file `name` to `writer`, and returns its
+    /// size. Uncompressed contents are streamed with incremental blob I/O,
+    /// without being loaded in memory.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if writing fails, or if reading the file fails, in
+    /// which case the error wraps the `rusqlite::Error`.
+    pub fn extract_to<W: Write>(&self, name: &str, mut writer: W) -> io::Result<u64> {
+        let (rowid, size, len): (i64, i64, i64) = self
+            .conn
+            .query_row(
+                "SELECT rowid, sz, coalesce(length(data), 0) FROM sqlar WHERE name = ?",
+                [name],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .map_err(to_io)?;
+        if size >= 0 && len == size {
+            if len == 0 {
+                return Ok(0);
+            }
+            let mut blob = self
+                .conn
+                .blob_open(DatabaseName::Main, "sqlar", "data", rowid, true)
+                .map_err(to_io)?;
+            return io::copy(&mut blob, &mut writer);
+        }
+        let data = self.read(name).map_err(to_io)?;
+        writer.write_all(&data)?;
+        Ok(data.len() as u64)
+    }
+
+    /// Adds the file, symbolic link, or directory and its contents,
+    /// at `path` as `name`, in a single transaction.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if reading the files fails, or if the underlying
+    /// SQLite calls fail, in which case the error wraps the
+    /// `rusqlite::Error`.
+    pub fn add_path(&self, path: &Path, name: &str) -> io::Result<()> {
+        let sp = Savepoint::with_depth_and_name(self.conn, 0, "_rusqlite_sqlar").map_err(to_io)?;
+        self.add_path_(path, name)?;
+        sp.commit().map_err(to_io)
+    }
+
+    fn add_path_(&self, path: &Path, name: &str) -> io::Result<()> {
+        let metadata = fs::symlink_metadata(path)?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        let permissions = permissions(&metadata);
+        if metadata.file_type().is_symlink() {
+            let target = fs::read_link(path)?;
+            return self
+                .add_symlink(name, &target.to_string_lossy(), mtime)
+                .map_err(to_io);
+        }
+        if metadata.is_dir() {
+            self.add_dir(name, permissions, mtime).map_err(to_io)?;
+            let mut children = fs::read_dir(path)?.collect::<io::Result<Vec<_>>>()?;
+            children.sort_by_key(fs::DirEntry::file_name);
+            for child in children {
+                let child_name = format!("{}/{}", name, child.file_name().to_string_lossy());
+                self.add_path_(&child.path(), &child_name)?;
+            }
+            return Ok(());
+        }
+        let data = fs::read(path)?;
+        self.add_file(name, permissions, mtime, &data)
+            .map_err(to_io)
+    }
+
+    /// Extracts all the entries to the directory `dir`. Symbolic links are
+    /// only created on Unix, and never written through.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if an entry name is absolute or contains `..`, if
+    /// an entry would be written through a symbolic link, e.g. one of the
+    /// archive, if writing the files fails, or if reading the archive fails,
+    /// in which case the error wraps the `rusqlite::Error`.
+    pub fn extract_all(&self, dir: &Path) -> io::Result<()> {
+        // The permissions of directories are set last, once their entries are
+        // written, from the deepest up.
+        let mut dirs = Vec::new();
+        for entry in self.entries().map_err(to_io)? {
+            let relative = Path::new(&entry.name);
+            if !relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unsafe archive entry name: {}", entry.name),
+                ));
+            }
+            check_no_symlink(dir, relative, &entry.name)?;
+            let path = dir.join(relative);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            match entry.kind() {
+                EntryKind::Directory => {
+                    fs::create_dir_all(&path)?;
+                    dirs.push((path, entry.permissions()));
+                    continue;
+                }
+                EntryKind::Symlink => {
+                    #[cfg(unix)]
+                    {
+                        let target = self.read(&entry.name).map_err(to_io)?;
+                        std::os::unix::fs::symlink(
+                            String::from_utf8_lossy(&target).as_ref(),
+                            &path,
+                        )?;
+                    }
+                    continue;
+                }
+                EntryKind::File => {
+                    let file = fs::File::create(&path)?;
+                    self.extract_to(&entry.name, io::BufWriter::new(file))?;
+                }
+            }
+            set_permissions(&path, entry.permissions())?;
+        }
+        for (path, permissions) in dirs.iter().rev() {
+            set_permissions(path, *permissions)?;
+        }
+        Ok(())
+    }
+}
+
+// Returns an error if the path `relative` in `dir`, or one of its parents, is
+// a symbolic link, through which the entry `name` would be written outside
+// `dir`.
+fn check_no_symlink(dir: &Path, relative: &Path, name: &str) -> io::Result<()> {
+    let mut path = dir.to_path_buf();
+    for component in relative.components() {
+        path.push(component);
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("archive entry {} is written through a symbolic link", name),
+                ));
+            }
+            Ok(_) => {}
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+fn corrupt(name: &str) -> Error {
+    Error::SqliteFailure(
+        ffi::Error::new(ffi::SQLITE_CORRUPT),
+        Some(format!("invalid compressed data for {}", name)),
+    )
+}
+
+#[cfg(unix)]
+fn permissions(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn permissions(metadata: &fs::Metadata) -> u32 {
+    match (metadata.is_dir(), metadata.permissions().readonly()) {
+        (true, _) => 0o755,
+        (false, true) => 0o444,
+        (false, false) => 0o644,
+    }
+}
+
+#[cfg(unix)]
+fn set_permissions(path: &Path, permissions: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(permissions))
+}
+
+#[cfg(not(unix))]
+fn set_permissions(_: &Path, _: u32) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Archive, EntryKind};
+    use crate::{params, Connection, Result};
+
+    // zlib.compress(b"hello hello hello hello\n")
+    const HELLO: [u8; 17] = [
+        0x78, 0x9c, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x27, 0xb9, 0x00, 0x70, 0xbe,
+        0x08, 0xbb,
+    ];
+
+    #[test]
+    fn test_archive() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        let archive = Archive::new(&db)?;
+        archive.add_dir("d", 0o755, 1)?;
+        archive.add_file("d/a.txt", 0o644, 2, b"abc")?;
+        archive.add_symlink("d/link", "a.txt", 3)?;
+        // As written by the shell.
+        db.execute(
+            "INSERT INTO sqlar VALUES ('d/hello', 33188, 4, 24, ?)",
+            [&HELLO[..]],
+        )?;
+
+        let entries = archive.entries()?;
+        let kinds: Vec<_> = entries
+            .iter()
+            .map(|e| (e.name.as_str(), e.kind()))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("d", EntryKind::Directory),
+                ("d/a.txt", EntryKind::File),
+                ("d/hello", EntryKind::File),
+                ("d/link", EntryKind::Symlink)
+            ]
+        );
+        assert_eq!(entries[1].mode, 0o100_644);
+        assert_eq!(entries[1].permissions(), 0o644);
+        assert_eq!(archive.read("d/a.txt")?, b"abc");
+        assert_eq!(archive.read("d/hello")?, b"hello hello hello hello\n");
+        assert_eq!(archive.read("d/link")?, b"a.txt");
+
+        let mut out = Vec::new();
+        assert_eq!(archive.extract_to("d/a.txt", &mut out).unwrap(), 3);
+        assert_eq!(out, b"abc");
+
+        db.execute(
+            "UPDATE sqlar SET data = x'789c00' WHERE name = 'd/hello'",
+            [],
+        )?;
+        assert!(archive.read("d/hello").is_err());
+        assert!(archive.remove("d/hello")?);
+        assert!(archive.entry("d/hello")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_compress() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        let archive = Archive::new(&db)?.compress(|_| HELLO.to_vec());
+        archive.add_file("hello", 0o600, 0, b"hello hello hello hello\n")?;
+        // Not smaller.
+        archive.add_file("short", 0o600, 0, b"hi")?;
+        let sizes: Vec<(i64, i64)> = db
+            .prepare("SELECT sz, length(data) FROM sqlar ORDER BY name")?
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?
+            .collect::<Result<_>>()?;
+        assert_eq!(sizes, [(24, 17), (2, 2)]);
+        assert_eq!(archive.read("hello")?, b"hello hello hello hello\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_compression() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        let archive = Archive::new(&db)?;
+        let data = b"hello ".repeat(100);
+        archive.add_file("hello", 0o600, 0, &data)?;
+        let (size, stored): (i64, i64) =
+            db.query_row("SELECT sz, length(data) FROM sqlar", [], |r| {
+                Ok((r.get(0)?, r.get(1)?))
+            })?;
+        assert_eq!(size, 600);
+        assert!(stored < size, "{}", stored);
+        assert_eq!(archive.read("hello")?, data);
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_and_extract_path() {
+        let src = tempfile::tempdir().unwrap();
+        std::fs::create_dir(src.path().join("sub")).unwrap();
+        std::fs::write(src.path().join("sub/x"), b"xyz").unwrap();
+        std::fs::write(src.path().join("top"), b"").unwrap();
+
+        let db = Connection::open_in_memory().unwrap();
+        let archive = Archive::new(&db).unwrap();
+        archive.add_path(src.path(), "root").unwrap();
+        let names: Vec<String> = archive
+            .entries()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, ["root", "root/sub", "root/sub/x", "root/top"]);
+
+        let dst = tempfile::tempdir().unwrap();
+        archive.extract_all(dst.path()).unwrap();
+        assert_eq!(
+            std::fs::read(dst.path().join("root/sub/x")).unwrap(),
+            b"xyz"
+        );
+        assert!(std::fs::read(dst.path().join("root/top"))
+            .unwrap()
+            .is_empty());
+
+        archive.add_file("../evil", 0o644, 0, b"").unwrap();
+        assert!(archive.extract_all(dst.path()).is_err());
+        assert!(!dst.path().parent().unwrap().join("evil").exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_extract_through_symlink() {
+        let outside = tempfile::tempdir().unwrap();
+        let db = Connection::open_in_memory().unwrap();
+        let archive = Archive::new(&db).unwrap();
+        archive
+            .add_symlink("d/link", outside.path().to_str().unwrap(), 0)
+            .unwrap();
+        archive.add_file("d/link/evil", 0o644, 0, b"").unwrap();
+
+        let dst = tempfile::tempdir().unwrap();
+        let err = archive.extract_all(dst.path()).unwrap_err();
+        assert!(err.to_string().contains("symbolic link"), "{}", err);
+        assert!(!outside.path().join("evil").exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_extract_read_only_dir() {
+        use std::os::unix::fs::PermissionsExt;
+        let db = Connection::open_in_memory().unwrap();
+        let archive = Archive::new(&db).unwrap();
+        archive.add_dir("ro", 0o555, 0).unwrap();
+        archive.add_dir("ro/sub", 0o555, 0).unwrap();
+        archive.add_file("ro/sub/x", 0o444, 0, b"xyz").unwrap();
+
+        let dst = tempfile::tempdir().unwrap();
+        archive.extract_all(dst.path()).unwrap();
+        let ro = dst.path().join("ro");
+        assert_eq!(std::fs::read(ro.join("sub/x")).unwrap(), b"xyz");
+        for dir in &[&ro, &ro.join("sub")] {
+            let mode = std::fs::metadata(dir).unwrap().permissions().mode();
+            assert_eq!(mode & 0o7777, 0o555);
+        }
+        // So that the temporary directory can be removed.
+        for dir in &[&ro, &ro.join("sub")] {
+            std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_size_mismatch() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        let archive = Archive::new(&db)?;
+        // A huge recorded size doesn't allocate, and a different one fails.
+        for size in &[i64::MAX, 23, 25] {
+            db.execute(
+                "REPLACE INTO sqlar VALUES ('hello', 33188, 0, ?, ?)",
+                params![size, &HELLO[..]],
+            )?;
+            assert!(archive.read("hello").is_err());
+        }
+        Ok(())
+    }
+}
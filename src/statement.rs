@@ -10,13 +10,15 @@ use super::{len_as_c_int, str_for_sqlite};
 use super::{
     AndThenRows, Connection, Error, MappedRows, Params, RawStatement, Result, Row, Rows, ValueRef,
 };
+#[cfg(feature = "modern_sqlite")]
+use crate::types::free_pointer;
 use crate::types::{ToSql, ToSqlOutput};
 #[cfg(feature = "array")]
 use crate::vtab::array::{free_array, ARRAY_TYPE};
 
 /// A prepared statement.
 pub struct Statement<'conn> {
-    conn: &'conn Connection,
+    pub(crate) conn: &'conn Connection,
     pub(crate) stmt: RawStatement,
 }
 
@@ -120,6 +122,11 @@ impl Statement<'_> {
     /// statement which _updates_ a single
     /// item rather than inserting one. Please don't do that.
     ///
+    /// Rows of `WITHOUT ROWID` tables have no rowid: the one returned is
+    /// the last one of another table. Check
+    /// [`TableOptions::without_rowid`](crate::schema::TableOptions::without_rowid)
+    /// first if the table can be one.
+    ///
     /// # Failure
     ///
     /// Will return `Err` if no row is inserted or many rows are inserted.
@@ -132,6 +139,38 @@ impl Statement<'_> {
         }
     }
 
+    /// Execute an INSERT, UPDATE or DELETE statement with a `RETURNING` clause
+    /// and map `f` over the returned rows.
+    ///
+    /// SQLite performs all of the statement's changes before the first row is
+    /// returned, so the rows are collected eagerly: the changes are made even
+    /// if `f` fails for one of the rows.
+    ///
+    /// Note that `RETURNING` requires SQLite 3.35.0 or later.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// # use rusqlite::{Connection, Result};
+    /// fn add_people(conn: &Connection) -> Result<Vec<i64>> {
+    ///     let mut stmt =
+    ///         conn.prepare("INSERT INTO people (name) VALUES (?), (?) RETURNING id")?;
+    ///     stmt.execute_returning(["Joe Smith", "Bob Jones"], |row| row.get(0))
+    /// }
+    /// ```
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if binding parameters fails, if the underlying SQLite
+    /// call fails or if `f` fails.
+    pub fn execute_returning<T, P, F>(&mut self, params: P, f: F) -> Result<Vec<T>>
+    where
+        P: Params,
+        F: FnMut(&Row<'_>) -> Result<T>,
+    {
+        self.query_map(params, f)?.collect()
+    }
+
     /// Execute the prepared statement, returning a handle to the resulting
     /// rows.
     ///
@@ -543,17 +582,32 @@ impl Statement<'_> {
         &mut self,
         params: &[(&str, &T)],
     ) -> Result<()> {
-        for &(name, value) in params {
-            if let Some(i) = self.parameter_index(name)? {
-                let ts: &dyn ToSql = &value;
-                self.bind_parameter(ts, i)?;
-            } else {
-                return Err(Error::InvalidParameterName(name.into()));
-            }
+        let indices = self
+            .stmt
+            .bind_parameter_indices(params.iter().map(|&(name, _)| name))
+            .map_err(|name| Error::InvalidParameterName(name.into()))?;
+        for (&(_, value), &i) in params.iter().zip(indices.iter()) {
+            let ts: &dyn ToSql = &value;
+            self.bind_parameter(ts, i)?;
         }
         Ok(())
     }
 
+    /// Set the maximum number of parameter names whose index this statement
+    /// will remember.
+    ///
+    /// Binding named parameters requires looking up the index of each name.
+    /// Those indices are cached, and binding the same set of names as the
+    /// previous call (e.g. when executing a statement repeatedly) skips the
+    /// lookups entirely. The cache is unbounded by default, and lives as long
+    /// as the statement (including in the
+    /// [`prepare_cached`](Connection::prepare_cached) statement cache). A
+    /// capacity of 0 disables it.
+    #[inline]
+    pub fn set_parameter_index_cache_capacity(&self, capacity: usize) {
+        self.stmt.set_parameter_index_cache_capacity(capacity)
+    }
+
     /// Return the number of parameters that can be bound to this statement.
     #[inline]
     pub fn parameter_count(&self) -> usize {
@@ -672,6 +726,19 @@ impl Statement<'_> {
                     )
                 });
             }
+            #[cfg(feature = "modern_sqlite")]
+            ToSqlOutput::Pointer(p) => {
+                let type_name = p.type_name().as_ptr();
+                return self.conn.decode_result(unsafe {
+                    ffi::sqlite3_bind_pointer(
+                        ptr,
+                        col as c_int,
+                        p.into_raw(),
+                        type_name,
+                        Some(free_pointer),
+                    )
+                });
+            }
         };
         self.conn.decode_result(match value {
             ValueRef::Null => unsafe { ffi::sqlite3_bind_null(ptr, col as c_int) },
@@ -701,13 +768,24 @@ impl Statement<'_> {
     #[inline]
     fn execute_with_bound_parameters(&mut self) -> Result<usize> {
         self.check_update()?;
+        self.execute_step(false)
+            .expect("execute_step cannot yield without yield_on_busy")
+    }
+
+    /// Steps a statement checked by `check_update`. Returns `None`, without
+    /// resetting the statement, if it is busy and `yield_on_busy` is set, so
+    /// that the step can be retried.
+    pub(crate) fn execute_step(&mut self, yield_on_busy: bool) -> Option<Result<usize>> {
         let r = self.stmt.step();
+        if r == ffi::SQLITE_BUSY && yield_on_busy {
+            return None;
+        }
         self.stmt.reset();
-        match r {
+        Some(match r {
             ffi::SQLITE_DONE => Ok(self.conn.changes()),
             ffi::SQLITE_ROW => Err(Error::ExecuteReturnedResults),
             _ => Err(self.conn.decode_result(r).unwrap_err()),
-        }
+        })
     }
 
     #[inline]
@@ -719,7 +797,7 @@ impl Statement<'_> {
 
     #[cfg(all(feature = "modern_sqlite", feature = "extra_check"))]
     #[inline]
-    fn check_update(&self) -> Result<()> {
+    pub(crate) fn check_update(&self) -> Result<()> {
         // sqlite3_column_count works for DML but not for DDL (ie ALTER)
         if self.column_count() > 0 && self.stmt.readonly() {
             return Err(Error::ExecuteReturnedResults);
@@ -729,7 +807,7 @@ impl Statement<'_> {
 
     #[cfg(all(not(feature = "modern_sqlite"), feature = "extra_check"))]
     #[inline]
-    fn check_update(&self) -> Result<()> {
+    pub(crate) fn check_update(&self) -> Result<()> {
         // sqlite3_column_count works for DML but not for DDL (ie ALTER)
         if self.column_count() > 0 {
             return Err(Error::ExecuteReturnedResults);
@@ -740,7 +818,7 @@ impl Statement<'_> {
     #[cfg(not(feature = "extra_check"))]
     #[inline]
     #[allow(clippy::unnecessary_wraps)]
-    fn check_update(&self) -> Result<()> {
+    pub(crate) fn check_update(&self) -> Result<()> {
         Ok(())
     }
 
@@ -753,6 +831,15 @@ impl Statement<'_> {
             .map(|s| s.to_string_lossy().to_string())
     }
 
+    /// Returns `true` if the statement has been stepped at least once but has
+    /// not run to completion or been reset, i.e. while its [`Rows`] are still
+    /// alive.
+    #[inline]
+    #[cfg(feature = "modern_sqlite")] // 3.7.10
+    pub fn is_busy(&self) -> bool {
+        self.stmt.is_busy()
+    }
+
     /// Get the value for one of the status counters for this statement.
     #[inline]
     pub fn get_status(&self, status: StatementStatus) -> i32 {
@@ -924,7 +1011,7 @@ pub enum StatementStatus {
 #[cfg(test)]
 mod test {
     use crate::types::ToSql;
-    use crate::{params_from_iter, Connection, Error, Result};
+    use crate::{params_from_iter, Connection, Error, ErrorCode, Result};
 
     #[test]
     #[allow(deprecated)]
@@ -1167,6 +1254,31 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_parameter_index_cache_capacity() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch("CREATE TABLE test (x TEXT, y TEXT)")?;
+
+        for &capacity in &[0, 1, 16] {
+            let mut stmt = db.prepare("INSERT INTO test (x, y) VALUES (:x, :y)")?;
+            stmt.set_parameter_index_cache_capacity(capacity);
+            stmt.execute(&[(":x", "one"), (":y", "two")])?;
+            stmt.execute(&[(":x", "one"), (":y", "two")])?;
+            stmt.execute(&[(":y", "one"), (":x", "two")])?;
+            match stmt.execute(&[(":x", "one"), (":z", "two")]) {
+                Err(Error::InvalidParameterName(name)) => assert_eq!(name, ":z"),
+                r => panic!("Unexpected result {:?}", r),
+            }
+        }
+        let n: i64 = db.query_row(
+            "SELECT count(*) FROM test WHERE x = 'one' AND y = 'two'",
+            [],
+            |r| r.get(0),
+        )?;
+        assert_eq!(n, 6);
+        Ok(())
+    }
+
     #[test]
     fn test_unbound_parameters_are_reused() -> Result<()> {
         let db = Connection::open_in_memory()?;
@@ -1218,6 +1330,58 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_execute_returning() -> Result<()> {
+        // RETURNING was added in SQLite 3.35.0.
+        if crate::version_number() < 3_035_000 {
+            return Ok(());
+        }
+        let db = Connection::open_in_memory()?;
+        db.execute_batch("CREATE TABLE foo(id INTEGER PRIMARY KEY, x TEXT)")?;
+        let mut stmt = db.prepare("INSERT INTO foo(x) VALUES (?), (?) RETURNING id, x")?;
+        let rows: Vec<(i64, String)> =
+            stmt.execute_returning(["a", "b"], |r| Ok((r.get(0)?, r.get(1)?)))?;
+        assert_eq!(rows, vec![(1, "a".to_owned()), (2, "b".to_owned())]);
+
+        let ids: Vec<i64> =
+            db.execute_returning("DELETE FROM foo WHERE x = ? RETURNING id", ["b"], |r| {
+                r.get(0)
+            })?;
+        assert_eq!(ids, vec![2]);
+        let none: Vec<i64> =
+            db.execute_returning("DELETE FROM foo WHERE x = ? RETURNING id", ["b"], |r| {
+                r.get(0)
+            })?;
+        assert!(none.is_empty());
+
+        // Changes are made even if mapping the returned rows fails.
+        let mut stmt = db.prepare("UPDATE foo SET x = 'c' RETURNING x")?;
+        assert!(stmt.execute_returning([], |r| r.get::<_, i64>(0)).is_err());
+        let x: String = db.query_row("SELECT x FROM foo", [], |r| r.get(0))?;
+        assert_eq!(x, "c");
+        Ok(())
+    }
+
+    #[test]
+    fn test_execute_returning_without_returning() -> Result<()> {
+        // Runs with any SQLite version, unlike `test_execute_returning`.
+        let db = Connection::open_in_memory()?;
+        db.execute_batch("CREATE TABLE foo(id INTEGER PRIMARY KEY, x TEXT UNIQUE)")?;
+        let mut stmt = db.prepare("INSERT INTO foo(x) VALUES (?)")?;
+        let rows: Vec<i64> = stmt.execute_returning(["a"], |r| r.get(0))?;
+        assert!(rows.is_empty());
+        let count: i64 = db.query_row("SELECT count(*) FROM foo", [], |r| r.get(0))?;
+        assert_eq!(count, 1);
+
+        match stmt.execute_returning(["a"], |r| r.get::<_, i64>(0)) {
+            Err(Error::SqliteFailure(err, _)) => {
+                assert_eq!(err.code, ErrorCode::ConstraintViolation)
+            }
+            r => panic!("Unexpected result {:?}", r),
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_exists() -> Result<()> {
         let db = Connection::open_in_memory()?;
@@ -1287,6 +1451,21 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    #[cfg(feature = "modern_sqlite")]
+    fn test_is_busy() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        let mut stmt = db.prepare("SELECT 1 UNION ALL SELECT 2")?;
+        assert!(!stmt.is_busy());
+        {
+            let mut rows = stmt.query([])?;
+            rows.next()?;
+            assert!(rows.stmt.unwrap().is_busy());
+        }
+        assert!(!stmt.is_busy());
+        Ok(())
+    }
+
     #[test]
     fn test_bind_parameters() -> Result<()> {
         let db = Connection::open_in_memory()?;
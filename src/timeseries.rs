@@ -0,0 +1,282 @@
+//! `feature = "timeseries"` Store time series, downsample them, and sweep
+//! old points.
+//!
+//! A [`TimeSeries`] table is a `WITHOUT ROWID` table clustered on
+//! `(ts, series)`: as points are mostly appended in time order, inserts go to
+//! the end of the b-tree, time range queries read contiguous pages, and the
+//! oldest points, removed by [`sweep`](TimeSeries::sweep), are at its start.
+//! Timestamps are integers in any unit (e.g. seconds or milliseconds since
+//! the Unix epoch), as long as it is the same for all points.
+//!
+//! ```rust,no_run
+//! use rusqlite::timeseries::{Aggregate, TimeSeries};
+//! use rusqlite::{Connection, Result};
+//!
+//! fn hourly_load(conn: &Connection, now: i64) -> Result<Vec<(i64, f64)>> {
+//!     let metrics = TimeSeries::create(conn, "metrics")?;
+//!     // Keep 30 days.
+//!     metrics.sweep(now - 30 * 86400, 10_000, None)?;
+//!     metrics.downsample("load", now - 86400, now, 3600, Aggregate::Avg)
+//! }
+//! ```
+
+use crate::util::quote_identifier;
+use crate::{ffi, params, Connection, Error, Result, Savepoint};
+
+/// How the points of a bucket are combined by
+/// [`downsample`](TimeSeries::downsample).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Aggregate {
+    /// The mean of the values.
+    Avg,
+    /// The smallest value.
+    Min,
+    /// The largest value.
+    Max,
+    /// The sum of the values.
+    Sum,
+    /// The number of points.
+    Count,
+}
+
+impl Aggregate {
+    fn function(self) -> &'static str {
+        match self {
+            Aggregate::Avg => "avg",
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+            Aggregate::Sum => "total",
+            Aggregate::Count => "count",
+        }
+    }
+}
+
+/// A table of points `(series, ts, value)`.
+#[derive(Debug)]
+pub struct TimeSeries<'conn> {
+    conn: &'conn Connection,
+    table: String,
+}
+
+impl TimeSeries<'_> {
+    /// Opens the time series in `table`, which is created if it doesn't
+    /// exist.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the table cannot be created.
+    pub fn create<'conn>(conn: &'conn Connection, table: &str) -> Result<TimeSeries<'conn>> {
+        let table = quote_identifier(table);
+        conn.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                ts INTEGER NOT NULL,
+                series TEXT NOT NULL,
+                value REAL,
+                PRIMARY KEY (ts, series)
+            ) WITHOUT ROWID",
+            table
+        ))?;
+        Ok(TimeSeries { conn, table })
+    }
+
+    /// Adds a point, replacing any point of `series` at `ts`.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite call fails.
+    pub fn append(&self, series: &str, ts: i64, value: f64) -> Result<()> {
+        self.conn
+            .prepare_cached(&format!(
+                "INSERT OR REPLACE INTO {} (ts, series, value) VALUES (?, ?, ?)",
+                self.table
+            ))?
+            .execute(params![ts, series, value])?;
+        Ok(())
+    }
+
+    /// Adds many points, in one transaction (or savepoint, if one is already
+    /// open), and returns their number.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite call fails, in which case
+    /// no point is added.
+    pub fn append_all<I, S>(&self, points: I) -> Result<usize>
+    where
+        I: IntoIterator<Item = (S, i64, f64)>,
+        S: AsRef<str>,
+    {
+        let sp = Savepoint::with_depth_and_name(self.conn, 0, "_rusqlite_timeseries")?;
+        let mut count = 0;
+        for (series, ts, value) in points {
+            self.append(series.as_ref(), ts, value)?;
+            count += 1;
+        }
+        sp.commit()?;
+        Ok(count)
+    }
+
+    /// Combines the points of `series` from `start` (included) to `end`
+    /// (excluded) into buckets of `bucket` time units, aligned on multiples
+    /// of `bucket`, and returns the start and value of the non-empty
+    /// buckets, in time order. Points without a value (NaNs are stored as
+    /// `NULL`) are skipped, and so are the buckets holding only such points.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `bucket` is not positive, or if the underlying
+    /// SQLite call fails.
+    pub fn downsample(
+        &self,
+        series: &str,
+        start: i64,
+        end: i64,
+        bucket: i64,
+        aggregate: Aggregate,
+    ) -> Result<Vec<(i64, f64)>> {
+        if bucket <= 0 {
+            return Err(Error::SqliteFailure(
+                ffi::Error::new(ffi::SQLITE_MISUSE),
+                Some(format!("bucket must be positive, not {}", bucket)),
+            ));
+        }
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "SELECT ts - ((ts % ?4) + ?4) % ?4 AS bucket, {}(value) FROM {}
+             WHERE ts >= ?1 AND ts < ?2 AND series = ?3 AND value IS NOT NULL
+             GROUP BY bucket ORDER BY bucket",
+            aggregate.function(),
+            self.table
+        ))?;
+        let rows = stmt.query_map(params![start, end, series, bucket], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+        rows.collect()
+    }
+
+    /// Removes the points older than `cutoff`, in transactions of about
+    /// `batch_size` points (more if the last timestamp of a batch is shared
+    /// by several series), so that writers are never locked out for long.
+    /// The points are first copied to `archive`, if any, which must be on
+    /// the same connection. Returns the number of points removed.
+    ///
+    /// If a transaction is already open, each batch is a savepoint in it
+    /// instead, and nothing is committed until it is.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite call fails; the batches
+    /// already swept stay removed.
+    pub fn sweep(
+        &self,
+        cutoff: i64,
+        batch_size: usize,
+        archive: Option<&TimeSeries<'_>>,
+    ) -> Result<usize> {
+        let batch_size = batch_size.max(1) as i64;
+        let mut removed = 0;
+        loop {
+            let sp = Savepoint::with_depth_and_name(self.conn, 0, "_rusqlite_timeseries")?;
+            let bound: Option<i64> = self
+                .conn
+                .prepare_cached(&format!(
+                    "SELECT max(ts) FROM (SELECT ts FROM {} WHERE ts < ? ORDER BY ts LIMIT ?)",
+                    self.table
+                ))?
+                .query_row(params![cutoff, batch_size], |row| row.get(0))?;
+            let bound = match bound {
+                Some(bound) => bound,
+                None => return Ok(removed),
+            };
+            if let Some(archive) = archive {
+                self.conn
+                    .prepare_cached(&format!(
+                        "INSERT OR REPLACE INTO {} (ts, series, value)
+                         SELECT ts, series, value FROM {} WHERE ts <= ?",
+                        archive.table, self.table
+                    ))?
+                    .execute([bound])?;
+            }
+            removed += self
+                .conn
+                .prepare_cached(&format!("DELETE FROM {} WHERE ts <= ?", self.table))?
+                .execute([bound])?;
+            sp.commit()?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Aggregate, TimeSeries};
+    use crate::{Connection, Error, ErrorCode, Result};
+
+    #[test]
+    fn test_downsample() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        let ts = TimeSeries::create(&db, "metrics")?;
+        let points = (-5..20).map(|t| ("cpu", t, t as f64));
+        assert_eq!(ts.append_all(points)?, 25);
+        ts.append("mem", 3, 100.0)?;
+        ts.append("cpu", 19, 40.0)?;
+
+        assert_eq!(
+            ts.downsample("cpu", -5, 20, 10, Aggregate::Avg)?,
+            vec![(-10, -3.0), (0, 4.5), (10, 16.6)]
+        );
+        assert_eq!(
+            ts.downsample("cpu", 0, 15, 10, Aggregate::Count)?,
+            vec![(0, 10.0), (10, 5.0)]
+        );
+        assert_eq!(
+            ts.downsample("cpu", 0, 20, 20, Aggregate::Max)?,
+            vec![(0, 40.0)]
+        );
+        assert_eq!(
+            ts.downsample("mem", 0, 20, 5, Aggregate::Sum)?,
+            vec![(0, 100.0)]
+        );
+        match ts.downsample("cpu", 0, 20, 0, Aggregate::Min).unwrap_err() {
+            Error::SqliteFailure(err, Some(msg)) => {
+                assert_eq!(err.code, ErrorCode::APIMisuse);
+                assert_eq!(msg, "bucket must be positive, not 0");
+            }
+            err => panic!("Unexpected error {}", err),
+        }
+
+        // Buckets without values are skipped.
+        ts.append("gpu", 1, f64::NAN)?;
+        ts.append("gpu", 11, f64::NAN)?;
+        ts.append("gpu", 12, 3.0)?;
+        for &aggregate in &[Aggregate::Avg, Aggregate::Min, Aggregate::Sum] {
+            assert_eq!(ts.downsample("gpu", 0, 20, 10, aggregate)?, vec![(10, 3.0)]);
+        }
+        assert_eq!(
+            ts.downsample("gpu", 0, 20, 10, Aggregate::Count)?,
+            vec![(10, 1.0)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sweep() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        let ts = TimeSeries::create(&db, "metrics")?;
+        let archive = TimeSeries::create(&db, "old_metrics")?;
+        ts.append_all((0..100).flat_map(|t| vec![("a", t, 1.0), ("b", t, 2.0)]))?;
+
+        assert_eq!(ts.sweep(50, 7, Some(&archive))?, 100);
+        assert_eq!(ts.sweep(50, 7, Some(&archive))?, 0);
+        let count = |table: &str| -> Result<i64> {
+            db.query_row(&format!("SELECT count(*) FROM {}", table), [], |r| r.get(0))
+        };
+        assert_eq!(count("metrics")?, 100);
+        assert_eq!(count("old_metrics")?, 100);
+        let oldest: i64 = db.query_row("SELECT min(ts) FROM metrics", [], |r| r.get(0))?;
+        assert_eq!(oldest, 50);
+
+        assert_eq!(ts.sweep(i64::MAX, 1000, None)?, 100);
+        assert_eq!(count("metrics")?, 0);
+        Ok(())
+    }
+}
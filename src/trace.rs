@@ -1,15 +1,159 @@
 //! `feature = "trace"` Tracing and profiling functions. Error and warning log.
 
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::ffi::{CStr, CString};
+use std::fmt;
 use std::mem;
 use std::os::raw::{c_char, c_int, c_void};
 use std::panic::catch_unwind;
 use std::ptr;
-use std::time::Duration;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 
 use super::ffi;
 use crate::error::error_from_sqlite_code;
-use crate::{Connection, Result};
+use crate::{Connection, Error, Result};
+
+// The `fn(&str) -> String` set by `set_redactor`, or null.
+static REDACTOR: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+/// `feature = "trace"` Set or clear the process-wide redaction callback.
+///
+/// When set, the SQL given to the [`trace`](Connection::trace) and
+/// [`profile`](Connection::profile) callbacks, in which the bound parameters
+/// have been expanded, and the messages given to the
+/// [`config_log`] callback and those of the
+/// [`SqliteFailure`](Error::SqliteFailure) errors, which may quote SQL, are
+/// first passed through `redactor`, e.g. [`redact_literals`], or a function
+/// hashing the values.
+///
+/// Messages are passed in pieces: the tokens of SQL they quote in double
+/// quotes, e.g. `'secret'` in `near "'secret'": syntax error`, and the text
+/// around them.
+pub fn set_redactor(redactor: Option<fn(&str) -> String>) {
+    let p = redactor.map_or(ptr::null_mut(), |f| f as *mut ());
+    REDACTOR.store(p, Ordering::Release);
+}
+
+fn redact(s: &str) -> Cow<'_, str> {
+    let p = REDACTOR.load(Ordering::Acquire);
+    if p.is_null() {
+        Cow::Borrowed(s)
+    } else {
+        let redactor: fn(&str) -> String = unsafe { mem::transmute(p) };
+        Cow::Owned(redactor(s))
+    }
+}
+
+// Redacts a message of SQLite, which quotes the tokens of SQL in double
+// quotes.
+pub(crate) fn redact_message(msg: &str) -> Cow<'_, str> {
+    if REDACTOR.load(Ordering::Acquire).is_null() {
+        Cow::Borrowed(msg)
+    } else {
+        let parts: Vec<_> = msg.split('"').map(redact).collect();
+        Cow::Owned(parts.join("\""))
+    }
+}
+
+/// `feature = "trace"` Replaces the string, blob and numeric literals of
+/// `sql` by `?`, keeping identifiers, keywords and comments.
+///
+/// ```rust
+/// # use rusqlite::trace::redact_literals;
+/// assert_eq!(
+///     redact_literals("SELECT * FROM t2 WHERE name = 'Bob' AND x > -1.5e3"),
+///     "SELECT * FROM t2 WHERE name = ? AND x > -?"
+/// );
+/// ```
+pub fn redact_literals(sql: &str) -> String {
+    let bytes = sql.as_bytes();
+    let mut out = String::with_capacity(sql.len());
+    let mut start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let end = match bytes[i] {
+            b'\'' => quoted_end(bytes, i, b'\''),
+            b'x' | b'X' if bytes.get(i + 1) == Some(&b'\'') && !is_word(bytes, i) => {
+                quoted_end(bytes, i + 1, b'\'')
+            }
+            b'0'..=b'9' | b'.' if !is_word(bytes, i) && starts_number(bytes, i) => {
+                number_end(bytes, i)
+            }
+            b'"' | b'`' => {
+                i = quoted_end(bytes, i, bytes[i]);
+                continue;
+            }
+            b'[' => {
+                i = sql[i..].find(']').map_or(bytes.len(), |j| i + j + 1);
+                continue;
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                i = sql[i..].find('\n').map_or(bytes.len(), |j| i + j + 1);
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = sql[i + 2..].find("*/").map_or(bytes.len(), |j| i + j + 4);
+                continue;
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        out.push_str(&sql[start..i]);
+        out.push('?');
+        start = end;
+        i = end;
+    }
+    out.push_str(&sql[start..]);
+    out
+}
+
+// Whether the byte at `i` continues an identifier, e.g. the `1` of `t1`.
+fn is_word(bytes: &[u8], i: usize) -> bool {
+    i > 0 && {
+        let c = bytes[i - 1];
+        c.is_ascii_alphanumeric() || c == b'_' || c == b'$' || c >= 0x80
+    }
+}
+
+fn starts_number(bytes: &[u8], i: usize) -> bool {
+    bytes[i].is_ascii_digit() || matches!(bytes.get(i + 1), Some(c) if c.is_ascii_digit())
+}
+
+// The index after the closing `quote` of the literal starting at `i`, where
+// doubled quotes are escapes.
+fn quoted_end(bytes: &[u8], mut i: usize, quote: u8) -> usize {
+    i += 1;
+    while i < bytes.len() {
+        if bytes[i] == quote {
+            if bytes.get(i + 1) != Some(&quote) {
+                return i + 1;
+            }
+            i += 1;
+        }
+        i += 1;
+    }
+    bytes.len()
+}
+
+fn number_end(bytes: &[u8], mut i: usize) -> usize {
+    let mut prev = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let exponent_sign = (c == b'+' || c == b'-') && (prev == b'e' || prev == b'E');
+        if !(c.is_ascii_alphanumeric() || c == b'.' || c == b'_' || exponent_sign) {
+            break;
+        }
+        prev = c;
+        i += 1;
+    }
+    i
+}
 
 /// `feature = "trace"` Set up the process-wide SQLite error logging callback.
 ///
@@ -31,7 +175,11 @@ pub unsafe fn config_log(callback: Option<fn(c_int, &str)>) -> Result<()> {
         let callback: fn(c_int, &str) = unsafe { mem::transmute(p_arg) };
 
         let s = String::from_utf8_lossy(c_slice);
-        let _ = catch_unwind(|| callback(err, &s));
+        let _ = catch_unwind(|| {
+            let msg = redact_message(&s);
+            record(err, &msg);
+            callback(err, &msg)
+        });
     }
 
     let rc = match callback {
@@ -63,7 +211,159 @@ pub fn log(err_code: c_int, msg: &str) {
     }
 }
 
+/// `feature = "trace"` A message of the error log, recorded by
+/// [`Connection::logged`].
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct LogEntry {
+    /// When the message was logged.
+    pub time: SystemTime,
+    /// The (extended) result code given with the message.
+    pub code: c_int,
+    /// The message, after redaction.
+    pub message: String,
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}) {}", self.code, self.message)
+    }
+}
+
+/// `feature = "trace"` An error returned by [`Connection::logged`], with the
+/// messages recently logged for the connection, oldest first.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct LoggedError {
+    /// The error.
+    pub error: Error,
+    /// The recent messages of the error log.
+    pub log: Vec<LogEntry>,
+}
+
+impl fmt::Display for LoggedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)?;
+        for entry in &self.log {
+            write!(f, "\n  logged: {}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for LoggedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<LoggedError> for Error {
+    #[inline]
+    fn from(err: LoggedError) -> Error {
+        err.error
+    }
+}
+
+// The most recent messages logged for a connection.
+#[derive(Debug)]
+pub(crate) struct LogBuffer {
+    capacity: usize,
+    entries: VecDeque<LogEntry>,
+}
+
+thread_local! {
+    // The buffers of the connections running `Connection::logged` on this
+    // thread, innermost last, or `None` when not kept.
+    static LOGGING: RefCell<Vec<Option<Arc<Mutex<LogBuffer>>>>> = const { RefCell::new(Vec::new()) };
+}
+
+// SQLite logs on the thread of the call which triggers the message, so it is
+// about the connection running `Connection::logged` there.
+fn record(code: c_int, message: &str) {
+    let buffer = LOGGING.with(|logging| logging.borrow().last().cloned().flatten());
+    if let Some(buffer) = buffer {
+        let mut buffer = buffer.lock().unwrap();
+        if buffer.entries.len() == buffer.capacity {
+            buffer.entries.pop_front();
+        }
+        buffer.entries.push_back(LogEntry {
+            time: SystemTime::now(),
+            code,
+            message: message.to_owned(),
+        });
+    }
+}
+
 impl Connection {
+    /// `feature = "trace"` Keep the last `capacity` messages of the error log
+    /// emitted while running [`logged`](Connection::logged) on this
+    /// connection, or stop keeping them if `capacity` is 0.
+    ///
+    /// The messages already kept are discarded. Messages are only logged
+    /// once a callback is set with [`config_log`], which may ignore them.
+    pub fn set_log_history(&self, capacity: usize) {
+        self.db.borrow_mut().log_history = if capacity == 0 {
+            None
+        } else {
+            Some(Arc::new(Mutex::new(LogBuffer {
+                capacity,
+                entries: VecDeque::with_capacity(capacity),
+            })))
+        };
+    }
+
+    /// `feature = "trace"` The messages kept since
+    /// [`set_log_history`](Connection::set_log_history), oldest first.
+    pub fn log_history(&self) -> Vec<LogEntry> {
+        match self.db.borrow().log_history {
+            Some(ref buffer) => buffer.lock().unwrap().entries.iter().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// `feature = "trace"` Run `f`, keeping the messages it logs, and, if it
+    /// fails, attach the messages kept for the connection to the error, e.g.
+    /// the warnings about recovering the write-ahead log preceding an I/O
+    /// error.
+    ///
+    /// Messages are kept only if enabled by
+    /// [`set_log_history`](Connection::set_log_history); they are attributed
+    /// to the connection because SQLite logs on the thread running the call
+    /// which triggers the message.
+    ///
+    /// ```rust,no_run
+    /// # use rusqlite::{Connection, Result};
+    /// fn count(conn: &Connection) -> Result<i64> {
+    ///     conn.set_log_history(16);
+    ///     conn.logged(|conn| conn.query_row("SELECT count(*) FROM t", [], |r| r.get(0)))
+    ///         .map_err(|err| {
+    ///             eprintln!("{}", err);
+    ///             err.into()
+    ///         })
+    /// }
+    /// ```
+    pub fn logged<T, F>(&self, f: F) -> std::result::Result<T, LoggedError>
+    where
+        F: FnOnce(&Connection) -> Result<T>,
+    {
+        struct Popped;
+        impl Drop for Popped {
+            fn drop(&mut self) {
+                LOGGING.with(|logging| logging.borrow_mut().pop());
+            }
+        }
+
+        let buffer = self.db.borrow().log_history.clone();
+        LOGGING.with(|logging| logging.borrow_mut().push(buffer));
+        let popped = Popped;
+        let result = f(self);
+        drop(popped);
+        result.map_err(|error| LoggedError {
+            error,
+            log: self.log_history(),
+        })
+    }
+
     /// `feature = "trace"` Register or clear a callback function that can be
     /// used for tracing the execution of SQL statements.
     ///
@@ -75,7 +375,7 @@ impl Connection {
             let trace_fn: fn(&str) = mem::transmute(p_arg);
             let c_slice = CStr::from_ptr(z_sql).to_bytes();
             let s = String::from_utf8_lossy(c_slice);
-            let _ = catch_unwind(|| trace_fn(&s));
+            let _ = catch_unwind(|| trace_fn(&redact(&s)));
         }
 
         let c = self.db.borrow_mut();
@@ -109,7 +409,7 @@ impl Connection {
                 nanoseconds / NANOS_PER_SEC,
                 (nanoseconds % NANOS_PER_SEC) as u32,
             );
-            let _ = catch_unwind(|| profile_fn(&s, duration));
+            let _ = catch_unwind(|| profile_fn(&redact(&s), duration));
         }
 
         let c = self.db.borrow_mut();
@@ -128,8 +428,25 @@ mod test {
     use std::sync::Mutex;
     use std::time::Duration;
 
+    use super::redact_literals;
     use crate::{Connection, Result};
 
+    #[test]
+    fn test_redact_literals() {
+        for (sql, expected) in &[
+            ("SELECT 1", "SELECT ?"),
+            ("SELECT 'it''s', X'00ff', 0x1F", "SELECT ?, ?, ?"),
+            ("SELECT .5, 1e-3, 2E+10", "SELECT ?, ?, ?"),
+            (
+                "SELECT \"a'1\", [b 2], t1.c3 FROM t1 -- 'x'\nWHERE y = '1'",
+                "SELECT \"a'1\", [b 2], t1.c3 FROM t1 -- 'x'\nWHERE y = ?",
+            ),
+            ("SELECT 1 /* 2 */ + 'unterminated", "SELECT ? /* 2 */ + ?"),
+        ] {
+            assert_eq!(redact_literals(sql), *expected, "{}", sql);
+        }
+    }
+
     #[test]
     fn test_trace() -> Result<()> {
         lazy_static! {
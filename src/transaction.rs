@@ -1,4 +1,5 @@
 use crate::{Connection, Result};
+use std::fmt;
 use std::ops::Deref;
 
 /// Options for transaction behavior. See [BEGIN
@@ -180,6 +181,65 @@ impl Transaction<'_> {
         self.drop_behavior = drop_behavior
     }
 
+    /// Defer the enforcement of all foreign key constraints until the
+    /// transaction is committed, with
+    /// [`PRAGMA defer_foreign_keys`](https://sqlite.org/pragma.html#pragma_defer_foreign_keys).
+    ///
+    /// This allows inserting rows in any order, e.g. children before their
+    /// parents. It only lasts until the end of the transaction.
+    #[inline]
+    pub fn defer_foreign_keys(&self) -> Result<()> {
+        self.conn.pragma_update(None, "defer_foreign_keys", &true)
+    }
+
+    /// Returns the rows which violate a foreign key constraint, so that they
+    /// can be reported or fixed before `COMMIT` fails with a bare "FOREIGN KEY
+    /// constraint failed".
+    ///
+    /// ```rust,no_run
+    /// # use rusqlite::{Connection, Result};
+    /// fn load(conn: &mut Connection) -> Result<()> {
+    ///     let tx = conn.transaction()?;
+    ///     tx.defer_foreign_keys()?;
+    ///     tx.execute_batch("INSERT INTO sample VALUES (1, 'cohort_1')")?;
+    ///     for violation in tx.check_deferred_constraints()? {
+    ///         eprintln!("{}", violation);
+    ///     }
+    ///     tx.commit()
+    /// }
+    /// ```
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying SQLite calls fail.
+    pub fn check_deferred_constraints(&self) -> Result<Vec<ForeignKeyViolation>> {
+        let mut violations = Vec::new();
+        self.conn.pragma_query(None, "foreign_key_check", |row| {
+            violations.push(ForeignKeyViolation {
+                table: row.get(0)?,
+                rowid: row.get(1)?,
+                parent: row.get(2)?,
+                from: Vec::new(),
+                to: Vec::new(),
+                id: row.get(3)?,
+            });
+            Ok(())
+        })?;
+        for violation in &mut violations {
+            let (from, to) = (&mut violation.from, &mut violation.to);
+            let id = violation.id;
+            self.conn
+                .pragma(None, "foreign_key_list", &violation.table, |row| {
+                    if row.get::<_, i64>(0)? == id {
+                        from.push(row.get(3)?);
+                        to.push(row.get(4)?);
+                    }
+                    Ok(())
+                })?;
+        }
+        Ok(violations)
+    }
+
     /// A convenience method which consumes and commits a transaction.
     #[inline]
     pub fn commit(mut self) -> Result<()> {
@@ -228,6 +288,44 @@ impl Transaction<'_> {
     }
 }
 
+/// A row which violates a foreign key constraint, as returned by
+/// [`Transaction::check_deferred_constraints`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ForeignKeyViolation {
+    /// The table of the row holding the foreign key.
+    pub table: String,
+    /// The rowid of the row, `None` for a `WITHOUT ROWID` table.
+    pub rowid: Option<i64>,
+    /// The parent table, which has no row matching the foreign key.
+    pub parent: String,
+    /// The columns of the foreign key in `table`.
+    pub from: Vec<String>,
+    /// The referenced columns of `parent`; a `None` is its primary key.
+    pub to: Vec<Option<String>>,
+    /// The index of the foreign key in `PRAGMA foreign_key_list(table)`.
+    pub id: i64,
+}
+
+impl fmt::Display for ForeignKeyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.table)?;
+        if let Some(rowid) = self.rowid {
+            write!(f, " (rowid {})", rowid)?;
+        }
+        write!(f, ": ({}) references {}", self.from.join(", "), self.parent)?;
+        if self.to.iter().any(Option::is_some) {
+            let to: Vec<&str> = self
+                .to
+                .iter()
+                .map(|c| c.as_deref().unwrap_or("<primary key>"))
+                .collect();
+            write!(f, " ({})", to.join(", "))?;
+        }
+        write!(f, " with no matching row")
+    }
+}
+
 impl Deref for Transaction<'_> {
     type Target = Connection;
 
@@ -247,7 +345,7 @@ impl Drop for Transaction<'_> {
 
 impl Savepoint<'_> {
     #[inline]
-    fn with_depth_and_name<T: Into<String>>(
+    pub(crate) fn with_depth_and_name<T: Into<String>>(
         conn: &Connection,
         depth: u32,
         name: T,
@@ -686,6 +784,37 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_check_deferred_constraints() -> Result<()> {
+        let mut db = Connection::open_in_memory()?;
+        db.execute_batch(
+            "PRAGMA foreign_keys = ON;
+             CREATE TABLE cohort(name TEXT PRIMARY KEY);
+             CREATE TABLE sample(id INTEGER PRIMARY KEY, cohort TEXT REFERENCES cohort);",
+        )?;
+        let tx = db.transaction()?;
+        tx.defer_foreign_keys()?;
+        tx.execute_batch(
+            "INSERT INTO sample VALUES (1, 'a');
+             INSERT INTO sample VALUES (2, 'b');
+             INSERT INTO cohort VALUES ('a');",
+        )?;
+        let violations = tx.check_deferred_constraints()?;
+        assert_eq!(violations.len(), 1);
+        let violation = &violations[0];
+        assert_eq!(violation.rowid, Some(2));
+        assert_eq!(violation.from, ["cohort"]);
+        assert_eq!(violation.to, [None]);
+        assert_eq!(
+            violation.to_string(),
+            "sample (rowid 2): (cohort) references cohort with no matching row"
+        );
+        tx.execute("DELETE FROM sample WHERE id = 2", [])?;
+        assert!(tx.check_deferred_constraints()?.is_empty());
+        tx.commit()?;
+        Ok(())
+    }
+
     #[test]
     fn test_rc() -> Result<()> {
         use std::rc::Rc;
@@ -103,6 +103,7 @@ from_sql_integral!(u16);
 from_sql_integral!(u32);
 from_sql_integral!(u64);
 from_sql_integral!(usize);
+from_sql_integral!(u128);
 
 impl FromSql for i64 {
     #[inline]
@@ -191,17 +192,24 @@ impl FromSql for i128 {
     }
 }
 
+/// 16-byte blob, or text in any format accepted by `Uuid::parse_str` (e.g.
+/// written by other tools) => `Uuid`.
 #[cfg(feature = "uuid")]
 impl FromSql for uuid::Uuid {
     #[inline]
     fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
-        value
-            .as_blob()
-            .and_then(|bytes| {
-                uuid::Builder::from_slice(bytes)
-                    .map_err(|_| FromSqlError::InvalidUuidSize(bytes.len()))
-            })
-            .map(|mut builder| builder.build())
+        match value {
+            ValueRef::Text(_) => value.as_str().and_then(|s| {
+                uuid::Uuid::parse_str(s).map_err(|err| FromSqlError::Other(Box::new(err)))
+            }),
+            _ => value
+                .as_blob()
+                .and_then(|bytes| {
+                    uuid::Builder::from_slice(bytes)
+                        .map_err(|_| FromSqlError::InvalidUuidSize(bytes.len()))
+                })
+                .map(|mut builder| builder.build()),
+        }
     }
 }
 
@@ -21,14 +21,17 @@
 //! * `INTEGER` to float: casts using `as` operator. Never fails.
 //! * `REAL` to float: casts using `as` operator. Never fails.
 //!
-//! [`ToSql`] always succeeds except when storing a `u64` or `usize` value that
-//! cannot fit in an `INTEGER` (`i64`). Also note that SQLite ignores column
+//! [`ToSql`] always succeeds except when storing a `u64`, `usize`, `u128` or
+//! [`AsInteger<i128>`](AsInteger) value that cannot fit in an `INTEGER` (`i64`);
+//! wrap them in [`AsText`] or [`AsBlob`] to store any value. Also note that SQLite ignores column
 //! types, so if you store an `i64` in a column with type `REAL` it will be
 //! stored as an `INTEGER`, not a `REAL`.
 //!
 //! If the `time` feature is enabled, implementations are
 //! provided for `time::OffsetDateTime` that use the RFC 3339 date/time format,
-//! `"%Y-%m-%dT%H:%M:%S.%fZ"`, to store time values as strings.  These values
+//! `"%Y-%m-%d %H:%M:%S.%fZ"`, to store time values as strings, and for
+//! `time::PrimitiveDateTime`, `time::Date` and `time::Time`, like for their
+//! `chrono` equivalents.  These values
 //! can be parsed by SQLite's builtin
 //! [datetime](https://www.sqlite.org/lang_datefunc.html) functions.  If you
 //! want different storage for datetimes, you can use a newtype.
@@ -39,26 +42,53 @@ For example, to store datetimes as `i64`s counting the number of seconds since
 the Unix epoch:
 
 ```
-use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
 use rusqlite::Result;
 
 pub struct DateTimeSql(pub time::OffsetDateTime);
 
 impl FromSql for DateTimeSql {
     fn column_result(value: ValueRef) -> FromSqlResult<Self> {
-        i64::column_result(value).map(|as_i64| {
-            DateTimeSql(time::OffsetDateTime::from_unix_timestamp(as_i64))
+        i64::column_result(value).and_then(|as_i64| {
+            time::OffsetDateTime::from_unix_timestamp(as_i64)
+                .map(DateTimeSql)
+                .map_err(|err| FromSqlError::Other(Box::new(err)))
         })
     }
 }
 
 impl ToSql for DateTimeSql {
     fn to_sql(&self) -> Result<ToSqlOutput> {
-        Ok(self.0.timestamp().into())
+        Ok(self.0.unix_timestamp().into())
     }
 }
 ```
 
+"##
+)]
+#![cfg_attr(
+    feature = "derive",
+    doc = r##"
+With the `derive` feature, [`ToSql`] and [`FromSql`] can be derived for enums
+without fields, stored as their discriminant, or as their name with
+`#[rusqlite(repr = "text")]`:
+
+```
+use rusqlite::types::{FromSql, ToSql};
+
+#[derive(Clone, Copy, Debug, PartialEq, ToSql, FromSql)]
+#[rusqlite(repr = "text")]
+enum Strand {
+    #[rusqlite(rename = "+")]
+    Forward,
+    #[rusqlite(rename = "-")]
+    Reverse,
+    // Any other value.
+    #[rusqlite(other)]
+    Unknown,
+}
+```
+
 "##
 )]
 //! [`ToSql`] and [`FromSql`] are also implemented for `Option<T>` where `T`
@@ -66,15 +96,26 @@ impl ToSql for DateTimeSql {
 //! value was NULL (which gets translated to `None`).
 
 pub use self::from_sql::{FromSql, FromSqlError, FromSqlResult};
+#[cfg(feature = "modern_sqlite")]
+pub(crate) use self::pointer::free_pointer;
+#[cfg(feature = "modern_sqlite")]
+pub use self::pointer::Pointer;
 pub use self::to_sql::{ToSql, ToSqlOutput};
 pub use self::value::Value;
 pub use self::value_ref::ValueRef;
+pub use self::wide::{AsBlob, AsInteger, AsText};
+#[cfg(feature = "derive")]
+pub use rusqlite_macros::{FromSql, ToSql};
 
 use std::fmt;
 
 #[cfg(feature = "chrono")]
 mod chrono;
 mod from_sql;
+#[cfg(feature = "modern_sqlite")]
+mod pointer;
+#[cfg(feature = "rust_decimal")]
+mod rust_decimal;
 #[cfg(feature = "serde_json")]
 mod serde_json;
 #[cfg(feature = "time")]
@@ -84,6 +125,7 @@ mod to_sql;
 mod url;
 mod value;
 mod value_ref;
+mod wide;
 
 /// Empty struct that can be used to fill in a query parameter as `NULL`.
 ///
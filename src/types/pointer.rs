@@ -0,0 +1,156 @@
+//! `feature = "modern_sqlite"` Passing Rust values through SQL, with
+//! [pointers](https://sqlite.org/bindptr.html).
+
+use std::any::Any;
+use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::c_void;
+use std::rc::Rc;
+
+use super::{ToSql, ToSqlOutput};
+use crate::Result;
+
+/// `feature = "modern_sqlite"` A Rust value bound as a parameter, or
+/// returned by a function, which SQL sees as `NULL` but which a function
+/// (see `Context::get_pointer`) or virtual table can get back, given the
+/// same type name.
+#[cfg_attr(
+    feature = "functions",
+    doc = r##"
+```rust
+# use std::ffi::CStr;
+# use std::rc::Rc;
+# use rusqlite::{Connection, Result};
+# use rusqlite::functions::FunctionFlags;
+# use rusqlite::types::Pointer;
+struct Panel(Vec<&'static str>);
+
+fn panel_type() -> &'static CStr {
+    CStr::from_bytes_with_nul(b"panel\0").unwrap()
+}
+
+# fn main() -> Result<()> {
+let db = Connection::open_in_memory()?;
+db.create_scalar_function("in_panel", 2, FunctionFlags::SQLITE_UTF8, |ctx| {
+    let gene = ctx.get::<String>(0)?;
+    Ok(ctx
+        .get_pointer::<Panel>(1, panel_type())
+        .map_or(false, |panel| panel.0.contains(&gene.as_str())))
+})?;
+let panel = Pointer::new(panel_type(), Rc::new(Panel(vec!["BRCA1", "TP53"])));
+let found: bool = db.query_row("SELECT in_panel('TP53', ?)", [panel], |r| r.get(0))?;
+assert!(found);
+# Ok(())
+# }
+```
+"##
+)]
+#[derive(Clone)]
+pub struct Pointer {
+    type_name: &'static CStr,
+    value: Rc<dyn Any>,
+}
+
+impl Pointer {
+    /// Wraps `value`, under `type_name`: functions only get back pointers of
+    /// the type name they ask for.
+    pub fn new<T: Any>(type_name: &'static CStr, value: Rc<T>) -> Pointer {
+        Pointer { type_name, value }
+    }
+
+    /// The type name of the pointer.
+    #[inline]
+    pub fn type_name(&self) -> &'static CStr {
+        self.type_name
+    }
+
+    /// The value, if it is a `T`.
+    #[inline]
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+
+    // A pointer SQLite owns, freed by `free_pointer`.
+    pub(crate) fn into_raw(self) -> *mut c_void {
+        Box::into_raw(Box::new(self.value)) as *mut c_void
+    }
+
+    // The value of a pointer from `into_raw`, which SQLite still owns.
+    #[cfg(feature = "functions")]
+    pub(crate) unsafe fn from_raw<'a>(p: *mut c_void) -> &'a dyn Any {
+        &**(p as *const Rc<dyn Any>)
+    }
+}
+
+pub(crate) unsafe extern "C" fn free_pointer(p: *mut c_void) {
+    drop(Box::from_raw(p as *mut Rc<dyn Any>));
+}
+
+impl fmt::Debug for Pointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pointer")
+            .field("type_name", &self.type_name)
+            .finish()
+    }
+}
+
+// The same value.
+impl PartialEq for Pointer {
+    fn eq(&self, other: &Pointer) -> bool {
+        self.type_name == other.type_name && Rc::ptr_eq(&self.value, &other.value)
+    }
+}
+
+impl ToSql for Pointer {
+    #[inline]
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::Pointer(self.clone()))
+    }
+}
+
+#[cfg(all(test, feature = "functions"))]
+mod test {
+    use std::ffi::CStr;
+    use std::rc::Rc;
+
+    use super::Pointer;
+    use crate::functions::FunctionFlags;
+    use crate::{Connection, Result};
+
+    fn name(bytes: &'static [u8]) -> &'static CStr {
+        CStr::from_bytes_with_nul(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_pointer() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.create_scalar_function("total", 1, FunctionFlags::SQLITE_UTF8, |ctx| {
+            Ok(ctx
+                .get_pointer::<Vec<i64>>(0, name(b"ints\0"))
+                .map(|v| v.iter().sum::<i64>()))
+        })?;
+        db.create_scalar_function("pass", 1, FunctionFlags::SQLITE_UTF8, |ctx| {
+            Ok(ctx
+                .get_pointer::<Vec<i64>>(0, name(b"ints\0"))
+                .map(|v| Pointer::new(name(b"ints\0"), Rc::new(v.clone()))))
+        })?;
+
+        let ints = Pointer::new(name(b"ints\0"), Rc::new(vec![1i64, 2, 3]));
+        let (total, passed, null): (Option<i64>, Option<i64>, bool) = db.query_row(
+            "SELECT total(?1), total(pass(?1)), ?1 IS NULL",
+            [&ints],
+            |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+        )?;
+        assert_eq!((total, passed, null), (Some(6), Some(6), true));
+
+        // Another type name, or another Rust type, isn't seen.
+        let floats = Pointer::new(name(b"floats\0"), Rc::new(vec![1i64]));
+        let strings = Pointer::new(name(b"ints\0"), Rc::new(vec!["1"]));
+        let totals: (Option<i64>, Option<i64>) =
+            db.query_row("SELECT total(?), total(?)", [floats, strings], |r| {
+                Ok((r.get(0)?, r.get(1)?))
+            })?;
+        assert_eq!(totals, (None, None));
+        Ok(())
+    }
+}
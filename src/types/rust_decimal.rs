@@ -0,0 +1,180 @@
+//! [`ToSql`] and [`FromSql`] implementation for [`rust_decimal::Decimal`].
+//!
+//! Decimals are stored as text, padded so that comparing the text (e.g. in
+//! `ORDER BY` or in an index) orders the values numerically: 29 integer
+//! digits, `.` and 28 fractional digits, the maximum precision of `Decimal`.
+//! Negative values are prefixed with `-`, and their digits are replaced by
+//! their nine's complement. Trailing fractional zeros are not preserved, so
+//! equal values are stored identically.
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+
+use crate::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use crate::Result;
+
+const INT_DIGITS: usize = 29;
+const FRAC_DIGITS: usize = 28;
+
+fn complement(digits: &mut [u8]) {
+    for d in digits.iter_mut().filter(|d| d.is_ascii_digit()) {
+        *d = b'9' - (*d - b'0');
+    }
+}
+
+/// Serialize `Decimal` to sortable text.
+impl ToSql for Decimal {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        let digits = self.mantissa().unsigned_abs().to_string();
+        let scale = self.scale() as usize;
+        let (int, frac) = if digits.len() > scale {
+            digits.split_at(digits.len() - scale)
+        } else {
+            ("", digits.as_str())
+        };
+        let mut s = format!(
+            "{:0>int$}.{:0>scale$}{:0<pad$}",
+            int,
+            frac,
+            "",
+            int = INT_DIGITS,
+            scale = scale,
+            pad = FRAC_DIGITS - scale,
+        )
+        .into_bytes();
+        if self.is_sign_negative() && !self.is_zero() {
+            complement(&mut s);
+            s.insert(0, b'-');
+        }
+        // Only ASCII digits, '.' and '-'.
+        Ok(ToSqlOutput::from(String::from_utf8(s).unwrap()))
+    }
+}
+
+/// Deserialize sortable text, or any other decimal text, integer or real, to
+/// `Decimal`.
+impl FromSql for Decimal {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value {
+            ValueRef::Text(_) => {
+                let s = value.as_str()?;
+                parse_sortable(s)
+                    .unwrap_or_else(|| Decimal::from_str(s))
+                    .map_err(|err| FromSqlError::Other(Box::new(err)))
+            }
+            ValueRef::Integer(i) => Ok(Decimal::from(i)),
+            ValueRef::Real(f) => {
+                Decimal::try_from(f).map_err(|err| FromSqlError::Other(Box::new(err)))
+            }
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+// Returns `None` if `s` is not in the sortable format.
+fn parse_sortable(s: &str) -> Option<rust_decimal::Result<Decimal>> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, s),
+    };
+    let bytes = digits.as_bytes();
+    if bytes.len() != INT_DIGITS + 1 + FRAC_DIGITS
+        || bytes[INT_DIGITS] != b'.'
+        || !bytes
+            .iter()
+            .enumerate()
+            .all(|(i, b)| i == INT_DIGITS || b.is_ascii_digit())
+    {
+        return None;
+    }
+    let mut bytes = bytes.to_vec();
+    if negative {
+        complement(&mut bytes);
+    }
+    // Only ASCII digits and '.'.
+    let digits = std::str::from_utf8(&bytes).unwrap();
+    let (int, frac) = digits.split_at(INT_DIGITS);
+    let int = int.trim_start_matches('0');
+    let frac = frac.trim_end_matches('0');
+    let plain = format!(
+        "{}{}{}",
+        if negative { "-" } else { "" },
+        if int.is_empty() { "0" } else { int },
+        if frac == "." { "" } else { frac },
+    );
+    Some(Decimal::from_str(&plain))
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use rust_decimal::Decimal;
+
+    use crate::{Connection, Result};
+
+    fn checked_memory_handle() -> Result<Connection> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch("CREATE TABLE foo (d TEXT)")?;
+        Ok(db)
+    }
+
+    #[test]
+    fn test_round_trip_and_order() -> Result<()> {
+        let db = checked_memory_handle()?;
+        let values: Vec<Decimal> = [
+            "-79228162514264337593543950335",
+            "-1000",
+            "-12.5",
+            "-12.05",
+            "-1",
+            "-0.0000000000000000000000000001",
+            "0",
+            "0.0000000000000000000000000001",
+            "0.1",
+            "1",
+            "1.10",
+            "12.05",
+            "12.5",
+            "1000",
+            "79228162514264337593543950335",
+        ]
+        .iter()
+        .map(|s| Decimal::from_str(s).unwrap())
+        .collect();
+        for value in values.iter().rev() {
+            db.execute("INSERT INTO foo (d) VALUES (?)", [value])?;
+        }
+
+        let mut stmt = db.prepare("SELECT d FROM foo ORDER BY d")?;
+        let sorted = stmt
+            .query_map([], |r| r.get::<_, Decimal>(0))?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(sorted, values);
+
+        let s: String = db.query_row("SELECT ?", [Decimal::from_str("-12.5").unwrap()], |r| {
+            r.get(0)
+        })?;
+        assert_eq!(
+            s,
+            "-99999999999999999999999999987.4999999999999999999999999999"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_other_formats() -> Result<()> {
+        let db = checked_memory_handle()?;
+        let d: Decimal = db.query_row("SELECT '-3.25'", [], |r| r.get(0))?;
+        assert_eq!(d, Decimal::from_str("-3.25").unwrap());
+        let d: Decimal = db.query_row("SELECT 42", [], |r| r.get(0))?;
+        assert_eq!(d, Decimal::from(42));
+        let d: Decimal = db.query_row("SELECT 0.5", [], |r| r.get(0))?;
+        assert_eq!(d, Decimal::from_str("0.5").unwrap());
+        assert!(db
+            .query_row("SELECT 'abc'", [], |r| r.get::<_, Decimal>(0))
+            .is_err());
+        Ok(())
+    }
+}
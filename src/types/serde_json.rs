@@ -1,6 +1,6 @@
 //! [`ToSql`] and [`FromSql`] implementation for JSON `Value`.
 
-use serde_json::Value;
+use serde_json::{Number, Value};
 
 use crate::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
 use crate::Result;
@@ -13,14 +13,17 @@ impl ToSql for Value {
     }
 }
 
-/// Deserialize text/blob to JSON `Value`.
+/// Deserialize text/blob to JSON `Value`. Integers, reals and `NULL` (e.g.
+/// returned by `json_extract`) are converted to JSON numbers and `null`.
 impl FromSql for Value {
     #[inline]
     fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
         match value {
             ValueRef::Text(s) => serde_json::from_slice(s),
             ValueRef::Blob(b) => serde_json::from_slice(b),
-            _ => return Err(FromSqlError::InvalidType),
+            ValueRef::Integer(i) => Ok(Value::from(i)),
+            ValueRef::Real(f) => Ok(Number::from_f64(f).map_or(Value::Null, Value::Number)),
+            ValueRef::Null => Ok(Value::Null),
         }
         .map_err(|err| FromSqlError::Other(Box::new(err)))
     }
@@ -28,6 +31,8 @@ impl FromSql for Value {
 
 #[cfg(test)]
 mod test {
+    use serde_json::{json, Value};
+
     use crate::types::ToSql;
     use crate::{Connection, Result};
 
@@ -54,4 +59,40 @@ mod test {
         assert_eq!(data, b);
         Ok(())
     }
+
+    #[test]
+    fn test_json_functions() -> Result<()> {
+        let db = checked_memory_handle()?;
+        let data = json!({"foo": 13, "bar": [1.5, null], "baz": {"qux": "x"}});
+        db.execute("INSERT INTO foo (t) VALUES (?)", [&data])?;
+
+        let foo: Value =
+            db.query_row("SELECT json_extract(t, '$.foo') FROM foo", [], |r| r.get(0))?;
+        assert_eq!(foo, json!(13));
+        let bar: Value =
+            db.query_row("SELECT json_extract(t, '$.bar') FROM foo", [], |r| r.get(0))?;
+        assert_eq!(bar, json!([1.5, null]));
+        let real: Value = db.query_row("SELECT json_extract(t, '$.bar[0]') FROM foo", [], |r| {
+            r.get(0)
+        })?;
+        assert_eq!(real, json!(1.5));
+        let null: Value = db.query_row("SELECT json_extract(t, '$.bar[1]') FROM foo", [], |r| {
+            r.get(0)
+        })?;
+        assert_eq!(null, Value::Null);
+
+        db.execute(
+            "UPDATE foo SET t = json_set(t, '$.baz.qux', ?)",
+            [&data["bar"]],
+        )?;
+        let t: Value = db.query_row("SELECT t FROM foo", [], |r| r.get(0))?;
+        assert_eq!(t["baz"]["qux"], json!("[1.5,null]"));
+        db.execute(
+            "UPDATE foo SET t = json_set(t, '$.baz.qux', json(?))",
+            [&data["bar"]],
+        )?;
+        let t: Value = db.query_row("SELECT t FROM foo", [], |r| r.get(0))?;
+        assert_eq!(t["baz"]["qux"], data["bar"]);
+        Ok(())
+    }
 }
@@ -1,41 +1,137 @@
-//! [`ToSql`] and [`FromSql`] implementation for [`time::OffsetDateTime`].
+//! [`ToSql`] and [`FromSql`] implementation for [`time::OffsetDateTime`],
+//! [`time::PrimitiveDateTime`], [`time::Date`] and [`time::Time`].
 use crate::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
 use crate::Result;
-use time::{OffsetDateTime, PrimitiveDateTime, UtcOffset};
+use time::format_description::well_known::Rfc3339;
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::{Date, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
 
-const CURRENT_TIMESTAMP_FMT: &str = "%Y-%m-%d %H:%M:%S";
-const SQLITE_DATETIME_FMT: &str = "%Y-%m-%d %H:%M:%S.%NZ";
-const SQLITE_DATETIME_FMT_LEGACY: &str = "%Y-%m-%d %H:%M:%S:%N %z";
+const DATE_FORMAT: &[FormatItem<'_>] = format_description!("[year]-[month]-[day]");
+const TIME_FORMAT: &[FormatItem<'_>] =
+    format_description!("[hour]:[minute][optional [:[second][optional [.[subsecond]]]]]");
+const PRIMITIVE_DATE_TIME_FORMAT: &[FormatItem<'_>] =
+    format_description!("[year]-[month]-[day] [hour]:[minute]:[second].[subsecond]");
+const PRIMITIVE_DATE_TIME_PARSE: &[FormatItem<'_>] = format_description!(
+    "[year]-[month]-[day][first [ ][T]][hour]:[minute][optional [:[second][optional [.[subsecond]]]]]"
+);
+const OFFSET_DATE_TIME_FORMAT: &[FormatItem<'_>] =
+    format_description!("[year]-[month]-[day] [hour]:[minute]:[second].[subsecond]Z");
+const LEGACY_DATE_TIME_FORMAT: &[FormatItem<'_>] = format_description!(
+    "[year]-[month]-[day] [hour]:[minute]:[second]:[subsecond] [offset_hour sign:mandatory][offset_minute]"
+);
 
+fn from_sql_error(err: impl std::error::Error + Send + Sync + 'static) -> FromSqlError {
+    FromSqlError::Other(Box::new(err))
+}
+
+/// ISO 8601 calendar date without timezone => "YYYY-MM-DD"
+impl ToSql for Date {
+    #[inline]
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        let date_str = self
+            .format(&DATE_FORMAT)
+            .map_err(|err| crate::Error::ToSqlConversionFailure(err.into()))?;
+        Ok(ToSqlOutput::from(date_str))
+    }
+}
+
+/// "YYYY-MM-DD" => ISO 8601 calendar date without timezone.
+impl FromSql for Date {
+    #[inline]
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()
+            .and_then(|s| Date::parse(s, &DATE_FORMAT).map_err(from_sql_error))
+    }
+}
+
+/// ISO 8601 time without timezone => "HH:MM:SS.SSS"
+impl ToSql for Time {
+    #[inline]
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        let time_str = self
+            .format(&TIME_FORMAT)
+            .map_err(|err| crate::Error::ToSqlConversionFailure(err.into()))?;
+        Ok(ToSqlOutput::from(time_str))
+    }
+}
+
+/// "HH:MM"/"HH:MM:SS"/"HH:MM:SS.SSS" => ISO 8601 time without timezone.
+impl FromSql for Time {
+    #[inline]
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()
+            .and_then(|s| Time::parse(s, &TIME_FORMAT).map_err(from_sql_error))
+    }
+}
+
+/// ISO 8601 combined date and time without timezone =>
+/// "YYYY-MM-DD HH:MM:SS.SSS"
+impl ToSql for PrimitiveDateTime {
+    #[inline]
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        let date_str = self
+            .format(&PRIMITIVE_DATE_TIME_FORMAT)
+            .map_err(|err| crate::Error::ToSqlConversionFailure(err.into()))?;
+        Ok(ToSqlOutput::from(date_str))
+    }
+}
+
+/// "YYYY-MM-DD HH:MM"/"YYYY-MM-DD HH:MM:SS"/"YYYY-MM-DD HH:MM:SS.SSS" => ISO
+/// 8601 combined date and time without timezone. ("YYYY-MM-DDTHH:MM:SS.SSS"
+/// also supported)
+impl FromSql for PrimitiveDateTime {
+    #[inline]
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value.as_str().and_then(|s| {
+            PrimitiveDateTime::parse(s, &PRIMITIVE_DATE_TIME_PARSE).map_err(from_sql_error)
+        })
+    }
+}
+
+/// Date and time with time zone => UTC RFC3339 timestamp
+/// ("YYYY-MM-DD HH:MM:SS.SSSZ").
 impl ToSql for OffsetDateTime {
     #[inline]
     fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
-        let time_string = self.to_offset(UtcOffset::UTC).format(SQLITE_DATETIME_FMT);
+        let time_string = self
+            .to_offset(UtcOffset::UTC)
+            .format(&OFFSET_DATE_TIME_FORMAT)
+            .map_err(|err| crate::Error::ToSqlConversionFailure(err.into()))?;
         Ok(ToSqlOutput::from(time_string))
     }
 }
 
+/// RFC3339 ("YYYY-MM-DD HH:MM:SS.SSS[+-]HH:MM" or "YYYY-MM-DDTHH:MM:SS.SSSZ",
+/// as written by other tools) into `OffsetDateTime`. Date and time without
+/// timezone (e.g. from `CURRENT_TIMESTAMP`) are assumed to be UTC.
 impl FromSql for OffsetDateTime {
     fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
-        value.as_str().and_then(|s| {
-            match s.len() {
-                19 => PrimitiveDateTime::parse(s, CURRENT_TIMESTAMP_FMT).map(|d| d.assume_utc()),
-                _ => PrimitiveDateTime::parse(s, SQLITE_DATETIME_FMT)
-                    .map(|d| d.assume_utc())
-                    .or_else(|err| {
-                        OffsetDateTime::parse(s, SQLITE_DATETIME_FMT_LEGACY).map_err(|_| err)
-                    }),
-            }
-            .map_err(|err| FromSqlError::Other(Box::new(err)))
-        })
+        let s = value.as_str()?;
+        // `Rfc3339` requires a 'T' between the date and the time.
+        let rfc3339 = if s.len() > 10 && s.as_bytes()[10] == b' ' {
+            OffsetDateTime::parse(&[&s[..10], "T", &s[11..]].concat(), &Rfc3339)
+        } else {
+            OffsetDateTime::parse(s, &Rfc3339)
+        };
+        rfc3339
+            .or_else(|err| {
+                PrimitiveDateTime::parse(s, &PRIMITIVE_DATE_TIME_PARSE)
+                    .map(PrimitiveDateTime::assume_utc)
+                    .or_else(|_| OffsetDateTime::parse(s, &LEGACY_DATE_TIME_FORMAT))
+                    .map_err(|_| err)
+            })
+            .map_err(from_sql_error)
     }
 }
 
 #[cfg(test)]
 mod test {
     use crate::{Connection, Result};
-    use std::time::Duration;
-    use time::OffsetDateTime;
+    use time::macros::{date, datetime, time};
+    use time::{Date, Duration, OffsetDateTime, PrimitiveDateTime, Time};
 
     fn checked_memory_handle() -> Result<Connection> {
         let db = Connection::open_in_memory()?;
@@ -49,8 +145,9 @@ mod test {
 
         let mut ts_vec = vec![];
 
-        let make_datetime =
-            |secs, nanos| OffsetDateTime::from_unix_timestamp(secs) + Duration::from_nanos(nanos);
+        let make_datetime = |secs, nanos| {
+            OffsetDateTime::from_unix_timestamp(secs).unwrap() + Duration::nanoseconds(nanos)
+        };
 
         ts_vec.push(make_datetime(10_000, 0)); //January 1, 1970 2:46:40 AM
         ts_vec.push(make_datetime(10_000, 1000)); //January 1, 1970 2:46:40 AM (and one microsecond)
@@ -71,12 +168,86 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_offset_date_time_parsing() -> Result<()> {
+        let db = checked_memory_handle()?;
+        let expected = datetime!(2013-10-07 08:23:19.120 UTC);
+        for s in &[
+            "2013-10-07 08:23:19.120Z",
+            "2013-10-07T08:23:19.120Z",
+            "2013-10-07T08:23:19.120z",
+            "2013-10-07 04:23:19.120-04:00",
+            "2013-10-07T10:23:19.12+02:00",
+            "2013-10-07 08:23:19.120",
+            "2013-10-07T08:23:19.12",
+            "2013-10-07 08:23:19:120000000 +0000",
+        ] {
+            let t: OffsetDateTime = db.query_row("SELECT ?", [s], |r| r.get(0))?;
+            assert_eq!(t, expected, "{}", s);
+        }
+        let t: OffsetDateTime = db.query_row("SELECT '2013-10-07 08:23'", [], |r| r.get(0))?;
+        assert_eq!(t, datetime!(2013-10-07 08:23 UTC));
+        Ok(())
+    }
+
+    #[test]
+    fn test_date() -> Result<()> {
+        let db = checked_memory_handle()?;
+        let date = date!(2016 - 02 - 23);
+        db.execute("INSERT INTO foo (t) VALUES (?)", [date])?;
+
+        let s: String = db.query_row("SELECT t FROM foo", [], |r| r.get(0))?;
+        assert_eq!("2016-02-23", s);
+        let t: Date = db.query_row("SELECT t FROM foo", [], |r| r.get(0))?;
+        assert_eq!(date, t);
+        Ok(())
+    }
+
+    #[test]
+    fn test_time() -> Result<()> {
+        let db = checked_memory_handle()?;
+        let time = time!(23:56:04.5);
+        db.execute("INSERT INTO foo (t) VALUES (?)", [time])?;
+
+        let s: String = db.query_row("SELECT t FROM foo", [], |r| r.get(0))?;
+        assert_eq!("23:56:04.5", s);
+        let v: Time = db.query_row("SELECT t FROM foo", [], |r| r.get(0))?;
+        assert_eq!(time, v);
+        let v: Time = db.query_row("SELECT '23:56'", [], |r| r.get(0))?;
+        assert_eq!(time!(23:56), v);
+        Ok(())
+    }
+
+    #[test]
+    fn test_primitive_date_time() -> Result<()> {
+        let db = checked_memory_handle()?;
+        let dt = datetime!(2016-02-23 23:56:04.789);
+        db.execute("INSERT INTO foo (t) VALUES (?)", [dt])?;
+
+        let s: String = db.query_row("SELECT t FROM foo", [], |r| r.get(0))?;
+        assert_eq!("2016-02-23 23:56:04.789", s);
+        let v: PrimitiveDateTime = db.query_row("SELECT t FROM foo", [], |r| r.get(0))?;
+        assert_eq!(dt, v);
+
+        db.execute("UPDATE foo set t = '2016-02-23T23:56:04.789'", [])?;
+        let v: PrimitiveDateTime = db.query_row("SELECT t FROM foo", [], |r| r.get(0))?;
+        assert_eq!(dt, v);
+        Ok(())
+    }
+
     #[test]
     fn test_sqlite_functions() -> Result<()> {
         let db = checked_memory_handle()?;
         let result: Result<OffsetDateTime> =
             db.query_row("SELECT CURRENT_TIMESTAMP", [], |r| r.get(0));
         assert!(result.is_ok());
+        let result: Result<PrimitiveDateTime> =
+            db.query_row("SELECT CURRENT_TIMESTAMP", [], |r| r.get(0));
+        assert!(result.is_ok());
+        let result: Result<Date> = db.query_row("SELECT CURRENT_DATE", [], |r| r.get(0));
+        assert!(result.is_ok());
+        let result: Result<Time> = db.query_row("SELECT CURRENT_TIME", [], |r| r.get(0));
+        assert!(result.is_ok());
         Ok(())
     }
 
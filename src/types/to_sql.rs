@@ -1,3 +1,5 @@
+#[cfg(feature = "modern_sqlite")]
+use super::Pointer;
 use super::{Null, Value, ValueRef};
 #[cfg(feature = "array")]
 use crate::vtab::array::Array;
@@ -24,6 +26,10 @@ pub enum ToSqlOutput<'a> {
     /// `feature = "array"`
     #[cfg(feature = "array")]
     Array(Array),
+
+    /// `feature = "modern_sqlite"` A Rust value passed as a pointer.
+    #[cfg(feature = "modern_sqlite")]
+    Pointer(Pointer),
 }
 
 // Generically allow any type that can be converted into a ValueRef
@@ -86,6 +92,8 @@ impl ToSql for ToSqlOutput<'_> {
             ToSqlOutput::ZeroBlob(i) => ToSqlOutput::ZeroBlob(i),
             #[cfg(feature = "array")]
             ToSqlOutput::Array(ref a) => ToSqlOutput::Array(a.clone()),
+            #[cfg(feature = "modern_sqlite")]
+            ToSqlOutput::Pointer(ref p) => ToSqlOutput::Pointer(p.clone()),
         })
     }
 }
@@ -183,9 +191,11 @@ macro_rules! to_sql_self_fallible(
     )
 );
 
-// Special implementations for usize and u64 because these conversions can fail.
+// Special implementations for usize, u64 and wider integers because these
+// conversions can fail.
 to_sql_self_fallible!(u64);
 to_sql_self_fallible!(usize);
+to_sql_self_fallible!(u128);
 
 impl<T: ?Sized> ToSql for &'_ T
 where
@@ -403,6 +413,21 @@ mod test {
 
         assert_eq!(found_id, id);
         assert_eq!(found_label, "target");
+
+        // Text is accepted on read.
+        let text = id.to_hyphenated().to_string();
+        let found_id: Uuid = db.query_row("SELECT ?", [&text], |r| r.get(0))?;
+        assert_eq!(found_id, id);
+        let found_id: Uuid = db.query_row("SELECT upper(?)", [&text], |r| r.get(0))?;
+        assert_eq!(found_id, id);
+        let err = db
+            .query_row("SELECT 'not a uuid'", [], |r| r.get::<_, Uuid>(0))
+            .unwrap_err();
+        assert!(matches!(err, crate::Error::FromSqlConversionFailure(..)));
+        let err = db
+            .query_row("SELECT x'0102'", [], |r| r.get::<_, Uuid>(0))
+            .unwrap_err();
+        assert!(matches!(err, crate::Error::InvalidColumnType(..)));
         Ok(())
     }
 }
@@ -0,0 +1,209 @@
+//! Storage of integers wider than `i64`.
+//!
+//! `u64` and `u128` are stored as `INTEGER`s, and fail to convert if they
+//! don't fit in an `i64`, as does `i128` wrapped in [`AsInteger`] (`i128`
+//! itself is stored as a blob with the `i128_blob` feature). [`AsText`] and
+//! [`AsBlob`] store any value of these types instead.
+use std::convert::TryFrom;
+
+use crate::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use crate::{Error, Result};
+
+/// Stores a `u64`, `u128` or `i128` as decimal text.
+///
+/// Values can also be read from `INTEGER`s. Note that the text doesn't sort
+/// in numeric order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AsText<T>(pub T);
+
+/// Stores a `u64`, `u128` or `i128` as a big-endian blob of 8 (`u64`) or 16
+/// bytes, with the sign bit of `i128` flipped, so that blobs sort in numeric
+/// order.
+///
+/// Values can also be read from `INTEGER`s.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AsBlob<T>(pub T);
+
+/// Stores a `u128` or `i128` as an `INTEGER`, failing to convert if it doesn't
+/// fit in an `i64`.
+///
+/// Unlike `i128` with the `i128_blob` feature, this doesn't depend on the
+/// enabled features.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AsInteger<T>(pub T);
+
+macro_rules! wide_integer(
+    ($t:ty, $flip:expr) => (
+        impl ToSql for AsText<$t> {
+            #[inline]
+            fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+                Ok(ToSqlOutput::from(self.0.to_string()))
+            }
+        }
+
+        impl FromSql for AsText<$t> {
+            fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+                match value {
+                    ValueRef::Integer(i) => {
+                        <$t>::try_from(i).map(AsText).map_err(|_| FromSqlError::OutOfRange(i))
+                    }
+                    _ => value.as_str().and_then(|s| {
+                        s.parse().map(AsText).map_err(|err| FromSqlError::Other(Box::new(err)))
+                    }),
+                }
+            }
+        }
+
+        impl ToSql for AsBlob<$t> {
+            #[inline]
+            fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+                Ok(ToSqlOutput::from((self.0 ^ $flip).to_be_bytes().to_vec()))
+            }
+        }
+
+        impl FromSql for AsBlob<$t> {
+            fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+                match value {
+                    ValueRef::Integer(i) => {
+                        <$t>::try_from(i).map(AsBlob).map_err(|_| FromSqlError::OutOfRange(i))
+                    }
+                    _ => value.as_blob().and_then(|bytes| {
+                        let bytes = TryFrom::try_from(bytes).map_err(|_| FromSqlError::InvalidType)?;
+                        Ok(AsBlob(<$t>::from_be_bytes(bytes) ^ $flip))
+                    }),
+                }
+            }
+        }
+    )
+);
+
+wide_integer!(u64, 0);
+wide_integer!(u128, 0);
+wide_integer!(i128, i128::MIN);
+
+macro_rules! as_integer(
+    ($t:ty) => (
+        impl ToSql for AsInteger<$t> {
+            #[inline]
+            fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+                Ok(ToSqlOutput::from(
+                    i64::try_from(self.0).map_err(|err| Error::ToSqlConversionFailure(err.into()))?,
+                ))
+            }
+        }
+
+        impl FromSql for AsInteger<$t> {
+            #[inline]
+            fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+                let i = value.as_i64()?;
+                <$t>::try_from(i).map(AsInteger).map_err(|_| FromSqlError::OutOfRange(i))
+            }
+        }
+    )
+);
+
+as_integer!(u128);
+as_integer!(i128);
+
+#[cfg(test)]
+mod test {
+    use super::{AsBlob, AsInteger, AsText};
+    use crate::{Connection, Error, Result};
+
+    #[test]
+    fn test_as_text() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        for &n in &[0, u64::MAX] {
+            let s: String = db.query_row("SELECT ?", [AsText(n)], |r| r.get(0))?;
+            assert_eq!(s, n.to_string());
+            let v: AsText<u64> = db.query_row("SELECT ?", [AsText(n)], |r| r.get(0))?;
+            assert_eq!(v.0, n);
+        }
+        for &n in &[i128::MIN, -1, i128::MAX] {
+            let v: AsText<i128> = db.query_row("SELECT ?", [AsText(n)], |r| r.get(0))?;
+            assert_eq!(v.0, n);
+        }
+        let v: AsText<u128> = db.query_row("SELECT ?", [AsText(u128::MAX)], |r| r.get(0))?;
+        assert_eq!(v.0, u128::MAX);
+
+        let v: AsText<u64> = db.query_row("SELECT 42", [], |r| r.get(0))?;
+        assert_eq!(v, AsText(42));
+        let err = db
+            .query_row("SELECT -1", [], |r| r.get::<_, AsText<u64>>(0))
+            .unwrap_err();
+        assert!(matches!(err, Error::IntegralValueOutOfRange(0, -1)));
+        let err = db
+            .query_row("SELECT '-1'", [], |r| r.get::<_, AsText<u64>>(0))
+            .unwrap_err();
+        assert!(matches!(err, Error::FromSqlConversionFailure(..)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_as_blob() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch("CREATE TABLE foo (u BLOB, i BLOB)")?;
+        let us = [0, 1, 1 << 63, u64::MAX];
+        let is = [i128::MIN, -1, 0, i128::MAX];
+        for (&u, &i) in us.iter().zip(is.iter()).rev() {
+            db.execute(
+                "INSERT INTO foo VALUES (?, ?)",
+                [&AsBlob(u) as &dyn crate::ToSql, &AsBlob(i)],
+            )?;
+        }
+        let mut stmt = db.prepare("SELECT u FROM foo ORDER BY u")?;
+        let sorted = stmt
+            .query_map([], |r| r.get::<_, AsBlob<u64>>(0))?
+            .map(|r| r.map(|v| v.0))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(sorted, us);
+        let mut stmt = db.prepare("SELECT i FROM foo ORDER BY i")?;
+        let sorted = stmt
+            .query_map([], |r| r.get::<_, AsBlob<i128>>(0))?
+            .map(|r| r.map(|v| v.0))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(sorted, is);
+
+        let len: i64 = db.query_row("SELECT length(?)", [AsBlob(u128::MAX)], |r| r.get(0))?;
+        assert_eq!(len, 16);
+        let v: AsBlob<u128> = db.query_row("SELECT 7", [], |r| r.get(0))?;
+        assert_eq!(v, AsBlob(7));
+        let err = db
+            .query_row("SELECT x'00'", [], |r| r.get::<_, AsBlob<u64>>(0))
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidColumnType(..)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_overflow() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        let v: u128 = db.query_row("SELECT ?", [u128::from(u64::MAX >> 1)], |r| r.get(0))?;
+        assert_eq!(v, u128::from(u64::MAX >> 1));
+        let err = db
+            .query_row("SELECT ?", [u128::MAX], |r| r.get::<_, u128>(0))
+            .unwrap_err();
+        assert!(matches!(err, Error::ToSqlConversionFailure(..)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_as_integer() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        for &n in &[i128::from(i64::MIN), -1, i128::from(i64::MAX)] {
+            let v: AsInteger<i128> = db.query_row("SELECT ?", [AsInteger(n)], |r| r.get(0))?;
+            assert_eq!(v, AsInteger(n));
+        }
+        let typ: String = db.query_row("SELECT typeof(?)", [AsInteger(-1i128)], |r| r.get(0))?;
+        assert_eq!(typ, "integer");
+        let err = db
+            .query_row("SELECT ?", [AsInteger(i128::MAX)], |r| r.get::<_, i64>(0))
+            .unwrap_err();
+        assert!(matches!(err, Error::ToSqlConversionFailure(..)));
+        let err = db
+            .query_row("SELECT -1", [], |r| r.get::<_, AsInteger<u128>>(0))
+            .unwrap_err();
+        assert!(matches!(err, Error::IntegralValueOutOfRange(0, -1)));
+        Ok(())
+    }
+}
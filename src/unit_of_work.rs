@@ -0,0 +1,252 @@
+use std::cell::{Cell, RefCell};
+use std::time::{Duration, Instant};
+
+use crate::{Connection, Error, Savepoint};
+
+/// The duration and outcome of a scope of a [`UnitOfWork`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ScopeMetrics {
+    /// The names of the enclosing scopes and of the scope, separated by `/`.
+    pub path: String,
+    /// The number of enclosing scopes.
+    pub depth: u32,
+    /// The time spent in the scope, including committing or rolling it back.
+    pub duration: Duration,
+    /// Whether the changes of the scope were kept (until an enclosing scope
+    /// rolls back).
+    pub committed: bool,
+}
+
+/// Nested units of work on a connection, each in a savepoint.
+///
+/// Each [`scope`](UnitOfWork::scope) runs in a savepoint, named after its
+/// depth, which is released if the scope succeeds, and rolled back if it
+/// fails, undoing its changes and those of its nested scopes, but not those
+/// of the enclosing scopes. The outermost scope is a transaction, unless one
+/// is already open. The duration of each scope is recorded.
+///
+/// ```rust,no_run
+/// # use rusqlite::{Connection, Result, UnitOfWork};
+/// fn import(conn: &Connection, files: &[&str]) -> Result<()> {
+///     let uow = UnitOfWork::new(conn);
+///     uow.scope("import", |uow| {
+///         for file in files {
+///             // A file which fails to load is skipped.
+///             let _ = uow.scope(file, |uow| load(uow.connection(), file));
+///         }
+///         Ok::<_, rusqlite::Error>(())
+///     })?;
+///     for scope in uow.take_metrics() {
+///         println!("{}: {:?}", scope.path, scope.duration);
+///     }
+///     Ok(())
+/// }
+/// # fn load(_: &Connection, _: &str) -> Result<()> { Ok(()) }
+/// ```
+#[derive(Debug)]
+pub struct UnitOfWork<'conn> {
+    conn: &'conn Connection,
+    depth: Cell<u32>,
+    path: RefCell<Vec<String>>,
+    metrics: RefCell<Vec<ScopeMetrics>>,
+}
+
+impl<'conn> UnitOfWork<'conn> {
+    /// Creates a unit of work on `conn`.
+    #[inline]
+    pub fn new(conn: &'conn Connection) -> UnitOfWork<'conn> {
+        UnitOfWork {
+            conn,
+            depth: Cell::new(0),
+            path: RefCell::new(Vec::new()),
+            metrics: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// The connection of the unit of work.
+    #[inline]
+    pub fn connection(&self) -> &'conn Connection {
+        self.conn
+    }
+
+    /// Runs `f` in a new scope, named `name` in the metrics, and commits its
+    /// changes if it returns `Ok`, or rolls them back if it returns `Err` (or
+    /// panics).
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `f` does, or if the savepoint cannot be created or
+    /// released.
+    pub fn scope<T, E, F>(&self, name: &str, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&Self) -> Result<T, E>,
+        E: From<Error>,
+    {
+        let start = Instant::now();
+        let depth = self.depth.get();
+        let sp =
+            Savepoint::with_depth_and_name(self.conn, depth, format!("_rusqlite_uow_{}", depth))?;
+        self.depth.set(depth + 1);
+        self.path.borrow_mut().push(name.to_owned());
+        let mut guard = ScopeGuard {
+            uow: self,
+            depth,
+            sp: Some(sp),
+        };
+        let result = f(self);
+        let mut sp = guard.sp.take().unwrap();
+        let path = self.path.borrow().join("/");
+        drop(guard);
+        let committed = result.is_ok();
+        let finished = if committed {
+            sp.commit()
+        } else {
+            // Roll back, then release the savepoint, to end the transaction
+            // if it started it.
+            sp.rollback().and_then(|_| sp.commit())
+        };
+        self.metrics.borrow_mut().push(ScopeMetrics {
+            path,
+            depth,
+            duration: start.elapsed(),
+            committed: committed && finished.is_ok(),
+        });
+        finished?;
+        result
+    }
+
+    /// Returns the metrics of the scopes completed since the last call, in
+    /// the order they completed (nested scopes first).
+    pub fn take_metrics(&self) -> Vec<ScopeMetrics> {
+        self.metrics.replace(Vec::new())
+    }
+}
+
+// Leaves a scope, even if it panics.
+struct ScopeGuard<'a, 'conn> {
+    uow: &'a UnitOfWork<'conn>,
+    depth: u32,
+    // Still there if the scope panicked.
+    sp: Option<Savepoint<'conn>>,
+}
+
+impl Drop for ScopeGuard<'_, '_> {
+    fn drop(&mut self) {
+        if let Some(mut sp) = self.sp.take() {
+            let _ = sp.rollback().and_then(|_| sp.commit());
+        }
+        self.uow.depth.set(self.depth);
+        self.uow.path.borrow_mut().pop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::UnitOfWork;
+    use crate::{Connection, Error, Result};
+
+    fn checked_memory_handle() -> Result<Connection> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch("CREATE TABLE foo (x INTEGER)")?;
+        Ok(db)
+    }
+
+    fn values(db: &Connection) -> Result<Vec<i64>> {
+        let mut stmt = db.prepare("SELECT x FROM foo ORDER BY x")?;
+        let rows = stmt.query_map([], |r| r.get(0))?;
+        rows.collect()
+    }
+
+    #[test]
+    fn test_nested_scopes() -> Result<()> {
+        let db = checked_memory_handle()?;
+        let uow = UnitOfWork::new(&db);
+        uow.scope("outer", |uow| {
+            uow.connection().execute("INSERT INTO foo VALUES (1)", [])?;
+            uow.scope("ok", |uow| {
+                uow.connection().execute("INSERT INTO foo VALUES (2)", [])
+            })?;
+            let failed: Result<()> = uow.scope("failed", |uow| {
+                uow.connection().execute("INSERT INTO foo VALUES (3)", [])?;
+                uow.scope("inner", |uow| {
+                    uow.connection().execute("INSERT INTO foo VALUES (4)", [])
+                })?;
+                Err(Error::QueryReturnedNoRows)
+            });
+            assert_eq!(failed, Err(Error::QueryReturnedNoRows));
+            uow.connection().execute("INSERT INTO foo VALUES (5)", [])?;
+            Ok::<_, Error>(())
+        })?;
+        assert!(db.is_autocommit());
+        assert_eq!(values(&db)?, vec![1, 2, 5]);
+
+        let metrics = uow.take_metrics();
+        let summary: Vec<_> = metrics
+            .iter()
+            .map(|m| (m.path.as_str(), m.depth, m.committed))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("outer/ok", 1, true),
+                ("outer/failed/inner", 2, true),
+                ("outer/failed", 1, false),
+                ("outer", 0, true),
+            ]
+        );
+        assert!(metrics[3].duration >= metrics[2].duration);
+        assert!(uow.take_metrics().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_outer_scope() -> Result<()> {
+        #[derive(Debug, PartialEq)]
+        enum AppError {
+            Sqlite(Error),
+            Invalid,
+        }
+        impl From<Error> for AppError {
+            fn from(err: Error) -> AppError {
+                AppError::Sqlite(err)
+            }
+        }
+
+        let db = checked_memory_handle()?;
+        let uow = UnitOfWork::new(&db);
+        let result = uow.scope("outer", |uow| {
+            uow.connection().execute("INSERT INTO foo VALUES (1)", [])?;
+            Err::<(), _>(AppError::Invalid)
+        });
+        assert_eq!(result, Err(AppError::Invalid));
+        // The transaction started by the scope is over.
+        assert!(db.is_autocommit());
+        assert!(values(&db)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_panicking_scope() -> Result<()> {
+        let db = checked_memory_handle()?;
+        let uow = UnitOfWork::new(&db);
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            uow.scope("panicking", |uow| -> Result<()> {
+                uow.connection().execute("INSERT INTO foo VALUES (1)", [])?;
+                panic!("oops");
+            })
+        }));
+        assert!(panicked.is_err());
+        assert!(db.is_autocommit());
+        assert!(values(&db)?.is_empty());
+
+        uow.take_metrics();
+        uow.scope("after", |uow| {
+            uow.connection().execute("INSERT INTO foo VALUES (2)", [])
+        })?;
+        assert_eq!(values(&db)?, vec![2]);
+        let metrics = uow.take_metrics();
+        assert_eq!((metrics[0].path.as_str(), metrics[0].depth), ("after", 0));
+        Ok(())
+    }
+}
@@ -0,0 +1,356 @@
+//! [URI filenames](https://sqlite.org/uri.html) with typed query parameters,
+//! to open or attach databases.
+//!
+//! SQLite ignores the query parameters it does not know, and most of the
+//! values it does not expect, so a misspelled option silently has no effect:
+//! [`DatabaseUri`] only builds the options SQLite knows, and rejects the
+//! combinations which contradict each other.
+//!
+//! ```rust,no_run
+//! use rusqlite::uri::{Cache, DatabaseUri, Mode};
+//! use rusqlite::{Connection, Result};
+//!
+//! fn attach_reference(conn: &Connection) -> Result<()> {
+//!     let reference = DatabaseUri::new("/data/GRCh38.db")
+//!         .mode(Mode::ReadOnly)
+//!         .immutable(true);
+//!     conn.attach_uri(&reference, "reference")
+//! }
+//!
+//! fn open_scratch() -> Result<Connection> {
+//!     Connection::open_uri(&DatabaseUri::memory("scratch").cache(Cache::Shared))
+//! }
+//! ```
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use crate::{Connection, Error, OpenFlags, Result};
+
+/// The access mode of a database, the `mode` query parameter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Mode {
+    /// `mode=ro`: read-only.
+    ReadOnly,
+    /// `mode=rw`: read-write, the database must exist.
+    ReadWrite,
+    /// `mode=rwc`: read-write, the database is created if needed.
+    ReadWriteCreate,
+    /// `mode=memory`: an in-memory database, named by the path, which is
+    /// never read from or written to the disk.
+    Memory,
+}
+
+impl Mode {
+    fn as_str(self) -> &'static str {
+        match self {
+            Mode::ReadOnly => "ro",
+            Mode::ReadWrite => "rw",
+            Mode::ReadWriteCreate => "rwc",
+            Mode::Memory => "memory",
+        }
+    }
+}
+
+/// Whether the page cache is shared, the `cache` query parameter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Cache {
+    /// `cache=shared`: share the cache with the other connections of the
+    /// process to the same database.
+    Shared,
+    /// `cache=private`: a cache of its own.
+    Private,
+}
+
+impl Cache {
+    fn as_str(self) -> &'static str {
+        match self {
+            Cache::Shared => "shared",
+            Cache::Private => "private",
+        }
+    }
+}
+
+/// A URI filename: the path of a database and its options.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DatabaseUri {
+    path: PathBuf,
+    vfs: Option<String>,
+    mode: Option<Mode>,
+    cache: Option<Cache>,
+    psow: Option<bool>,
+    nolock: bool,
+    immutable: bool,
+}
+
+impl DatabaseUri {
+    /// The database at `path`, with the default options.
+    pub fn new<P: AsRef<Path>>(path: P) -> DatabaseUri {
+        DatabaseUri {
+            path: path.as_ref().to_path_buf(),
+            vfs: None,
+            mode: None,
+            cache: None,
+            psow: None,
+            nolock: false,
+            immutable: false,
+        }
+    }
+
+    /// The in-memory database `name`, which the connections sharing their
+    /// cache can all open.
+    pub fn memory(name: &str) -> DatabaseUri {
+        DatabaseUri::new(name).mode(Mode::Memory)
+    }
+
+    /// Use the VFS `vfs`.
+    #[must_use]
+    pub fn vfs(mut self, vfs: &str) -> Self {
+        self.vfs = Some(vfs.to_owned());
+        self
+    }
+
+    /// Set the access mode, instead of the one of the opening flags.
+    #[must_use]
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Share the cache or not, instead of the default of the process.
+    #[must_use]
+    pub fn cache(mut self, cache: Cache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Whether writes are powersafe overwrites (`psow`), instead of the
+    /// default of the VFS.
+    #[must_use]
+    pub fn psow(mut self, psow: bool) -> Self {
+        self.psow = Some(psow);
+        self
+    }
+
+    /// Whether to skip file locking (`nolock`), only safe when no other
+    /// process uses the database.
+    #[must_use]
+    pub fn nolock(mut self, nolock: bool) -> Self {
+        self.nolock = nolock;
+        self
+    }
+
+    /// Whether the database is stored on read-only media (`immutable`), so
+    /// that it is read without locking nor checking for changes.
+    #[must_use]
+    pub fn immutable(mut self, immutable: bool) -> Self {
+        self.immutable = immutable;
+        self
+    }
+
+    /// Returns the URI, e.g. `file:/data/GRCh38.db?mode=ro&immutable=1`.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the path is not valid UTF-8, or if the options
+    /// contradict each other: an immutable database which is not read-only,
+    /// or an in-memory database with options applying to files.
+    pub fn to_uri(&self) -> Result<String> {
+        match self.mode {
+            Some(Mode::Memory) if self.immutable || self.nolock || self.psow.is_some() => {
+                return Err(Error::InvalidUri(
+                    "immutable, nolock and psow do not apply to mode=memory".to_owned(),
+                ));
+            }
+            Some(mode) if self.immutable && mode != Mode::ReadOnly => {
+                return Err(Error::InvalidUri(format!(
+                    "immutable requires mode=ro, not mode={}",
+                    mode.as_str()
+                )));
+            }
+            _ => {}
+        }
+        if self.vfs.as_deref() == Some("") {
+            return Err(Error::InvalidUri("empty vfs".to_owned()));
+        }
+        let path = self
+            .path
+            .to_str()
+            .ok_or_else(|| Error::InvalidPath(self.path.clone()))?;
+
+        let mut uri = String::from("file:");
+        // Elsewhere, `\` is a character of file names, and is encoded.
+        #[cfg(windows)]
+        let path: &str = &{
+            // A path starting with a drive letter.
+            if path.as_bytes().get(1) == Some(&b':') {
+                uri.push('/');
+            }
+            path.replace('\\', "/")
+        };
+        encode(&mut uri, path, "/:");
+        let mut params = Vec::new();
+        if let Some(ref vfs) = self.vfs {
+            params.push(("vfs", vfs.as_str()));
+        }
+        if let Some(mode) = self.mode {
+            params.push(("mode", mode.as_str()));
+        }
+        if let Some(cache) = self.cache {
+            params.push(("cache", cache.as_str()));
+        }
+        if let Some(psow) = self.psow {
+            params.push(("psow", if psow { "1" } else { "0" }));
+        }
+        if self.nolock {
+            params.push(("nolock", "1"));
+        }
+        if self.immutable {
+            params.push(("immutable", "1"));
+        }
+        for (i, (name, value)) in params.into_iter().enumerate() {
+            uri.push(if i == 0 { '?' } else { '&' });
+            uri.push_str(name);
+            uri.push('=');
+            encode(&mut uri, value, "");
+        }
+        Ok(uri)
+    }
+}
+
+// Percent-encodes `s`, keeping the unreserved characters and `keep`.
+fn encode(uri: &mut String, s: &str, keep: &str) {
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) || keep.as_bytes().contains(&b) {
+            uri.push(char::from(b));
+        } else {
+            write!(uri, "%{:02X}", b).unwrap();
+        }
+    }
+}
+
+impl Connection {
+    /// Open the database `uri`, with the default flags.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the options of `uri` contradict each other, or
+    /// if the underlying SQLite open call fails.
+    pub fn open_uri(uri: &DatabaseUri) -> Result<Connection> {
+        Connection::open_with_flags(uri.to_uri()?, OpenFlags::default())
+    }
+
+    /// Attach the database `uri` as `schema`.
+    ///
+    /// The connection must have been opened with
+    /// [`SQLITE_OPEN_URI`](OpenFlags::SQLITE_OPEN_URI), as with the default
+    /// flags, for the options to be applied.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the options of `uri` contradict each other, or
+    /// if the underlying SQLite call fails.
+    pub fn attach_uri(&self, uri: &DatabaseUri, schema: &str) -> Result<()> {
+        self.execute("ATTACH ?1 AS ?2", [uri.to_uri()?.as_str(), schema])
+            .map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Cache, DatabaseUri, Mode};
+    use crate::{Connection, Error, ErrorCode, Result};
+
+    #[test]
+    fn test_to_uri() -> Result<()> {
+        assert_eq!(DatabaseUri::new("data.db").to_uri()?, "file:data.db");
+        assert_eq!(
+            DatabaseUri::new("/data/run 1?#.db")
+                .mode(Mode::ReadOnly)
+                .cache(Cache::Private)
+                .psow(false)
+                .nolock(true)
+                .immutable(true)
+                .to_uri()?,
+            "file:/data/run%201%3F%23.db?mode=ro&cache=private&psow=0&nolock=1&immutable=1"
+        );
+        #[cfg(windows)]
+        assert_eq!(
+            DatabaseUri::new(r"C:\data\a.db").vfs("win32").to_uri()?,
+            "file:/C:/data/a.db?vfs=win32"
+        );
+        #[cfg(not(windows))]
+        assert_eq!(
+            DatabaseUri::new(r"C:\data\a.db")
+                .vfs("unix-dotfile")
+                .to_uri()?,
+            "file:C:%5Cdata%5Ca.db?vfs=unix-dotfile"
+        );
+        assert_eq!(
+            DatabaseUri::memory("scratch")
+                .cache(Cache::Shared)
+                .to_uri()?,
+            "file:scratch?mode=memory&cache=shared"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_combinations() {
+        for uri in &[
+            DatabaseUri::new("a.db")
+                .mode(Mode::ReadWrite)
+                .immutable(true),
+            DatabaseUri::memory("a").immutable(true),
+            DatabaseUri::memory("a").nolock(true),
+            DatabaseUri::memory("a").psow(true),
+            DatabaseUri::new("a.db").vfs(""),
+        ] {
+            match uri.to_uri().unwrap_err() {
+                Error::InvalidUri(_) => {}
+                err => panic!("Unexpected error {}", err),
+            }
+        }
+        assert!(DatabaseUri::new("a.db")
+            .mode(Mode::ReadOnly)
+            .immutable(true)
+            .to_uri()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_attach_uri() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("reference db.db3");
+        Connection::open(&path)?.execute_batch("CREATE TABLE genes (name TEXT)")?;
+
+        let db = Connection::open_in_memory()?;
+        let reference = DatabaseUri::new(&path).mode(Mode::ReadOnly).immutable(true);
+        db.attach_uri(&reference, "reference")?;
+        db.query_row("SELECT count(*) FROM reference.genes", [], |r| {
+            r.get::<_, i64>(0)
+        })?;
+        match db
+            .execute("INSERT INTO reference.genes VALUES ('BRCA1')", [])
+            .unwrap_err()
+        {
+            Error::SqliteFailure(err, _) => assert_eq!(err.code, ErrorCode::ReadOnly),
+            err => panic!("Unexpected error {}", err),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_uri_shared_memory() -> Result<()> {
+        let uri = DatabaseUri::memory("test_open_uri_shared_memory").cache(Cache::Shared);
+        let db1 = Connection::open_uri(&uri)?;
+        db1.execute_batch("CREATE TABLE samples (name TEXT)")?;
+        let db2 = Connection::open_uri(&uri)?;
+        db2.execute("INSERT INTO samples VALUES ('NA12878')", [])?;
+        let count: i64 = db1.query_row("SELECT count(*) FROM samples", [], |r| r.get(0))?;
+        assert_eq!(count, 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+//! A minimal executor, for the tests of futures.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake};
+use std::thread;
+
+struct ThreadWaker(thread::Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Polls `future` once.
+pub(crate) fn poll_once<F: Future + Unpin>(future: &mut F) -> Poll<F::Output> {
+    let waker = Arc::new(ThreadWaker(thread::current())).into();
+    Pin::new(future).poll(&mut Context::from_waker(&waker))
+}
+
+/// Polls `future` to completion, parking the thread while it is pending,
+/// and returns its output with the number of times it was pending.
+pub(crate) fn block_on<F: Future + Unpin>(mut future: F) -> (F::Output, usize) {
+    let waker = Arc::new(ThreadWaker(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    let mut pending = 0;
+    loop {
+        match Pin::new(&mut future).poll(&mut cx) {
+            Poll::Ready(output) => return (output, pending),
+            Poll::Pending => {
+                pending += 1;
+                thread::park();
+            }
+        }
+    }
+}
@@ -1,11 +1,27 @@
 // Internal utilities
+#[cfg(test)]
+pub(crate) mod executor;
 pub(crate) mod param_cache;
-mod small_cstr;
+mod sql_str;
 pub(crate) use param_cache::ParamIndexCache;
-pub(crate) use small_cstr::SmallCString;
+pub(crate) use sql_str::SqlStr;
+
+/// Quotes `identifier`, e.g. a table, column or schema name, with double
+/// quotes, doubling the ones it contains, as `%w` does.
+pub(crate) fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
 
 // Doesn't use any modern features or vtab stuff, but is only used by them.
-#[cfg(any(feature = "modern_sqlite", feature = "vtab"))]
+#[cfg(any(
+    feature = "modern_sqlite",
+    feature = "vtab",
+    feature = "extension_init"
+))]
 mod sqlite_string;
-#[cfg(any(feature = "modern_sqlite", feature = "vtab"))]
+#[cfg(any(
+    feature = "modern_sqlite",
+    feature = "vtab",
+    feature = "extension_init"
+))]
 pub(crate) use sqlite_string::SqliteMallocString;
@@ -1,30 +1,97 @@
-use super::SmallCString;
-use std::cell::RefCell;
+use super::SqlStr;
+use std::cell::{Cell, Ref, RefCell};
 use std::collections::BTreeMap;
 
 /// Maps parameter names to parameter indices.
-#[derive(Default, Clone, Debug)]
-// BTreeMap seems to do better here unless we want to pull in a custom hash
-// function.
-pub(crate) struct ParamIndexCache(RefCell<BTreeMap<SmallCString, usize>>);
+#[derive(Clone, Debug)]
+pub(crate) struct ParamIndexCache {
+    // BTreeMap seems to do better here unless we want to pull in a custom hash
+    // function.
+    names: RefCell<BTreeMap<SqlStr, usize>>,
+    // Indices of the most recently resolved set of named parameters, so that
+    // binding the same set again (the common case) needs no lookup at all.
+    // The names are not kept when the cache is disabled.
+    last_set: RefCell<(Option<Vec<SqlStr>>, Vec<usize>)>,
+    capacity: Cell<usize>,
+}
+
+impl Default for ParamIndexCache {
+    fn default() -> Self {
+        ParamIndexCache {
+            names: RefCell::default(),
+            last_set: RefCell::default(),
+            capacity: Cell::new(usize::MAX),
+        }
+    }
+}
 
 impl ParamIndexCache {
     pub fn get_or_insert_with<F>(&self, s: &str, func: F) -> Option<usize>
     where
         F: FnOnce(&std::ffi::CStr) -> Option<usize>,
     {
-        let mut cache = self.0.borrow_mut();
+        let mut cache = self.names.borrow_mut();
         // Avoid entry API, needs allocation to test membership.
         if let Some(v) = cache.get(s) {
             return Some(*v);
         }
         // If there's an internal nul in the name it couldn't have been a
         // parameter, so early return here is ok.
-        let name = SmallCString::new(s).ok()?;
+        let name = SqlStr::new(s).ok()?;
         let val = func(&name)?;
-        cache.insert(name, val);
+        if cache.len() < self.capacity.get() {
+            cache.insert(name, val);
+        }
         Some(val)
     }
+
+    /// Returns the indices of all of `names`, in order, resolving them with
+    /// `func` unless they are the same names as the previous call. Returns the
+    /// first name which cannot be resolved as an error.
+    pub fn get_or_insert_set<'a, I, F>(
+        &self,
+        names: I,
+        mut func: F,
+    ) -> Result<Ref<'_, [usize]>, &'a str>
+    where
+        I: Iterator<Item = &'a str> + Clone,
+        F: FnMut(&str) -> Option<usize>,
+    {
+        let hit = match self.last_set.borrow().0 {
+            Some(ref last) => names.clone().eq(last.iter().map(SqlStr::as_str)),
+            None => false,
+        };
+        if !hit {
+            let mut set_names = Vec::new();
+            let mut indices = Vec::new();
+            for name in names {
+                let idx = func(name).ok_or(name)?;
+                // `func` succeeding means `name` has no internal nul.
+                set_names.push(SqlStr::new(name).map_err(|_| name)?);
+                indices.push(idx);
+            }
+            let set_names = if self.capacity.get() > 0 {
+                Some(set_names)
+            } else {
+                None
+            };
+            *self.last_set.borrow_mut() = (set_names, indices);
+        }
+        Ok(Ref::map(self.last_set.borrow(), |set| &set.1[..]))
+    }
+
+    /// Limits the number of names remembered. A capacity of 0 disables the
+    /// cache.
+    pub fn set_capacity(&self, capacity: usize) {
+        self.capacity.set(capacity);
+        let mut cache = self.names.borrow_mut();
+        if cache.len() > capacity {
+            cache.clear();
+        }
+        if capacity == 0 {
+            self.last_set.borrow_mut().0 = None;
+        }
+    }
 }
 
 #[cfg(test)]
@@ -57,4 +124,45 @@ mod test {
         });
         assert_eq!(v, Some(30));
     }
+
+    #[test]
+    fn test_cache_set() {
+        let p = ParamIndexCache::default();
+        let names = [":a", ":b"];
+        let v = p.get_or_insert_set(names.iter().copied(), |name| match name {
+            ":a" => Some(1),
+            ":b" => Some(2),
+            _ => None,
+        });
+        assert_eq!(&*v.unwrap(), &[1, 2]);
+        let v = p.get_or_insert_set(names.iter().copied(), |_| {
+            panic!("shouldn't be called this time");
+        });
+        assert_eq!(&*v.unwrap(), &[1, 2]);
+        let v = p.get_or_insert_set([":b"].iter().copied(), |_| Some(2));
+        assert_eq!(&*v.unwrap(), &[2]);
+        let v = p.get_or_insert_set([":b", ":c"].iter().copied(), |name| match name {
+            ":b" => Some(2),
+            _ => None,
+        });
+        assert_eq!(v.unwrap_err(), ":c");
+    }
+
+    #[test]
+    fn test_cache_capacity() {
+        let p = ParamIndexCache::default();
+        p.set_capacity(1);
+        assert_eq!(p.get_or_insert_with("foo", |_| Some(1)), Some(1));
+        assert_eq!(p.get_or_insert_with("bar", |_| Some(2)), Some(2));
+        // "bar" didn't fit, so it has to be looked up again.
+        assert_eq!(p.get_or_insert_with("bar", |_| Some(3)), Some(3));
+        assert_eq!(p.get_or_insert_with("foo", |_| panic!("cached")), Some(1));
+
+        p.set_capacity(0);
+        assert_eq!(p.get_or_insert_with("foo", |_| Some(4)), Some(4));
+        let v = p.get_or_insert_set(["foo"].iter().copied(), |_| Some(5));
+        assert_eq!(&*v.unwrap(), &[5]);
+        let v = p.get_or_insert_set(["foo"].iter().copied(), |_| Some(6));
+        assert_eq!(&*v.unwrap(), &[6]);
+    }
 }
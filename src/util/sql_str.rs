@@ -1,13 +1,21 @@
 use smallvec::{smallvec, SmallVec};
 use std::ffi::{CStr, CString, NulError};
 
-/// Similar to std::ffi::CString, but avoids heap allocating if the string is
-/// small enough. Also guarantees it's input is UTF-8 -- used for cases where we
-/// need to pass a NUL-terminated string to SQLite, and we have a `&str`.
+/// A NUL-terminated UTF-8 string, as passed to SQLite for SQL fragments and
+/// identifiers (table, column, parameter, database and function names...).
+///
+/// Similar to `std::ffi::CString`, but avoids heap allocating if the string is
+/// small enough (up to 15 bytes): used wherever a `&str` has to be handed to
+/// SQLite as a C string, and as a cheap map key.
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub(crate) struct SmallCString(smallvec::SmallVec<[u8; 16]>);
-
-impl SmallCString {
+pub(crate) struct SqlStr(smallvec::SmallVec<[u8; 16]>);
+
+impl SqlStr {
+    /// Copies `s` and appends a NUL terminator.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `s` contains an internal NUL byte.
     #[inline]
     pub fn new(s: &str) -> Result<Self, NulError> {
         if s.as_bytes().contains(&0u8) {
@@ -21,6 +29,7 @@ impl SmallCString {
         Ok(res)
     }
 
+    /// Get the string, not including the NUL terminator.
     #[inline]
     pub fn as_str(&self) -> &str {
         self.debug_checks();
@@ -30,8 +39,8 @@ impl SmallCString {
 
     /// Get the bytes not including the NUL terminator. E.g. the bytes which
     /// make up our `str`:
-    /// - `SmallCString::new("foo").as_bytes_without_nul() == b"foo"`
-    /// - `SmallCString::new("foo").as_bytes_with_nul() == b"foo\0"
+    /// - `SqlStr::new("foo").as_bytes_without_nul() == b"foo"`
+    /// - `SqlStr::new("foo").as_bytes_with_nul() == b"foo\0"`
     #[inline]
     pub fn as_bytes_without_nul(&self) -> &[u8] {
         self.debug_checks();
@@ -60,18 +69,22 @@ impl SmallCString {
     #[cfg(not(debug_assertions))]
     fn debug_checks(&self) {}
 
+    /// Length in bytes, not including the NUL terminator.
     #[inline]
     pub fn len(&self) -> usize {
         debug_assert_ne!(self.0.len(), 0);
         self.0.len() - 1
     }
 
+    /// Returns `true` if the string (not including the NUL terminator) is
+    /// empty.
     #[inline]
     #[allow(unused)] // clippy wants this function.
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
 
+    /// Borrow as a `CStr`, e.g. to pass it to SQLite with `as_ptr()`.
     #[inline]
     pub fn as_cstr(&self) -> &CStr {
         let bytes = self.as_bytes_with_nul();
@@ -85,20 +98,20 @@ impl SmallCString {
     }
 }
 
-impl Default for SmallCString {
+impl Default for SqlStr {
     #[inline]
     fn default() -> Self {
         Self(smallvec![0])
     }
 }
 
-impl std::fmt::Debug for SmallCString {
+impl std::fmt::Debug for SqlStr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_tuple("SmallCString").field(&self.as_str()).finish()
+        f.debug_tuple("SqlStr").field(&self.as_str()).finish()
     }
 }
 
-impl std::ops::Deref for SmallCString {
+impl std::ops::Deref for SqlStr {
     type Target = CStr;
 
     #[inline]
@@ -107,21 +120,21 @@ impl std::ops::Deref for SmallCString {
     }
 }
 
-impl PartialEq<SmallCString> for str {
+impl PartialEq<SqlStr> for str {
     #[inline]
-    fn eq(&self, s: &SmallCString) -> bool {
+    fn eq(&self, s: &SqlStr) -> bool {
         s.as_bytes_without_nul() == self.as_bytes()
     }
 }
 
-impl PartialEq<str> for SmallCString {
+impl PartialEq<str> for SqlStr {
     #[inline]
     fn eq(&self, s: &str) -> bool {
         self.as_bytes_without_nul() == s.as_bytes()
     }
 }
 
-impl std::borrow::Borrow<str> for SmallCString {
+impl std::borrow::Borrow<str> for SqlStr {
     #[inline]
     fn borrow(&self) -> &str {
         self.as_str()
@@ -136,35 +149,29 @@ mod test {
     fn test_small_cstring() {
         // We don't go through the normal machinery for default, so make sure
         // things work.
-        assert_eq!(SmallCString::default().0, SmallCString::new("").unwrap().0);
-        assert_eq!(SmallCString::new("foo").unwrap().len(), 3);
-        assert_eq!(
-            SmallCString::new("foo").unwrap().as_bytes_with_nul(),
-            b"foo\0"
-        );
-        assert_eq!(
-            SmallCString::new("foo").unwrap().as_bytes_without_nul(),
-            b"foo",
-        );
+        assert_eq!(SqlStr::default().0, SqlStr::new("").unwrap().0);
+        assert_eq!(SqlStr::new("foo").unwrap().len(), 3);
+        assert_eq!(SqlStr::new("foo").unwrap().as_bytes_with_nul(), b"foo\0");
+        assert_eq!(SqlStr::new("foo").unwrap().as_bytes_without_nul(), b"foo",);
 
-        assert_eq!(SmallCString::new("😀").unwrap().len(), 4);
+        assert_eq!(SqlStr::new("😀").unwrap().len(), 4);
         assert_eq!(
-            SmallCString::new("😀").unwrap().0.as_slice(),
+            SqlStr::new("😀").unwrap().0.as_slice(),
             b"\xf0\x9f\x98\x80\0",
         );
         assert_eq!(
-            SmallCString::new("😀").unwrap().as_bytes_without_nul(),
+            SqlStr::new("😀").unwrap().as_bytes_without_nul(),
             b"\xf0\x9f\x98\x80",
         );
 
-        assert_eq!(SmallCString::new("").unwrap().len(), 0);
-        assert!(SmallCString::new("").unwrap().is_empty());
+        assert_eq!(SqlStr::new("").unwrap().len(), 0);
+        assert!(SqlStr::new("").unwrap().is_empty());
 
-        assert_eq!(SmallCString::new("").unwrap().0.as_slice(), b"\0");
-        assert_eq!(SmallCString::new("").unwrap().as_bytes_without_nul(), b"");
+        assert_eq!(SqlStr::new("").unwrap().0.as_slice(), b"\0");
+        assert_eq!(SqlStr::new("").unwrap().as_bytes_without_nul(), b"");
 
-        assert!(SmallCString::new("\0").is_err());
-        assert!(SmallCString::new("\0abc").is_err());
-        assert!(SmallCString::new("abc\0").is_err());
+        assert!(SqlStr::new("\0").is_err());
+        assert!(SqlStr::new("\0abc").is_err());
+        assert!(SqlStr::new("abc\0").is_err());
     }
 }
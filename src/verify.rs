@@ -0,0 +1,218 @@
+use std::path::Path;
+
+use crate::{Connection, Error, ErrorCode, OpenFlags, Result};
+
+/// What [`Connection::open_verified`] checks about a database before
+/// returning a connection to it.
+///
+/// ```rust,no_run
+/// # use rusqlite::{Connection, Error, OpenRequirements, Result};
+/// const APPLICATION_ID: u32 = 0x4745_4e4f;
+///
+/// fn open(path: &str) -> Result<Connection> {
+///     let requirements = OpenRequirements::new()
+///         .application_id(APPLICATION_ID)
+///         .user_version(12)
+///         .quick_check(true);
+///     match Connection::open_verified(path, &requirements) {
+///         Err(Error::NeedsMigration { expected, found }) => {
+///             eprintln!("{} is at version {}, run the migrations", path, found);
+///             Err(Error::NeedsMigration { expected, found })
+///         }
+///         result => result,
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct OpenRequirements {
+    flags: OpenFlags,
+    application_id: Option<u32>,
+    user_version: Option<i32>,
+    quick_check: bool,
+}
+
+impl OpenRequirements {
+    /// No requirements: the database is opened with the default flags,
+    /// and not checked.
+    pub fn new() -> OpenRequirements {
+        OpenRequirements::default()
+    }
+
+    /// Sets the flags the database is opened with.
+    #[must_use]
+    pub fn flags(mut self, flags: OpenFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Requires the `application_id` of the database to be `id`. It is 0
+    /// for a new database.
+    #[must_use]
+    pub fn application_id(mut self, id: u32) -> Self {
+        self.application_id = Some(id);
+        self
+    }
+
+    /// Requires the `user_version` of the database, the version of its
+    /// schema, to be `version`. It is 0 for a new database.
+    #[must_use]
+    pub fn user_version(mut self, version: i32) -> Self {
+        self.user_version = Some(version);
+        self
+    }
+
+    /// Sets whether `PRAGMA quick_check` is run, which reads the whole
+    /// database, and takes time proportional to its size.
+    #[must_use]
+    pub fn quick_check(mut self, quick_check: bool) -> Self {
+        self.quick_check = quick_check;
+        self
+    }
+}
+
+impl Connection {
+    /// Opens the database at `path`, with the flags of `requirements`, and
+    /// checks that it meets them.
+    ///
+    /// # Failure
+    ///
+    /// Will return [`Error::WrongApplication`] if the `application_id` of
+    /// the database is not the required one, e.g. because `path` is the
+    /// database of another program, [`Error::NeedsMigration`] if its
+    /// `user_version` is older than the required one,
+    /// [`Error::UnsupportedVersion`] if it is newer, and [`Error::Corrupt`]
+    /// if the quick check finds problems. Will return other errors if the database
+    /// cannot be opened or read, e.g. if it is not a database.
+    pub fn open_verified<P: AsRef<Path>>(
+        path: P,
+        requirements: &OpenRequirements,
+    ) -> Result<Connection> {
+        let conn = Connection::open_with_flags(path, requirements.flags)?;
+        conn.verify(requirements)?;
+        Ok(conn)
+    }
+
+    fn verify(&self, requirements: &OpenRequirements) -> Result<()> {
+        if let Some(expected) = requirements.application_id {
+            let found = self.application_id()?;
+            if found != expected {
+                return Err(Error::WrongApplication { expected, found });
+            }
+        }
+        if let Some(expected) = requirements.user_version {
+            let found = self.user_version()?;
+            if found < expected {
+                return Err(Error::NeedsMigration { expected, found });
+            }
+            if found > expected {
+                return Err(Error::UnsupportedVersion { expected, found });
+            }
+        }
+        if requirements.quick_check {
+            let mut problems = Vec::new();
+            let checked = self.pragma_query(None, "quick_check", |row| {
+                problems.push(row.get::<_, String>(0)?);
+                Ok(())
+            });
+            match checked {
+                Err(Error::SqliteFailure(err, msg)) if err.code == ErrorCode::DatabaseCorrupt => {
+                    problems.push(msg.unwrap_or_else(|| err.to_string()));
+                }
+                checked => checked?,
+            }
+            if problems != ["ok"] {
+                return Err(Error::Corrupt(problems));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::OpenRequirements;
+    use crate::{Connection, Error, OpenFlags, Result};
+
+    #[test]
+    fn test_open_verified() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db3");
+        {
+            let db = Connection::open(&path)?;
+            db.execute_batch(
+                "PRAGMA application_id = -2;
+                 PRAGMA user_version = 3;
+                 CREATE TABLE foo (x INTEGER);",
+            )?;
+        }
+
+        let requirements = OpenRequirements::new()
+            .flags(OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .application_id(0xffff_fffe)
+            .user_version(3)
+            .quick_check(true);
+        let db = Connection::open_verified(&path, &requirements)?;
+        assert!(db.execute_batch("CREATE TABLE bar (x)").is_err());
+        drop(db);
+
+        match Connection::open_verified(&path, &requirements.clone().application_id(1)) {
+            Err(Error::WrongApplication {
+                expected: 1,
+                found: 0xffff_fffe,
+            }) => {}
+            r => panic!("unexpected {:?}", r.map(|_| ())),
+        }
+        match Connection::open_verified(&path, &requirements.clone().user_version(4)) {
+            Err(Error::NeedsMigration {
+                expected: 4,
+                found: 3,
+            }) => {}
+            r => panic!("unexpected {:?}", r.map(|_| ())),
+        }
+        match Connection::open_verified(&path, &requirements.clone().user_version(2)) {
+            Err(Error::UnsupportedVersion {
+                expected: 2,
+                found: 3,
+            }) => {}
+            r => panic!("unexpected {:?}", r.map(|_| ())),
+        }
+        // A new database.
+        match Connection::open_verified(
+            dir.path().join("new.db3"),
+            &OpenRequirements::new().application_id(1),
+        ) {
+            Err(Error::WrongApplication {
+                expected: 1,
+                found: 0,
+            }) => {}
+            r => panic!("unexpected {:?}", r.map(|_| ())),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db3");
+        {
+            let db = Connection::open(&path)?;
+            db.execute_batch(
+                "PRAGMA page_size = 1024;
+                 CREATE TABLE foo (x INTEGER PRIMARY KEY, y TEXT);
+                 WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1000)
+                 INSERT INTO foo SELECT i, printf('%050d', i) FROM n;",
+            )?;
+        }
+        // Overwrite the cells of a leaf page of foo.
+        let mut bytes = std::fs::read(&path).unwrap();
+        for b in &mut bytes[1024 * 3 + 100..1024 * 4] {
+            *b = 0xa5;
+        }
+        std::fs::write(&path, bytes).unwrap();
+        match Connection::open_verified(&path, &OpenRequirements::new().quick_check(true)) {
+            Err(Error::Corrupt(problems)) => assert!(!problems.is_empty()),
+            r => panic!("unexpected {:?}", r.map(|_| ())),
+        }
+        Ok(())
+    }
+}
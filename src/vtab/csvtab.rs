@@ -40,10 +40,14 @@ use crate::{Connection, Error, Result};
 /// CREATE VIRTUAL TABLE vtab USING csv(
 ///   filename=FILENAME -- Name of file containing CSV content
 ///   [, schema=SCHEMA] -- Alternative CSV schema. 'CREATE TABLE x(col1 TEXT NOT NULL, col2 INT, ...);'
-///   [, header=YES|NO] -- First row of CSV defines the names of columns if "yes". Default "no".
+///   [, header=YES|NO|AUTO] -- First row of CSV defines the names of columns if "yes".
+///                            Guessed from the first two rows if "auto". Default "no".
 ///   [, columns=N] -- Assume the CSV file contains N columns.
 ///   [, delimiter=C] -- CSV delimiter. Default ','.
 ///   [, quote=C] -- CSV quote. Default '"'. 0 means no quote.
+///   [, typed=YES|NO] -- Declare columns INTEGER, REAL or TEXT from their values in the
+///                       first 100 rows, and return integers and reals (and empty
+///                       values as NULL) for them. Default "no": all values are TEXT.
 /// );
 /// ```
 pub fn load_module(conn: &Connection) -> Result<()> {
@@ -63,6 +67,57 @@ struct CSVTab {
     quote: u8,
     /// Offset to start of data
     offset_first_row: csv::Position,
+    /// Type of each column, empty when all are TEXT
+    types: Vec<Affinity>,
+}
+
+// Rows scanned to infer column types.
+const TYPED_ROWS: usize = 100;
+
+/// Type of a column, from its values
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Affinity {
+    Integer,
+    Real,
+    Text,
+}
+
+impl Affinity {
+    fn of(value: &str) -> Option<Affinity> {
+        let value = value.trim();
+        if value.is_empty() {
+            None
+        } else if value.parse::<i64>().is_ok() {
+            Some(Affinity::Integer)
+        } else if is_real(value) {
+            Some(Affinity::Real)
+        } else {
+            Some(Affinity::Text)
+        }
+    }
+
+    // The type of a column holding values of types `self` and `other`.
+    fn merge(self, other: Affinity) -> Affinity {
+        match (self, other) {
+            (Affinity::Text, _) | (_, Affinity::Text) => Affinity::Text,
+            (Affinity::Integer, Affinity::Integer) => Affinity::Integer,
+            _ => Affinity::Real,
+        }
+    }
+
+    fn decl_type(self) -> &'static str {
+        match self {
+            Affinity::Integer => "INTEGER",
+            Affinity::Real => "REAL",
+            Affinity::Text => "TEXT",
+        }
+    }
+}
+
+// A decimal number, but not "inf" or "NaN".
+fn is_real(value: &str) -> bool {
+    value.starts_with(|c: char| c.is_ascii_digit() || c == '.' || c == '-' || c == '+')
+        && value.parse::<f64>().is_ok()
 }
 
 impl CSVTab {
@@ -74,6 +129,45 @@ impl CSVTab {
             .from_path(&self.filename)
     }
 
+    // Whether the first row looks like a header: none of its values are
+    // numbers, and some of the second row are.
+    fn detect_header(&self) -> Result<bool> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .from_path(&self.filename)?;
+        let mut records = reader.records();
+        let first = match records.next() {
+            Some(first) => first?,
+            None => return Ok(false),
+        };
+        let second = match records.next() {
+            Some(second) => second?,
+            None => return Ok(false),
+        };
+        let numeric = |v: &str| matches!(Affinity::of(v), Some(a) if a != Affinity::Text);
+        Ok(!first.iter().any(numeric) && second.iter().any(numeric))
+    }
+
+    // The types of the `n_col` columns, from the values of the first rows.
+    fn infer_types(&self, n_col: usize) -> Result<Vec<Affinity>> {
+        let mut reader = self.reader()?;
+        reader.seek(self.offset_first_row.clone())?;
+        let mut types: Vec<Option<Affinity>> = vec![None; n_col];
+        for record in reader.records().take(TYPED_ROWS) {
+            for (t, value) in types.iter_mut().zip(record?.iter()) {
+                if let Some(a) = Affinity::of(value) {
+                    *t = Some(t.map_or(a, |t| t.merge(a)));
+                }
+            }
+        }
+        Ok(types
+            .into_iter()
+            .map(|t| t.unwrap_or(Affinity::Text))
+            .collect())
+    }
+
     fn parameter(c_slice: &[u8]) -> Result<(&str, &str)> {
         let arg = str::from_utf8(c_slice)?.trim();
         let mut split = arg.split('=');
@@ -116,9 +210,12 @@ unsafe impl<'vtab> VTab<'vtab> for CSVTab {
             delimiter: b',',
             quote: b'"',
             offset_first_row: csv::Position::new(),
+            types: Vec::new(),
         };
         let mut schema = None;
         let mut n_col = None;
+        let mut detect_header = false;
+        let mut typed = false;
 
         let args = &args[3..];
         for c_slice in args {
@@ -156,8 +253,11 @@ unsafe impl<'vtab> VTab<'vtab> for CSVTab {
                     }
                 }
                 "header" => {
-                    if let Some(b) = parse_boolean(value) {
+                    if value.eq_ignore_ascii_case("auto") {
+                        detect_header = true;
+                    } else if let Some(b) = parse_boolean(value) {
                         vtab.has_headers = b;
+                        detect_header = false;
                     } else {
                         return Err(Error::ModuleError(format!(
                             "unrecognized argument to 'header': {}",
@@ -165,6 +265,16 @@ unsafe impl<'vtab> VTab<'vtab> for CSVTab {
                         )));
                     }
                 }
+                "typed" => {
+                    if let Some(b) = parse_boolean(value) {
+                        typed = b;
+                    } else {
+                        return Err(Error::ModuleError(format!(
+                            "unrecognized argument to 'typed': {}",
+                            value
+                        )));
+                    }
+                }
                 "delimiter" => {
                     if let Some(b) = CSVTab::parse_byte(value) {
                         vtab.delimiter = b;
@@ -201,6 +311,9 @@ unsafe impl<'vtab> VTab<'vtab> for CSVTab {
         if vtab.filename.is_empty() {
             return Err(Error::ModuleError("no CSV file specified".to_owned()));
         }
+        if detect_header {
+            vtab.has_headers = vtab.detect_header()?;
+        }
 
         let mut cols: Vec<String> = Vec::new();
         if vtab.has_headers || (n_col.is_none() && schema.is_none()) {
@@ -236,11 +349,15 @@ unsafe impl<'vtab> VTab<'vtab> for CSVTab {
         }
 
         if schema.is_none() {
+            if typed {
+                vtab.types = vtab.infer_types(cols.len())?;
+            }
             let mut sql = String::from("CREATE TABLE x(");
             for (i, col) in cols.iter().enumerate() {
                 sql.push('"');
                 sql.push_str(col);
-                sql.push_str("\" TEXT");
+                sql.push_str("\" ");
+                sql.push_str(vtab.types.get(i).unwrap_or(&Affinity::Text).decl_type());
                 if i == cols.len() - 1 {
                     sql.push_str(");");
                 } else {
@@ -344,8 +461,22 @@ unsafe impl VTabCursor for CSVTabCursor<'_> {
         if self.cols.is_empty() {
             return ctx.set_result(&Null);
         }
-        // TODO Affinity
-        ctx.set_result(&self.cols[col as usize].to_owned())
+        let value = &self.cols[col as usize];
+        match self.vtab().types.get(col as usize) {
+            Some(Affinity::Integer) | Some(Affinity::Real) if value.trim().is_empty() => {
+                ctx.set_result(&Null)
+            }
+            Some(Affinity::Integer) => match value.trim().parse::<i64>() {
+                Ok(i) => ctx.set_result(&i),
+                // Beyond the rows the type was inferred from.
+                Err(_) => ctx.set_result(&value.to_owned()),
+            },
+            Some(Affinity::Real) => match value.trim().parse::<f64>() {
+                Ok(f) if is_real(value.trim()) => ctx.set_result(&f),
+                _ => ctx.set_result(&value.to_owned()),
+            },
+            _ => ctx.set_result(&value.to_owned()),
+        }
     }
 
     fn rowid(&self) -> Result<i64> {
@@ -404,4 +535,60 @@ mod test {
         }
         db.execute_batch("DROP TABLE vtab")
     }
+
+    #[test]
+    fn test_csv_typed() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("samples.csv");
+        std::fs::write(
+            &path,
+            "id,name,depth,score\n1,a,30.5,7\n2,b,,x\n3,\"c, d\",12,8\n",
+        )
+        .unwrap();
+        let db = Connection::open_in_memory()?;
+        csvtab::load_module(&db)?;
+        db.execute_batch(&format!(
+            "CREATE VIRTUAL TABLE vtab USING csv(filename='{}', header=auto, typed=yes)",
+            path.display()
+        ))?;
+
+        let types: Vec<String> = db
+            .prepare("SELECT type FROM pragma_table_info('vtab')")?
+            .query([])?
+            .map(|r| r.get(0))
+            .collect()?;
+        assert_eq!(types, vec!["INTEGER", "TEXT", "REAL", "TEXT"]);
+        let (sum, depth, nulls): (i64, f64, i64) = db.query_row(
+            "SELECT sum(id), sum(depth), count(*) - count(depth) FROM vtab",
+            [],
+            |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+        )?;
+        assert_eq!((sum, depth, nulls), (6, 42.5, 1));
+        let name: String = db.query_row("SELECT name FROM vtab WHERE id = 3", [], |r| r.get(0))?;
+        assert_eq!(name, "c, d");
+
+        // A missing file can't be sniffed.
+        db.execute_batch(&format!(
+            "CREATE VIRTUAL TABLE data USING csv(filename='{}', header=auto)",
+            dir.path().join("data.csv").display()
+        ))
+        .unwrap_err();
+        // No header: the first row has numbers.
+        std::fs::write(dir.path().join("data.csv"), "1,a\n2,b\n").unwrap();
+        db.execute_batch(&format!(
+            "CREATE VIRTUAL TABLE data USING csv(filename='{}', header=auto)",
+            dir.path().join("data.csv").display()
+        ))?;
+        let count: i64 = db.query_row("SELECT count(*) FROM data", [], |r| r.get(0))?;
+        assert_eq!(count, 2);
+        // Untyped, the header of test.csv is detected too.
+        db.execute_batch("CREATE VIRTUAL TABLE t USING csv(filename='test.csv', header=auto)")?;
+        let col: String = db.query_row(
+            "SELECT name FROM pragma_table_info('t') WHERE cid = 0",
+            [],
+            |r| r.get(0),
+        )?;
+        assert_eq!(col, "colA");
+        Ok(())
+    }
 }
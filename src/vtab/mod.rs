@@ -84,16 +84,23 @@ const ZERO_MODULE: ffi::sqlite3_module = unsafe {
     .module
 };
 
+// The version of the modules of non-eponymous virtual tables: V3 for
+// xShadowName, which is only in the bindings of recent SQLite versions.
+#[cfg(feature = "modern_sqlite")]
+const CREATE_MODULE_VERSION: c_int = 3;
+#[cfg(not(feature = "modern_sqlite"))]
+const CREATE_MODULE_VERSION: c_int = 2;
+
 /// `feature = "vtab"` Create a read-only virtual table implementation.
 ///
 /// Step 2 of [Creating New Virtual Table Implementations](https://sqlite.org/vtab.html#creating_new_virtual_table_implementations).
+#[allow(clippy::needless_update)] // ZERO_MODULE is needed without modern_sqlite
 pub fn read_only_module<'vtab, T: CreateVTab<'vtab>>() -> &'static Module<'vtab, T> {
     // The xConnect and xCreate methods do the same thing, but they must be
     // different so that the virtual table is not an eponymous virtual table.
     &Module {
         base: ffi::sqlite3_module {
-            // We don't use V3
-            iVersion: 2, // We don't use V2 or V3 features in read_only_module types
+            iVersion: CREATE_MODULE_VERSION,
             xCreate: Some(rust_create::<T>),
             xConnect: Some(rust_connect::<T>),
             xBestIndex: Some(rust_best_index::<T>),
@@ -111,11 +118,13 @@ pub fn read_only_module<'vtab, T: CreateVTab<'vtab>>() -> &'static Module<'vtab,
             xSync: None,
             xCommit: None,
             xRollback: None,
-            xFindFunction: None,
+            xFindFunction: Some(rust_find_function::<T>),
             xRename: None,
             xSavepoint: None,
             xRelease: None,
             xRollbackTo: None,
+            #[cfg(feature = "modern_sqlite")]
+            xShadowName: Some(rust_shadow_name::<T>),
             ..ZERO_MODULE
         },
         phantom: PhantomData::<&'vtab T>,
@@ -150,7 +159,7 @@ pub fn eponymous_only_module<'vtab, T: VTab<'vtab>>() -> &'static Module<'vtab,
             xSync: None,
             xCommit: None,
             xRollback: None,
-            xFindFunction: None,
+            xFindFunction: Some(rust_find_function::<T>),
             xRename: None,
             xSavepoint: None,
             xRelease: None,
@@ -161,6 +170,84 @@ pub fn eponymous_only_module<'vtab, T: VTab<'vtab>>() -> &'static Module<'vtab,
     }
 }
 
+/// `feature = "vtab"` Create a writable virtual table implementation.
+///
+/// Step 2 of [Creating New Virtual Table Implementations](https://sqlite.org/vtab.html#creating_new_virtual_table_implementations).
+#[allow(clippy::needless_update)] // ZERO_MODULE is needed without modern_sqlite
+pub fn update_module<'vtab, T: UpdateVTab<'vtab>>() -> &'static Module<'vtab, T> {
+    &Module {
+        base: ffi::sqlite3_module {
+            iVersion: CREATE_MODULE_VERSION,
+            xCreate: Some(rust_create::<T>),
+            xConnect: Some(rust_connect::<T>),
+            xBestIndex: Some(rust_best_index::<T>),
+            xDisconnect: Some(rust_disconnect::<T>),
+            xDestroy: Some(rust_destroy::<T>),
+            xOpen: Some(rust_open::<T>),
+            xClose: Some(rust_close::<T::Cursor>),
+            xFilter: Some(rust_filter::<T::Cursor>),
+            xNext: Some(rust_next::<T::Cursor>),
+            xEof: Some(rust_eof::<T::Cursor>),
+            xColumn: Some(rust_column::<T::Cursor>),
+            xRowid: Some(rust_rowid::<T::Cursor>),
+            xUpdate: Some(rust_update::<T>),
+            xBegin: None,
+            xSync: None,
+            xCommit: None,
+            xRollback: None,
+            xFindFunction: Some(rust_find_function::<T>),
+            xRename: None,
+            xSavepoint: None,
+            xRelease: None,
+            xRollbackTo: None,
+            #[cfg(feature = "modern_sqlite")]
+            xShadowName: Some(rust_shadow_name::<T>),
+            ..ZERO_MODULE
+        },
+        phantom: PhantomData::<&'vtab T>,
+    }
+}
+
+/// `feature = "vtab"` Create a writable virtual table implementation, which
+/// takes part in the transactions and savepoints of the connection.
+///
+/// Step 2 of [Creating New Virtual Table Implementations](https://sqlite.org/vtab.html#creating_new_virtual_table_implementations).
+#[allow(clippy::needless_update)] // ZERO_MODULE is needed without modern_sqlite
+pub fn transaction_module<'vtab, T: TransactionVTab<'vtab>>() -> &'static Module<'vtab, T> {
+    &Module {
+        base: ffi::sqlite3_module {
+            // V2 for xSavepoint, xRelease and xRollbackTo
+            iVersion: CREATE_MODULE_VERSION,
+            xCreate: Some(rust_create::<T>),
+            xConnect: Some(rust_connect::<T>),
+            xBestIndex: Some(rust_best_index::<T>),
+            xDisconnect: Some(rust_disconnect::<T>),
+            xDestroy: Some(rust_destroy::<T>),
+            xOpen: Some(rust_open::<T>),
+            xClose: Some(rust_close::<T::Cursor>),
+            xFilter: Some(rust_filter::<T::Cursor>),
+            xNext: Some(rust_next::<T::Cursor>),
+            xEof: Some(rust_eof::<T::Cursor>),
+            xColumn: Some(rust_column::<T::Cursor>),
+            xRowid: Some(rust_rowid::<T::Cursor>),
+            xUpdate: Some(rust_update::<T>),
+            xBegin: Some(rust_begin::<T>),
+            xSync: Some(rust_sync::<T>),
+            xCommit: Some(rust_commit::<T>),
+            xRollback: Some(rust_rollback::<T>),
+            xFindFunction: Some(rust_find_function::<T>),
+            xRename: None,
+            xSavepoint: Some(rust_savepoint::<T>),
+            xRelease: Some(rust_release::<T>),
+            xRollbackTo: Some(rust_rollback_to::<T>),
+            #[cfg(feature = "modern_sqlite")]
+            xShadowName: Some(rust_shadow_name::<T>),
+            ..ZERO_MODULE
+        },
+        phantom: PhantomData::<&'vtab T>,
+    }
+}
+
 /// `feature = "vtab"`
 pub struct VTabConnection(*mut ffi::sqlite3);
 
@@ -226,6 +313,35 @@ pub unsafe trait VTab<'vtab>: Sized {
     /// Create a new cursor used for accessing a virtual table.
     /// (See [SQLite doc](https://sqlite.org/vtab.html#the_xopen_method))
     fn open(&'vtab self) -> Result<Self::Cursor>;
+
+    /// Overload the function `name`, called with `n_arg` arguments, when its
+    /// first argument is a column of this virtual table, e.g. `match` for
+    /// the `MATCH` operator (`x MATCH y` calls `match(y, x)`, and is
+    /// overloaded when `x` is a column). Other functions must exist: see
+    /// [`Connection::overload_function`].
+    ///
+    /// Return `None` by default, to keep the function as is.
+    /// (See [SQLite doc](https://sqlite.org/vtab.html#the_xfindfunction_method))
+    fn find_function(&self, n_arg: c_int, name: &str) -> Option<FunctionOverload> {
+        let _ = (n_arg, name);
+        None
+    }
+}
+
+/// `feature = "vtab"` A function overloaded by a virtual table: it sets the
+/// result of the call from the arguments.
+pub type VTabFunction = fn(&mut Context, &Values<'_>) -> Result<()>;
+
+/// `feature = "vtab"` How [`VTab::find_function`] overloads a function.
+#[derive(Clone, Copy)]
+pub enum FunctionOverload {
+    /// Call the given function instead.
+    Function(VTabFunction),
+    /// Call the given function instead, and, when the call is a constraint
+    /// of the `WHERE` clause, pass it to [`VTab::best_index`] as
+    /// [`SQLITE_INDEX_CONSTRAINT_FUNCTION`](IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_FUNCTION)
+    /// with the given code, which must be at least 150 (SQLite >= 3.25.0).
+    Constraint(VTabFunction, u8),
 }
 
 /// `feature = "vtab"` Non-eponymous virtual table instance trait.
@@ -255,6 +371,93 @@ pub trait CreateVTab<'vtab>: VTab<'vtab> {
     fn destroy(&self) -> Result<()> {
         Ok(())
     }
+
+    /// Whether `{table}_{suffix}` is the name of a shadow table of a virtual
+    /// table `table`, i.e. a real table where it keeps its data. When
+    /// [`SQLITE_DBCONFIG_DEFENSIVE`](crate::config::DbConfig::SQLITE_DBCONFIG_DEFENSIVE)
+    /// is set, shadow tables are read-only to ordinary SQL, e.g. from
+    /// untrusted sources, and can only be written by the virtual table.
+    ///
+    /// Return `false` by default.
+    /// (See [SQLite doc](https://sqlite.org/vtab.html#the_xshadowname_method))
+    #[cfg(feature = "modern_sqlite")]
+    fn shadow_name(suffix: &str) -> bool {
+        let _ = suffix;
+        false
+    }
+}
+
+/// `feature = "vtab"` Writable virtual table instance trait.
+///
+/// (See [SQLite doc](https://sqlite.org/vtab.html#xupdate))
+pub trait UpdateVTab<'vtab>: CreateVTab<'vtab> {
+    /// Delete the row with rowid `arg`.
+    fn delete(&mut self, arg: ValueRef<'_>) -> Result<()>;
+    /// Insert a row, and return its rowid.
+    ///
+    /// `args[0]` is `NULL`, `args[1]` is the rowid of the new row, `NULL` if
+    /// it is to be chosen by the virtual table, and `args[2..]` are the
+    /// values of its columns.
+    fn insert(&mut self, args: &Values<'_>) -> Result<i64>;
+    /// Update a row.
+    ///
+    /// `args[0]` is the rowid of the row to update, `args[1]` its new rowid
+    /// (the same one, unless it is changed), and `args[2..]` the new values
+    /// of its columns.
+    fn update(&mut self, args: &Values<'_>) -> Result<()>;
+}
+
+/// `feature = "vtab"` Writable virtual table instance trait, with hooks
+/// called as the transactions and savepoints of the connection begin and
+/// end, e.g. to write the changes to an external file on commit.
+///
+/// All the hooks do nothing by default.
+///
+/// (See [SQLite doc](https://sqlite.org/vtab.html#the_xbegin_method))
+pub trait TransactionVTab<'vtab>: UpdateVTab<'vtab> {
+    /// Begin a transaction, before the first change to the virtual table in
+    /// the transaction.
+    fn begin(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Start the commit of the transaction: the first phase of a two-phase
+    /// commit. An error aborts the commit.
+    fn sync(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Commit the transaction, once all the virtual tables and databases of
+    /// the transaction are synced.
+    fn commit(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Roll back the transaction.
+    fn rollback(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Mark the current state as savepoint `n`: savepoints are numbered
+    /// from 0 for the outermost one, as they are nested.
+    ///
+    /// (See [SQLite doc](https://sqlite.org/vtab.html#the_xsavepoint_xrelease_and_xrollbackto_methods))
+    fn savepoint(&mut self, n: c_int) -> Result<()> {
+        let _ = n;
+        Ok(())
+    }
+
+    /// Release savepoint `n`, and the ones nested in it.
+    fn release(&mut self, n: c_int) -> Result<()> {
+        let _ = n;
+        Ok(())
+    }
+
+    /// Roll back to the state of savepoint `n`, which stays active.
+    fn rollback_to(&mut self, n: c_int) -> Result<()> {
+        let _ = n;
+        Ok(())
+    }
 }
 
 /// `feature = "vtab"` Index constraint operator.
@@ -351,6 +554,18 @@ impl IndexInfo {
         }
     }
 
+    /// String used to identify the index, passed to [`VTabCursor::filter`]
+    #[inline]
+    pub fn set_idx_str(&mut self, idx_str: &str) {
+        unsafe {
+            if (*self.0).needToFreeIdxStr != 0 {
+                ffi::sqlite3_free((*self.0).idxStr as *mut c_void);
+            }
+            (*self.0).idxStr = alloc(idx_str);
+            (*self.0).needToFreeIdxStr = 1;
+        }
+    }
+
     /// True if output is already ordered
     #[inline]
     pub fn set_order_by_consumed(&mut self, order_by_consumed: bool) {
@@ -643,6 +858,20 @@ impl Connection {
     }
 }
 
+impl Connection {
+    /// `feature = "vtab"` Make sure that a function named `name`, taking
+    /// `n_arg` arguments (-1 for any number), exists, so that virtual tables
+    /// can overload it with [`VTab::find_function`]. If there is no such
+    /// function, one which fails when called is registered.
+    ///
+    /// (See [SQLite doc](https://sqlite.org/c3ref/overload_function.html))
+    pub fn overload_function(&self, name: &str, n_arg: c_int) -> Result<()> {
+        let c_name = str_to_cstring(name)?;
+        let r = unsafe { ffi::sqlite3_overload_function(self.handle(), c_name.as_ptr(), n_arg) };
+        self.decode_result(r)
+    }
+}
+
 impl InnerConnection {
     fn create_module<'vtab, T: VTab<'vtab>>(
         &mut self,
@@ -857,6 +1086,59 @@ where
     }
 }
 
+unsafe extern "C" fn rust_find_function<'vtab, T>(
+    vtab: *mut ffi::sqlite3_vtab,
+    n_arg: c_int,
+    z_name: *const c_char,
+    px_func: *mut Option<
+        unsafe extern "C" fn(*mut ffi::sqlite3_context, c_int, *mut *mut ffi::sqlite3_value),
+    >,
+    pp_arg: *mut *mut c_void,
+) -> c_int
+where
+    T: VTab<'vtab>,
+{
+    use std::ffi::CStr;
+    let vt = vtab as *mut T;
+    let name = match CStr::from_ptr(z_name).to_str() {
+        Ok(name) => name,
+        Err(_) => return 0,
+    };
+    let (f, r) = match (*vt).find_function(n_arg, name) {
+        None => return 0,
+        Some(FunctionOverload::Constraint(f, code)) if code >= 150 => (f, c_int::from(code)),
+        Some(FunctionOverload::Function(f)) | Some(FunctionOverload::Constraint(f, _)) => (f, 1),
+    };
+    *px_func = Some(rust_overloaded_function);
+    *pp_arg = f as *mut c_void;
+    r
+}
+
+unsafe extern "C" fn rust_overloaded_function(
+    ctx: *mut ffi::sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+) {
+    let f: VTabFunction = std::mem::transmute(ffi::sqlite3_user_data(ctx));
+    let mut ctxt = Context(ctx);
+    let args = Values {
+        args: slice::from_raw_parts(argv, argc as usize),
+    };
+    result_error(ctx, f(&mut ctxt, &args));
+}
+
+#[cfg(feature = "modern_sqlite")]
+unsafe extern "C" fn rust_shadow_name<'vtab, T>(suffix: *const c_char) -> c_int
+where
+    T: CreateVTab<'vtab>,
+{
+    use std::ffi::CStr;
+    match CStr::from_ptr(suffix).to_str() {
+        Ok(suffix) => T::shadow_name(suffix) as c_int,
+        Err(_) => 0,
+    }
+}
+
 unsafe extern "C" fn rust_disconnect<'vtab, T>(vtab: *mut ffi::sqlite3_vtab) -> c_int
 where
     T: VTab<'vtab>,
@@ -1001,6 +1283,92 @@ where
     }
 }
 
+unsafe extern "C" fn rust_update<'vtab, T>(
+    vtab: *mut ffi::sqlite3_vtab,
+    argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+    p_rowid: *mut ffi::sqlite3_int64,
+) -> c_int
+where
+    T: UpdateVTab<'vtab>,
+{
+    assert!(argc >= 1);
+    let args = slice::from_raw_parts_mut(argv, argc as usize);
+    let vt = vtab as *mut T;
+    let r = if args.len() == 1 {
+        (*vt).delete(ValueRef::from_value(args[0]))
+    } else if ffi::sqlite3_value_type(args[0]) == ffi::SQLITE_NULL {
+        let values = Values { args };
+        match (*vt).insert(&values) {
+            Ok(rowid) => {
+                *p_rowid = rowid;
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    } else {
+        let values = Values { args };
+        (*vt).update(&values)
+    };
+    vtab_error(vtab, r)
+}
+
+macro_rules! transaction_hook {
+    ($($name:ident => $method:ident),*) => {$(
+        unsafe extern "C" fn $name<'vtab, T>(vtab: *mut ffi::sqlite3_vtab) -> c_int
+        where
+            T: TransactionVTab<'vtab>,
+        {
+            let vt = vtab as *mut T;
+            vtab_error(vtab, (*vt).$method())
+        }
+    )*};
+}
+
+transaction_hook!(
+    rust_begin => begin,
+    rust_sync => sync,
+    rust_commit => commit,
+    rust_rollback => rollback
+);
+
+macro_rules! savepoint_hook {
+    ($($name:ident => $method:ident),*) => {$(
+        unsafe extern "C" fn $name<'vtab, T>(vtab: *mut ffi::sqlite3_vtab, n: c_int) -> c_int
+        where
+            T: TransactionVTab<'vtab>,
+        {
+            let vt = vtab as *mut T;
+            vtab_error(vtab, (*vt).$method(n))
+        }
+    )*};
+}
+
+savepoint_hook!(
+    rust_savepoint => savepoint,
+    rust_release => release,
+    rust_rollback_to => rollback_to
+);
+
+/// Virtual tables methods can set an error message by assigning a string to
+/// `zErrMsg`.
+#[cold]
+unsafe fn vtab_error<T>(vtab: *mut ffi::sqlite3_vtab, result: Result<T>) -> c_int {
+    match result {
+        Ok(_) => ffi::SQLITE_OK,
+        Err(Error::SqliteFailure(err, s)) => {
+            if let Some(err_msg) = s {
+                set_err_msg(vtab, &err_msg);
+            }
+            err.extended_code
+        }
+        Err(err) => {
+            set_err_msg(vtab, &err.to_string());
+            ffi::SQLITE_ERROR
+        }
+    }
+}
+
 /// Virtual table cursors can set an error message by assigning a string to
 /// `zErrMsg`.
 #[cold]
@@ -1073,11 +1441,26 @@ fn alloc(s: &str) -> *mut c_char {
 pub mod array;
 #[cfg(feature = "csvtab")]
 pub mod csvtab;
+#[cfg(feature = "remotetab")]
+pub mod remotetab;
 #[cfg(feature = "series")]
 pub mod series; // SQLite >= 3.9.0
+pub mod table_function;
 
 #[cfg(test)]
 mod test {
+    use std::cell::RefCell;
+    use std::marker::PhantomData;
+    use std::os::raw::c_int;
+    use std::rc::Rc;
+
+    use super::{
+        transaction_module, Context, CreateVTab, FunctionOverload, IndexConstraintOp, IndexInfo,
+        TransactionVTab, UpdateVTab, VTab, VTabConnection, VTabCursor, Values,
+    };
+    use crate::types::ValueRef;
+    use crate::{ffi, Connection, Result};
+
     #[test]
     fn test_dequote() {
         assert_eq!("", super::dequote(""));
@@ -1102,4 +1485,308 @@ mod test {
         assert_eq!(Some(false), super::parse_boolean("off"));
         assert_eq!(Some(false), super::parse_boolean("false"));
     }
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    // (rowid, x) rows, kept in memory until committed.
+    #[repr(C)]
+    struct LogTab {
+        base: ffi::sqlite3_vtab,
+        log: Log,
+        rows: Vec<(i64, i64)>,
+        committed: Vec<(i64, i64)>,
+        savepoints: Vec<Vec<(i64, i64)>>,
+    }
+
+    impl LogTab {
+        fn log(&self, event: String) {
+            self.log.borrow_mut().push(event);
+        }
+    }
+
+    unsafe impl<'vtab> VTab<'vtab> for LogTab {
+        type Aux = Log;
+        type Cursor = LogTabCursor<'vtab>;
+
+        fn connect(
+            _: &mut VTabConnection,
+            aux: Option<&Log>,
+            _args: &[&[u8]],
+        ) -> Result<(String, LogTab)> {
+            let vtab = LogTab {
+                base: ffi::sqlite3_vtab::default(),
+                log: aux.unwrap().clone(),
+                rows: Vec::new(),
+                committed: Vec::new(),
+                savepoints: Vec::new(),
+            };
+            Ok(("CREATE TABLE x(x)".to_owned(), vtab))
+        }
+
+        fn best_index(&self, info: &mut IndexInfo) -> Result<()> {
+            for constraint in info.constraints() {
+                if let IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_FUNCTION(code) =
+                    constraint.operator()
+                {
+                    self.log(format!("best_index function {}", code));
+                }
+            }
+            info.set_estimated_cost(1.);
+            Ok(())
+        }
+
+        fn open(&'vtab self) -> Result<LogTabCursor<'vtab>> {
+            Ok(LogTabCursor {
+                base: ffi::sqlite3_vtab_cursor::default(),
+                rows: &self.rows,
+                i: 0,
+                phantom: PhantomData,
+            })
+        }
+
+        fn find_function(&self, n_arg: c_int, name: &str) -> Option<FunctionOverload> {
+            match (name, n_arg) {
+                ("match", 2) => Some(FunctionOverload::Function(is_divided)),
+                ("divides", 2) => Some(FunctionOverload::Constraint(divides, 150)),
+                _ => None,
+            }
+        }
+    }
+
+    // Whether the second argument divides the first one.
+    fn divides(ctx: &mut Context, args: &Values<'_>) -> Result<()> {
+        let (x, y): (i64, i64) = (args.get(0)?, args.get(1)?);
+        ctx.set_result(&(y != 0 && x % y == 0))
+    }
+
+    // Whether the first argument divides the second one: `x MATCH y` is
+    // `match(y, x)`.
+    fn is_divided(ctx: &mut Context, args: &Values<'_>) -> Result<()> {
+        let (y, x): (i64, i64) = (args.get(0)?, args.get(1)?);
+        ctx.set_result(&(y != 0 && x % y == 0))
+    }
+
+    impl CreateVTab<'_> for LogTab {
+        #[cfg(feature = "modern_sqlite")]
+        fn shadow_name(suffix: &str) -> bool {
+            suffix == "data"
+        }
+    }
+
+    impl UpdateVTab<'_> for LogTab {
+        fn delete(&mut self, arg: ValueRef<'_>) -> Result<()> {
+            let rowid = arg.as_i64()?;
+            self.rows.retain(|row| row.0 != rowid);
+            Ok(())
+        }
+
+        fn insert(&mut self, args: &Values<'_>) -> Result<i64> {
+            let rowid = match args.get::<Option<i64>>(1)? {
+                Some(rowid) => rowid,
+                None => self.rows.iter().map(|row| row.0).max().unwrap_or(0) + 1,
+            };
+            self.rows.push((rowid, args.get(2)?));
+            Ok(rowid)
+        }
+
+        fn update(&mut self, args: &Values<'_>) -> Result<()> {
+            let rowid: i64 = args.get(0)?;
+            let row = (args.get(1)?, args.get(2)?);
+            for r in self.rows.iter_mut().filter(|r| r.0 == rowid) {
+                *r = row;
+            }
+            Ok(())
+        }
+    }
+
+    impl TransactionVTab<'_> for LogTab {
+        fn begin(&mut self) -> Result<()> {
+            self.log("begin".to_owned());
+            Ok(())
+        }
+
+        fn sync(&mut self) -> Result<()> {
+            self.log("sync".to_owned());
+            Ok(())
+        }
+
+        fn commit(&mut self) -> Result<()> {
+            self.log("commit".to_owned());
+            self.committed = self.rows.clone();
+            self.savepoints.clear();
+            Ok(())
+        }
+
+        fn rollback(&mut self) -> Result<()> {
+            self.log("rollback".to_owned());
+            self.rows = self.committed.clone();
+            self.savepoints.clear();
+            Ok(())
+        }
+
+        fn savepoint(&mut self, n: c_int) -> Result<()> {
+            self.log(format!("savepoint {}", n));
+            self.savepoints.truncate(n as usize);
+            self.savepoints.push(self.rows.clone());
+            Ok(())
+        }
+
+        fn release(&mut self, n: c_int) -> Result<()> {
+            self.log(format!("release {}", n));
+            self.savepoints.truncate(n as usize);
+            Ok(())
+        }
+
+        fn rollback_to(&mut self, n: c_int) -> Result<()> {
+            self.log(format!("rollback_to {}", n));
+            if let Some(rows) = self.savepoints.get(n as usize) {
+                self.rows = rows.clone();
+            }
+            self.savepoints.truncate(n as usize + 1);
+            Ok(())
+        }
+    }
+
+    #[repr(C)]
+    struct LogTabCursor<'vtab> {
+        base: ffi::sqlite3_vtab_cursor,
+        rows: &'vtab [(i64, i64)],
+        i: usize,
+        phantom: PhantomData<&'vtab LogTab>,
+    }
+
+    unsafe impl VTabCursor for LogTabCursor<'_> {
+        fn filter(&mut self, _: c_int, _: Option<&str>, _: &Values<'_>) -> Result<()> {
+            self.i = 0;
+            Ok(())
+        }
+
+        fn next(&mut self) -> Result<()> {
+            self.i += 1;
+            Ok(())
+        }
+
+        fn eof(&self) -> bool {
+            self.i >= self.rows.len()
+        }
+
+        fn column(&self, ctx: &mut Context, _: c_int) -> Result<()> {
+            ctx.set_result(&self.rows[self.i].1)
+        }
+
+        fn rowid(&self) -> Result<i64> {
+            Ok(self.rows[self.i].0)
+        }
+    }
+
+    #[test]
+    fn test_transaction_module() -> Result<()> {
+        let log = Log::default();
+        let db = Connection::open_in_memory()?;
+        db.create_module("log", transaction_module::<LogTab>(), Some(log.clone()))?;
+        db.execute_batch("CREATE VIRTUAL TABLE t USING log()")?;
+        log.borrow_mut().clear();
+        let sum = || -> Result<i64> {
+            db.query_row("SELECT coalesce(sum(x), 0) FROM t", [], |r| r.get(0))
+        };
+
+        db.execute_batch(
+            "BEGIN;
+             INSERT INTO t VALUES (1);
+             SAVEPOINT a;
+             INSERT INTO t VALUES (2);
+             ROLLBACK TO a;
+             UPDATE t SET x = 3;
+             RELEASE a;
+             COMMIT;",
+        )?;
+        assert_eq!(sum()?, 3);
+        // Statements writing to the table run in a nested savepoint.
+        assert_eq!(
+            *log.borrow(),
+            [
+                "begin",
+                "savepoint 0",
+                "rollback_to 0",
+                "savepoint 1",
+                "release 1",
+                "release 0",
+                "sync",
+                "commit"
+            ]
+        );
+        log.borrow_mut().clear();
+
+        db.execute_batch("BEGIN; DELETE FROM t; INSERT INTO t VALUES (4); ROLLBACK;")?;
+        assert_eq!(sum()?, 3);
+        assert_eq!(
+            *log.borrow(),
+            ["begin", "savepoint 0", "release 0", "rollback"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_function() -> Result<()> {
+        let log = Log::default();
+        let db = Connection::open_in_memory()?;
+        db.create_module("log", transaction_module::<LogTab>(), Some(log.clone()))?;
+        db.execute_batch(
+            "CREATE VIRTUAL TABLE t USING log();
+             INSERT INTO t VALUES (2), (3), (4), (6);",
+        )?;
+        let values = |sql: &str| -> Result<Vec<i64>> {
+            let mut stmt = db.prepare(sql)?;
+            let rows = stmt.query_map([], |r| r.get(0))?;
+            rows.collect()
+        };
+
+        assert_eq!(values("SELECT x FROM t WHERE x MATCH 2")?, [2, 4, 6]);
+        // Calls without a column of the table are not overloaded.
+        assert!(db.query_row("SELECT 4 MATCH 2", [], |_| Ok(())).is_err());
+
+        assert!(db.prepare("SELECT divides(x, 3) FROM t").is_err());
+        db.overload_function("divides", 2)?;
+        let err = db
+            .query_row("SELECT divides(4, 2)", [], |_| Ok(()))
+            .unwrap_err();
+        assert!(err.to_string().contains("divides"), "{}", err);
+        log.borrow_mut().clear();
+        assert_eq!(values("SELECT x FROM t WHERE divides(x, 3)")?, [3, 6]);
+        if crate::version_number() >= 3_025_000 {
+            assert!(log
+                .borrow()
+                .iter()
+                .any(|event| event == "best_index function 150"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "modern_sqlite")]
+    fn test_shadow_name() -> Result<()> {
+        use crate::config::DbConfig;
+
+        if crate::version_number() < 3_026_000 {
+            return Ok(());
+        }
+        let db = Connection::open_in_memory()?;
+        db.create_module("log", transaction_module::<LogTab>(), Some(Log::default()))?;
+        db.execute_batch(
+            "CREATE VIRTUAL TABLE t USING log();
+             CREATE TABLE t_data (x);
+             CREATE TABLE t_other (x);
+             INSERT INTO t_data VALUES (1);",
+        )?;
+        db.set_db_config(DbConfig::SQLITE_DBCONFIG_DEFENSIVE, true)?;
+        let err = db.execute("INSERT INTO t_data VALUES (2)", []).unwrap_err();
+        assert!(err.to_string().contains("may not be modified"), "{}", err);
+        db.execute("INSERT INTO t_other VALUES (2)", [])?;
+        let n: i64 = db.query_row("SELECT count(*) FROM t_data", [], |r| r.get(0))?;
+        assert_eq!(n, 1);
+
+        db.set_db_config(DbConfig::SQLITE_DBCONFIG_DEFENSIVE, false)?;
+        db.execute("INSERT INTO t_data VALUES (2)", [])?;
+        Ok(())
+    }
 }
@@ -0,0 +1,401 @@
+//! `feature = "remotetab"` Remote Table Virtual Table.
+//!
+//! Proxies a table of another `Connection`, which other threads may use
+//! too, so that it can be joined with local tables without `ATTACH`, e.g.
+//! when the two databases are encrypted with different keys or are opened
+//! with different VFSes. Constraints of the WHERE clause on columns are
+//! pushed down to the remote connection, so that only the rows matching them
+//! are copied.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! # use rusqlite::{Connection, Result};
+//! # use std::sync::{Arc, Mutex};
+//! fn example() -> Result<()> {
+//!     let remote = Arc::new(Mutex::new(Connection::open("samples.db")?));
+//!     let db = Connection::open("variants.db")?;
+//!     rusqlite::vtab::remotetab::load_module(&db, "samples_db", remote)?;
+//!     db.execute_batch("CREATE VIRTUAL TABLE temp.samples USING samples_db(table=samples)")?;
+//!     // Now `samples` can be joined with the tables of `variants.db`...
+//!     Ok(())
+//! }
+//! ```
+use std::marker::PhantomData;
+use std::os::raw::c_int;
+use std::str;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::ffi;
+use crate::types::Value;
+use crate::util::quote_identifier;
+use crate::vtab::{
+    dequote, read_only_module, Context, CreateVTab, IndexConstraintOp, IndexInfo, VTab,
+    VTabConnection, VTabCursor, Values,
+};
+use crate::{params_from_iter, Connection, Error, Result};
+
+/// `feature = "remotetab"` Register a module named `module_name`, whose
+/// tables proxy the tables of `remote`.
+/// ```sql
+/// CREATE VIRTUAL TABLE vtab USING module_name(
+///   table=TABLE -- Name of the remote table
+///   [, schema=SCHEMA] -- Name of the remote database holding it. Default "main".
+/// );
+/// ```
+/// The columns of `vtab` are those of the remote table when it is created,
+/// with the same declared types.
+pub fn load_module(
+    conn: &Connection,
+    module_name: &str,
+    remote: Arc<Mutex<Connection>>,
+) -> Result<()> {
+    conn.create_module(module_name, read_only_module::<RemoteTab>(), Some(remote))
+}
+
+fn lock(remote: &Mutex<Connection>) -> Result<MutexGuard<'_, Connection>> {
+    remote
+        .lock()
+        .map_err(|_| Error::ModuleError("remote connection poisoned".to_owned()))
+}
+
+// The SQL operator of the constraints pushed down, and whether it has a
+// right-hand side. `LIKE` is not, as it depends on `case_sensitive_like`.
+fn operator(op: IndexConstraintOp) -> Option<(&'static str, bool)> {
+    match op {
+        IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_EQ => Some(("=", true)),
+        IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_GT => Some((">", true)),
+        IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_LE => Some(("<=", true)),
+        IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_LT => Some(("<", true)),
+        IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_GE => Some((">=", true)),
+        IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_GLOB => Some(("GLOB", true)),
+        IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_NE => Some(("<>", true)),
+        IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_IS => Some(("IS", true)),
+        IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_ISNOT => Some(("IS NOT", true)),
+        IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_ISNULL => Some(("IS NULL", false)),
+        IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_ISNOTNULL => Some(("IS NOT NULL", false)),
+        _ => None,
+    }
+}
+
+/// An instance of the remote table virtual table
+#[repr(C)]
+struct RemoteTab {
+    /// Base class. Must be first
+    base: ffi::sqlite3_vtab,
+    remote: Arc<Mutex<Connection>>,
+    /// Quoted name of the remote table, with its schema
+    table: String,
+    /// Quoted names of the columns
+    columns: Vec<String>,
+}
+
+impl RemoteTab {
+    fn parameter(c_slice: &[u8]) -> Result<(&str, &str)> {
+        let arg = str::from_utf8(c_slice)?.trim();
+        let mut split = arg.split('=');
+        if let Some(key) = split.next() {
+            if let Some(value) = split.next() {
+                return Ok((key.trim(), dequote(value)));
+            }
+        }
+        Err(Error::ModuleError(format!("illegal argument: '{}'", arg)))
+    }
+}
+
+unsafe impl<'vtab> VTab<'vtab> for RemoteTab {
+    type Aux = Arc<Mutex<Connection>>;
+    type Cursor = RemoteTabCursor<'vtab>;
+
+    fn connect(
+        _: &mut VTabConnection,
+        aux: Option<&Arc<Mutex<Connection>>>,
+        args: &[&[u8]],
+    ) -> Result<(String, RemoteTab)> {
+        let remote = aux
+            .cloned()
+            .ok_or_else(|| Error::ModuleError("no remote connection".to_owned()))?;
+        let mut table = None;
+        let mut schema = "main";
+        for c_slice in &args[3..] {
+            let (param, value) = RemoteTab::parameter(c_slice)?;
+            match param {
+                "table" => table = Some(value),
+                "schema" => schema = value,
+                _ => {
+                    return Err(Error::ModuleError(format!(
+                        "unrecognized parameter '{}'",
+                        param
+                    )));
+                }
+            }
+        }
+        let table =
+            table.ok_or_else(|| Error::ModuleError("no remote table specified".to_owned()))?;
+
+        let columns: Vec<(String, String)> = {
+            let conn = lock(&remote)?;
+            let mut stmt =
+                conn.prepare("SELECT name, type FROM pragma_table_info(?1, ?2) ORDER BY cid")?;
+            let rows = stmt.query_map([table, schema], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<Result<_>>()?
+        };
+        if columns.is_empty() {
+            return Err(Error::ModuleError(format!(
+                "no such remote table: {}.{}",
+                schema, table
+            )));
+        }
+
+        let columns: Vec<(String, String)> = columns
+            .into_iter()
+            .map(|(name, decl_type)| (quote_identifier(&name), decl_type))
+            .collect();
+        let mut sql = String::from("CREATE TABLE x(");
+        for (i, (name, decl_type)) in columns.iter().enumerate() {
+            if i > 0 {
+                sql.push_str(", ");
+            }
+            sql.push_str(name);
+            if !decl_type.is_empty() {
+                sql.push(' ');
+                sql.push_str(decl_type);
+            }
+        }
+        sql.push_str(");");
+
+        let vtab = RemoteTab {
+            base: ffi::sqlite3_vtab::default(),
+            remote,
+            table: format!("{}.{}", quote_identifier(schema), quote_identifier(table)),
+            columns: columns.into_iter().map(|(name, _)| name).collect(),
+        };
+        Ok((sql, vtab))
+    }
+
+    // The usable constraints are pushed down, as the WHERE clause given as
+    // `idx_str`. SQLite still checks them, as collations may differ.
+    fn best_index(&self, info: &mut IndexInfo) -> Result<()> {
+        let mut terms = Vec::new();
+        let mut args = Vec::new();
+        let mut cost = 1_000_000.;
+        for (i, constraint) in info.constraints().enumerate() {
+            if !constraint.is_usable() || constraint.column() < 0 {
+                continue;
+            }
+            let eq = constraint.operator() == IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_EQ;
+            let (op, has_arg) = match operator(constraint.operator()) {
+                Some(op) => op,
+                None => continue,
+            };
+            let column = &self.columns[constraint.column() as usize];
+            if has_arg {
+                args.push(i);
+                terms.push(format!("{} {} ?{}", column, op, args.len()));
+            } else {
+                terms.push(format!("{} {}", column, op));
+            }
+            cost /= if eq { 100. } else { 4. };
+        }
+        for (n, i) in args.into_iter().enumerate() {
+            info.constraint_usage(i).set_argv_index(n as c_int + 1);
+        }
+        if !terms.is_empty() {
+            info.set_idx_str(&terms.join(" AND "));
+        }
+        info.set_estimated_cost(cost);
+        Ok(())
+    }
+
+    fn open(&self) -> Result<RemoteTabCursor<'_>> {
+        Ok(RemoteTabCursor::new())
+    }
+}
+
+impl CreateVTab<'_> for RemoteTab {}
+
+/// A cursor for the remote table virtual table
+#[repr(C)]
+struct RemoteTabCursor<'vtab> {
+    /// Base class. Must be first
+    base: ffi::sqlite3_vtab_cursor,
+    /// Rows copied from the remote table
+    rows: Vec<Vec<Value>>,
+    /// Index of the current row
+    row: usize,
+    phantom: PhantomData<&'vtab RemoteTab>,
+}
+
+impl RemoteTabCursor<'_> {
+    fn new<'vtab>() -> RemoteTabCursor<'vtab> {
+        RemoteTabCursor {
+            base: ffi::sqlite3_vtab_cursor::default(),
+            rows: Vec::new(),
+            row: 0,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Accessor to the associated virtual table.
+    fn vtab(&self) -> &RemoteTab {
+        unsafe { &*(self.base.pVtab as *const RemoteTab) }
+    }
+}
+
+unsafe impl VTabCursor for RemoteTabCursor<'_> {
+    fn filter(&mut self, _idx_num: c_int, idx_str: Option<&str>, args: &Values<'_>) -> Result<()> {
+        let rows = {
+            let vtab = self.vtab();
+            let mut sql = format!("SELECT {} FROM {}", vtab.columns.join(", "), vtab.table);
+            if let Some(terms) = idx_str {
+                sql.push_str(" WHERE ");
+                sql.push_str(terms);
+            }
+            let params: Vec<Value> = args.iter().map(Value::from).collect();
+            let n_col = vtab.columns.len();
+            let conn = lock(&vtab.remote)?;
+            let mut stmt = conn.prepare_cached(&sql)?;
+            let rows = stmt.query_map(params_from_iter(params), |row| {
+                (0..n_col).map(|i| row.get::<_, Value>(i)).collect()
+            })?;
+            rows.collect::<Result<Vec<_>>>()?
+        };
+        self.rows = rows;
+        self.row = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<()> {
+        self.row += 1;
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.row >= self.rows.len()
+    }
+
+    fn column(&self, ctx: &mut Context, col: c_int) -> Result<()> {
+        match self.rows[self.row].get(col as usize) {
+            Some(value) => ctx.set_result(value),
+            None => Err(Error::ModuleError(format!(
+                "column index out of bounds: {}",
+                col
+            ))),
+        }
+    }
+
+    fn rowid(&self) -> Result<i64> {
+        Ok(self.row as i64 + 1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use crate::vtab::remotetab;
+    use crate::{Connection, Result};
+    use fallible_iterator::FallibleIterator;
+
+    fn remote() -> Result<Arc<Mutex<Connection>>> {
+        let remote = Connection::open_in_memory()?;
+        remote.execute_batch(
+            "CREATE TABLE samples (id INTEGER PRIMARY KEY, name TEXT NOT NULL, depth REAL);
+             INSERT INTO samples VALUES (1, 'NA12878', 30.5), (2, 'NA12891', NULL), (3, 'HG002', 42.0);",
+        )?;
+        Ok(Arc::new(Mutex::new(remote)))
+    }
+
+    #[test]
+    fn test_remote_join() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        remotetab::load_module(&db, "remote", remote()?)?;
+        db.execute_batch(
+            "CREATE VIRTUAL TABLE samples USING remote(table=samples);
+             CREATE TABLE calls (sample_id INTEGER, variant TEXT);
+             INSERT INTO calls VALUES (1, 'rs1'), (1, 'rs2'), (3, 'rs1');",
+        )?;
+
+        let types: Vec<String> = db
+            .prepare("SELECT type FROM pragma_table_info('samples')")?
+            .query([])?
+            .map(|r| r.get(0))
+            .collect()?;
+        assert_eq!(types, vec!["INTEGER", "TEXT", "REAL"]);
+        let names: Vec<String> = db
+            .prepare(
+                "SELECT name FROM calls JOIN samples ON samples.id = calls.sample_id \
+                 WHERE variant = 'rs1' ORDER BY name",
+            )?
+            .query([])?
+            .map(|r| r.get(0))
+            .collect()?;
+        assert_eq!(names, vec!["HG002", "NA12878"]);
+        let count: i64 = db.query_row(
+            "SELECT count(*) FROM samples WHERE depth IS NULL",
+            [],
+            |r| r.get(0),
+        )?;
+        assert_eq!(count, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_remote_pushdown() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        remotetab::load_module(&db, "remote", remote()?)?;
+        db.execute_batch("CREATE VIRTUAL TABLE samples USING remote(table=samples)")?;
+
+        let plan: String = db.query_row(
+            "EXPLAIN QUERY PLAN SELECT * FROM samples WHERE depth > 35 AND name GLOB '*02'",
+            [],
+            |r| r.get(3),
+        )?;
+        assert!(
+            plan.contains("\"depth\" > ?1 AND \"name\" GLOB ?2"),
+            "{}",
+            plan
+        );
+        let name: String = db.query_row(
+            "SELECT name FROM samples WHERE depth > ? AND name GLOB '*02'",
+            [35],
+            |r| r.get(0),
+        )?;
+        assert_eq!(name, "HG002");
+        Ok(())
+    }
+
+    #[test]
+    fn test_remote_on_other_thread() -> Result<()> {
+        let remote = remote()?;
+        let db = Connection::open_in_memory()?;
+        remotetab::load_module(&db, "remote", remote.clone())?;
+        db.execute_batch("CREATE VIRTUAL TABLE samples USING remote(table=samples)")?;
+
+        thread::spawn(move || {
+            remote
+                .lock()
+                .unwrap()
+                .execute("INSERT INTO samples VALUES (4, 'HG003', 12.5)", [])
+        })
+        .join()
+        .unwrap()?;
+        let count: i64 = db.query_row("SELECT count(*) FROM samples", [], |r| r.get(0))?;
+        assert_eq!(count, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_remote_errors() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        remotetab::load_module(&db, "remote", remote()?)?;
+        db.execute_batch("CREATE VIRTUAL TABLE t USING remote(table=missing)")
+            .unwrap_err();
+        db.execute_batch("CREATE VIRTUAL TABLE t USING remote(schema=main)")
+            .unwrap_err();
+        db.execute_batch("CREATE VIRTUAL TABLE t USING remote(table=samples, key=x)")
+            .unwrap_err();
+        Ok(())
+    }
+}
@@ -21,6 +21,21 @@ pub fn load_module(conn: &Connection) -> Result<()> {
     conn.create_module("generate_series", eponymous_only_module::<SeriesTab>(), aux)
 }
 
+/// `feature = "series"` Register the "generate_series" module, unless
+/// SQLite already provides it, e.g. when compiled with
+/// `SQLITE_ENABLE_SERIES` or from 3.43 onwards. Returns whether the module
+/// was registered.
+pub fn load_module_if_missing(conn: &Connection) -> Result<bool> {
+    if conn
+        .prepare("SELECT value FROM generate_series(1, 1)")
+        .is_ok()
+    {
+        return Ok(false);
+    }
+    load_module(conn)?;
+    Ok(true)
+}
+
 // Column numbers
 // const SERIES_COLUMN_VALUE : c_int = 0;
 const SERIES_COLUMN_START: c_int = 1;
@@ -309,4 +324,21 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_load_module_if_missing() -> Result<()> {
+        let version = unsafe { ffi::sqlite3_libversion_number() };
+        if version < 3_008_012 {
+            return Ok(());
+        }
+
+        let db = Connection::open_in_memory()?;
+        series::load_module_if_missing(&db)?;
+        assert!(!series::load_module_if_missing(&db)?);
+        let n: i64 = db.query_row("SELECT count(*) FROM generate_series(1, 10)", [], |r| {
+            r.get(0)
+        })?;
+        assert_eq!(10, n);
+        Ok(())
+    }
 }
@@ -0,0 +1,342 @@
+//! `feature = "vtab"` Table-valued functions backed by Rust iterators.
+//!
+//! A table-valued function is an eponymous-only virtual table: it is
+//! queried like a function in the `FROM` clause, without any
+//! `CREATE VIRTUAL TABLE`, and its arguments are constraints on hidden
+//! columns.
+//!
+//! ```rust
+//! # use rusqlite::types::Value;
+//! # use rusqlite::{Connection, Result};
+//! # fn main() -> Result<()> {
+//! let db = Connection::open_in_memory()?;
+//! db.create_table_function("split", &["value"], &["text", "sep"], |args| {
+//!     let words: Vec<Vec<Value>> = match (&args[0], &args[1]) {
+//!         (Value::Text(text), Value::Text(sep)) => text
+//!             .split(sep.as_str())
+//!             .map(|word| vec![Value::Text(word.to_owned())])
+//!             .collect(),
+//!         _ => Vec::new(),
+//!     };
+//!     Ok(words)
+//! })?;
+//! let n: i64 = db.query_row("SELECT count(*) FROM split('a,b,c', ',')", [], |r| r.get(0))?;
+//! assert_eq!(n, 3);
+//! # Ok(())
+//! # }
+//! ```
+use std::marker::PhantomData;
+use std::os::raw::c_int;
+use std::rc::Rc;
+
+use crate::ffi;
+use crate::types::Value;
+use crate::vtab::{
+    eponymous_only_module, escape_double_quote, Context, IndexConstraintOp, IndexInfo, VTab,
+    VTabConnection, VTabCursor, Values,
+};
+use crate::{Connection, Error, Result};
+
+type Rows = Box<dyn Iterator<Item = Vec<Value>>>;
+
+// The arguments given are a bitmask in `idx_num`.
+const MAX_PARAMS: usize = c_int::BITS as usize;
+
+// The columns of a table-valued function, and how its rows are produced.
+struct TableFunction {
+    columns: Vec<String>,
+    params: Vec<String>,
+    #[allow(clippy::type_complexity)]
+    rows: Box<dyn Fn(&[Value]) -> Result<Rows>>,
+}
+
+impl Connection {
+    /// `feature = "vtab"` Register a table-valued function named `name`,
+    /// whose rows have the columns `columns`, and whose arguments are named
+    /// `params`.
+    ///
+    /// For `SELECT * FROM name(a, b)`, `rows` is called with the values of
+    /// the arguments, in the order of `params`, and returns the rows, each
+    /// with the values of `columns` in order (missing values are `NULL`).
+    /// Arguments which are not given, e.g. `c` in `name(a, b)`, are `NULL`.
+    /// The arguments can also be given as constraints on the columns named
+    /// by `params`, e.g. `SELECT * FROM name WHERE a = 1 AND b = 2`, and are
+    /// returned as such.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if there are more than 32 `params`, or if the
+    /// function cannot be registered. Errors of `rows` are returned by the
+    /// query.
+    pub fn create_table_function<F, I>(
+        &self,
+        name: &str,
+        columns: &[&str],
+        params: &[&str],
+        rows: F,
+    ) -> Result<()>
+    where
+        F: Fn(&[Value]) -> Result<I> + 'static,
+        I: IntoIterator<Item = Vec<Value>>,
+        I::IntoIter: 'static,
+    {
+        if params.len() > MAX_PARAMS {
+            return Err(Error::ModuleError(format!(
+                "{} has {} parameters, more than {}",
+                name,
+                params.len(),
+                MAX_PARAMS
+            )));
+        }
+        let function = TableFunction {
+            columns: columns.iter().map(|&c| c.to_owned()).collect(),
+            params: params.iter().map(|&p| p.to_owned()).collect(),
+            rows: Box::new(move |args| Ok(Box::new(rows(args)?.into_iter()) as Rows)),
+        };
+        self.create_module(
+            name,
+            eponymous_only_module::<TableFunctionTab>(),
+            Some(Rc::new(function)),
+        )
+    }
+}
+
+/// An instance of a table-valued function
+#[repr(C)]
+struct TableFunctionTab {
+    /// Base class. Must be first
+    base: ffi::sqlite3_vtab,
+    function: Rc<TableFunction>,
+}
+
+unsafe impl<'vtab> VTab<'vtab> for TableFunctionTab {
+    type Aux = Rc<TableFunction>;
+    type Cursor = TableFunctionCursor<'vtab>;
+
+    fn connect(
+        _: &mut VTabConnection,
+        aux: Option<&Rc<TableFunction>>,
+        _args: &[&[u8]],
+    ) -> Result<(String, TableFunctionTab)> {
+        let function = aux.expect("table function without aux data").clone();
+        let columns = function
+            .columns
+            .iter()
+            .map(|c| format!("\"{}\"", escape_double_quote(c)))
+            .chain(
+                function
+                    .params
+                    .iter()
+                    .map(|p| format!("\"{}\" HIDDEN", escape_double_quote(p))),
+            )
+            .collect::<Vec<_>>()
+            .join(",");
+        let vtab = TableFunctionTab {
+            base: ffi::sqlite3_vtab::default(),
+            function,
+        };
+        Ok((format!("CREATE TABLE x({})", columns), vtab))
+    }
+
+    fn best_index(&self, info: &mut IndexInfo) -> Result<()> {
+        let n_columns = self.function.columns.len() as c_int;
+        // The constraint giving each argument, as a bitmask in idx_num.
+        let mut args = vec![None; self.function.params.len()];
+        let mut unusable = false;
+        for (i, constraint) in info.constraints().enumerate() {
+            let param = constraint.column() - n_columns;
+            if param < 0 || constraint.operator() != IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_EQ {
+                continue;
+            }
+            if constraint.is_usable() {
+                args[param as usize] = Some(i);
+            } else {
+                unusable = true;
+            }
+        }
+        if unusable {
+            // Another plan gives the arguments.
+            return Err(Error::SqliteFailure(
+                ffi::Error::new(ffi::SQLITE_CONSTRAINT),
+                None,
+            ));
+        }
+        let mut idx_num = 0;
+        let mut n_arg = 0;
+        for (param, constraint) in args.iter().enumerate() {
+            if let Some(i) = constraint {
+                n_arg += 1;
+                idx_num |= 1 << param;
+                let mut usage = info.constraint_usage(*i);
+                usage.set_argv_index(n_arg);
+                usage.set_omit(true);
+            }
+        }
+        info.set_idx_num(idx_num);
+        info.set_estimated_cost(1_000.0);
+        Ok(())
+    }
+
+    fn open(&self) -> Result<TableFunctionCursor<'_>> {
+        Ok(TableFunctionCursor {
+            base: ffi::sqlite3_vtab_cursor::default(),
+            function: &self.function,
+            args: Vec::new(),
+            rows: None,
+            row: None,
+            row_id: 0,
+            phantom: PhantomData,
+        })
+    }
+}
+
+/// A cursor for a table-valued function
+#[repr(C)]
+struct TableFunctionCursor<'vtab> {
+    /// Base class. Must be first
+    base: ffi::sqlite3_vtab_cursor,
+    function: &'vtab TableFunction,
+    args: Vec<Value>,
+    rows: Option<Rows>,
+    /// The current row, `None` at the end
+    row: Option<Vec<Value>>,
+    row_id: i64,
+    phantom: PhantomData<&'vtab TableFunctionTab>,
+}
+
+unsafe impl VTabCursor for TableFunctionCursor<'_> {
+    fn filter(&mut self, idx_num: c_int, _idx_str: Option<&str>, args: &Values<'_>) -> Result<()> {
+        let mut given = args.iter();
+        self.args = (0..self.function.params.len())
+            .map(|param| {
+                if idx_num & (1 << param) == 0 {
+                    return Value::Null;
+                }
+                given.next().map_or(Value::Null, Value::from)
+            })
+            .collect();
+        let mut rows = (self.function.rows)(&self.args)?;
+        self.row = rows.next();
+        self.rows = Some(rows);
+        self.row_id = 1;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<()> {
+        self.row = self.rows.as_mut().and_then(Iterator::next);
+        self.row_id += 1;
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.row.is_none()
+    }
+
+    fn column(&self, ctx: &mut Context, i: c_int) -> Result<()> {
+        let i = i as usize;
+        let n_columns = self.function.columns.len();
+        let value = if i < n_columns {
+            self.row.as_ref().and_then(|row| row.get(i))
+        } else {
+            self.args.get(i - n_columns)
+        };
+        ctx.set_result(value.unwrap_or(&Value::Null))
+    }
+
+    fn rowid(&self) -> Result<i64> {
+        Ok(self.row_id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::Value;
+    use crate::{Connection, Error, Result};
+
+    fn range(db: &Connection) -> Result<()> {
+        db.create_table_function("range", &["value", "square"], &["stop", "step"], |args| {
+            let stop = match args[0] {
+                Value::Integer(stop) => stop,
+                Value::Null => 3,
+                _ => return Err(Error::ModuleError("stop must be an integer".to_owned())),
+            };
+            let step = match args[1] {
+                Value::Integer(step) if step > 0 => step as usize,
+                _ => 1,
+            };
+            Ok((0..stop)
+                .step_by(step)
+                .map(|i| vec![Value::Integer(i), Value::Integer(i * i)]))
+        })
+    }
+
+    fn values(db: &Connection, sql: &str) -> Result<Vec<(i64, i64)>> {
+        let mut stmt = db.prepare(sql)?;
+        let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+        rows.collect()
+    }
+
+    #[test]
+    fn test_table_function() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        range(&db)?;
+        assert_eq!(
+            values(&db, "SELECT * FROM range(5, 2)")?,
+            vec![(0, 0), (2, 4), (4, 16)]
+        );
+        assert_eq!(
+            values(&db, "SELECT value, stop FROM range WHERE stop = 2")?,
+            vec![(0, 2), (1, 2)]
+        );
+        // Missing arguments are NULL.
+        assert_eq!(
+            values(&db, "SELECT value, square FROM range()")?,
+            vec![(0, 0), (1, 1), (2, 4)]
+        );
+        assert_eq!(
+            values(&db, "SELECT value, square FROM range WHERE step = 2")?,
+            vec![(0, 0), (2, 4)]
+        );
+        // Arguments from another table.
+        db.execute_batch("CREATE TABLE t (n INTEGER); INSERT INTO t VALUES (1), (2);")?;
+        assert_eq!(
+            values(&db, "SELECT n, value FROM t, range(t.n) ORDER BY n, value")?,
+            vec![(1, 0), (2, 0), (2, 1)]
+        );
+
+        let err = values(&db, "SELECT * FROM range('x')").unwrap_err();
+        assert!(
+            err.to_string().contains("stop must be an integer"),
+            "{}",
+            err
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_too_many_params() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        let names: Vec<String> = (0..33).map(|i| format!("p{}", i)).collect();
+        let params: Vec<&str> = names.iter().map(String::as_str).collect();
+        let err = db
+            .create_table_function("wide", &["value"], &params, |args| {
+                Ok(std::iter::once(vec![args[args.len() - 1].clone()]))
+            })
+            .unwrap_err();
+        assert!(err.to_string().contains("more than 32"), "{}", err);
+
+        db.create_table_function("wide", &["value"], &params[..32], |args| {
+            Ok(std::iter::once(vec![args[31].clone()]))
+        })?;
+        let sql = format!(
+            "SELECT value FROM wide({})",
+            (0..32)
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let last: i64 = db.query_row(&sql, [], |r| r.get(0))?;
+        assert_eq!(last, 31);
+        Ok(())
+    }
+}
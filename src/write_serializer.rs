@@ -0,0 +1,247 @@
+//! Funnel the writes of many threads through one connection.
+
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::{self, JoinHandle};
+
+use crate::{Connection, Error, Result, Transaction, TransactionBehavior};
+
+type Job = Box<dyn FnOnce(&mut Connection) + Send>;
+
+/// Runs write transactions submitted from any number of threads, one at a
+/// time, on a connection owned by a dedicated thread.
+///
+/// As there is only one writer in the process, writers never wait on each
+/// other's locks, and so never fail with `SQLITE_BUSY` (other processes, or
+/// readers holding a lock which prevents a checkpoint, can still cause it).
+/// Each job runs in its own `IMMEDIATE` transaction, which is committed if
+/// the job returns `Ok`, and rolled back otherwise.
+///
+/// ```rust,no_run
+/// # use rusqlite::{Connection, Result, WriteSerializer};
+/// fn log_events(events: Vec<String>) -> Result<()> {
+///     let writer = WriteSerializer::new(Connection::open("events.db")?);
+///     let results: Vec<_> = events
+///         .into_iter()
+///         .map(|event| {
+///             writer.submit(move |tx| tx.execute("INSERT INTO events (e) VALUES (?)", [event]))
+///         })
+///         .collect();
+///     for result in results {
+///         result.wait()?;
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct WriteSerializer {
+    sender: Option<Mutex<Sender<Job>>>,
+    thread: Option<JoinHandle<Connection>>,
+}
+
+impl WriteSerializer {
+    /// Moves `conn` to a new thread, where submitted jobs are run.
+    pub fn new(conn: Connection) -> WriteSerializer {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let thread = thread::spawn(move || {
+            let mut conn = conn;
+            for job in receiver {
+                job(&mut conn);
+            }
+            conn
+        });
+        WriteSerializer {
+            sender: Some(Mutex::new(sender)),
+            thread: Some(thread),
+        }
+    }
+
+    /// Queues `job` to run in a transaction after all previously submitted
+    /// jobs, and returns its result, which can be awaited or waited for.
+    ///
+    /// If `job` panics, its transaction is rolled back and the result is an
+    /// error; later jobs still run.
+    pub fn submit<F, T>(&self, job: F) -> WriteResult<T>
+    where
+        F: FnOnce(&Transaction<'_>) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let slot = Arc::new(Slot::default());
+        let job_slot = Arc::clone(&slot);
+        let job: Job = Box::new(move |conn: &mut Connection| {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                let value = job(&tx)?;
+                tx.commit()?;
+                Ok(value)
+            }))
+            .unwrap_or_else(|_| Err(Error::WriteJobPanicked));
+            job_slot.complete(result);
+        });
+        let sent = self
+            .sender
+            .as_ref()
+            .and_then(|sender| sender.lock().unwrap().send(job).ok());
+        if sent.is_none() {
+            slot.complete(Err(Error::WriteSerializerStopped));
+        }
+        WriteResult { slot }
+    }
+
+    /// Waits for all submitted jobs to complete, stops the writer thread, and
+    /// returns its connection.
+    pub fn into_inner(mut self) -> Connection {
+        self.stop().expect("WriteSerializer thread panicked")
+    }
+
+    fn stop(&mut self) -> thread::Result<Connection> {
+        drop(self.sender.take());
+        self.thread.take().unwrap().join()
+    }
+}
+
+impl Drop for WriteSerializer {
+    #[allow(unused_must_use)]
+    fn drop(&mut self) {
+        if self.thread.is_some() {
+            self.stop();
+        }
+    }
+}
+
+/// The result of a job submitted to a [`WriteSerializer`].
+///
+/// It is a `Future`, for async code, and can also be waited for with
+/// [`wait`](WriteResult::wait).
+pub struct WriteResult<T> {
+    slot: Arc<Slot<T>>,
+}
+
+struct Slot<T> {
+    state: Mutex<SlotState<T>>,
+    done: Condvar,
+}
+
+struct SlotState<T> {
+    result: Option<Result<T>>,
+    waker: Option<Waker>,
+}
+
+impl<T> Default for Slot<T> {
+    fn default() -> Slot<T> {
+        Slot {
+            state: Mutex::new(SlotState {
+                result: None,
+                waker: None,
+            }),
+            done: Condvar::new(),
+        }
+    }
+}
+
+impl<T> Slot<T> {
+    fn complete(&self, result: Result<T>) {
+        let mut state = self.state.lock().unwrap();
+        state.result = Some(result);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        self.done.notify_all();
+    }
+}
+
+impl<T> WriteResult<T> {
+    /// Blocks until the job has run, and returns its result.
+    pub fn wait(self) -> Result<T> {
+        let mut state = self.slot.state.lock().unwrap();
+        loop {
+            if let Some(result) = state.result.take() {
+                return result;
+            }
+            state = self.slot.done.wait(state).unwrap();
+        }
+    }
+}
+
+impl<T> Future for WriteResult<T> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<T>> {
+        let mut state = self.slot.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::WriteSerializer;
+    use crate::util::executor::block_on;
+    use crate::{Connection, Error, Result};
+
+    #[test]
+    fn test_write_serializer() -> Result<()> {
+        let db = Connection::open_in_memory()?;
+        db.execute_batch("CREATE TABLE foo(x INTEGER)")?;
+        let writer = Arc::new(WriteSerializer::new(db));
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let writer = Arc::clone(&writer);
+                thread::spawn(move || {
+                    for j in 0..25 {
+                        let n = writer
+                            .submit(move |tx| {
+                                tx.execute("INSERT INTO foo VALUES (?)", [i * 25 + j])
+                            })
+                            .wait()
+                            .unwrap();
+                        assert_eq!(n, 1);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // A failed job is rolled back.
+        let err = writer
+            .submit(|tx| {
+                tx.execute("INSERT INTO foo VALUES (1000)", [])?;
+                tx.execute("INSERT INTO bar VALUES (1000)", [])
+            })
+            .wait()
+            .unwrap_err();
+        assert!(matches!(err, Error::SqliteFailure(..)));
+        let err = writer.submit(|_| -> Result<()> { panic!("oops") }).wait();
+        assert_eq!(err, Err(Error::WriteJobPanicked));
+
+        let writer = Arc::try_unwrap(writer).ok().unwrap();
+        let db = writer.into_inner();
+        let (count, sum): (i64, i64) =
+            db.query_row("SELECT count(*), sum(x) FROM foo", [], |r| {
+                Ok((r.get(0)?, r.get(1)?))
+            })?;
+        assert_eq!((count, sum), (100, 4950));
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_result_future() -> Result<()> {
+        let writer = WriteSerializer::new(Connection::open_in_memory()?);
+        let result = writer.submit(|tx| tx.query_row("SELECT 42", [], |r| r.get::<_, i64>(0)));
+        assert_eq!(block_on(result).0?, 42);
+        Ok(())
+    }
+}
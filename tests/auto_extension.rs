@@ -0,0 +1,55 @@
+//! Ensure automatic extensions initialize new connections. In a separate
+//! process, as they are registered for the whole process.
+use std::os::raw::{c_char, c_int, c_void};
+
+use rusqlite::{auto_extension, ffi, Connection, Result};
+
+unsafe extern "C" fn sqlite3_answer_init(
+    db: *mut ffi::sqlite3,
+    _: *mut *mut c_char,
+    _: *const c_void,
+) -> c_int {
+    match Connection::from_handle(db)
+        .and_then(|conn| conn.execute_batch("CREATE TEMP VIEW answer AS SELECT 42 AS value"))
+    {
+        Ok(()) => ffi::SQLITE_OK,
+        Err(_) => ffi::SQLITE_ERROR,
+    }
+}
+
+unsafe extern "C" fn sqlite3_broken_init(
+    _: *mut ffi::sqlite3,
+    pz_err_msg: *mut *mut c_char,
+    _: *const c_void,
+) -> c_int {
+    *pz_err_msg = ffi::sqlite3_mprintf("broken extension\0".as_ptr() as *const c_char);
+    ffi::SQLITE_ERROR
+}
+
+fn answer(db: &Connection) -> Result<i64> {
+    db.query_row("SELECT value FROM answer", [], |r| r.get(0))
+}
+
+#[test]
+fn test_auto_extension() -> Result<()> {
+    unsafe { auto_extension::register(sqlite3_answer_init) }?;
+    // Registering twice is a no-op.
+    unsafe { auto_extension::register(sqlite3_answer_init) }?;
+    assert_eq!(answer(&Connection::open_in_memory()?)?, 42);
+
+    #[cfg(feature = "modern_sqlite")]
+    {
+        assert!(auto_extension::unregister(sqlite3_answer_init));
+        assert!(!auto_extension::unregister(sqlite3_answer_init));
+        answer(&Connection::open_in_memory()?).unwrap_err();
+        unsafe { auto_extension::register(sqlite3_answer_init) }?;
+    }
+
+    unsafe { auto_extension::register(sqlite3_broken_init) }?;
+    let err = Connection::open_in_memory().unwrap_err();
+    assert!(err.to_string().contains("broken extension"), "{}", err);
+
+    auto_extension::reset();
+    answer(&Connection::open_in_memory()?).unwrap_err();
+    Ok(())
+}
@@ -0,0 +1,66 @@
+//! This file contains unit tests for `rusqlite::allocator::config_malloc`.
+//! This function affects SQLite process-wide and so is not safe to run as a
+//! normal #[test] in the library.
+
+#[cfg(feature = "allocator")]
+fn main() {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicIsize, AtomicUsize, Ordering};
+
+    struct Counting {
+        allocations: AtomicUsize,
+        in_use: AtomicIsize,
+    }
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            self.allocations.fetch_add(1, Ordering::SeqCst);
+            self.in_use
+                .fetch_add(layout.size() as isize, Ordering::SeqCst);
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            self.in_use
+                .fetch_sub(layout.size() as isize, Ordering::SeqCst);
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            self.allocations.fetch_add(1, Ordering::SeqCst);
+            self.in_use
+                .fetch_add(new_size as isize - layout.size() as isize, Ordering::SeqCst);
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    static COUNTING: Counting = Counting {
+        allocations: AtomicUsize::new(0),
+        in_use: AtomicIsize::new(0),
+    };
+
+    use rusqlite::{allocator, Connection};
+
+    unsafe { allocator::config_malloc(&COUNTING) }.unwrap();
+    unsafe { allocator::config_malloc(&System) }.unwrap_err();
+
+    {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "CREATE TABLE foo(x BLOB);
+             INSERT INTO foo VALUES (zeroblob(100000));
+             UPDATE foo SET x = x || x;",
+        )
+        .unwrap();
+        assert!(COUNTING.allocations.load(Ordering::SeqCst) > 0);
+        assert!(COUNTING.in_use.load(Ordering::SeqCst) > 0);
+    }
+    // Everything a connection allocates is freed when it is closed.
+    let in_use = COUNTING.in_use.load(Ordering::SeqCst);
+    let db = Connection::open_in_memory().unwrap();
+    drop(db);
+    assert_eq!(COUNTING.in_use.load(Ordering::SeqCst), in_use);
+}
+
+#[cfg(not(feature = "allocator"))]
+fn main() {}
@@ -0,0 +1,98 @@
+//! This file contains unit tests for `rusqlite::pcache::config_page_cache`.
+//! This function affects SQLite process-wide and so is not safe to run as a
+//! normal #[test] in the library.
+
+#[cfg(feature = "pcache")]
+fn main() {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use rusqlite::pcache::{self, CreateFlag, Page, PageCache, PageCacheFactory};
+    use rusqlite::Connection;
+
+    static CACHES: AtomicUsize = AtomicUsize::new(0);
+    static PAGES: AtomicUsize = AtomicUsize::new(0);
+
+    struct Unbounded {
+        page_size: usize,
+        extra_size: usize,
+        pages: HashMap<u32, Page>,
+    }
+
+    unsafe impl PageCache for Unbounded {
+        fn page_count(&self) -> usize {
+            self.pages.len()
+        }
+
+        fn fetch(&mut self, key: u32, create: CreateFlag) -> Option<&Page> {
+            if create == CreateFlag::Never && !self.pages.contains_key(&key) {
+                return None;
+            }
+            let (page_size, extra_size) = (self.page_size, self.extra_size);
+            Some(self.pages.entry(key).or_insert_with(|| {
+                PAGES.fetch_add(1, Ordering::SeqCst);
+                Page::new(page_size, extra_size)
+            }))
+        }
+
+        fn unpin(&mut self, key: u32, discard: bool) {
+            if discard {
+                self.pages.remove(&key);
+            }
+        }
+
+        fn rekey(&mut self, old_key: u32, new_key: u32) {
+            let page = self.pages.remove(&old_key).unwrap();
+            self.pages.insert(new_key, page);
+        }
+
+        fn truncate(&mut self, limit: u32) {
+            self.pages.retain(|&key, _| key < limit);
+        }
+    }
+
+    impl Drop for Unbounded {
+        fn drop(&mut self) {
+            CACHES.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    struct Factory;
+
+    impl PageCacheFactory for Factory {
+        fn create(&self, page_size: usize, extra_size: usize, _: bool) -> Box<dyn PageCache> {
+            CACHES.fetch_add(1, Ordering::SeqCst);
+            Box::new(Unbounded {
+                page_size,
+                extra_size,
+                pages: HashMap::new(),
+            })
+        }
+    }
+
+    unsafe { pcache::config_page_cache(Factory) }.unwrap();
+    unsafe { pcache::config_page_cache(Factory) }.unwrap_err();
+
+    let db = Connection::open_in_memory().unwrap();
+    db.execute_batch(
+        "CREATE TABLE foo(x BLOB);
+         INSERT INTO foo VALUES (zeroblob(100000));
+         INSERT INTO foo SELECT x FROM foo;
+         DELETE FROM foo WHERE rowid = 1;
+         VACUUM;",
+    )
+    .unwrap();
+    let len: i64 = db
+        .query_row("SELECT sum(length(x)) FROM foo", [], |r| r.get(0))
+        .unwrap();
+    assert_eq!(len, 100000);
+    assert!(CACHES.load(Ordering::SeqCst) > 0);
+    assert!(PAGES.load(Ordering::SeqCst) > 0);
+
+    // Caches are destroyed when the connection is closed.
+    drop(db);
+    assert_eq!(CACHES.load(Ordering::SeqCst), 0);
+}
+
+#[cfg(not(feature = "pcache"))]
+fn main() {}
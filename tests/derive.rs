@@ -0,0 +1,80 @@
+//! Ensure `ToSql` and `FromSql` can be derived outside `rusqlite` crate.
+#![cfg(feature = "derive")]
+
+use rusqlite::types::{FromSql, FromSqlError, ToSql, ValueRef};
+use rusqlite::{Connection, Error, Result};
+
+#[derive(Clone, Copy, Debug, PartialEq, ToSql, FromSql)]
+enum Level {
+    Low = 1,
+    High = 10,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ToSql, FromSql)]
+#[rusqlite(repr = "text")]
+enum Strand {
+    #[rusqlite(rename = "+")]
+    Forward,
+    #[rusqlite(rename = "-")]
+    Reverse,
+    Both,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ToSql, FromSql)]
+#[rusqlite(repr = "text")]
+enum Kind {
+    Snp,
+    #[rusqlite(other)]
+    Other,
+}
+
+#[test]
+fn test_integer_repr() -> Result<()> {
+    let db = Connection::open_in_memory()?;
+    let i: i64 = db.query_row("SELECT ?", [Level::High], |r| r.get(0))?;
+    assert_eq!(i, 10);
+    let level: Level = db.query_row("SELECT ?", [Level::Low], |r| r.get(0))?;
+    assert_eq!(level, Level::Low);
+    let err = db
+        .query_row("SELECT 2", [], |r| r.get::<_, Level>(0))
+        .unwrap_err();
+    assert!(matches!(err, Error::IntegralValueOutOfRange(0, 2)));
+    let err = db
+        .query_row("SELECT 'Low'", [], |r| r.get::<_, Level>(0))
+        .unwrap_err();
+    assert!(matches!(err, Error::InvalidColumnType(..)));
+    Ok(())
+}
+
+#[test]
+fn test_text_repr() -> Result<()> {
+    let db = Connection::open_in_memory()?;
+    for &(strand, name) in &[
+        (Strand::Forward, "+"),
+        (Strand::Reverse, "-"),
+        (Strand::Both, "Both"),
+    ] {
+        let s: String = db.query_row("SELECT ?", [strand], |r| r.get(0))?;
+        assert_eq!(s, name);
+        let v: Strand = db.query_row("SELECT ?", [name], |r| r.get(0))?;
+        assert_eq!(v, strand);
+    }
+    let err = db
+        .query_row("SELECT 'Forward'", [], |r| r.get::<_, Strand>(0))
+        .unwrap_err();
+    match err {
+        Error::FromSqlConversionFailure(_, _, err) => {
+            assert_eq!(err.to_string(), "unknown Strand variant: \"Forward\"")
+        }
+        err => panic!("unexpected error {}", err),
+    }
+
+    let kind: Kind = db.query_row("SELECT 'Indel'", [], |r| r.get(0))?;
+    assert_eq!(kind, Kind::Other);
+    assert!(matches!(
+        Kind::column_result(ValueRef::Integer(1)),
+        Err(FromSqlError::InvalidType)
+    ));
+    assert!(Kind::Snp.to_sql().is_ok());
+    Ok(())
+}
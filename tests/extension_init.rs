@@ -0,0 +1,90 @@
+//! Ensure `#[sqlite_extension_init]` generates working entry points outside
+//! `rusqlite` crate.
+#![cfg(feature = "extension_init")]
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr;
+
+use rusqlite::extension::{entry_point_name, sqlite_extension_init};
+use rusqlite::{auto_extension, ffi, Connection, Result};
+
+#[sqlite_extension_init(name = "strands", name = "extension")]
+fn strands(conn: &Connection) -> Result<()> {
+    conn.execute_batch("CREATE TABLE strand (name TEXT); INSERT INTO strand VALUES ('+'), ('-')")
+}
+
+#[sqlite_extension_init(name = "broken")]
+fn failing(conn: &Connection) -> Result<()> {
+    conn.execute_batch("DROP TABLE missing")
+}
+
+#[sqlite_extension_init]
+fn panicking(_: &Connection) -> Result<()> {
+    panic!("not today")
+}
+
+fn error_message(err: *mut c_char) -> String {
+    assert!(!err.is_null());
+    let message = unsafe { CStr::from_ptr(err) }
+        .to_string_lossy()
+        .into_owned();
+    unsafe { ffi::sqlite3_free(err as *mut _) };
+    message
+}
+
+#[test]
+fn test_entry_point() -> Result<()> {
+    let db = Connection::open_in_memory()?;
+    let mut err = ptr::null_mut();
+    let rc = unsafe { sqlite3_strands_init(db.handle(), &mut err, ptr::null()) };
+    assert_eq!(ffi::SQLITE_OK, rc);
+    assert!(err.is_null());
+    let count: i64 = db.query_row("SELECT count(*) FROM strand", [], |r| r.get(0))?;
+    assert_eq!(2, count);
+    // The entry point can be linked statically too.
+    let _: auto_extension::RawAutoExtension = sqlite3_strands_init;
+    Ok(())
+}
+
+#[test]
+fn test_entry_point_error() -> Result<()> {
+    let db = Connection::open_in_memory()?;
+    let mut err = ptr::null_mut();
+    let rc = unsafe { sqlite3_broken_init(db.handle(), &mut err, ptr::null()) };
+    assert_eq!(ffi::SQLITE_ERROR, rc);
+    assert!(error_message(err).contains("no such table: missing"));
+    Ok(())
+}
+
+#[test]
+fn test_entry_point_panic() -> Result<()> {
+    let db = Connection::open_in_memory()?;
+    let mut err = ptr::null_mut();
+    let rc = unsafe { sqlite3_panicking_init(db.handle(), &mut err, ptr::null()) };
+    assert_eq!(ffi::SQLITE_ERROR, rc);
+    assert_eq!("unwinding panic: not today", error_message(err));
+    Ok(())
+}
+
+#[test]
+fn test_several_entry_points() -> Result<()> {
+    let db = Connection::open_in_memory()?;
+    let mut err = ptr::null_mut();
+    let rc = unsafe { sqlite3_extension_init(db.handle(), &mut err, ptr::null()) };
+    assert_eq!(ffi::SQLITE_OK, rc);
+    let count: i64 = db.query_row("SELECT count(*) FROM strand", [], |r| r.get(0))?;
+    assert_eq!(2, count);
+    Ok(())
+}
+
+#[test]
+fn test_entry_point_name() {
+    assert_eq!(entry_point_name("libstrands.so"), "sqlite3_strands_init");
+    assert_eq!(
+        entry_point_name("/opt/ext/Strands.dylib"),
+        "sqlite3_strands_init"
+    );
+    assert_eq!(entry_point_name("strands_2.1.dll"), "sqlite3_strands_init");
+    assert_eq!(entry_point_name("library"), "sqlite3_rary_init");
+}
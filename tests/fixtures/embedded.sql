@@ -0,0 +1,2 @@
+CREATE TABLE samples (id INTEGER PRIMARY KEY, name TEXT NOT NULL);
+INSERT INTO samples (name) VALUES ('NA12878'), ('NA12891'), ('NA12892');
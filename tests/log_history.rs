@@ -0,0 +1,56 @@
+//! This file contains unit tests for `rusqlite::Connection::logged`. It sets
+//! the process-wide error log callback and so is not safe to run as a normal
+//! #[test] in the library.
+
+#[cfg(feature = "trace")]
+fn main() {
+    use rusqlite::{trace, Connection};
+    use std::os::raw::c_int;
+
+    fn ignore(_: c_int, _: &str) {}
+
+    unsafe { trace::config_log(Some(ignore)) }.unwrap();
+
+    let db = Connection::open_in_memory().unwrap();
+    let other = Connection::open_in_memory().unwrap();
+    // Not kept unless enabled.
+    db.logged(|db| db.execute_batch("SELECT * FROM missing"))
+        .unwrap_err();
+    assert!(db.log_history().is_empty());
+
+    db.set_log_history(2);
+    trace::log(1, "outside");
+    db.logged(|_| {
+        trace::log(2, "first");
+        // Not attributed to `db`.
+        other.logged(|_| {
+            trace::log(3, "other");
+            Ok(())
+        })?;
+        Ok(())
+    })
+    .unwrap();
+    let history = db.log_history();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].code, 2);
+    assert_eq!(history[0].message, "first");
+
+    let err = db
+        .logged(|db| db.execute_batch("SELECT * FROM missing"))
+        .unwrap_err();
+    // The oldest message was dropped.
+    assert_eq!(err.log.len(), 2);
+    assert_eq!(err.log[0].message, "first");
+    assert!(
+        err.log[1].message.contains("no such table: missing"),
+        "{}",
+        err
+    );
+    assert!(err.to_string().contains("logged: (1) "), "{}", err);
+
+    db.set_log_history(0);
+    assert!(db.log_history().is_empty());
+}
+
+#[cfg(not(feature = "trace"))]
+fn main() {}
@@ -0,0 +1,46 @@
+//! This file contains unit tests for `rusqlite::trace::set_redactor`. This
+//! function affects rusqlite process-wide and so is not safe to run as a
+//! normal #[test] in the library.
+
+#[cfg(feature = "trace")]
+fn main() {
+    use lazy_static::lazy_static;
+    use std::sync::Mutex;
+
+    use rusqlite::trace;
+    use rusqlite::Connection;
+
+    lazy_static! {
+        static ref TRACED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    }
+
+    fn tracer(s: &str) {
+        TRACED.lock().unwrap().push(s.to_owned());
+    }
+
+    let mut db = Connection::open_in_memory().unwrap();
+    db.trace(Some(tracer));
+    trace::set_redactor(Some(trace::redact_literals));
+    db.query_row("SELECT ?, 'secret'", ["patient-42"], |_| Ok(()))
+        .unwrap();
+    // Errors quote the tokens of the SQL.
+    for (sql, expected) in &[
+        (
+            "SELECT 'public' 'alias' 'secret'",
+            "near \"?\": syntax error",
+        ),
+        ("SELECT 'secret", "unrecognized token: \"?\""),
+        ("SELECT no_such FROM (SELECT 1)", "no such column: no_such"),
+    ] {
+        let err = db.prepare(sql).unwrap_err();
+        assert_eq!(err.to_string(), *expected);
+    }
+    trace::set_redactor(None);
+    db.query_row("SELECT ?", ["public"], |_| Ok(())).unwrap();
+
+    let traced = TRACED.lock().unwrap();
+    assert_eq!(*traced, vec!["SELECT ?, ?", "SELECT 'public'"]);
+}
+
+#[cfg(not(feature = "trace"))]
+fn main() {}
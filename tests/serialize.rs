@@ -0,0 +1,30 @@
+//! Ensure `embedded_db!` can be used outside `rusqlite` crate.
+#![cfg(feature = "serialize")]
+
+use rusqlite::serialize::EmbeddedDb;
+use rusqlite::{embedded_db, Result};
+
+fn count(db: &rusqlite::Connection) -> Result<i64> {
+    db.query_row("SELECT count(*) FROM samples", [], |r| r.get(0))
+}
+
+#[test]
+fn test_embedded_image() -> Result<()> {
+    let db = embedded_db!("fixtures/embedded.db")?;
+    db.execute("INSERT INTO samples (name) VALUES ('HG002')", [])?;
+    assert_eq!(count(&db)?, 4);
+    let db = embedded_db!("fixtures/embedded.db")?;
+    assert_eq!(count(&db)?, 3);
+    Ok(())
+}
+
+#[test]
+fn test_embedded_sql() -> Result<()> {
+    static SAMPLES: EmbeddedDb = EmbeddedDb::sql(include_str!("fixtures/embedded.sql"));
+    let db = SAMPLES.open()?;
+    db.execute("DELETE FROM samples WHERE id = 1", [])?;
+    assert_eq!(count(&db)?, 2);
+    let db = SAMPLES.open()?;
+    assert_eq!(count(&db)?, 3);
+    Ok(())
+}